@@ -0,0 +1,349 @@
+// Reads the metadata curated with Calibre: the *metadata.opf* files saved next to
+// the documents, and the *metadata.db* database of a Calibre library.
+
+pub mod sqlite;
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::collections::BTreeMap;
+use fxhash::FxHashMap;
+use lazy_static::lazy_static;
+use regex::Regex;
+use anyhow::{Error, format_err};
+use crate::document::html::xml::XmlParser;
+use crate::helpers::decode_entities;
+use crate::library::Library;
+use crate::metadata::Info;
+use crate::settings::LibraryMode;
+use self::sqlite::{Database, Value};
+
+pub const OPF_FILENAME: &str = "metadata.opf";
+pub const COVER_FILENAME: &str = "cover.jpg";
+pub const DATABASE_FILENAME: &str = "metadata.db";
+
+lazy_static! {
+    static ref BLOCK_END: Regex = Regex::new(r"(?i)</(p|div|h[1-6]|li)>|<br\s*/?>").unwrap();
+    static ref TAG: Regex = Regex::new(r"<[^>]*>").unwrap();
+}
+
+// Returns the path of the OPF file saved by Calibre next to the given document.
+pub fn sidecar_opf<P: AsRef<Path>>(path: P) -> Option<PathBuf> {
+    let opf = path.as_ref().with_file_name(OPF_FILENAME);
+    opf.is_file().then_some(opf)
+}
+
+// Returns the path of the cover saved by Calibre next to the given document.
+pub fn sidecar_cover<P: AsRef<Path>>(path: P) -> Option<PathBuf> {
+    let cover = path.as_ref().with_file_name(COVER_FILENAME);
+    cover.is_file().then_some(cover)
+}
+
+pub fn read_opf<P: AsRef<Path>>(path: P, info: &mut Info) -> Result<(), Error> {
+    let text = fs::read_to_string(path.as_ref())?;
+    parse_opf(&text, info)
+}
+
+fn parse_opf(text: &str, info: &mut Info) -> Result<(), Error> {
+    let tree = XmlParser::new(text).parse();
+    let root = tree.root();
+    let metadata = root.find("metadata")
+                       .ok_or_else(|| format_err!("missing metadata element"))?;
+
+    let mut authors = Vec::new();
+    let mut identifiers = Vec::new();
+    let mut categories = Vec::new();
+    let mut series = None;
+    let mut number = None;
+
+    for child in metadata.children() {
+        let text = || decode_entities(&child.text()).trim().to_string();
+        match child.tag_name() {
+            Some("title") => info.title = text(),
+            Some("creator") if child.attribute("opf:role").is_none_or(|role| role == "aut") => {
+                authors.push(text());
+            },
+            // Calibre uses the year 101 for unknown dates.
+            Some("date") => {
+                let date = text();
+                if !date.starts_with("0101") {
+                    info.year = date.chars().take(4).collect();
+                }
+            },
+            Some("publisher") => info.publisher = text(),
+            Some("language") => info.language = language_code(&text()).to_string(),
+            Some("subject") => categories.push(text()),
+            Some("description") => info.description = plain_text(&text()),
+            Some("identifier") => {
+                let scheme = child.attribute("opf:scheme").unwrap_or_default().to_lowercase();
+                identifiers.push((scheme, text()));
+            },
+            Some("meta") => {
+                let content = child.attribute("content").map(|s| decode_entities(s).into_owned());
+                match child.attribute("name") {
+                    Some("calibre:series") => series = content,
+                    Some("calibre:series_index") => number = content,
+                    _ => (),
+                }
+            },
+            _ => (),
+        }
+    }
+
+    authors.retain(|a| !a.is_empty());
+    info.author = authors.join(", ");
+    info.categories.extend(categories.into_iter().filter(|c| !c.is_empty()));
+
+    if let Some(identifier) = preferred_identifier(identifiers.iter().map(|(k, v)| (k.as_str(), v.as_str()))) {
+        info.identifier = identifier;
+    }
+
+    if let Some(series) = series.filter(|s| !s.is_empty()) {
+        info.series = series;
+        info.number = number.as_deref().map(series_number).unwrap_or_default();
+    }
+
+    Ok(())
+}
+
+// Imports the metadata of the books of the Calibre library at *calibre_home* into the
+// matching entries of *library*. The Calibre library is either inside *library*, or
+// its directory structure is mirrored at the root of *library*.
+// Returns the number of updated entries.
+pub fn import_database<P: AsRef<Path>>(library: &mut Library, calibre_home: P) -> Result<usize, Error> {
+    if library.mode != LibraryMode::Database {
+        return Err(format_err!("the Calibre import requires a database library"));
+    }
+
+    let calibre_home = calibre_home.as_ref();
+    let prefix = calibre_home.canonicalize().ok()
+                             .zip(library.home.canonicalize().ok())
+                             .and_then(|(calibre, home)| calibre.strip_prefix(home).ok().map(Path::to_path_buf))
+                             .unwrap_or_default();
+    let mut count = 0;
+
+    for book in read_database(calibre_home.join(DATABASE_FILENAME))? {
+        let path = prefix.join(&book.file.path);
+        let Some(mut info) = library.info(&path) else {
+            continue;
+        };
+        info.title = book.title.clone();
+        info.author = book.author.clone();
+        info.year = book.year.clone();
+        info.language = book.language.clone();
+        info.publisher = book.publisher.clone();
+        info.series = book.series.clone();
+        info.number = book.number.clone();
+        info.identifier = book.identifier.clone();
+        info.description = book.description.clone();
+        info.categories.extend(book.categories.iter().cloned());
+        library.update(info)?;
+        count += 1;
+    }
+
+    Ok(count)
+}
+
+// Returns an entry for each file of each book of the given Calibre database.
+// The paths are relative to the Calibre library.
+pub fn read_database<P: AsRef<Path>>(path: P) -> Result<Vec<Info>, Error> {
+    let db = Database::open(path.as_ref())?;
+    let books = db.table("books")?;
+
+    let names = |table: &str, column: &str| -> Result<FxHashMap<i64, String>, Error> {
+        Ok(db.table(table)?.select(&["id", column])
+             .filter_map(|row| Some((row[0].as_i64()?, row[1].as_str()?.to_string())))
+             .collect())
+    };
+    // The linked values of each book, in link order.
+    let links = |table: &str, column: &str, values: &FxHashMap<i64, String>| -> Result<FxHashMap<i64, Vec<String>>, Error> {
+        let mut rows: Vec<(i64, i64, String)> = db.table(table)?.select(&["id", "book", column])
+            .filter_map(|row| {
+                let value = match row[2] {
+                    Value::Text(s) => s.clone(),
+                    value => values.get(&value.as_i64()?)?.clone(),
+                };
+                Some((row[0].as_i64()?, row[1].as_i64()?, value))
+            }).collect();
+        rows.sort_by_key(|(id, _, _)| *id);
+        let mut result: FxHashMap<i64, Vec<String>> = FxHashMap::default();
+        for (_, book, value) in rows {
+            result.entry(book).or_default().push(value);
+        }
+        Ok(result)
+    };
+
+    let authors = links("books_authors_link", "author", &names("authors", "name")?)?;
+    let series = links("books_series_link", "series", &names("series", "name")?)?;
+    let tags = links("books_tags_link", "tag", &names("tags", "name")?)?;
+    let publishers = links("books_publishers_link", "publisher", &names("publishers", "name")?)?;
+    let languages = links("books_languages_link", "lang_code", &names("languages", "lang_code")?)?;
+    let comments: FxHashMap<i64, String> = db.table("comments")?.select(&["book", "text"])
+        .filter_map(|row| Some((row[0].as_i64()?, row[1].as_str()?.to_string())))
+        .collect();
+    let mut identifiers: FxHashMap<i64, Vec<(String, String)>> = FxHashMap::default();
+    for row in db.table("identifiers")?.select(&["book", "type", "val"]) {
+        if let (Some(book), Some(kind), Some(value)) = (row[0].as_i64(), row[1].as_str(), row[2].as_str()) {
+            identifiers.entry(book).or_default().push((kind.to_lowercase(), value.to_string()));
+        }
+    }
+    let mut files: BTreeMap<i64, Vec<String>> = BTreeMap::new();
+    for row in db.table("data")?.select(&["book", "format", "name"]) {
+        if let (Some(book), Some(format), Some(name)) = (row[0].as_i64(), row[1].as_str(), row[2].as_str()) {
+            files.entry(book).or_default().push(format!("{}.{}", name, format.to_lowercase()));
+        }
+    }
+
+    let mut infos = Vec::new();
+
+    for row in books.select(&["id", "title", "pubdate", "series_index", "path", "isbn"]) {
+        let (Some(id), Some(dir)) = (row[0].as_i64(), row[4].as_str()) else {
+            continue;
+        };
+        let first = |map: &FxHashMap<i64, Vec<String>>| {
+            map.get(&id).and_then(|v| v.first()).cloned().unwrap_or_default()
+        };
+        let mut book_identifiers = identifiers.remove(&id).unwrap_or_default();
+        if let Some(isbn) = row[5].as_str().filter(|s| !s.is_empty()) {
+            book_identifiers.push(("isbn".to_string(), isbn.to_string()));
+        }
+        let series_name = first(&series);
+        let mut info = Info {
+            title: row[1].as_str().unwrap_or_default().to_string(),
+            author: authors.get(&id).map(|v| v.join(", ")).unwrap_or_default(),
+            // Calibre uses the year 101 for unknown dates.
+            year: row[2].as_str().filter(|d| !d.starts_with("0101"))
+                        .map(|d| d.chars().take(4).collect())
+                        .unwrap_or_default(),
+            language: language_code(&first(&languages)).to_string(),
+            publisher: first(&publishers),
+            number: if series_name.is_empty() {
+                String::new()
+            } else {
+                row[3].as_f64().map(|n| series_number(&n.to_string())).unwrap_or_default()
+            },
+            series: series_name,
+            identifier: preferred_identifier(book_identifiers.iter().map(|(k, v)| (k.as_str(), v.as_str())))
+                                            .unwrap_or_default(),
+            description: comments.get(&id).map(|c| plain_text(c)).unwrap_or_default(),
+            categories: tags.get(&id).map(|v| v.iter().cloned().collect()).unwrap_or_default(),
+            .. Default::default()
+        };
+        for name in files.get(&id).into_iter().flatten() {
+            info.file.path = Path::new(dir).join(name);
+            info.file.kind = info.file.path.extension()
+                                 .map(|e| e.to_string_lossy().into_owned())
+                                 .unwrap_or_default();
+            infos.push(info.clone());
+        }
+    }
+
+    Ok(infos)
+}
+
+// The ISBN is preferred to the other identifiers, and Calibre's own identifiers are ignored.
+fn preferred_identifier<'a, I>(identifiers: I) -> Option<String> where I: Iterator<Item=(&'a str, &'a str)> {
+    let mut result = None;
+    for (scheme, value) in identifiers {
+        let value = value.trim();
+        if value.is_empty() || matches!(scheme, "calibre" | "uuid") {
+            continue;
+        }
+        let lower = value.to_lowercase();
+        if scheme == "isbn" || lower.starts_with("isbn:") || lower.starts_with("urn:isbn:") {
+            return Some(value.rsplit(':').next().unwrap_or(value).to_string());
+        }
+        if result.is_none() && !lower.starts_with("urn:uuid:") {
+            result = Some(if scheme.is_empty() { value.to_string() } else { format!("{}:{}", scheme, value) });
+        }
+    }
+    result
+}
+
+// Calibre stores the series indices as real numbers: *2.0* becomes *2*.
+fn series_number(index: &str) -> String {
+    let index = index.trim();
+    index.strip_suffix(".0")
+         .or_else(|| index.strip_suffix(".00"))
+         .unwrap_or(index)
+         .to_string()
+}
+
+// Calibre uses the ISO 639-2 language codes.
+fn language_code(code: &str) -> &str {
+    match code {
+        "eng" => "en",
+        "fra" | "fre" => "fr",
+        "deu" | "ger" => "de",
+        "spa" => "es",
+        "ita" => "it",
+        "por" => "pt",
+        "nld" | "dut" => "nl",
+        "rus" => "ru",
+        "pol" => "pl",
+        "ces" | "cze" => "cs",
+        "swe" => "sv",
+        "dan" => "da",
+        "fin" => "fi",
+        "nor" | "nob" => "nb",
+        "hun" => "hu",
+        "tur" => "tr",
+        "ell" | "gre" => "el",
+        "jpn" => "ja",
+        "kor" => "ko",
+        "zho" | "chi" => "zh",
+        "und" => "",
+        _ => code,
+    }
+}
+
+// The descriptions are HTML fragments.
+pub fn plain_text(html: &str) -> String {
+    let text = BLOCK_END.replace_all(html, "\n");
+    let text = TAG.replace_all(&text, "");
+    decode_entities(&text).lines()
+                          .map(str::trim)
+                          .filter(|line| !line.is_empty())
+                          .collect::<Vec<&str>>()
+                          .join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_opf() {
+        let text = r#"<?xml version='1.0' encoding='utf-8'?>
+<package xmlns="http://www.idpf.org/2007/opf" unique-identifier="uuid_id" version="2.0">
+  <metadata xmlns:dc="http://purl.org/dc/elements/1.1/" xmlns:opf="http://www.idpf.org/2007/opf">
+    <dc:identifier opf:scheme="calibre" id="calibre_id">42</dc:identifier>
+    <dc:identifier opf:scheme="uuid" id="uuid_id">0b2d3a4c-0000</dc:identifier>
+    <dc:title>The Left Hand of Darkness</dc:title>
+    <dc:creator opf:file-as="Le Guin, Ursula K." opf:role="aut">Ursula K. Le Guin</dc:creator>
+    <dc:creator opf:role="ill">Someone Else</dc:creator>
+    <dc:description>&lt;p&gt;A lone human ambassador&lt;/p&gt;&lt;p&gt;on Gethen.&lt;/p&gt;</dc:description>
+    <dc:publisher>Ace</dc:publisher>
+    <dc:identifier opf:scheme="ISBN">9780441478125</dc:identifier>
+    <dc:date>1969-03-01T00:00:00+00:00</dc:date>
+    <dc:language>eng</dc:language>
+    <dc:subject>Science Fiction</dc:subject>
+    <meta name="calibre:series" content="Hainish Cycle"/>
+    <meta name="calibre:series_index" content="4.0"/>
+  </metadata>
+</package>"#;
+        let mut info = Info::default();
+        parse_opf(text, &mut info).unwrap();
+        assert_eq!(info.title, "The Left Hand of Darkness");
+        assert_eq!(info.author, "Ursula K. Le Guin");
+        assert_eq!(info.year, "1969");
+        assert_eq!(info.language, "en");
+        assert_eq!(info.identifier, "9780441478125");
+        assert_eq!((info.series.as_str(), info.number.as_str()), ("Hainish Cycle", "4"));
+        assert_eq!(info.description, "A lone human ambassador\non Gethen.");
+        assert!(info.categories.contains("Science Fiction"));
+        let mut info = Info { year: "1969".to_string(), .. Default::default() };
+        parse_opf(&text.replace("1969-03-01", "0101-01-01"), &mut info).unwrap();
+        assert_eq!(info.year, "1969");
+        assert_eq!(series_number("1.5"), "1.5");
+    }
+}
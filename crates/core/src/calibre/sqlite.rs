@@ -0,0 +1,496 @@
+// A minimal reader of the tables of the SQLite database files.
+// The indexes and the views are ignored. The write-ahead log isn't read:
+// the databases with changes that haven't been checkpointed are refused.
+
+use std::fs::{self, File};
+use std::io::{Read, Seek, SeekFrom, Cursor};
+use std::cell::RefCell;
+use std::path::Path;
+use byteorder::{BigEndian, ByteOrder};
+use anyhow::{Error, format_err, bail};
+
+const MAGIC: &[u8] = b"SQLite format 3\0";
+const HEADER_SIZE: usize = 100;
+const SCHEMA_ROOT_PAGE: usize = 1;
+const TABLE_INTERIOR_PAGE: u8 = 0x05;
+const TABLE_LEAF_PAGE: u8 = 0x0D;
+const UTF_8: u32 = 1;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Null,
+    Integer(i64),
+    Real(f64),
+    Text(String),
+    Blob(Vec<u8>),
+}
+
+impl Value {
+    pub fn as_i64(&self) -> Option<i64> {
+        match self {
+            Value::Integer(n) => Some(*n),
+            Value::Real(x) => Some(*x as i64),
+            _ => None,
+        }
+    }
+
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            Value::Integer(n) => Some(*n as f64),
+            Value::Real(x) => Some(*x),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Value::Text(s) => Some(s),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Table {
+    pub columns: Vec<String>,
+    pub rows: Vec<Vec<Value>>,
+}
+
+impl Table {
+    pub fn column(&self, name: &str) -> Option<usize> {
+        self.columns.iter().position(|c| c.eq_ignore_ascii_case(name))
+    }
+
+    // Returns the values of the given columns, in order.
+    pub fn select<'a>(&'a self, names: &[&str]) -> impl Iterator<Item=Vec<&'a Value>> + 'a {
+        let indices: Vec<Option<usize>> = names.iter().map(|name| self.column(name)).collect();
+        self.rows.iter().map(move |row| {
+            indices.iter().map(|index| index.and_then(|i| row.get(i)).unwrap_or(&Value::Null)).collect()
+        })
+    }
+}
+
+pub trait Source: Read + Seek {}
+
+impl<T: Read + Seek> Source for T {}
+
+// The pages are read on demand.
+pub struct Database {
+    source: RefCell<Box<dyn Source>>,
+    page_size: usize,
+    page_count: usize,
+    usable_size: usize,
+}
+
+impl Database {
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Database, Error> {
+        let path = path.as_ref();
+        let mut wal = path.as_os_str().to_owned();
+        wal.push("-wal");
+        if fs::metadata(&wal).is_ok_and(|md| md.len() > 0) {
+            bail!("the write-ahead log {} isn't empty: close the application that uses the database",
+                  Path::new(&wal).display());
+        }
+        Database::from_source(Box::new(File::open(path)?))
+    }
+
+    pub fn from_bytes(data: Vec<u8>) -> Result<Database, Error> {
+        Database::from_source(Box::new(Cursor::new(data)))
+    }
+
+    pub fn from_source(mut source: Box<dyn Source>) -> Result<Database, Error> {
+        let len = source.seek(SeekFrom::End(0))? as usize;
+        let mut data = [0; HEADER_SIZE];
+        source.seek(SeekFrom::Start(0))?;
+        if len < HEADER_SIZE || source.read_exact(&mut data).is_err() || &data[..16] != MAGIC {
+            bail!("not a SQLite database");
+        }
+
+        let page_size = match BigEndian::read_u16(&data[16..18]) {
+            1 => 65536,
+            n => n as usize,
+        };
+
+        if page_size < 512 || !page_size.is_power_of_two() {
+            bail!("invalid page size: {}", page_size);
+        }
+
+        if BigEndian::read_u32(&data[56..60]) > UTF_8 {
+            bail!("unsupported text encoding");
+        }
+
+        let usable_size = page_size - data[20] as usize;
+        let page_count = len / page_size;
+
+        Ok(Database { source: RefCell::new(source), page_size, page_count, usable_size })
+    }
+
+    pub fn table(&self, name: &str) -> Result<Table, Error> {
+        let (root, sql) = self.rows(SCHEMA_ROOT_PAGE)?.into_iter()
+            .find_map(|(_, values)| {
+                match values.as_slice() {
+                    [Value::Text(kind), Value::Text(table_name), _, Value::Integer(root), Value::Text(sql), ..]
+                        if kind == "table" && table_name.eq_ignore_ascii_case(name) => Some((*root as usize, sql.clone())),
+                    _ => None,
+                }
+            }).ok_or_else(|| format_err!("unknown table: {}", name))?;
+
+        let definitions = parse_columns(&sql);
+        let columns = definitions.iter().map(|(name, _)| name.clone()).collect();
+        let rows = self.rows(root)?.into_iter().map(|(rowid, mut values)| {
+            // The columns added by *ALTER TABLE* might be missing from the old records.
+            values.resize(definitions.len(), Value::Null);
+            for (index, (_, is_rowid)) in definitions.iter().enumerate() {
+                if *is_rowid {
+                    values[index] = Value::Integer(rowid);
+                }
+            }
+            values
+        }).collect();
+
+        Ok(Table { columns, rows })
+    }
+
+    // Returns the rowids and the records of the table whose B-tree starts at *root*.
+    fn rows(&self, root: usize) -> Result<Vec<(i64, Vec<Value>)>, Error> {
+        let mut rows = Vec::new();
+        let mut pages = vec![root];
+        let mut visited = 0;
+
+        while let Some(number) = pages.pop() {
+            visited += 1;
+            if visited > self.page_count {
+                bail!("cyclic B-tree");
+            }
+
+            let page = &self.page(number)?;
+            let start = if number == 1 { HEADER_SIZE } else { 0 };
+            let header = page.get(start..start+12).ok_or_else(|| format_err!("truncated page {}", number))?;
+            let count = BigEndian::read_u16(&header[3..5]) as usize;
+
+            match header[0] {
+                TABLE_INTERIOR_PAGE => {
+                    let pointers = start + 12;
+                    // The children are pushed in reverse order to keep the rows sorted.
+                    pages.push(BigEndian::read_u32(&header[8..12]) as usize);
+                    for index in (0..count).rev() {
+                        let offset = cell_offset(page, pointers, index)?;
+                        let child = page.get(offset..offset+4).ok_or_else(|| format_err!("truncated cell"))?;
+                        pages.push(BigEndian::read_u32(child) as usize);
+                    }
+                },
+                TABLE_LEAF_PAGE => {
+                    let pointers = start + 8;
+                    for index in 0..count {
+                        let mut offset = cell_offset(page, pointers, index)?;
+                        let size = varint(page, &mut offset)? as usize;
+                        let rowid = varint(page, &mut offset)?;
+                        let payload = self.payload(page, offset, size)?;
+                        rows.push((rowid, record(&payload)?));
+                    }
+                },
+                kind => bail!("unexpected page kind {} in table B-tree", kind),
+            }
+        }
+
+        Ok(rows)
+    }
+
+    fn page(&self, number: usize) -> Result<Vec<u8>, Error> {
+        if number == 0 || number > self.page_count {
+            bail!("invalid page number: {}", number);
+        }
+        let mut page = vec![0; self.page_size];
+        let mut source = self.source.borrow_mut();
+        source.seek(SeekFrom::Start(((number - 1) * self.page_size) as u64))?;
+        source.read_exact(&mut page)?;
+        Ok(page)
+    }
+
+    fn payload(&self, page: &[u8], offset: usize, size: usize) -> Result<Vec<u8>, Error> {
+        if size > self.page_count * self.page_size {
+            bail!("payload larger than the database: {}", size);
+        }
+
+        let usable = self.usable_size;
+        let max_local = usable - 35;
+        let local = if size <= max_local {
+            size
+        } else {
+            let min_local = (usable - 12) * 32 / 255 - 23;
+            let local = min_local + (size - min_local) % (usable - 4);
+            if local <= max_local { local } else { min_local }
+        };
+
+        let mut payload = page.get(offset..offset+local)
+                              .ok_or_else(|| format_err!("truncated cell"))?
+                              .to_vec();
+
+        if local < size {
+            let pointer = page.get(offset+local..offset+local+4)
+                              .ok_or_else(|| format_err!("truncated cell"))?;
+            let mut next = BigEndian::read_u32(pointer) as usize;
+            let mut visited = 0;
+            while payload.len() < size {
+                // The page holding the cell isn't part of the chain.
+                visited += 1;
+                if visited >= self.page_count {
+                    bail!("cyclic overflow chain");
+                }
+                let overflow = self.page(next)?;
+                let len = (size - payload.len()).min(usable - 4);
+                payload.extend_from_slice(&overflow[4..4+len]);
+                next = BigEndian::read_u32(&overflow[..4]) as usize;
+            }
+        }
+
+        Ok(payload)
+    }
+}
+
+fn cell_offset(page: &[u8], pointers: usize, index: usize) -> Result<usize, Error> {
+    page.get(pointers + 2 * index..pointers + 2 * index + 2)
+        .map(|bytes| BigEndian::read_u16(bytes) as usize)
+        .ok_or_else(|| format_err!("truncated cell pointer array"))
+}
+
+fn varint(bytes: &[u8], offset: &mut usize) -> Result<i64, Error> {
+    let mut value: u64 = 0;
+    for i in 0..9 {
+        let byte = *bytes.get(*offset).ok_or_else(|| format_err!("truncated varint"))?;
+        *offset += 1;
+        if i == 8 {
+            value = (value << 8) | byte as u64;
+            break;
+        }
+        value = (value << 7) | (byte & 0x7F) as u64;
+        if byte & 0x80 == 0 {
+            break;
+        }
+    }
+    Ok(value as i64)
+}
+
+fn record(payload: &[u8]) -> Result<Vec<Value>, Error> {
+    let mut offset = 0;
+    let header_size = varint(payload, &mut offset)? as usize;
+    let mut types = Vec::new();
+
+    while offset < header_size {
+        types.push(varint(payload, &mut offset)?);
+    }
+
+    let mut offset = header_size;
+    let mut values = Vec::with_capacity(types.len());
+
+    for kind in types {
+        let len = match kind {
+            0 | 8 | 9 => 0,
+            1..=4 => kind as usize,
+            5 => 6,
+            6 | 7 => 8,
+            _ if kind >= 12 => (kind as usize - 12) / 2,
+            _ => bail!("invalid serial type: {}", kind),
+        };
+        let bytes = payload.get(offset..offset+len).ok_or_else(|| format_err!("truncated record"))?;
+        offset += len;
+        values.push(match kind {
+            0 => Value::Null,
+            1..=6 => Value::Integer(bytes.iter().fold(if bytes[0] & 0x80 != 0 { -1 } else { 0 },
+                                                      |n, &b| (n << 8) | b as i64)),
+            7 => Value::Real(BigEndian::read_f64(bytes)),
+            8 => Value::Integer(0),
+            9 => Value::Integer(1),
+            _ if kind % 2 == 0 => Value::Blob(bytes.to_vec()),
+            _ => Value::Text(String::from_utf8_lossy(bytes).into_owned()),
+        });
+    }
+
+    Ok(values)
+}
+
+// Returns the names of the columns of a *CREATE TABLE* statement,
+// and whether they're aliases for the rowid.
+fn parse_columns(sql: &str) -> Vec<(String, bool)> {
+    let (Some(start), Some(end)) = (sql.find('('), sql.rfind(')')) else {
+        return Vec::new();
+    };
+
+    let mut definitions = Vec::new();
+    let mut depth = 0;
+    let mut quote = None;
+    let mut last = start + 1;
+
+    for (index, c) in sql[..end].char_indices().skip_while(|(i, _)| *i <= start) {
+        match (quote, c) {
+            (Some(q), _) if c == q => quote = None,
+            (Some(_), _) => (),
+            (None, '\'' | '"' | '`') => quote = Some(c),
+            (None, '[') => quote = Some(']'),
+            (None, '(') => depth += 1,
+            (None, ')') => depth -= 1,
+            (None, ',') if depth == 0 => {
+                definitions.push(&sql[last..index]);
+                last = index + 1;
+            },
+            _ => (),
+        }
+    }
+    definitions.push(&sql[last..end]);
+
+    definitions.into_iter().filter_map(|definition| {
+        let mut words = definition.split_whitespace();
+        let name = words.next()?;
+        let upper = definition.to_uppercase();
+        if ["CONSTRAINT", "PRIMARY", "UNIQUE", "CHECK", "FOREIGN"].iter()
+                                                               .any(|w| upper.trim_start().starts_with(w)) {
+            return None;
+        }
+        let is_rowid = words.next().is_some_and(|kind| kind.eq_ignore_ascii_case("INTEGER")) &&
+                       upper.contains("PRIMARY KEY");
+        let name = name.trim_matches(|c| matches!(c, '"' | '`' | '[' | ']' | '\''));
+        Some((name.to_string(), is_rowid))
+    }).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn varint_bytes(value: u64) -> Vec<u8> {
+        let mut groups = vec![(value & 0x7F) as u8];
+        let mut value = value >> 7;
+        while value > 0 {
+            groups.push((value & 0x7F) as u8 | 0x80);
+            value >>= 7;
+        }
+        groups.reverse();
+        groups
+    }
+
+    fn record_bytes(values: &[Value]) -> Vec<u8> {
+        let mut types = Vec::new();
+        let mut body = Vec::new();
+        for value in values {
+            match value {
+                Value::Null => types.push(0),
+                Value::Integer(n) => {
+                    types.push(6);
+                    body.extend_from_slice(&n.to_be_bytes());
+                },
+                Value::Text(s) => {
+                    types.extend(varint_bytes(13 + 2 * s.len() as u64));
+                    body.extend_from_slice(s.as_bytes());
+                },
+                _ => unreachable!(),
+            }
+        }
+        let mut bytes = vec![types.len() as u8 + 1];
+        bytes.extend(types);
+        bytes.extend(body);
+        bytes
+    }
+
+    // Writes a leaf table page with the given rows.
+    fn leaf_page(page: &mut [u8], start: usize, rows: &[(i64, Vec<Value>)]) {
+        page[start] = TABLE_LEAF_PAGE;
+        page[start+3..start+5].copy_from_slice(&(rows.len() as u16).to_be_bytes());
+        let mut end = page.len();
+        for (index, (rowid, values)) in rows.iter().enumerate() {
+            let payload = record_bytes(values);
+            let mut cell = varint_bytes(payload.len() as u64);
+            cell.extend(varint_bytes(*rowid as u64));
+            cell.extend(payload);
+            end -= cell.len();
+            page[end..end+cell.len()].copy_from_slice(&cell);
+            let pointer = start + 8 + 2 * index;
+            page[pointer..pointer+2].copy_from_slice(&(end as u16).to_be_bytes());
+        }
+    }
+
+    #[test]
+    fn test_tables() {
+        let page_size = 1024;
+        let mut data = vec![0; 3 * page_size];
+        data[..16].copy_from_slice(MAGIC);
+        data[16..18].copy_from_slice(&(page_size as u16).to_be_bytes());
+        data[56..60].copy_from_slice(&UTF_8.to_be_bytes());
+
+        let sql = "CREATE TABLE books ( id INTEGER PRIMARY KEY AUTOINCREMENT, \
+                   title TEXT NOT NULL DEFAULT 'Unknown, yet' COLLATE NOCASE, \
+                   series_index REAL NOT NULL DEFAULT 1.0, UNIQUE(title, series_index))";
+        leaf_page(&mut data[..page_size], HEADER_SIZE,
+                  &[(1, vec![Value::Text("table".to_string()), Value::Text("books".to_string()),
+                             Value::Text("books".to_string()), Value::Integer(2), Value::Text(sql.to_string())])]);
+        let long_title = "Long ".repeat(400);
+        leaf_page(&mut data[page_size..2 * page_size], 0,
+                  &[(3, vec![Value::Null, Value::Text("Dune".to_string()), Value::Integer(1)]),
+                    (7, vec![Value::Null, Value::Text("Emma".to_string())])]);
+
+        let db = Database::from_bytes(data.clone()).unwrap();
+        let table = db.table("books").unwrap();
+        assert_eq!(table.columns, ["id", "title", "series_index"]);
+        let rows: Vec<Vec<&Value>> = table.select(&["title", "id", "series_index"]).collect();
+        assert_eq!(rows[0], [&Value::Text("Dune".to_string()), &Value::Integer(3), &Value::Integer(1)]);
+        assert_eq!(rows[1], [&Value::Text("Emma".to_string()), &Value::Integer(7), &Value::Null]);
+        assert!(db.table("authors").is_err());
+
+        // A payload that doesn't fit in its page spills into an overflow page.
+        let payload = record_bytes(&[Value::Null, Value::Text(long_title.clone())]);
+        let mut page = vec![0; page_size];
+        page[0] = TABLE_LEAF_PAGE;
+        page[3..5].copy_from_slice(&1u16.to_be_bytes());
+        let db = Database::from_bytes(data.clone()).unwrap();
+        let local = {
+            let usable = db.usable_size;
+            let min_local = (usable - 12) * 32 / 255 - 23;
+            let local = min_local + (payload.len() - min_local) % (usable - 4);
+            if local <= usable - 35 { local } else { min_local }
+        };
+        let mut cell = varint_bytes(payload.len() as u64);
+        cell.extend(varint_bytes(9));
+        cell.extend_from_slice(&payload[..local]);
+        cell.extend_from_slice(&3u32.to_be_bytes());
+        let offset = page_size - cell.len();
+        page[offset..].copy_from_slice(&cell);
+        page[8..10].copy_from_slice(&(offset as u16).to_be_bytes());
+        data[page_size..2*page_size].copy_from_slice(&page);
+        data[2*page_size+4..2*page_size+4+payload.len()-local].copy_from_slice(&payload[local..]);
+        let db = Database::from_bytes(data.clone()).unwrap();
+        let table = db.table("books").unwrap();
+        assert_eq!(table.rows[0][1].as_str(), Some(long_title.as_str()));
+        assert_eq!(table.rows[0][0], Value::Integer(9));
+
+        // An overflow page that points to itself.
+        let mut looping = data.clone();
+        let size = 3 * page_size;
+        let min_local = (db.usable_size - 12) * 32 / 255 - 23;
+        let mut cell = varint_bytes(size as u64);
+        cell.extend(varint_bytes(9));
+        cell.extend_from_slice(&payload[..min_local]);
+        cell.extend_from_slice(&3u32.to_be_bytes());
+        let offset = 2 * page_size - cell.len();
+        looping[offset..2*page_size].copy_from_slice(&cell);
+        looping[page_size+8..page_size+10].copy_from_slice(&((offset - page_size) as u16).to_be_bytes());
+        looping[2*page_size..2*page_size+4].copy_from_slice(&3u32.to_be_bytes());
+        let db = Database::from_bytes(looping.clone()).unwrap();
+        let err = db.table("books").unwrap_err();
+        assert!(format!("{:#}", err).contains("cyclic overflow chain"));
+
+        // A payload larger than the file.
+        let cell = varint_bytes(size as u64 + 1);
+        looping[offset..offset+cell.len()].copy_from_slice(&cell);
+        let db = Database::from_bytes(looping).unwrap();
+        let err = db.table("books").unwrap_err();
+        assert!(format!("{:#}", err).contains("payload larger than the database"));
+
+        let path = std::env::temp_dir().join(format!("plato-sqlite-{}.db", std::process::id()));
+        let wal = path.with_extension("db-wal");
+        fs::write(&path, &data).unwrap();
+        assert!(Database::open(&path).is_ok());
+        fs::write(&wal, b"frames").unwrap();
+        assert!(Database::open(&path).is_err());
+        fs::remove_file(&wal).ok();
+        fs::remove_file(&path).ok();
+    }
+}
@@ -10,7 +10,7 @@ use rand_xoshiro::Xoroshiro128Plus;
 use crate::dictionary::{Dictionary, load_dictionary_from_file};
 use crate::framebuffer::{Framebuffer, Display};
 use crate::view::ViewId;
-use crate::helpers::{load_json, IsHidden};
+use crate::helpers::{load_json, asset_path, IsHidden};
 use crate::settings::Settings;
 use crate::frontlight::Frontlight;
 use crate::lightsensor::LightSensor;
@@ -20,6 +20,8 @@ use crate::device::CURRENT_DEVICE;
 use crate::library::Library;
 use crate::font::Fonts;
 use crate::rtc::Rtc;
+use crate::services::Services;
+use crate::view::Hub;
 
 const KEYBOARD_LAYOUTS_DIRNAME: &str = "keyboard-layouts";
 const DICTIONARIES_DIRNAME: &str = "dictionaries";
@@ -46,6 +48,7 @@ pub struct Context {
     pub covered: bool,
     pub shared: bool,
     pub online: bool,
+    pub services: Services,
 }
 
 impl Context {
@@ -60,7 +63,7 @@ impl Context {
                   keyboard_layouts: BTreeMap::new(), input_history: FxHashMap::default(),
                   battery, frontlight, lightsensor, notification_index: 0,
                   kb_rect: Rectangle::default(), rng, plugged: false, covered: false,
-                  shared: false, online: false }
+                  shared: false, online: false, services: Services::default() }
     }
 
     pub fn batch_import(&mut self) {
@@ -78,9 +81,13 @@ impl Context {
         }
     }
 
+    pub fn start_services(&mut self, hub: &Hub) {
+        self.services.start(&self.settings.services, self.settings.wifi, self.online, hub);
+    }
+
     pub fn load_keyboard_layouts(&mut self) {
         let glob = Glob::new("**/*.json").unwrap().compile_matcher();
-        for entry in WalkDir::new(Path::new(KEYBOARD_LAYOUTS_DIRNAME)).min_depth(1)
+        for entry in WalkDir::new(asset_path(KEYBOARD_LAYOUTS_DIRNAME)).min_depth(1)
                              .into_iter().filter_entry(|e| !e.is_hidden()) {
             if entry.is_err() {
                 continue;
@@ -0,0 +1,205 @@
+// A local control socket, used to drive the application from scripts.
+//
+// A client writes commands, one JSON object per line, and reads one JSON object per command.
+// The commands are forwarded to the main loop through the hub, and the responses are sent
+// back once the main loop has handled them.
+
+use std::fs;
+use std::mem;
+use std::thread;
+use std::path::{Path, PathBuf};
+use std::io::{BufRead, BufReader, Write};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::sync::mpsc::{self, Sender};
+use std::time::Duration;
+use anyhow::{Error, Context as ResultExt, format_err};
+use chrono::Local;
+use serde::{Serialize, Deserialize};
+use serde_json::{json, Value as JsonValue};
+use crate::view::{View, Event, Hub, EntryId, UpdateData, wait_for_all};
+use crate::view::home::Home;
+use crate::view::reader::Reader;
+use crate::document::Location;
+use crate::geom::CycleDir;
+use crate::context::Context;
+
+// How long a client waits for the main loop to handle a command.
+const RESPONSE_TIMEOUT: Duration = Duration::from_secs(30);
+
+pub type Responder = Sender<JsonValue>;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum Command {
+    // Opens the document at *path*, which is either absolute or relative to the current library.
+    // The document is opened at *location*, if given.
+    Open { path: PathBuf, location: Option<usize> },
+    // Goes to the given location (a page index, or an offset for reflowable documents).
+    GoTo { location: usize },
+    // Goes to the target of an URI (e.g. the path of a chapter inside an EPUB).
+    GoToUri { uri: String },
+    Page { dir: CycleDir },
+    Chapter { dir: CycleDir },
+    GetReaderInfo,
+    Screenshot { path: Option<PathBuf> },
+    LoadLibrary { index: Option<usize>, name: Option<String> },
+    Back,
+}
+
+pub fn listen<P: AsRef<Path>>(path: P, hub: &Hub) -> Result<(), Error> {
+    let path = path.as_ref();
+    // Remove the socket left by a previous instance.
+    if path.exists() {
+        fs::remove_file(path)
+           .with_context(|| format!("can't remove {}", path.display()))?;
+    }
+    let listener = UnixListener::bind(path)
+                                .with_context(|| format!("can't bind to {}", path.display()))?;
+    let hub = hub.clone();
+    thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            let hub = hub.clone();
+            thread::spawn(move || serve(stream, &hub));
+        }
+    });
+    Ok(())
+}
+
+fn serve(stream: UnixStream, hub: &Hub) {
+    let mut writer = match stream.try_clone() {
+        Ok(writer) => writer,
+        Err(e) => {
+            eprintln!("Can't clone control stream: {:#}.", e);
+            return;
+        },
+    };
+    let reader = BufReader::new(stream);
+    for line in reader.lines().map_while(Result::ok) {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let response = match serde_json::from_str::<Command>(&line) {
+            Ok(cmd) => request(cmd, hub),
+            Err(e) => failure(&format_err!("invalid command: {}", e)),
+        };
+        if writeln!(writer, "{}", response).is_err() {
+            break;
+        }
+    }
+}
+
+// Sends a command to the main loop and waits for the response.
+pub fn request(cmd: Command, hub: &Hub) -> JsonValue {
+    let (tx, rx) = mpsc::channel();
+    if hub.send(Event::Control(cmd, tx)).is_err() {
+        return failure(&format_err!("the main loop is gone"));
+    }
+    rx.recv_timeout(RESPONSE_TIMEOUT)
+      .unwrap_or_else(|_| failure(&format_err!("no response")))
+}
+
+fn failure(e: &Error) -> JsonValue {
+    json!({"status": "error", "message": format!("{:#}", e)})
+}
+
+// Executes a command received through the control socket and sends the response back.
+pub fn handle_command(cmd: &Command, responder: &Responder, view: &mut dyn View, hub: &Hub,
+                      updating: &mut Vec<UpdateData>, context: &mut Context) {
+    let response = match execute(cmd, view, hub, updating, context) {
+        Ok(JsonValue::Null) => json!({"status": "ok"}),
+        Ok(result) => json!({"status": "ok", "result": result}),
+        Err(e) => failure(&e),
+    };
+    responder.send(response).ok();
+}
+
+fn execute(cmd: &Command, view: &mut dyn View, hub: &Hub, updating: &mut Vec<UpdateData>, context: &mut Context) -> Result<JsonValue, Error> {
+    match *cmd {
+        Command::Open { ref path, location } => {
+            let mut info = context.library.info(path)
+                                  .ok_or_else(|| format_err!("unknown document: {}", path.display()))?;
+            if let Some(location) = location {
+                let reader_info = info.reader.get_or_insert_with(Default::default);
+                reader_info.current_page = location;
+                reader_info.finished = false;
+            }
+            // Close the current document first.
+            if let Some(reader) = view.downcast_mut::<Reader>() {
+                reader.quit(context);
+                hub.send(Event::Back).ok();
+            }
+            hub.send(Event::Open(Box::new(info))).ok();
+        },
+        Command::GoTo { location } => {
+            reader(view)?;
+            hub.send(Event::GoTo(location)).ok();
+        },
+        Command::GoToUri { ref uri } => {
+            reader(view)?;
+            hub.send(Event::GoToLocation(Location::Uri(uri.clone()))).ok();
+        },
+        Command::Page { dir } => {
+            reader(view)?;
+            hub.send(Event::Page(dir)).ok();
+        },
+        Command::Chapter { dir } => {
+            reader(view)?;
+            hub.send(Event::Chapter(dir)).ok();
+        },
+        Command::GetReaderInfo => {
+            let mut info = reader(view)?.current_info();
+            mem::swap(&mut info.reader, &mut info.reader_info);
+            return Ok(json!(info));
+        },
+        Command::Screenshot { ref path } => {
+            let path = path.clone().unwrap_or_else(|| {
+                PathBuf::from(Local::now().format("screenshot-%Y%m%d_%H%M%S.png").to_string())
+            });
+            wait_for_all(updating, context);
+            context.fb.save(&path.to_string_lossy())?;
+            return Ok(json!({"path": path}));
+        },
+        Command::LoadLibrary { index, ref name } => {
+            let index = index.or_else(|| {
+                name.as_ref().and_then(|name| context.settings.libraries.iter()
+                                                     .position(|lib| &lib.name == name))
+            }).filter(|&index| index < context.settings.libraries.len())
+              .ok_or_else(|| format_err!("unknown library"))?;
+            if !view.is::<Home>() {
+                return Err(format_err!("the library can only be switched from the home view"));
+            }
+            hub.send(Event::Select(EntryId::LoadLibrary(index))).ok();
+        },
+        Command::Back => {
+            if let Some(reader) = view.downcast_mut::<Reader>() {
+                reader.quit(context);
+            }
+            hub.send(Event::Back).ok();
+        },
+    }
+    Ok(JsonValue::Null)
+}
+
+fn reader(view: &mut dyn View) -> Result<&mut Reader, Error> {
+    view.downcast_mut::<Reader>()
+        .ok_or_else(|| format_err!("no document is open"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_commands() {
+        let cmd: Command = serde_json::from_str(r#"{"type": "open", "path": "Books/a.epub"}"#).unwrap();
+        assert!(matches!(cmd, Command::Open { ref path, location: None } if path == Path::new("Books/a.epub")));
+        let cmd: Command = serde_json::from_str(r#"{"type": "page", "dir": "previous"}"#).unwrap();
+        assert!(matches!(cmd, Command::Page { dir: CycleDir::Previous }));
+        let cmd: Command = serde_json::from_str(r#"{"type": "goToUri", "uri": "text/ch2.xhtml"}"#).unwrap();
+        assert!(matches!(cmd, Command::GoToUri { ref uri } if uri == "text/ch2.xhtml"));
+        let cmd: Command = serde_json::from_str(r#"{"type": "loadLibrary", "name": "Articles"}"#).unwrap();
+        assert!(matches!(cmd, Command::LoadLibrary { index: None, name: Some(_) }));
+        assert!(serde_json::from_str::<Command>(r#"{"type": "goTo"}"#).is_err());
+        assert!(serde_json::from_str::<Command>(r#"{"type": "fly"}"#).is_err());
+    }
+}
@@ -76,6 +76,38 @@ impl fmt::Display for Model {
     }
 }
 
+// The product name and the model number of each model.
+pub const MODELS: [(Model, &str, &str); 28] = [
+    (Model::LibraColour, "monza", ""),
+    (Model::ClaraColour, "spaColour", ""),
+    (Model::ClaraBW, "spaBW", ""),
+    (Model::Elipsa2E, "condor", ""),
+    (Model::Clara2E, "goldfinch", ""),
+    (Model::Libra2, "io", ""),
+    (Model::Sage, "cadmus", ""),
+    (Model::Elipsa, "europa", ""),
+    (Model::Nia, "luna", ""),
+    (Model::LibraH2O, "storm", ""),
+    (Model::Forma32GB, "frost", "380"),
+    (Model::Forma, "frost", "377"),
+    (Model::ClaraHD, "nova", ""),
+    (Model::AuraH2OEd2V2, "snow", "378"),
+    (Model::AuraH2OEd2V1, "snow", "374"),
+    (Model::AuraEd2V2, "star", "379"),
+    (Model::AuraEd2V1, "star", "375"),
+    (Model::AuraONELimEd, "daylight", "381"),
+    (Model::AuraONE, "daylight", "373"),
+    (Model::Touch2, "pika", ""),
+    (Model::GloHD, "alyssum", ""),
+    (Model::AuraH2O, "dahlia", ""),
+    (Model::Aura, "phoenix", ""),
+    (Model::AuraHD, "dragon", ""),
+    (Model::Mini, "pixie", ""),
+    (Model::Glo, "kraken", ""),
+    (Model::TouchC, "trilogy", "320"),
+    (Model::TouchAB, "trilogy", "310"),
+];
+
 #[derive(Debug)]
 pub struct Device {
     pub model: Model,
@@ -437,7 +469,7 @@ lazy_static! {
 
 #[cfg(test)]
 mod tests {
-    use super::Device;
+    use super::{Device, MODELS};
 
     #[test]
     fn test_device_canonical_rotation() {
@@ -450,4 +482,11 @@ mod tests {
         assert_eq!(forma.from_canonical(1) - forma.from_canonical(0),
                    aura_one.from_canonical(2) - aura_one.from_canonical(3));
     }
+
+    #[test]
+    fn test_models_identifiers() {
+        for (model, product, model_number) in MODELS.iter() {
+            assert_eq!(Device::new(product, model_number).model, *model);
+        }
+    }
 }
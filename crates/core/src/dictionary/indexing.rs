@@ -232,8 +232,8 @@ mod tests {
     use super::*;
     use std::io::Empty;
 
-    const PATH_CASE_SENSITIVE_INDEX: &str = "src/dictionary/testdata/case_sensitive_dict.index";
-    const PATH_CASE_INSENSITIVE_INDEX: &str = "src/dictionary/testdata/case_insensitive_dict.index";
+    const PATH_CASE_SENSITIVE_INDEX: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/src/dictionary/testdata/case_sensitive_dict.index");
+    const PATH_CASE_INSENSITIVE_INDEX: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/src/dictionary/testdata/case_insensitive_dict.index");
 
     #[test]
     fn test_index_find() {
@@ -121,10 +121,10 @@ pub fn load_dictionary(content: Box<dyn DictReader>, index: Box<dyn IndexReader>
 mod tests {
     use super::*;
 
-    const PATH_CASE_SENSITIVE_DICT: &str = "src/dictionary/testdata/case_sensitive_dict.dict";
-    const PATH_CASE_SENSITIVE_INDEX: &str = "src/dictionary/testdata/case_sensitive_dict.index";
-    const PATH_CASE_INSENSITIVE_DICT: &str = "src/dictionary/testdata/case_insensitive_dict.dict";
-    const PATH_CASE_INSENSITIVE_INDEX: &str = "src/dictionary/testdata/case_insensitive_dict.index";
+    const PATH_CASE_SENSITIVE_DICT: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/src/dictionary/testdata/case_sensitive_dict.dict");
+    const PATH_CASE_SENSITIVE_INDEX: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/src/dictionary/testdata/case_sensitive_dict.index");
+    const PATH_CASE_INSENSITIVE_DICT: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/src/dictionary/testdata/case_insensitive_dict.dict");
+    const PATH_CASE_INSENSITIVE_INDEX: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/src/dictionary/testdata/case_insensitive_dict.index");
 
     fn assert_dict_word_exists(mut dict: Dictionary, headword: &str, definition: &str) -> Dictionary {
         let r = dict.lookup(headword, false);
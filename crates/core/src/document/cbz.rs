@@ -0,0 +1,92 @@
+// Reads the metadata of the comic book archives from their ComicInfo.xml entry.
+
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+use zip::ZipArchive;
+use anyhow::{Error, format_err};
+use crate::metadata::Info;
+use crate::helpers::decode_entities;
+use super::html::xml::XmlParser;
+
+const COMIC_INFO_FILENAME: &str = "comicinfo.xml";
+
+pub fn extract_metadata<P: AsRef<Path>>(path: P, info: &mut Info) -> Result<(), Error> {
+    let file = File::open(path.as_ref())?;
+    let mut archive = ZipArchive::new(file)?;
+    let name = archive.file_names()
+                      .filter(|name| name.rsplit('/').next()
+                                         .is_some_and(|name| name.eq_ignore_ascii_case(COMIC_INFO_FILENAME)))
+                      .min_by_key(|name| name.len())
+                      .map(String::from)
+                      .ok_or_else(|| format_err!("missing ComicInfo.xml"))?;
+    let mut text = String::new();
+    archive.by_name(&name)?.read_to_string(&mut text)?;
+    parse_metadata(&text, info)
+}
+
+fn parse_metadata(text: &str, info: &mut Info) -> Result<(), Error> {
+    let tree = XmlParser::new(text).parse();
+    let root = tree.root();
+    let comic_info = root.find("ComicInfo")
+                         .ok_or_else(|| format_err!("missing ComicInfo element"))?;
+    let field = |name: &str| {
+        comic_info.children().find(|c| c.tag_name() == Some(name))
+                  .map(|c| decode_entities(&c.text()).trim().to_string())
+                  .unwrap_or_default()
+    };
+
+    let title = field("Title");
+    let series = field("Series");
+
+    info.title = if title.is_empty() { series.clone() } else { title };
+    info.author = field("Writer");
+    info.year = field("Year");
+    info.language = field("LanguageISO");
+    info.publisher = field("Publisher");
+    info.volume = field("Volume");
+    info.identifier = field("GTIN");
+
+    if !series.is_empty() {
+        info.series = series;
+        info.number = field("Number");
+    }
+
+    for categories in [field("Genre"), field("Tags")] {
+        info.categories.extend(categories.split(',')
+                                         .map(|s| s.trim().to_string())
+                                         .filter(|s| !s.is_empty()));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_comic_info() {
+        let text = r#"<?xml version="1.0" encoding="utf-8"?>
+<ComicInfo xmlns:xsd="http://www.w3.org/2001/XMLSchema" xmlns:xsi="http://www.w3.org/2001/XMLSchema-instance">
+  <Series>Saga</Series>
+  <Number>12</Number>
+  <Volume>2</Volume>
+  <Year>2013</Year>
+  <Writer>Brian K. Vaughan</Writer>
+  <Penciller>Fiona Staples</Penciller>
+  <Publisher>Image</Publisher>
+  <Genre>Science Fiction, Fantasy</Genre>
+  <LanguageISO>en</LanguageISO>
+</ComicInfo>"#;
+        let mut info = Info::default();
+        parse_metadata(text, &mut info).unwrap();
+        assert_eq!(info.title, "Saga");
+        assert_eq!((info.series.as_str(), info.number.as_str(), info.volume.as_str()), ("Saga", "12", "2"));
+        assert_eq!(info.author, "Brian K. Vaughan");
+        assert_eq!(info.year, "2013");
+        assert_eq!(info.publisher, "Image");
+        assert_eq!(info.language, "en");
+        assert_eq!(info.categories.len(), 2);
+    }
+}
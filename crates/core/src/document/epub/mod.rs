@@ -1,3 +1,5 @@
+pub mod opf;
+
 use std::io::Read;
 use std::fs::{self, File};
 use std::path::{Path, PathBuf};
@@ -7,10 +9,11 @@ use zip::ZipArchive;
 use percent_encoding::percent_decode_str;
 use anyhow::{Error, format_err};
 use crate::framebuffer::Pixmap;
-use crate::helpers::{Normalize, decode_entities};
+use crate::helpers::{Normalize, decode_entities, asset_path};
 use crate::document::{Document, Location, TextLocation, TocEntry, BoundedText, chapter_from_uri};
+use crate::document::{chapter, chapter_relative};
 use crate::unit::pt_to_px;
-use crate::geom::{Boundary, CycleDir};
+use crate::geom::{Boundary, CycleDir, Edge};
 use super::pdf::PdfOpener;
 use super::html::dom::{XmlTree, NodeRef};
 use super::html::engine::{Page, Engine, ResourceFetcher};
@@ -23,6 +26,10 @@ use super::html::xml::XmlParser;
 
 const VIEWER_STYLESHEET: &str = "css/epub.css";
 const USER_STYLESHEET: &str = "css/epub-user.css";
+// Fixed layout pages are laid out with one CSS pixel per pixel.
+const CSS_DPI: u16 = 96;
+// The default font size of web browsers: 16px, i.e. 12pt at 96 DPI.
+const CSS_FONT_SIZE: f32 = 12.0;
 
 type UriCache = FxHashMap<String, usize>;
 
@@ -43,12 +50,15 @@ pub struct EpubDocument {
     spine: Vec<Chunk>,
     cache: FxHashMap<usize, Vec<Page>>,
     ignore_document_css: bool,
+    fixed_layout: bool,
 }
 
 #[derive(Debug)]
 struct Chunk {
     path: String,
     size: usize,
+    // The dimensions of the page, for fixed layout documents.
+    viewport: Option<(u32, u32)>,
 }
 
 unsafe impl Send for EpubDocument {}
@@ -107,7 +117,7 @@ impl EpubDocument {
                 });
 
                 if let Some((size, path)) = vertebra_opt {
-                    spine.push(Chunk { path, size });
+                    spine.push(Chunk { path, size, viewport: None });
                 }
             }
         }
@@ -116,7 +126,7 @@ impl EpubDocument {
             return Err(format_err!("the spine is empty"));
         }
 
-        Ok(EpubDocument {
+        let mut doc = EpubDocument {
             archive,
             info,
             parent: parent.to_path_buf(),
@@ -124,7 +134,105 @@ impl EpubDocument {
             spine,
             cache: FxHashMap::default(),
             ignore_document_css: false,
-        })
+            fixed_layout: false,
+        };
+
+        // TODO: Handle documents mixing reflowable and pre-paginated spine items,
+        // through the `rendition:layout-*` properties of the itemrefs.
+        if doc.rendition("rendition:layout").as_deref() == Some("pre-paginated") {
+            doc.fixed_layout = true;
+            doc.load_viewports();
+        }
+
+        Ok(doc)
+    }
+
+    fn rendition(&self, property: &str) -> Option<String> {
+        self.info.root().find("metadata")
+            .and_then(|md| md.children().find(|child| {
+                child.tag_name() == Some("meta") &&
+                child.attribute("property") == Some(property)
+            }))
+            .map(|child| child.text().trim().to_string())
+    }
+
+    // Each spine item of a fixed layout document declares its dimensions in its viewport meta tag.
+    fn load_viewports(&mut self) {
+        let mut last_viewport = self.rendition("rendition:viewport")
+                                    .as_deref()
+                                    .and_then(parse_viewport)
+                                    .unwrap_or(self.engine.dims);
+
+        for index in 0..self.spine.len() {
+            let mut text = String::new();
+            if let Ok(mut zf) = self.archive.by_name(&self.spine[index].path) {
+                zf.read_to_string(&mut text).ok();
+            }
+            let root = XmlParser::new(&text).parse();
+            let viewport = root.root().descendants()
+                               .find(|child| child.tag_name() == Some("meta") &&
+                                             child.attribute("name") == Some("viewport"))
+                               .and_then(|child| child.attribute("content"))
+                               .and_then(parse_viewport)
+                               .unwrap_or(last_viewport);
+            self.spine[index].viewport = Some(viewport);
+            last_viewport = viewport;
+        }
+    }
+
+    // Runs the given closure with the engine set up for the viewport of the given fixed layout page.
+    fn with_viewport<T, F>(&mut self, index: usize, f: F) -> T where F: FnOnce(&mut Self) -> T {
+        let (width, height) = self.spine[index].viewport.unwrap_or(self.engine.dims);
+        let (dims, font_size, dpi, margin) = (self.engine.dims, self.engine.font_size,
+                                              self.engine.dpi, self.engine.margin);
+        self.engine.layout(width, height, CSS_FONT_SIZE, CSS_DPI);
+        self.engine.set_margin(&Edge::default());
+        let result = f(self);
+        self.engine.layout(dims.0, dims.1, font_size, dpi);
+        self.engine.set_margin(&margin);
+        result
+    }
+
+    // The display list of a fixed layout page is made of a single page.
+    fn fixed_page(&mut self, index: usize) -> Option<&Page> {
+        if !self.cache.contains_key(&index) {
+            let start_offset = self.offset(index);
+            let display_list = self.with_viewport(index, |doc| doc.build_display_list(index, start_offset));
+            self.cache.insert(index, vec![display_list.into_iter().flatten().collect()]);
+        }
+        self.cache.get(&index).and_then(|display_list| display_list.first())
+    }
+
+    fn fixed_page_index(&self, uri: &str) -> Option<usize> {
+        let name = &uri[..uri.find('#').unwrap_or(uri.len())];
+        self.spine.iter().position(|chunk| chunk.path == name)
+    }
+
+    fn fixed_toc(&self, toc: Vec<TocEntry>) -> Vec<TocEntry> {
+        toc.into_iter().map(|entry| {
+            let location = match entry.location {
+                Location::Uri(ref uri) => Location::Exact(self.fixed_page_index(uri).unwrap_or(0)),
+                location => location,
+            };
+            TocEntry {
+                location,
+                children: self.fixed_toc(entry.children),
+                .. entry
+            }
+        }).collect()
+    }
+
+    fn fixed_texts<F>(&mut self, loc: Location, filter: F) -> Option<(Vec<BoundedText>, usize)>
+                     where F: Fn(&DrawCommand) -> Option<(String, Boundary)> {
+        let index = self.resolve_location(loc)?;
+        let page = self.fixed_page(index)?;
+        Some((page.iter().filter_map(filter).enumerate().map(|(i, (text, rect))| {
+            BoundedText {
+                text,
+                rect,
+                location: TextLocation::Static(index, i),
+            }
+        }).collect(), index))
     }
 
     fn offset(&self, index: usize) -> usize {
@@ -331,12 +439,12 @@ impl EpubDocument {
 
         let mut stylesheet = StyleSheet::new();
 
-        if let Ok(text) = fs::read_to_string(VIEWER_STYLESHEET) {
+        if let Ok(text) = fs::read_to_string(asset_path(VIEWER_STYLESHEET)) {
             let mut css = CssParser::new(&text).parse();
             stylesheet.append(&mut css, true);
         }
 
-        if let Ok(text) = fs::read_to_string(USER_STYLESHEET) {
+        if let Ok(text) = fs::read_to_string(asset_path(USER_STYLESHEET)) {
             let mut css = CssParser::new(&text).parse();
             stylesheet.append(&mut css, true);
         }
@@ -608,11 +716,19 @@ impl Document for EpubDocument {
     }
 
     #[inline]
-    fn dims(&self, _index: usize) -> Option<(f32, f32)> {
+    fn dims(&self, index: usize) -> Option<(f32, f32)> {
+        if self.fixed_layout {
+            return self.spine.get(index)
+                       .and_then(|chunk| chunk.viewport)
+                       .map(|(width, height)| (width as f32, height as f32));
+        }
         Some((self.engine.dims.0 as f32, self.engine.dims.1 as f32))
     }
 
     fn pages_count(&self) -> usize {
+        if self.fixed_layout {
+            return self.spine.len();
+        }
         self.spine.iter().map(|c| c.size).sum()
     }
 
@@ -647,7 +763,7 @@ impl Document for EpubDocument {
 
         let root = XmlParser::new(&text).parse();
 
-        if name.ends_with(".ncx") {
+        let toc = if name.ends_with(".ncx") {
             root.root().find("navMap").map(|map| {
                 self.walk_toc_ncx(map, toc_dir, &mut 0, &mut FxHashMap::default())
             })
@@ -658,10 +774,20 @@ impl Document for EpubDocument {
                 .and_then(|map| map.find("ol")).map(|map| {
                 self.walk_toc_nav(map, toc_dir, &mut 0, &mut FxHashMap::default())
             })
+        };
+
+        if self.fixed_layout {
+            toc.map(|toc| self.fixed_toc(toc))
+        } else {
+            toc
         }
     }
 
     fn chapter<'a>(&mut self, offset: usize, toc: &'a [TocEntry]) -> Option<(&'a TocEntry, f32)> {
+        if self.fixed_layout {
+            return chapter(offset, self.pages_count(), toc);
+        }
+
         let next_offset = self.resolve_location(Location::Next(offset))
                               .unwrap_or(usize::MAX);
         let (index, start_offset) = self.vertebra_coordinates(offset)?;
@@ -701,6 +827,10 @@ impl Document for EpubDocument {
     }
 
     fn chapter_relative<'a>(&mut self, offset: usize, dir: CycleDir, toc: &'a [TocEntry]) -> Option<&'a TocEntry> {
+        if self.fixed_layout {
+            return chapter_relative(offset, dir, toc);
+        }
+
         let next_offset = self.resolve_location(Location::Next(offset))
                               .unwrap_or(usize::MAX);
         let chap = self.chapter(offset, toc).map(|(c, _)| c);
@@ -714,6 +844,27 @@ impl Document for EpubDocument {
     fn resolve_location(&mut self, loc: Location) -> Option<usize> {
         self.engine.load_fonts();
 
+        if self.fixed_layout {
+            return match loc {
+                Location::Exact(index) => Some(index).filter(|&index| index < self.spine.len()),
+                Location::Previous(index) => index.checked_sub(1),
+                Location::Next(index) => Some(index + 1).filter(|&index| index < self.spine.len()),
+                Location::LocalUri(index, ref uri) => {
+                    let path = &self.spine.get(index)?.path;
+                    let parent = Path::new(path).parent()
+                                      .unwrap_or_else(|| Path::new(""));
+                    let uri = if uri.starts_with('#') {
+                        format!("{}{}", path, uri)
+                    } else {
+                        parent.join(uri).normalize()
+                              .to_string_lossy().into_owned()
+                    };
+                    self.fixed_page_index(&uri)
+                },
+                Location::Uri(ref uri) => self.fixed_page_index(uri),
+            };
+        }
+
         match loc {
             Location::Exact(offset) => {
                 let (index, start_offset) = self.vertebra_coordinates(offset)?;
@@ -787,6 +938,13 @@ impl Document for EpubDocument {
             return None;
         }
 
+        if self.fixed_layout {
+            return self.fixed_texts(loc, |dc| match dc {
+                DrawCommand::Text(TextCommand { text, rect, .. }) => Some((text.clone(), (*rect).into())),
+                _ => None,
+            });
+        }
+
         let offset = self.resolve_location(loc)?;
         let (index, start_offset) = self.vertebra_coordinates(offset)?;
         let page_index = self.page_index(offset, index, start_offset)?;
@@ -816,6 +974,16 @@ impl Document for EpubDocument {
             return None;
         }
 
+        if self.fixed_layout {
+            return self.fixed_texts(loc, |dc| match dc {
+                DrawCommand::Text(TextCommand { uri, rect, .. }) |
+                DrawCommand::Image(ImageCommand { uri, rect, .. }) if uri.is_some() => {
+                    Some((uri.clone().unwrap(), (*rect).into()))
+                },
+                _ => None,
+            });
+        }
+
         let offset = self.resolve_location(loc)?;
         let (index, start_offset) = self.vertebra_coordinates(offset)?;
         let page_index = self.page_index(offset, index, start_offset)?;
@@ -842,6 +1010,16 @@ impl Document for EpubDocument {
             return None;
         }
 
+        if self.fixed_layout {
+            let index = self.resolve_location(loc)?;
+            return self.fixed_page(index).map(|page| {
+                (page.iter().filter_map(|dc| match dc {
+                    DrawCommand::Image(ImageCommand { rect, .. }) => Some((*rect).into()),
+                    _ => None,
+                }).collect(), index)
+            });
+        }
+
         let offset = self.resolve_location(loc)?;
         let (index, start_offset) = self.vertebra_coordinates(offset)?;
         let page_index = self.page_index(offset, index, start_offset)?;
@@ -861,6 +1039,15 @@ impl Document for EpubDocument {
             return None;
         }
 
+        if self.fixed_layout {
+            let index = self.resolve_location(loc)?;
+            let page = self.fixed_page(index)?.clone();
+            let pixmap = self.with_viewport(index, |doc| {
+                doc.engine.render_page(&page, scale, samples, &mut doc.archive)
+            })?;
+            return Some((pixmap, index));
+        }
+
         let offset = self.resolve_location(loc)?;
         let (index, start_offset) = self.vertebra_coordinates(offset)?;
 
@@ -928,10 +1115,51 @@ impl Document for EpubDocument {
     }
 
     fn is_reflowable(&self) -> bool {
-        true
+        !self.fixed_layout
     }
 
     fn has_synthetic_page_numbers(&self) -> bool {
-        true
+        !self.fixed_layout
+    }
+
+    fn spread(&self) -> Option<bool> {
+        if !self.fixed_layout {
+            return None;
+        }
+        // The spreads are only honored in landscape.
+        Some(self.rendition("rendition:spread").as_deref() != Some("none"))
+    }
+}
+
+// Parses viewport declarations such as *width=1200, height=1600*.
+fn parse_viewport(text: &str) -> Option<(u32, u32)> {
+    let mut width = None;
+    let mut height = None;
+
+    for pair in text.split([',', ';']) {
+        let mut parts = pair.splitn(2, '=');
+        let key = parts.next().map(str::trim);
+        let value = parts.next().and_then(|v| v.trim().trim_end_matches("px").parse::<f32>().ok());
+        match key {
+            Some("width") => width = value,
+            Some("height") => height = value,
+            _ => (),
+        }
+    }
+
+    width.zip(height)
+         .filter(|&(w, h)| w >= 1.0 && h >= 1.0)
+         .map(|(w, h)| (w.round() as u32, h.round() as u32))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse_viewport;
+
+    #[test]
+    fn test_parse_viewport() {
+        assert_eq!(parse_viewport("width=1200, height=1600"), Some((1200, 1600)));
+        assert_eq!(parse_viewport("width = 600px; height = 800px"), Some((600, 800)));
+        assert_eq!(parse_viewport("width=device-width, initial-scale=1"), None);
     }
 }
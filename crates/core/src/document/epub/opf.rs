@@ -0,0 +1,264 @@
+// Writes the metadata of a library entry into the package document of an EPUB file.
+
+use std::fs::{self, File};
+use std::io::{Read, Write};
+use std::path::Path;
+use lazy_static::lazy_static;
+use regex::Regex;
+use zip::{ZipArchive, ZipWriter, CompressionMethod};
+use zip::write::SimpleFileOptions;
+use anyhow::{Error, format_err};
+use crate::metadata::Info;
+use crate::helpers::escape;
+use super::super::html::xml::XmlParser;
+
+lazy_static! {
+    static ref METADATA_START: Regex = Regex::new(r"<(?:[\w.-]+:)?metadata\b[^>]*>").unwrap();
+    static ref METADATA_END: Regex = Regex::new(r"</(?:[\w.-]+:)?metadata\s*>").unwrap();
+    static ref TITLE: Regex = element(r"dc:title");
+    static ref CREATOR: Regex = element(r"dc:creator");
+    static ref LANGUAGE: Regex = element(r"dc:language");
+    static ref SUBJECT: Regex = element(r"dc:subject");
+    static ref SERIES: Regex = meta(r#"\b(?:name\s*=\s*["']calibre:series(?:_index)?["']|property\s*=\s*["'](?:belongs-to-collection|group-position)["'])"#);
+    static ref ID: Regex = Regex::new(r#"^[^>]*\bid\s*=\s*["']([^"']+)["']"#).unwrap();
+    static ref ROLE: Regex = Regex::new(r#"^[^>]*\brole\s*=\s*["']([^"']+)["']"#).unwrap();
+    static ref ROLE_PROPERTY: Regex = Regex::new(r#"^[^>]*\bproperty\s*=\s*["']role["']"#).unwrap();
+}
+
+// Matches the element and the white space that precedes it.
+fn element(name: &str) -> Regex {
+    Regex::new(&format!(r"(?s)\s*<{0}\b[^>]*?(?:/>|>.*?</{0}\s*>)", name)).unwrap()
+}
+
+fn meta(attribute: &str) -> Regex {
+    Regex::new(&format!(r"(?s)\s*<(?:opf:)?meta\b[^>]*?{}[^>]*?(?:/>|>.*?</(?:opf:)?meta\s*>)", attribute)).unwrap()
+}
+
+pub fn write_metadata<P: AsRef<Path>>(path: P, info: &Info) -> Result<(), Error> {
+    let path = path.as_ref();
+    let mut archive = ZipArchive::new(File::open(path)?)?;
+
+    let opf_path = {
+        let mut zf = archive.by_name("META-INF/container.xml")?;
+        let mut text = String::new();
+        zf.read_to_string(&mut text)?;
+        let root = XmlParser::new(&text).parse();
+        root.root().find("rootfile")
+            .and_then(|e| e.attribute("full-path"))
+            .map(String::from)
+    }.ok_or_else(|| format_err!("can't get the OPF path"))?;
+
+    let text = {
+        let mut zf = archive.by_name(&opf_path)?;
+        let mut text = String::new();
+        zf.read_to_string(&mut text)?;
+        text
+    };
+
+    let text = update_metadata(&text, info)?;
+    let file_name = path.file_name().ok_or_else(|| format_err!("invalid path"))?;
+    let tmp_path = path.with_file_name(format!(".{}.tmp", file_name.to_string_lossy()));
+
+    let result = (|| -> Result<(), Error> {
+        let mut writer = ZipWriter::new(File::create(&tmp_path)?);
+        // The entries are copied in order, so that the mimetype stays first.
+        for index in 0..archive.len() {
+            let zf = archive.by_index_raw(index)?;
+            if zf.name() == opf_path {
+                let options = SimpleFileOptions::default().compression_method(CompressionMethod::Deflated);
+                writer.start_file(opf_path.as_str(), options)?;
+                writer.write_all(text.as_bytes())?;
+            } else {
+                writer.raw_copy_file(zf)?;
+            }
+        }
+        writer.finish()?.sync_all()?;
+        fs::rename(&tmp_path, path)?;
+        Ok(())
+    })();
+
+    if result.is_err() {
+        fs::remove_file(&tmp_path).ok();
+    }
+
+    result
+}
+
+// Writes the metadata into a package document saved next to a document, e.g. by Calibre.
+pub fn write_opf<P: AsRef<Path>>(path: P, info: &Info) -> Result<(), Error> {
+    let path = path.as_ref();
+    let text = fs::read_to_string(path)?;
+    let text = update_metadata(&text, info)?;
+    let file_name = path.file_name().ok_or_else(|| format_err!("invalid path"))?;
+    let tmp_path = path.with_file_name(format!(".{}.tmp", file_name.to_string_lossy()));
+    fs::write(&tmp_path, text).and_then(|_| fs::rename(&tmp_path, path)).map_err(|e| {
+        fs::remove_file(&tmp_path).ok();
+        e.into()
+    })
+}
+
+// Replaces the title, authors, language, subjects and series of the package document.
+// The title and the language are required: empty values leave them untouched.
+// The creators that aren't authors, e.g. the illustrators, are kept.
+fn update_metadata(text: &str, info: &Info) -> Result<String, Error> {
+    let start = METADATA_START.find(text)
+                              .ok_or_else(|| format_err!("the metadata is missing"))?;
+    let end = METADATA_END.find_at(text, start.end())
+                          .ok_or_else(|| format_err!("the metadata isn't closed"))?;
+    let mut body = text[start.end()..end.start()].to_string();
+    let mut ids = Vec::new();
+    let mut elements = Vec::new();
+
+    if !info.title.is_empty() {
+        body = remove(&body, &TITLE, &mut ids);
+        elements.push(format!("<dc:title>{}</dc:title>", escape(&info.title)));
+    }
+
+    body = remove_authors(&body, &mut ids);
+    for author in info.author.split(',').map(str::trim).filter(|author| !author.is_empty()) {
+        elements.push(format!("<dc:creator>{}</dc:creator>", escape(author)));
+    }
+
+    if !info.language.is_empty() {
+        body = remove(&body, &LANGUAGE, &mut ids);
+        elements.push(format!("<dc:language>{}</dc:language>", escape(&info.language)));
+    }
+
+    body = remove(&body, &SUBJECT, &mut ids);
+    for category in &info.categories {
+        elements.push(format!("<dc:subject>{}</dc:subject>", escape(category)));
+    }
+
+    body = remove(&body, &SERIES, &mut ids);
+    if !info.series.is_empty() {
+        elements.push(format!("<meta name=\"calibre:series\" content=\"{}\"/>", escape(&info.series)));
+        if !info.number.is_empty() {
+            elements.push(format!("<meta name=\"calibre:series_index\" content=\"{}\"/>", escape(&info.number)));
+        }
+    }
+
+    // Drop the refinements of the removed elements.
+    for id in ids {
+        let refines = meta(&format!(r#"\brefines\s*=\s*["']#{}["']"#, regex::escape(&id)));
+        body = refines.replace_all(&body, "").into_owned();
+    }
+
+    // Indent the new elements like the first remaining one.
+    let indent = body.find('<')
+                     .and_then(|index| body[..index].rsplit('\n').next())
+                     .filter(|prefix| prefix.trim().is_empty())
+                     .unwrap_or("    ")
+                     .to_string();
+    let trimmed_len = body.trim_end().len();
+    let trailer = body.split_off(trimmed_len);
+
+    for element in elements {
+        body.push('\n');
+        body.push_str(&indent);
+        body.push_str(&element);
+    }
+
+    body.push_str(if trailer.is_empty() { "\n" } else { &trailer });
+
+    let mut result = String::with_capacity(text.len() + body.len());
+    result.push_str(&text[..start.end()]);
+    result.push_str(&body);
+    result.push_str(&text[end.start()..]);
+
+    Ok(result)
+}
+
+fn remove(body: &str, re: &Regex, ids: &mut Vec<String>) -> String {
+    for m in re.find_iter(body) {
+        if let Some(caps) = ID.captures(m.as_str().trim_start()) {
+            ids.push(caps[1].to_string());
+        }
+    }
+    re.replace_all(body, "").into_owned()
+}
+
+// The creators without a role are considered authors. The role is either
+// an attribute (EPUB 2), or a refinement (EPUB 3).
+fn remove_authors(body: &str, ids: &mut Vec<String>) -> String {
+    let mut result = String::with_capacity(body.len());
+    let mut last = 0;
+
+    for m in CREATOR.find_iter(body) {
+        let element = m.as_str().trim_start();
+        let id = ID.captures(element).map(|caps| caps[1].to_string());
+        let role = ROLE.captures(element).map(|caps| caps[1].trim().to_string())
+                       .or_else(|| id.as_deref().and_then(|id| refined_role(body, id)));
+        if role.is_none_or(|role| role == "aut") {
+            result.push_str(&body[last..m.start()]);
+            last = m.end();
+            ids.extend(id);
+        }
+    }
+
+    result.push_str(&body[last..]);
+    result
+}
+
+fn refined_role(body: &str, id: &str) -> Option<String> {
+    let refines = meta(&format!(r#"\brefines\s*=\s*["']#{}["']"#, regex::escape(id)));
+    let role = refines.find_iter(body)
+                      .map(|m| m.as_str().trim_start())
+                      .find(|element| ROLE_PROPERTY.is_match(element))
+                      .and_then(|element| element.split_once('>'))
+                      .map(|(_, rest)| rest.split('<').next().unwrap_or_default().trim().to_string());
+    role
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_update_metadata() {
+        let text = r##"<?xml version="1.0" encoding="UTF-8"?>
+<package xmlns="http://www.idpf.org/2007/opf" version="3.0" unique-identifier="uid">
+  <metadata xmlns:dc="http://purl.org/dc/elements/1.1/" xmlns:opf="http://www.idpf.org/2007/opf">
+    <dc:identifier id="uid">urn:isbn:9780441478125</dc:identifier>
+    <dc:title id="t1">Left Hand</dc:title>
+    <meta refines="#t1" property="title-type">main</meta>
+    <dc:creator id="c1">Le Guin</dc:creator>
+    <meta refines="#c1" property="role" scheme="marc:relators">aut</meta>
+    <dc:creator id="c2">Jane Doe</dc:creator>
+    <meta refines="#c2" property="role" scheme="marc:relators">ill</meta>
+    <dc:creator opf:role="trl">John Doe</dc:creator>
+    <dc:language>en</dc:language>
+    <dc:subject>Fiction</dc:subject>
+    <meta property="belongs-to-collection" id="s1">Hainish</meta>
+    <meta refines="#s1" property="group-position">4</meta>
+    <meta name="cover" content="cover-image"/>
+  </metadata>
+  <manifest/>
+</package>"##;
+        let info = Info {
+            title: "The Left Hand of Darkness".to_string(),
+            author: "Ursula K. Le Guin, Someone Else".to_string(),
+            series: "Hainish Cycle".to_string(),
+            number: "6".to_string(),
+            categories: ["Science Fiction".to_string(), "Gender & Sexuality".to_string()].into_iter().collect(),
+            .. Default::default()
+        };
+        let result = update_metadata(text, &info).unwrap();
+        let tree = XmlParser::new(&result).parse();
+        let metadata = tree.root().find("metadata").unwrap();
+        let texts = |name: &str| metadata.children()
+                                         .filter(|c| c.tag_qualified_name() == Some(name))
+                                         .map(|c| c.text())
+                                         .collect::<Vec<String>>();
+        assert_eq!(texts("dc:title"), ["The Left Hand of Darkness"]);
+        assert_eq!(texts("dc:creator"), ["Jane Doe", "John Doe", "Ursula K. Le Guin", "Someone Else"]);
+        assert_eq!(texts("dc:language"), ["en"]);
+        assert_eq!(texts("dc:subject"), ["Gender &amp; Sexuality", "Science Fiction"]);
+        assert_eq!(texts("dc:identifier"), ["urn:isbn:9780441478125"]);
+        assert!(!result.contains("#c1"));
+        assert!(result.contains(r##"<meta refines="#c2" property="role" scheme="marc:relators">ill</meta>"##));
+        assert!(!result.contains("belongs-to-collection"));
+        assert!(result.contains(r#"<meta name="cover" content="cover-image"/>"#));
+        assert!(result.contains("\n    <meta name=\"calibre:series\" content=\"Hainish Cycle\"/>"));
+        assert!(result.contains("\n    <meta name=\"calibre:series_index\" content=\"6\"/>\n  </metadata>"));
+    }
+}
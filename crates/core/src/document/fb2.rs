@@ -0,0 +1,191 @@
+// Reads the metadata of the FictionBook documents from their description.
+
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+use anyhow::{Error, format_err};
+use crate::metadata::Info;
+use crate::helpers::decode_entities;
+use super::html::xml::XmlParser;
+use super::html::dom::NodeRef;
+
+const DESCRIPTION_END: &[u8] = b"</description>";
+
+// The body and the embedded images come after the description: the file is only read up to its end.
+pub fn extract_metadata<P: AsRef<Path>>(path: P, info: &mut Info) -> Result<(), Error> {
+    let mut file = File::open(path.as_ref())?;
+    let mut bytes = Vec::new();
+    let mut chunk = [0; 8192];
+
+    loop {
+        let n = file.read(&mut chunk)?;
+        if n == 0 {
+            break;
+        }
+        let start = bytes.len().saturating_sub(DESCRIPTION_END.len() - 1);
+        bytes.extend_from_slice(&chunk[..n]);
+        if bytes[start..].windows(DESCRIPTION_END.len()).any(|w| w == DESCRIPTION_END) {
+            break;
+        }
+    }
+
+    parse_metadata(&bytes, info)
+}
+
+fn parse_metadata(bytes: &[u8], info: &mut Info) -> Result<(), Error> {
+    let end = bytes.windows(DESCRIPTION_END.len()).position(|w| w == DESCRIPTION_END)
+                   .ok_or_else(|| format_err!("missing description"))?;
+    let text = decode(&bytes[..end]);
+    let tree = XmlParser::new(&text).parse();
+    let root = tree.root();
+
+    let title_info = root.find("title-info")
+                         .ok_or_else(|| format_err!("missing title info"))?;
+    let publish_info = root.find("publish-info");
+    let text_of = |node: Option<NodeRef>, name: &str| {
+        node.and_then(|n| n.children().find(|c| c.tag_name() == Some(name)))
+            .map(|c| decode_entities(&c.text()).trim().to_string())
+            .unwrap_or_default()
+    };
+
+    let mut authors = Vec::new();
+
+    for child in title_info.children() {
+        match child.tag_name() {
+            Some("author") => {
+                let names: Vec<String> = ["first-name", "middle-name", "last-name"].iter()
+                                             .map(|name| text_of(Some(child), name))
+                                             .filter(|name| !name.is_empty())
+                                             .collect();
+                if names.is_empty() {
+                    let nickname = text_of(Some(child), "nickname");
+                    if !nickname.is_empty() {
+                        authors.push(nickname);
+                    }
+                } else {
+                    authors.push(names.join(" "));
+                }
+            },
+            Some("genre") => {
+                let genre = decode_entities(&child.text()).trim().to_string();
+                if !genre.is_empty() {
+                    info.categories.insert(genre);
+                }
+            },
+            _ => (),
+        }
+    }
+
+    info.title = text_of(Some(title_info), "book-title");
+    info.author = authors.join(", ");
+    info.language = text_of(Some(title_info), "lang");
+    info.publisher = text_of(publish_info, "publisher");
+
+    let year = text_of(publish_info, "year");
+    info.year = if year.is_empty() {
+        title_info.children().find(|c| c.tag_name() == Some("date"))
+                  .map(|date| date.attribute("value").map(String::from)
+                                  .unwrap_or_else(|| date.text()))
+                  .map(|date| date.trim().chars().take(4).collect())
+                  .unwrap_or_default()
+    } else {
+        year
+    };
+
+    let isbn = text_of(publish_info, "isbn");
+    info.identifier = if isbn.is_empty() {
+        text_of(root.find("document-info"), "id")
+    } else {
+        isbn
+    };
+
+    let sequence = title_info.children().find(|c| c.tag_name() == Some("sequence"))
+                             .or_else(|| publish_info.and_then(|n| n.children()
+                                                                    .find(|c| c.tag_name() == Some("sequence"))));
+    if let Some(sequence) = sequence {
+        info.series = sequence.attribute("name").map(|s| decode_entities(s).trim().to_string())
+                              .unwrap_or_default();
+        info.number = sequence.attribute("number").map(|s| s.trim().to_string())
+                              .unwrap_or_default();
+    }
+
+    Ok(())
+}
+
+// Most documents are encoded in UTF-8 or Windows-1251.
+fn decode(bytes: &[u8]) -> String {
+    let declaration = bytes.iter().position(|&b| b == b'>')
+                           .map(|end| String::from_utf8_lossy(&bytes[..end]).to_lowercase())
+                           .unwrap_or_default();
+    if declaration.starts_with("<?xml") && declaration.contains("1251") {
+        bytes.iter().map(|&b| cp1251_char(b)).collect()
+    } else if declaration.starts_with("<?xml") && (declaration.contains("1252") || declaration.contains("8859-1")) {
+        bytes.iter().map(|&b| b as char).collect()
+    } else {
+        String::from_utf8_lossy(bytes).into_owned()
+    }
+}
+
+fn cp1251_char(byte: u8) -> char {
+    const HIGH: [char; 64] = ['Ђ', 'Ѓ', '‚', 'ѓ', '„', '…', '†', '‡', '€', '‰', 'Љ', '‹', 'Њ', 'Ќ', 'Ћ', 'Џ',
+                              'ђ', '‘', '’', '“', '”', '•', '–', '—', '\u{98}', '™', 'љ', '›', 'њ', 'ќ', 'ћ', 'џ',
+                              '\u{A0}', 'Ў', 'ў', 'Ј', '¤', 'Ґ', '¦', '§', 'Ё', '©', 'Є', '«', '¬', '\u{AD}', '®', 'Ї',
+                              '°', '±', 'І', 'і', 'ґ', 'µ', '¶', '·', 'ё', '№', 'є', '»', 'ј', 'Ѕ', 'ѕ', 'ї'];
+    match byte {
+        0x00..=0x7F => byte as char,
+        0x80..=0xBF => HIGH[(byte - 0x80) as usize],
+        _ => char::from_u32(0x0410 + (byte - 0xC0) as u32).unwrap_or('\u{FFFD}'),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_title_info() {
+        let text = r#"<?xml version="1.0" encoding="UTF-8"?>
+<FictionBook xmlns="http://www.gribuser.ru/xml/fictionbook/2.0" xmlns:l="http://www.w3.org/1999/xlink">
+  <description>
+    <title-info>
+      <genre>sf_fantasy</genre>
+      <author><first-name>Terry</first-name><last-name>Pratchett</last-name></author>
+      <book-title>Guards! Guards!</book-title>
+      <date value="1989-01-01">1989</date>
+      <lang>en</lang>
+      <sequence name="Discworld" number="8"/>
+    </title-info>
+    <document-info>
+      <author><nickname>scanner</nickname></author>
+      <id>6F3C1B2E-0000</id>
+    </document-info>
+    <publish-info>
+      <publisher>Gollancz &amp; Co</publisher>
+      <isbn>978-0-575-04606-1</isbn>
+    </publish-info>
+  </description>
+  <body><section><p>Text.</p></section></body>
+</FictionBook>"#;
+        let mut info = Info::default();
+        parse_metadata(text.as_bytes(), &mut info).unwrap();
+        assert_eq!(info.title, "Guards! Guards!");
+        assert_eq!(info.author, "Terry Pratchett");
+        assert_eq!(info.year, "1989");
+        assert_eq!(info.language, "en");
+        assert_eq!(info.publisher, "Gollancz & Co");
+        assert_eq!(info.identifier, "978-0-575-04606-1");
+        assert_eq!((info.series.as_str(), info.number.as_str()), ("Discworld", "8"));
+        assert!(info.categories.contains("sf_fantasy"));
+        // The closing tag of the description straddles two chunks.
+        let padding = " ".repeat(8192 - text.find("</description>").unwrap() - 7);
+        let path = std::env::temp_dir().join(format!("plato-fb2-{}.fb2", std::process::id()));
+        std::fs::write(&path, text.replacen("</publish-info>", &format!("</publish-info>{}", padding), 1)).unwrap();
+        let mut other = Info::default();
+        let result = extract_metadata(&path, &mut other);
+        std::fs::remove_file(&path).ok();
+        result.unwrap();
+        assert_eq!((other.title, other.identifier), (info.title.clone(), info.identifier.clone()));
+        assert_eq!(decode(b"<?xml version=\"1.0\" encoding=\"windows-1251\"?>\xCC\xE8\xF0"),
+                   "<?xml version=\"1.0\" encoding=\"windows-1251\"?>Мир");
+    }
+}
@@ -1,10 +1,11 @@
 use std::fs;
-use std::path::{Path, PathBuf};
+use std::path::PathBuf;
 use std::fmt::Debug;
 use fxhash::FxHashMap;
 use lazy_static::lazy_static;
 use kl_hyphenate::{Standard, Language, Load};
 use crate::color::Color;
+use crate::helpers::asset_path;
 use crate::geom::{Point, Rectangle, Edge};
 use crate::font::{FontFamily, Font, RenderPlan};
 pub use crate::metadata::TextAlign;
@@ -547,7 +548,7 @@ pub static ref HYPHENATION_PATTERNS: FxHashMap<Language, Standard> = {
         if map.contains_key(lang) {
             continue;
         }
-        let base = Path::new("hyphenation-patterns")
+        let base = asset_path("hyphenation-patterns")
                         .join(lang.code());
         let path = base.with_extension("standard.bincode");
         if let Ok(mut patterns) = Standard::from_path(*lang, path) {
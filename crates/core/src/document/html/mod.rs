@@ -12,7 +12,7 @@ use std::path::{Path, PathBuf};
 use fxhash::FxHashMap;
 use anyhow::Error;
 use crate::framebuffer::Pixmap;
-use crate::helpers::{Normalize, decode_entities};
+use crate::helpers::{Normalize, decode_entities, asset_path};
 use crate::document::{Document, Location, TextLocation, TocEntry, BoundedText};
 use crate::unit::pt_to_px;
 use crate::geom::{Boundary, Edge, CycleDir};
@@ -163,12 +163,12 @@ impl HtmlDocument {
         let mut stylesheet = StyleSheet::new();
         let spine_dir = PathBuf::default();
 
-        if let Ok(text) = fs::read_to_string(&self.viewer_stylesheet) {
+        if let Ok(text) = fs::read_to_string(asset_path(&self.viewer_stylesheet)) {
             let mut css = CssParser::new(&text).parse();
             stylesheet.append(&mut css, true);
         }
 
-        if let Ok(text) = fs::read_to_string(&self.user_stylesheet) {
+        if let Ok(text) = fs::read_to_string(asset_path(&self.user_stylesheet)) {
             let mut css = CssParser::new(&text).parse();
             stylesheet.append(&mut css, true);
         }
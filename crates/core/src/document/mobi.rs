@@ -0,0 +1,251 @@
+// Reads the metadata of the MOBI, AZW and AZW3 (KF8) documents from the EXTH records.
+
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::Path;
+use byteorder::{BigEndian, ByteOrder};
+use lazy_static::lazy_static;
+use regex::Regex;
+use anyhow::{Error, format_err};
+use crate::metadata::Info;
+
+const EXTH_AUTHOR: u32 = 100;
+const EXTH_PUBLISHER: u32 = 101;
+const EXTH_ISBN: u32 = 104;
+const EXTH_SUBJECT: u32 = 105;
+const EXTH_PUBLISHING_DATE: u32 = 106;
+const EXTH_ASIN: u32 = 113;
+const EXTH_UPDATED_TITLE: u32 = 503;
+const EXTH_LANGUAGE: u32 = 524;
+
+const UTF_8: u32 = 65001;
+
+// The size of the PDB header, without the record list.
+const PDB_HEADER_LEN: usize = 78;
+// The first record holds the MOBI and the EXTH headers: it's seldom bigger than a few KiB.
+const MAX_RECORD_LEN: u64 = 1 << 20;
+
+lazy_static! {
+    // Amazon puts the series in the title: *The Two Towers (The Lord of the Rings, Book 2)*.
+    static ref SERIES_TITLE: Regex = Regex::new(r"^(.+?)\s+\((.+?),?\s+(?:Book|Volume|Vol\.|Part|#)\s*(\d+(?:\.\d+)?)\)$").unwrap();
+}
+
+// Only the PDB header and the first record are read.
+pub fn extract_metadata<P: AsRef<Path>>(path: P, info: &mut Info) -> Result<(), Error> {
+    let mut file = File::open(path.as_ref())?;
+    let mut pdb = [0; PDB_HEADER_LEN];
+    file.read_exact(&mut pdb)?;
+
+    if !matches!(&pdb[60..68], b"BOOKMOBI" | b"TEXtREAd") {
+        return Err(format_err!("not a MOBI document"));
+    }
+
+    let count = BigEndian::read_u16(&pdb[76..78]) as usize;
+    if count == 0 {
+        return Err(format_err!("no records"));
+    }
+
+    let mut entries = vec![0; 8 * count.min(2)];
+    file.read_exact(&mut entries)?;
+    let start = BigEndian::read_u32(&entries[0..4]) as u64;
+    let end = if count > 1 {
+        BigEndian::read_u32(&entries[8..12]) as u64
+    } else {
+        file.metadata()?.len()
+    };
+    let len = end.checked_sub(start)
+                 .ok_or_else(|| format_err!("invalid first record offset"))?;
+
+    let mut header = Vec::new();
+    file.seek(SeekFrom::Start(start))?;
+    file.take(len.min(MAX_RECORD_LEN)).read_to_end(&mut header)?;
+    parse_metadata(&header, info)
+}
+
+// Parses the first record of the document.
+fn parse_metadata(header: &[u8], info: &mut Info) -> Result<(), Error> {
+    if header.len() < 132 || &header[16..20] != b"MOBI" {
+        return Err(format_err!("missing MOBI header"));
+    }
+
+    let header_len = BigEndian::read_u32(&header[20..24]) as usize;
+    let encoding = BigEndian::read_u32(&header[28..32]);
+    let decode = |data: &[u8]| {
+        if encoding == UTF_8 {
+            String::from_utf8_lossy(data).trim().to_string()
+        } else {
+            data.iter().map(|&b| cp1252_char(b)).collect::<String>().trim().to_string()
+        }
+    };
+
+    let name_offset = BigEndian::read_u32(&header[84..88]) as usize;
+    let name_len = BigEndian::read_u32(&header[88..92]) as usize;
+    let locale = BigEndian::read_u32(&header[92..96]);
+    let exth_flags = BigEndian::read_u32(&header[128..132]);
+
+    let mut title = header.get(name_offset..name_offset.saturating_add(name_len))
+                          .map(decode)
+                          .unwrap_or_default();
+    let mut authors = Vec::new();
+    let mut language = String::new();
+    let mut isbn = String::new();
+    let mut asin = String::new();
+
+    if exth_flags & 0x40 != 0 {
+        let exth = header.get(16 + header_len..)
+                         .filter(|exth| exth.len() >= 12 && &exth[..4] == b"EXTH")
+                         .ok_or_else(|| format_err!("missing EXTH header"))?;
+        let count = BigEndian::read_u32(&exth[8..12]);
+        let mut offset = 12;
+
+        for _ in 0..count {
+            if offset + 8 > exth.len() {
+                break;
+            }
+            let kind = BigEndian::read_u32(&exth[offset..offset+4]);
+            let len = BigEndian::read_u32(&exth[offset+4..offset+8]) as usize;
+            let Some(data) = exth.get(offset+8..offset+len.max(8)) else {
+                break;
+            };
+            offset += len.max(8);
+
+            let value = decode(data);
+            if value.is_empty() {
+                continue;
+            }
+
+            match kind {
+                EXTH_AUTHOR => authors.push(value),
+                EXTH_PUBLISHER => info.publisher = value,
+                EXTH_ISBN => isbn = value,
+                EXTH_SUBJECT => {
+                    info.categories.extend(value.split(';')
+                                                .map(|s| s.trim().to_string())
+                                                .filter(|s| !s.is_empty()));
+                },
+                EXTH_PUBLISHING_DATE => info.year = value.chars().take(4).collect(),
+                EXTH_ASIN => asin = value,
+                EXTH_UPDATED_TITLE => title = value,
+                EXTH_LANGUAGE => language = value,
+                _ => (),
+            }
+        }
+    }
+
+    if let Some(caps) = SERIES_TITLE.captures(&title) {
+        info.series = caps[2].to_string();
+        info.number = caps[3].to_string();
+        title = caps[1].to_string();
+    }
+
+    if language.is_empty() {
+        language = locale_language(locale).unwrap_or_default().to_string();
+    }
+
+    info.title = title;
+    info.author = authors.join(", ");
+    info.language = language;
+    info.identifier = if isbn.is_empty() { asin } else { isbn };
+
+    Ok(())
+}
+
+// The primary language of a Windows locale identifier.
+fn locale_language(locale: u32) -> Option<&'static str> {
+    match locale & 0xFF {
+        0x04 => Some("zh"),
+        0x05 => Some("cs"),
+        0x06 => Some("da"),
+        0x07 => Some("de"),
+        0x08 => Some("el"),
+        0x09 => Some("en"),
+        0x0A => Some("es"),
+        0x0B => Some("fi"),
+        0x0C => Some("fr"),
+        0x0E => Some("hu"),
+        0x10 => Some("it"),
+        0x11 => Some("ja"),
+        0x12 => Some("ko"),
+        0x13 => Some("nl"),
+        0x14 => Some("nb"),
+        0x15 => Some("pl"),
+        0x16 => Some("pt"),
+        0x19 => Some("ru"),
+        0x1D => Some("sv"),
+        0x1F => Some("tr"),
+        _ => None,
+    }
+}
+
+fn cp1252_char(byte: u8) -> char {
+    const HIGH: [char; 32] = ['€', '\u{81}', '‚', 'ƒ', '„', '…', '†', '‡', 'ˆ', '‰', 'Š', '‹', 'Œ', '\u{8D}', 'Ž', '\u{8F}',
+                              '\u{90}', '‘', '’', '“', '”', '•', '–', '—', '˜', '™', 'š', '›', 'œ', '\u{9D}', 'ž', 'Ÿ'];
+    if (0x80..0xA0).contains(&byte) {
+        HIGH[(byte - 0x80) as usize]
+    } else {
+        byte as char
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn exth_record(kind: u32, value: &str) -> Vec<u8> {
+        let mut record = Vec::new();
+        record.extend_from_slice(&kind.to_be_bytes());
+        record.extend_from_slice(&(8 + value.len() as u32).to_be_bytes());
+        record.extend_from_slice(value.as_bytes());
+        record
+    }
+
+    #[test]
+    fn test_exth() {
+        let records = [exth_record(EXTH_AUTHOR, "J. R. R. Tolkien"),
+                       exth_record(EXTH_PUBLISHER, "Houghton Mifflin"),
+                       exth_record(EXTH_PUBLISHING_DATE, "1954-11-11T00:00:00+00:00"),
+                       exth_record(EXTH_ASIN, "B007978NPG"),
+                       exth_record(EXTH_SUBJECT, "Fantasy; Classics"),
+                       exth_record(EXTH_UPDATED_TITLE, "The Two Towers (The Lord of the Rings, Book 2)")];
+        let name = b"The_Two_Towers";
+
+        let mut bytes = vec![0; 86];
+        bytes[60..68].copy_from_slice(b"BOOKMOBI");
+        bytes[76..78].copy_from_slice(&1u16.to_be_bytes());
+        bytes[78..82].copy_from_slice(&86u32.to_be_bytes());
+
+        let mut header = vec![0; 248];
+        header[16..20].copy_from_slice(b"MOBI");
+        header[20..24].copy_from_slice(&232u32.to_be_bytes());
+        header[28..32].copy_from_slice(&UTF_8.to_be_bytes());
+        header[92..96].copy_from_slice(&0x0409u32.to_be_bytes());
+        header[128..132].copy_from_slice(&0x50u32.to_be_bytes());
+        header.extend_from_slice(b"EXTH");
+        header.extend_from_slice(&0u32.to_be_bytes());
+        header.extend_from_slice(&(records.len() as u32).to_be_bytes());
+        for record in &records {
+            header.extend_from_slice(record);
+        }
+        let name_offset = header.len() as u32;
+        header[84..88].copy_from_slice(&name_offset.to_be_bytes());
+        header[88..92].copy_from_slice(&(name.len() as u32).to_be_bytes());
+        header.extend_from_slice(name);
+        bytes.extend_from_slice(&header);
+
+        let path = std::env::temp_dir().join(format!("plato-mobi-{}.mobi", std::process::id()));
+        std::fs::write(&path, &bytes).unwrap();
+        let mut info = Info::default();
+        let result = extract_metadata(&path, &mut info);
+        std::fs::remove_file(&path).ok();
+        result.unwrap();
+        assert_eq!(info.title, "The Two Towers");
+        assert_eq!(info.series, "The Lord of the Rings");
+        assert_eq!(info.number, "2");
+        assert_eq!(info.author, "J. R. R. Tolkien");
+        assert_eq!(info.publisher, "Houghton Mifflin");
+        assert_eq!(info.year, "1954");
+        assert_eq!(info.language, "en");
+        assert_eq!(info.identifier, "B007978NPG");
+        assert_eq!(info.categories.iter().map(String::as_str).collect::<Vec<_>>(), ["Classics", "Fantasy"]);
+    }
+}
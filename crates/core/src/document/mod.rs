@@ -2,6 +2,9 @@ pub mod djvu;
 pub mod pdf;
 pub mod epub;
 pub mod html;
+pub mod mobi;
+pub mod fb2;
+pub mod cbz;
 
 mod djvulibre_sys;
 mod mupdf_sys;
@@ -121,6 +124,11 @@ pub trait Document: Send+Sync {
         false
     }
 
+    // Whether facing pages should be displayed side by side, if known.
+    fn spread(&self) -> Option<bool> {
+        None
+    }
+
     fn save(&self, _path: &str) -> Result<(), Error> {
         Err(format_err!("this document can't be saved"))
     }
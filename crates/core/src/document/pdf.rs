@@ -12,6 +12,7 @@ use std::os::unix::ffi::OsStrExt;
 use super::{Document, Location, TextLocation, BoundedText, TocEntry};
 use super::{chapter, chapter_relative};
 use crate::metadata::TextAlign;
+use crate::helpers::asset_path;
 use crate::geom::{Boundary, CycleDir};
 use crate::unit::pt_to_px;
 use crate::framebuffer::Pixmap;
@@ -94,7 +95,7 @@ impl PdfOpener {
     }
 
     pub fn load_user_stylesheet(&mut self) {
-        if let Ok(content) = fs::read_to_string(USER_STYLESHEET)
+        if let Ok(content) = fs::read_to_string(asset_path(USER_STYLESHEET))
                                 .and_then(|s| CString::new(s).map_err(Into::into))
                                 .map_err(|e| if e.kind() != ErrorKind::NotFound { eprintln!("{:#}", e) }) {
             unsafe { fz_set_user_css((self.0).0, content.as_ptr()) }
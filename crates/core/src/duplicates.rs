@@ -0,0 +1,304 @@
+// Finds the entries of a library that are copies of the same book, and merges them.
+
+use std::path::{Path, PathBuf};
+use std::collections::BTreeSet;
+use fxhash::FxHashMap;
+use serde::Serialize;
+use anyhow::{Error, format_err};
+use crate::library::{Library, Database};
+use crate::metadata::{Info, ReaderInfo};
+use crate::document::asciify;
+use crate::helpers::{content_hash, Fp};
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum MatchKind {
+    Identifier,
+    TitleAuthor,
+    Content,
+}
+
+impl MatchKind {
+    pub fn label(&self) -> &str {
+        match self {
+            MatchKind::Identifier => "identifier",
+            MatchKind::TitleAuthor => "title and author",
+            MatchKind::Content => "content",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Duplicates {
+    pub title: String,
+    pub author: String,
+    // The criteria that matched.
+    pub kinds: BTreeSet<MatchKind>,
+    // The first path is the suggested copy to keep.
+    pub paths: Vec<PathBuf>,
+}
+
+// Returns the groups of entries that share an identifier, a title and an author, or a content.
+// Only the database libraries are supported.
+pub fn find_duplicates(library: &Library) -> Vec<Duplicates> {
+    search_duplicates(&library.home, &library.db, &library.hashes)
+}
+
+// Computing the missing content hashes can take a while: this function
+// can be given copies of the entries and the hashes of a library from another thread.
+pub fn search_duplicates(home: &Path, db: &Database, hashes: &FxHashMap<Fp, Fp>) -> Vec<Duplicates> {
+    let infos: Vec<&Info> = db.values().collect();
+    let fps: Vec<&Fp> = db.keys().collect();
+    let mut parents: Vec<usize> = (0..infos.len()).collect();
+    let mut links = Vec::new();
+    let mut keys: FxHashMap<(MatchKind, String), usize> = FxHashMap::default();
+
+    for (index, info) in infos.iter().enumerate() {
+        let hash = hashes.get(fps[index]).cloned()
+                         .or_else(|| content_hash(home.join(&info.file.path)).ok());
+        let candidates = [
+            (MatchKind::Identifier, normalize_identifier(&info.identifier)),
+            (MatchKind::TitleAuthor, title_author_key(info)),
+            (MatchKind::Content, hash.map(|h| h.to_string())),
+        ];
+        for (kind, key) in candidates {
+            if let Some(key) = key {
+                if let Some(&other) = keys.get(&(kind, key.clone())) {
+                    union(&mut parents, index, other);
+                    links.push((index, kind));
+                } else {
+                    keys.insert((kind, key), index);
+                }
+            }
+        }
+    }
+
+    let mut groups: FxHashMap<usize, (BTreeSet<MatchKind>, Vec<usize>)> = FxHashMap::default();
+    for index in 0..infos.len() {
+        let root = find(&mut parents, index);
+        groups.entry(root).or_default().1.push(index);
+    }
+    for (index, kind) in links {
+        let root = find(&mut parents, index);
+        if let Some(group) = groups.get_mut(&root) {
+            group.0.insert(kind);
+        }
+    }
+
+    let mut duplicates: Vec<Duplicates> = groups.into_values()
+        .filter(|(_, indices)| indices.len() > 1)
+        .map(|(kinds, mut indices)| {
+            indices.sort_by(|&a, &b| keep_order(infos[a], infos[b]));
+            let first = infos[indices[0]];
+            Duplicates {
+                title: first.title.clone(),
+                author: first.author.clone(),
+                kinds,
+                paths: indices.into_iter().map(|i| infos[i].file.path.clone()).collect(),
+            }
+        }).collect();
+
+    duplicates.sort_by(|a, b| a.title.to_lowercase().cmp(&b.title.to_lowercase())
+                               .then_with(|| a.paths.cmp(&b.paths)));
+    duplicates
+}
+
+// Merges the metadata and the reading state of the entry at *other* into the entry at *keep*.
+// The entry at *other* is left untouched.
+pub fn merge<P: AsRef<Path>, Q: AsRef<Path>>(library: &mut Library, keep: P, other: Q) -> Result<(), Error> {
+    let mut info = library.info(keep.as_ref())
+                          .ok_or_else(|| format_err!("unknown document: {}", keep.as_ref().display()))?;
+    let other_info = library.info(other.as_ref())
+                            .ok_or_else(|| format_err!("unknown document: {}", other.as_ref().display()))?;
+    let hash = library.content_hash(&info.file.path);
+    let same_content = hash.is_some() && hash == library.content_hash(&other_info.file.path);
+    merge_info(&mut info, &other_info, same_content);
+    library.update(info)
+}
+
+// The locations of the annotations, the bookmarks and the current page
+// are only meaningful if both copies have the same content.
+fn merge_info(info: &mut Info, other: &Info, same_content: bool) {
+    for (field, value) in [(&mut info.title, &other.title), (&mut info.subtitle, &other.subtitle),
+                           (&mut info.author, &other.author), (&mut info.year, &other.year),
+                           (&mut info.language, &other.language), (&mut info.publisher, &other.publisher),
+                           (&mut info.series, &other.series), (&mut info.edition, &other.edition),
+                           (&mut info.volume, &other.volume), (&mut info.number, &other.number),
+                           (&mut info.identifier, &other.identifier),
+                           (&mut info.description, &other.description)] {
+        if field.is_empty() {
+            field.clone_from(value);
+        }
+    }
+
+    info.categories.extend(other.categories.iter().cloned());
+
+    if same_content && info.toc.is_none() {
+        info.toc.clone_from(&other.toc);
+    }
+
+    let Some(other_reader) = other.reader.as_ref() else {
+        return;
+    };
+
+    match info.reader.as_mut() {
+        None if same_content => {
+            info.reader = Some(other_reader.clone());
+        },
+        None => {
+            if other_reader.finished {
+                info.reader = Some(ReaderInfo {
+                    opened: other_reader.opened,
+                    finished: true,
+                    .. Default::default()
+                });
+            }
+        },
+        Some(reader) => {
+            reader.finished |= other_reader.finished;
+            if same_content {
+                if other_reader.opened > reader.opened {
+                    reader.current_page = other_reader.current_page;
+                    reader.pages_count = other_reader.pages_count;
+                }
+                reader.bookmarks.extend(other_reader.bookmarks.iter().cloned());
+                for (page, name) in &other_reader.page_names {
+                    reader.page_names.entry(*page).or_insert_with(|| name.clone());
+                }
+                for annot in &other_reader.annotations {
+                    if !reader.annotations.iter().any(|a| a.selection == annot.selection) {
+                        reader.annotations.push(annot.clone());
+                    }
+                }
+                reader.annotations.sort_by_key(|a| a.selection[0]);
+            }
+            reader.opened = reader.opened.max(other_reader.opened);
+        },
+    }
+}
+
+// The most recently opened copy comes first, then the EPUB copies, then the oldest.
+fn keep_order(a: &Info, b: &Info) -> std::cmp::Ordering {
+    let opened = |info: &Info| info.reader.as_ref().map(|r| r.opened);
+    opened(b).cmp(&opened(a))
+             .then_with(|| (b.file.kind == "epub").cmp(&(a.file.kind == "epub")))
+             .then_with(|| a.added.cmp(&b.added))
+             .then_with(|| a.file.path.cmp(&b.file.path))
+}
+
+fn normalize(text: &str) -> String {
+    asciify(text).to_lowercase()
+                 .split(|c: char| !c.is_alphanumeric())
+                 .filter(|w| !w.is_empty())
+                 .collect::<Vec<&str>>()
+                 .join(" ")
+}
+
+fn title_author_key(info: &Info) -> Option<String> {
+    let title = normalize(&info.title);
+    let author = normalize(&info.author);
+    // Untitled or anonymous entries aren't enough alike.
+    if title.is_empty() || author.is_empty() {
+        return None;
+    }
+    // *Doe, John* and *John Doe* are considered equal.
+    let mut words: Vec<String> = author.split(' ')
+                                       .map(String::from)
+                                       .collect();
+    words.sort();
+    Some(format!("{}\u{1f}{}", title, words.join(" ")))
+}
+
+// The ISBN-10 are converted to ISBN-13.
+fn normalize_identifier(identifier: &str) -> Option<String> {
+    let identifier = identifier.trim().to_lowercase();
+    if identifier.is_empty() {
+        return None;
+    }
+
+    let compact: String = identifier.trim_start_matches("urn:")
+                                    .trim_start_matches("isbn")
+                                    .trim_start_matches(':')
+                                    .chars()
+                                    .filter(|c| !matches!(c, '-' | ' '))
+                                    .collect();
+
+    if compact.len() == 13 && compact.bytes().all(|b| b.is_ascii_digit()) &&
+       (compact.starts_with("978") || compact.starts_with("979")) {
+        return Some(compact);
+    }
+
+    if compact.len() == 10 && compact.is_ascii() && compact[..9].bytes().all(|b| b.is_ascii_digit()) &&
+       compact[9..].bytes().all(|b| b.is_ascii_digit() || b == b'x') {
+        let digits = format!("978{}", &compact[..9]);
+        let sum: u32 = digits.bytes().enumerate()
+                             .map(|(i, b)| (b - b'0') as u32 * if i % 2 == 0 { 1 } else { 3 })
+                             .sum();
+        return Some(format!("{}{}", digits, (10 - sum % 10) % 10));
+    }
+
+    Some(identifier)
+}
+
+fn find(parents: &mut [usize], mut index: usize) -> usize {
+    while parents[index] != index {
+        parents[index] = parents[parents[index]];
+        index = parents[index];
+    }
+    index
+}
+
+fn union(parents: &mut [usize], a: usize, b: usize) {
+    let (ra, rb) = (find(parents, a), find(parents, b));
+    parents[ra] = rb;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::metadata::Annotation;
+    use crate::document::TextLocation;
+
+    #[test]
+    fn test_keys() {
+        assert_eq!(normalize_identifier("urn:isbn:0-306-40615-2").as_deref(), Some("9780306406157"));
+        assert_eq!(normalize_identifier("ISBN 978-0-306-40615-7").as_deref(), Some("9780306406157"));
+        assert_eq!(normalize_identifier("urn:uuid:ABC").as_deref(), Some("urn:uuid:abc"));
+        assert_eq!(normalize_identifier(" "), None);
+        assert_eq!(normalize_identifier("ééééé").as_deref(), Some("ééééé"));
+        assert_eq!(normalize_identifier("1234ééé").as_deref(), Some("1234ééé"));
+        let a = Info { title: "L'Étranger".to_string(), author: "Camus, Albert".to_string(), .. Default::default() };
+        let b = Info { title: "l’étranger".to_string(), author: "Albert Camus".to_string(), .. Default::default() };
+        assert_eq!(title_author_key(&a), title_author_key(&b));
+        let c = Info { title: "L'Étranger".to_string(), .. Default::default() };
+        assert_eq!(title_author_key(&c), None);
+    }
+
+    #[test]
+    fn test_merge_info() {
+        let annot = |offset| Annotation {
+            selection: [TextLocation::Dynamic(offset), TextLocation::Dynamic(offset + 10)],
+            .. Default::default()
+        };
+        let mut info = Info {
+            title: "Title".to_string(),
+            reader: Some(ReaderInfo { annotations: vec![annot(20)], .. Default::default() }),
+            .. Default::default()
+        };
+        let other = Info {
+            author: "Author".to_string(),
+            reader: Some(ReaderInfo { finished: true, annotations: vec![annot(5), annot(20)], .. Default::default() }),
+            .. Default::default()
+        };
+
+        let mut copy = info.clone();
+        merge_info(&mut copy, &other, false);
+        assert_eq!(copy.author, "Author");
+        assert!(copy.reader.as_ref().is_some_and(|r| r.finished && r.annotations.len() == 1));
+
+        merge_info(&mut info, &other, true);
+        assert_eq!(info.reader.map(|r| r.annotations.iter().map(|a| a.selection[0]).collect::<Vec<_>>()),
+                   Some(vec![TextLocation::Dynamic(5), TextLocation::Dynamic(20)]));
+    }
+}
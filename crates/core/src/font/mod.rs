@@ -21,7 +21,7 @@ use globset::Glob;
 use walkdir::WalkDir;
 use crate::color::Color;
 use crate::geom::{Point, Vec2};
-use crate::helpers::IsHidden;
+use crate::helpers::{IsHidden, asset_path};
 use crate::framebuffer::Framebuffer;
 use crate::device::CURRENT_DEVICE;
 
@@ -1069,7 +1069,8 @@ impl FontOpener {
     pub fn open<P: AsRef<Path>>(&self, path: P) -> Result<Font, Error> {
         unsafe {
             let mut face = ptr::null_mut();
-            let c_path = CString::new(path.as_ref().as_os_str().as_bytes()).unwrap();
+            let path = asset_path(path);
+            let c_path = CString::new(path.as_os_str().as_bytes()).unwrap();
             let ret = FT_New_Face((self.0).0, c_path.as_ptr(), 0, &mut face);
             if ret != FT_ERR_OK {
                return Err(Error::from(FreetypeError::from(ret)));
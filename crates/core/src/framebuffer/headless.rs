@@ -0,0 +1,157 @@
+use anyhow::{Error, format_err};
+use super::{Framebuffer, UpdateMode, Pixmap};
+use crate::color::Color;
+use crate::device::CURRENT_DEVICE;
+use crate::geom::Rectangle;
+
+// A framebuffer that lives entirely in memory.
+// It keeps track of the updates it receives, and is meant to be used
+// in places where there's no screen, e.g. tests.
+pub struct HeadlessFramebuffer {
+    pixmap: Pixmap,
+    rotation: i8,
+    monochrome: bool,
+    dithered: bool,
+    inverted: bool,
+    token: u32,
+    updates: Vec<(Rectangle, UpdateMode)>,
+}
+
+impl HeadlessFramebuffer {
+    pub fn new(width: u32, height: u32, samples: usize) -> HeadlessFramebuffer {
+        HeadlessFramebuffer {
+            pixmap: Pixmap::new(width, height, samples),
+            rotation: CURRENT_DEVICE.startup_rotation(),
+            monochrome: false,
+            dithered: false,
+            inverted: false,
+            token: 0,
+            updates: Vec::new(),
+        }
+    }
+
+    pub fn pixmap(&self) -> &Pixmap {
+        &self.pixmap
+    }
+
+    // Returns a copy of the given region.
+    pub fn region(&self, rect: &Rectangle) -> Option<Pixmap> {
+        let rect = rect.intersection(&self.rect())?;
+        let mut pixmap = Pixmap::new(rect.width(), rect.height(), self.pixmap.samples);
+        pixmap.draw_framed_pixmap(&self.pixmap, &rect, pt!(0, 0));
+        Some(pixmap)
+    }
+
+    pub fn updates(&self) -> &[(Rectangle, UpdateMode)] {
+        &self.updates
+    }
+
+    pub fn clear_updates(&mut self) {
+        self.updates.clear();
+    }
+}
+
+impl Framebuffer for HeadlessFramebuffer {
+    fn set_pixel(&mut self, x: u32, y: u32, color: Color) {
+        self.pixmap.set_pixel(x, y, color);
+    }
+
+    fn set_blended_pixel(&mut self, x: u32, y: u32, color: Color, alpha: f32) {
+        self.pixmap.set_blended_pixel(x, y, color, alpha);
+    }
+
+    fn invert_region(&mut self, rect: &Rectangle) {
+        if let Some(rect) = rect.intersection(&self.rect()) {
+            self.pixmap.invert_region(&rect);
+        }
+    }
+
+    fn shift_region(&mut self, rect: &Rectangle, drift: u8) {
+        if let Some(rect) = rect.intersection(&self.rect()) {
+            self.pixmap.shift_region(&rect, drift);
+        }
+    }
+
+    fn update(&mut self, rect: &Rectangle, mode: UpdateMode) -> Result<u32, Error> {
+        self.token = self.token.wrapping_add(1);
+        self.updates.push((*rect, mode));
+        Ok(self.token)
+    }
+
+    fn wait(&self, _token: u32) -> Result<i32, Error> {
+        Ok(0)
+    }
+
+    fn save(&self, path: &str) -> Result<(), Error> {
+        self.pixmap.save(path)
+    }
+
+    fn rotation(&self) -> i8 {
+        self.rotation
+    }
+
+    fn set_rotation(&mut self, n: i8) -> Result<(u32, u32), Error> {
+        if !(0..4).contains(&n) {
+            return Err(format_err!("invalid rotation: {}", n));
+        }
+        let (mut width, mut height) = self.dims();
+        if (n - self.rotation).abs() % 2 == 1 {
+            std::mem::swap(&mut width, &mut height);
+            self.pixmap = Pixmap::new(width, height, self.pixmap.samples);
+        }
+        self.rotation = n;
+        Ok((width, height))
+    }
+
+    fn set_monochrome(&mut self, enable: bool) {
+        self.monochrome = enable;
+    }
+
+    fn set_dithered(&mut self, enable: bool) {
+        self.dithered = enable;
+    }
+
+    fn set_inverted(&mut self, enable: bool) {
+        self.inverted = enable;
+    }
+
+    fn monochrome(&self) -> bool {
+        self.monochrome
+    }
+
+    fn dithered(&self) -> bool {
+        self.dithered
+    }
+
+    fn inverted(&self) -> bool {
+        self.inverted
+    }
+
+    fn width(&self) -> u32 {
+        self.pixmap.width
+    }
+
+    fn height(&self) -> u32 {
+        self.pixmap.height
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color::{BLACK, WHITE};
+
+    #[test]
+    fn test_rotation_and_region() {
+        let mut fb = HeadlessFramebuffer::new(30, 40, 1);
+        let rotation = fb.rotation();
+        assert_eq!(fb.set_rotation((rotation + 1) % 4).ok(), Some((40, 30)));
+        assert_eq!(fb.set_rotation((rotation + 3) % 4).ok(), Some((40, 30)));
+        fb.draw_rectangle(&rect![10, 10, 20, 20], BLACK);
+        let region = fb.region(&rect![5, 5, 15, 15]).unwrap();
+        assert_eq!((region.width, region.height), (10, 10));
+        assert_eq!(region.get_pixel(0, 0), WHITE);
+        assert_eq!(region.get_pixel(9, 9), BLACK);
+        assert!(fb.region(&rect![50, 50, 60, 60]).is_none());
+    }
+}
@@ -3,11 +3,13 @@ mod ion_sys;
 mod mxcfb_sys;
 mod sunxi_sys;
 mod image;
+mod headless;
 mod transform;
 mod kobo1;
 mod kobo2;
 
 use anyhow::Error;
+use downcast_rs::{Downcast, impl_downcast};
 use crate::geom::{Point, Rectangle, surface_area, nearest_segment_point, lerp};
 use crate::geom::{CornerSpec, BorderSpec, ColorSource, Vec2};
 use crate::color::{Color, BLACK, WHITE};
@@ -15,6 +17,7 @@ use crate::color::{Color, BLACK, WHITE};
 pub use self::kobo1::KoboFramebuffer1;
 pub use self::kobo2::KoboFramebuffer2;
 pub use self::image::Pixmap;
+pub use self::headless::HeadlessFramebuffer;
 
 #[derive(Debug, Copy, Clone)]
 pub struct Display {
@@ -31,7 +34,7 @@ pub enum UpdateMode {
     FastMono,
 }
 
-pub trait Framebuffer {
+pub trait Framebuffer: Downcast {
     fn set_pixel(&mut self, x: u32, y: u32, color: Color);
     fn set_blended_pixel(&mut self, x: u32, y: u32, color: Color, alpha: f32);
     fn invert_region(&mut self, rect: &Rectangle);
@@ -397,3 +400,5 @@ pub trait Framebuffer {
         }
     }
 }
+
+impl_downcast!(Framebuffer);
@@ -2,6 +2,7 @@ use std::fmt;
 use std::sync::mpsc::{self, Sender, Receiver};
 use std::sync::{Arc, Mutex};
 use fxhash::FxHashMap;
+use serde::{Serialize, Deserialize};
 use std::f64;
 use std::time::Duration;
 use std::thread;
@@ -16,7 +17,7 @@ pub const HOLD_JITTER_MM: f32 = 1.5;
 pub const HOLD_DELAY_SHORT: Duration = Duration::from_millis(666);
 pub const HOLD_DELAY_LONG: Duration = Duration::from_millis(1333);
 
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Copy, Clone, Serialize, Deserialize)]
 pub enum GestureEvent {
     Tap(Point),
     MultiTap([Point; 2]),
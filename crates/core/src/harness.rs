@@ -0,0 +1,270 @@
+// A harness for testing views without a screen.
+//
+// The views are rendered into a `HeadlessFramebuffer`, and the context is built with a fake battery,
+// frontlight and light sensor. Scripted events are fed to the root view and the rendered regions
+// can then be compared against reference images.
+//
+// The reference images are stored in `crates/core/snapshots`. They are written instead of being
+// compared when the `PLATO_UPDATE_SNAPSHOTS` environment variable is set, and a missing reference
+// image is an error otherwise. Regions that depend on the current time (e.g. the clock) shouldn't
+// be compared. The snapshot tests are ignored by default: they depend on the rendering of the
+// native libraries of the machine that generated the reference images.
+
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::{self, Receiver};
+use std::collections::VecDeque;
+use anyhow::{Error, Context as ResultExt, format_err};
+use rand_core::SeedableRng;
+use rand_xoshiro::Xoroshiro128Plus;
+use crate::framebuffer::{Framebuffer, HeadlessFramebuffer, Pixmap, UpdateMode};
+use crate::view::{View, Event, Hub, Bus, RenderQueue, RenderData, UpdateData};
+use crate::view::{handle_event, process_render_queue, wait_for_all};
+use crate::gesture::GestureEvent;
+use crate::battery::{Battery, FakeBattery};
+use crate::frontlight::{Frontlight, LightLevels};
+use crate::lightsensor::LightSensor;
+use crate::settings::{Settings, LibraryMode};
+use crate::device::CURRENT_DEVICE;
+use crate::library::Library;
+use crate::font::Fonts;
+use crate::context::Context;
+use crate::geom::{Point, Rectangle};
+
+pub const UPDATE_SNAPSHOTS_VAR: &str = "PLATO_UPDATE_SNAPSHOTS";
+// Maximum difference between two samples that are considered equal.
+const SAMPLE_TOLERANCE: u8 = 2;
+// Bounds the number of rounds of hub events processed after each scripted event.
+const MAX_ROUNDS: usize = 64;
+
+static LIBRARY_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+pub struct Harness {
+    pub view: Box<dyn View>,
+    pub context: Context,
+    // The events sent through the hub, in order.
+    pub events: Vec<Event>,
+    hub: Hub,
+    receiver: Receiver<Event>,
+    rq: RenderQueue,
+    updating: Vec<UpdateData>,
+    library_path: PathBuf,
+}
+
+pub fn build_context(settings: Settings, library_path: &Path) -> Result<Context, Error> {
+    let (width, height) = CURRENT_DEVICE.dims;
+    let fb = Box::new(HeadlessFramebuffer::new(width, height, CURRENT_DEVICE.color_samples())) as Box<dyn Framebuffer>;
+    let library = Library::new(library_path, LibraryMode::Database)?;
+    let battery = Box::new(FakeBattery::new()) as Box<dyn Battery>;
+    let frontlight = Box::new(LightLevels::default()) as Box<dyn Frontlight>;
+    let lightsensor = Box::new(0u16) as Box<dyn LightSensor>;
+    let fonts = Fonts::load()?;
+    let mut context = Context::new(fb, None, library, settings,
+                                   fonts, battery, frontlight, lightsensor);
+    context.rng = Xoroshiro128Plus::seed_from_u64(0);
+    context.load_keyboard_layouts();
+    Ok(context)
+}
+
+impl Harness {
+    pub fn new<F>(settings: Settings, build_view: F) -> Result<Harness, Error>
+        where F: FnOnce(Rectangle, &Hub, &mut RenderQueue, &mut Context) -> Result<Box<dyn View>, Error> {
+        let library_path = env::temp_dir().join(format!("plato-harness-{}-{}",
+                                                        std::process::id(),
+                                                        LIBRARY_COUNTER.fetch_add(1, Ordering::Relaxed)));
+        let mut context = build_context(settings, &library_path)?;
+        let (hub, receiver) = mpsc::channel();
+        let mut rq = RenderQueue::new();
+        let rect = context.fb.rect();
+        let view = build_view(rect, &hub, &mut rq, &mut context)?;
+        rq.add(RenderData::new(view.id(), rect, UpdateMode::Full));
+        let mut harness = Harness {
+            view,
+            context,
+            events: Vec::new(),
+            hub,
+            receiver,
+            rq,
+            updating: Vec::new(),
+            library_path,
+        };
+        harness.settle();
+        Ok(harness)
+    }
+
+    // Delivers the given event to the root view, then processes the resulting events and renders.
+    pub fn send(&mut self, evt: Event) {
+        let mut bus: Bus = VecDeque::new();
+        handle_event(self.view.as_mut(), &evt, &self.hub, &mut bus, &mut self.rq, &mut self.context);
+        for evt in bus.drain(..) {
+            self.hub.send(evt).ok();
+        }
+        self.settle();
+    }
+
+    pub fn play(&mut self, events: &[Event]) {
+        for evt in events {
+            self.send(evt.clone());
+        }
+    }
+
+    pub fn tap(&mut self, pt: Point) {
+        self.send(Event::Gesture(GestureEvent::Tap(pt)));
+    }
+
+    fn settle(&mut self) {
+        let mut bus: Bus = VecDeque::new();
+        for _ in 0..MAX_ROUNDS {
+            process_render_queue(self.view.as_ref(), &mut self.rq, &mut self.context, &mut self.updating);
+            let pending: Vec<Event> = self.receiver.try_iter().collect();
+            if pending.is_empty() {
+                break;
+            }
+            for evt in pending {
+                handle_event(self.view.as_mut(), &evt, &self.hub, &mut bus, &mut self.rq, &mut self.context);
+                self.events.push(evt);
+            }
+            for evt in bus.drain(..) {
+                self.hub.send(evt).ok();
+            }
+        }
+        wait_for_all(&mut self.updating, &mut self.context);
+    }
+
+    pub fn framebuffer(&self) -> &HeadlessFramebuffer {
+        self.context.fb.downcast_ref::<HeadlessFramebuffer>()
+            .expect("the harness framebuffer is headless")
+    }
+
+    // Compares the given region of the screen with the reference image at *path*.
+    // When they differ, the rendered region is saved next to the reference image.
+    pub fn compare_region<P: AsRef<Path>>(&self, rect: &Rectangle, path: P) -> Result<(), Error> {
+        let path = path.as_ref();
+        let region = self.framebuffer().region(rect)
+                         .ok_or_else(|| format_err!("the region {} is outside the screen", rect))?;
+
+        if env::var_os(UPDATE_SNAPSHOTS_VAR).is_some() {
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            return region.save(&path.to_string_lossy());
+        }
+
+        if !path.exists() {
+            return Err(format_err!("the reference image {} is missing, set {} to create it",
+                                   path.display(), UPDATE_SNAPSHOTS_VAR));
+        }
+
+        let reference = Pixmap::from_png(path)
+                               .with_context(|| format!("can't load {}", path.display()))?;
+        let count = differing_pixels(&region, &reference);
+
+        if count > 0 {
+            let actual_path = path.with_extension("actual.png");
+            region.save(&actual_path.to_string_lossy())?;
+            return Err(format_err!("{} pixels differ from {}, the rendered region was saved to {}",
+                                   count, path.display(), actual_path.display()));
+        }
+
+        Ok(())
+    }
+}
+
+impl Drop for Harness {
+    fn drop(&mut self) {
+        fs::remove_dir_all(&self.library_path).ok();
+    }
+}
+
+// Returns the number of pixels that differ between the two given pixmaps.
+pub fn differing_pixels(a: &Pixmap, b: &Pixmap) -> usize {
+    if a.width != b.width || a.height != b.height || a.samples != b.samples {
+        return a.width.max(b.width) as usize * a.height.max(b.height) as usize;
+    }
+    a.data.chunks(a.samples).zip(b.data.chunks(b.samples))
+          .filter(|(p, q)| p.iter().zip(q.iter()).any(|(u, v)| u.abs_diff(*v) > SAMPLE_TOLERANCE))
+          .count()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color::{BLACK, GRAY08};
+    use crate::metadata::{Info, FileInfo};
+    use crate::view::ViewId;
+    use crate::view::home::Home;
+    use crate::view::reader::Reader;
+    use crate::view::common::locate_by_id;
+
+    fn snapshot_path(name: &str) -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR")).join("snapshots")
+                                             .join(format!("{}.png", name))
+    }
+
+    fn home() -> Harness {
+        Harness::new(Settings::default(), |rect, hub, rq, context| {
+            Home::new(rect, hub, rq, context).map(|home| Box::new(home) as Box<dyn View>)
+        }).unwrap()
+    }
+
+    #[test]
+    #[ignore = "the reference images are generated locally, see doc/BUILD.md"]
+    fn test_home_snapshot() {
+        let harness = home();
+        // The top bar shows the time.
+        let top_bar_rect = *harness.view.child(0).rect();
+        let rect = rect![top_bar_rect.min.x, top_bar_rect.max.y,
+                         harness.view.rect().max.x, harness.view.rect().max.y];
+        harness.compare_region(&rect, snapshot_path("home")).unwrap();
+    }
+
+    #[test]
+    #[ignore = "the reference images are generated locally, see doc/BUILD.md"]
+    fn test_sort_menu_snapshot() {
+        let mut harness = home();
+        let top_bar_rect = *harness.view.child(0).rect();
+        harness.send(Event::ToggleNear(ViewId::TitleMenu, top_bar_rect));
+        let index = locate_by_id(harness.view.as_ref(), ViewId::SortMenu).unwrap();
+        let rect = *harness.view.child(index).rect();
+        harness.compare_region(&rect, snapshot_path("sort_menu")).unwrap();
+    }
+
+    #[test]
+    #[ignore = "the reference images are generated locally, see doc/BUILD.md"]
+    fn test_keyboard_snapshot() {
+        let mut harness = home();
+        harness.send(Event::Toggle(ViewId::SearchBar));
+        let rect = harness.context.kb_rect;
+        assert!(!rect.is_empty());
+        harness.compare_region(&rect, snapshot_path("keyboard")).unwrap();
+    }
+
+    #[test]
+    #[ignore = "the reference images are generated locally, see doc/BUILD.md"]
+    fn test_reader_snapshot() {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("../../contrib/unicode_test.epub");
+        let info = Info {
+            file: FileInfo { path, kind: "epub".to_string(), .. Default::default() },
+            .. Default::default()
+        };
+        let harness = Harness::new(Settings::default(), |rect, hub, _rq, context| {
+            Reader::new(rect, info, hub, context).map(|reader| Box::new(reader) as Box<dyn View>)
+                   .ok_or_else(|| format_err!("can't open the document"))
+        }).unwrap();
+        let rect = *harness.view.rect();
+        harness.compare_region(&rect, snapshot_path("reader")).unwrap();
+    }
+
+    #[test]
+    fn test_differing_pixels() {
+        let a = Pixmap::new(8, 8, 1);
+        let mut b = a.clone();
+        assert_eq!(differing_pixels(&a, &b), 0);
+        b.draw_rectangle(&rect![0, 0, 2, 2], BLACK);
+        b.set_pixel(7, 7, GRAY08);
+        assert_eq!(differing_pixels(&a, &b), 5);
+        assert_eq!(differing_pixels(&a, &Pixmap::new(8, 4, 1)), 64);
+    }
+}
@@ -5,7 +5,7 @@ use std::borrow::Cow;
 use std::time::SystemTime;
 use std::num::ParseIntError;
 use std::fs::{self, File, Metadata};
-use std::io::{self, BufReader, BufWriter};
+use std::io::{self, BufReader, BufWriter, Read, Seek, SeekFrom};
 use std::path::{Path, PathBuf, Component};
 use fxhash::FxHashMap;
 use std::ops::{Deref, DerefMut};
@@ -66,6 +66,38 @@ pub fn decode_entities(text: &str) -> Cow<str> {
     Cow::Owned(buf)
 }
 
+// Escapes the characters that are special in the XML texts and attribute values.
+pub fn escape(text: &str) -> Cow<'_, str> {
+    if !text.contains(['&', '<', '>', '"', '\'']) {
+        return Cow::Borrowed(text);
+    }
+
+    let mut buf = String::with_capacity(text.len() + 16);
+
+    for c in text.chars() {
+        match c {
+            '&' => buf.push_str("&amp;"),
+            '<' => buf.push_str("&lt;"),
+            '>' => buf.push_str("&gt;"),
+            '"' => buf.push_str("&quot;"),
+            '\'' => buf.push_str("&apos;"),
+            _ => buf.push(c),
+        }
+    }
+
+    Cow::Owned(buf)
+}
+
+// The assets (fonts, icons, stylesheets, etc.) are loaded from paths relative to the current
+// directory, except in the unit tests, which run in parallel from the directory of the crate.
+pub fn asset_path<P: AsRef<Path>>(path: P) -> PathBuf {
+    if cfg!(test) {
+        Path::new(env!("CARGO_MANIFEST_DIR")).join("../..").join(path)
+    } else {
+        path.as_ref().to_path_buf()
+    }
+}
+
 pub fn load_json<T, P: AsRef<Path>>(path: P) -> Result<T, Error> where for<'a> T: Deserialize<'a> {
     let file = File::open(path.as_ref())
                     .with_context(|| format!("can't open file {}", path.as_ref().display()))?;
@@ -112,6 +144,38 @@ impl Fingerprint for Metadata {
     }
 }
 
+// The size of the chunks hashed by `content_hash`.
+const HASH_CHUNK_SIZE: u64 = 64 * 1024;
+
+// The initial value of the 64 bits FNV-1a hash.
+pub const FNV_OFFSET_BASIS: u64 = 0xcbf29ce484222325;
+
+// Feeds *bytes* to the 64 bits FNV-1a hash *hash*.
+pub fn fnv1a(hash: u64, bytes: &[u8]) -> u64 {
+    bytes.iter().fold(hash, |hash, &byte| (hash ^ byte as u64).wrapping_mul(0x100000001b3))
+}
+
+// Computes a fingerprint from the size of the file and the content of
+// its first, middle and last chunks, using the 64 bits FNV-1a hash.
+// Unlike `Fingerprint`, the result doesn't depend on the modification time:
+// it survives copies and backups.
+pub fn content_hash<P: AsRef<Path>>(path: P) -> io::Result<Fp> {
+    let mut file = File::open(path.as_ref())?;
+    let size = file.metadata()?.len();
+    let mut hash = fnv1a(FNV_OFFSET_BASIS, &size.to_le_bytes());
+
+    let last = size.saturating_sub(HASH_CHUNK_SIZE);
+    let mut chunk = Vec::with_capacity(HASH_CHUNK_SIZE as usize);
+    for offset in [0, last / 2, last] {
+        chunk.clear();
+        file.seek(SeekFrom::Start(offset))?;
+        (&mut file).take(HASH_CHUNK_SIZE).read_to_end(&mut chunk)?;
+        hash = fnv1a(hash, &chunk);
+    }
+
+    Ok(Fp(hash))
+}
+
 #[derive(Debug, Copy, Clone, Hash, Eq, PartialEq)]
 pub struct Fp(u64);
 
@@ -256,4 +320,11 @@ mod tests {
         assert_eq!(decode_entities("a &#38; b"), "a & b");
         assert_eq!(decode_entities("a &lt; b &gt; c"), "a < b > c");
     }
+
+    #[test]
+    fn test_escape() {
+        assert_eq!(escape("Tom & Jerry <3"), "Tom &amp; Jerry &lt;3");
+        assert_eq!(escape("\"Don't\""), "&quot;Don&apos;t&quot;");
+        assert!(matches!(escape("plain"), Cow::Borrowed("plain")));
+    }
 }
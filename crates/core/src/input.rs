@@ -8,6 +8,7 @@ use std::sync::mpsc::{self, Sender, Receiver};
 use std::os::unix::io::AsRawFd;
 use std::ffi::CString;
 use fxhash::FxHashMap;
+use serde::{Serialize, Deserialize};
 use crate::framebuffer::Display;
 use crate::settings::ButtonScheme;
 use crate::device::CURRENT_DEVICE;
@@ -105,14 +106,14 @@ pub enum TouchProto {
     MultiC,
 }
 
-#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Serialize, Deserialize)]
 pub enum FingerStatus {
     Down,
     Motion,
     Up,
 }
 
-#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Serialize, Deserialize)]
 pub enum ButtonStatus {
     Pressed,
     Released,
@@ -130,7 +131,7 @@ impl ButtonStatus {
     }
 }
 
-#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash, Serialize, Deserialize)]
 pub enum ButtonCode {
     Power,
     Home,
@@ -169,29 +170,26 @@ fn resolve_button_direction(mut direction: LinearDir, rotation: i8, button_schem
     ButtonCode::Backward
 }
 
-pub fn display_rotate_event(n: i8) -> InputEvent {
+pub fn input_event(kind: u16, code: u16, value: i32) -> InputEvent {
     let mut tp = libc::timeval { tv_sec: 0, tv_usec: 0 };
     unsafe { libc::gettimeofday(&mut tp, ptr::null_mut()); }
     InputEvent {
         time: tp,
-        kind: EV_KEY,
-        code: KEY_ROTATE_DISPLAY,
-        value: n as i32,
+        kind,
+        code,
+        value,
     }
 }
 
+pub fn display_rotate_event(n: i8) -> InputEvent {
+    input_event(EV_KEY, KEY_ROTATE_DISPLAY, n as i32)
+}
+
 pub fn button_scheme_event(v: i32) -> InputEvent {
-    let mut tp = libc::timeval { tv_sec: 0, tv_usec: 0 };
-    unsafe { libc::gettimeofday(&mut tp, ptr::null_mut()); }
-    InputEvent {
-        time: tp,
-        kind: EV_KEY,
-        code: KEY_BUTTON_SCHEME,
-        value: v,
-    }
+    input_event(EV_KEY, KEY_BUTTON_SCHEME, v)
 }
 
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Copy, Clone, Serialize, Deserialize)]
 pub enum DeviceEvent {
     Finger {
         id: i32,
@@ -213,7 +211,7 @@ pub enum DeviceEvent {
     UserActivity,
 }
 
-#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Serialize, Deserialize)]
 pub enum PowerSource {
     Host,
     Wall,
@@ -508,3 +506,104 @@ pub fn parse_device_events(rx: &Receiver<InputEvent>, ty: &Sender<DeviceEvent>,
         }
     }
 }
+
+// Produces the raw events that the touch screen of the current device would
+// emit for a given set of contacts: it's the inverse of `parse_device_events`.
+pub struct TouchEncoder {
+    dims: (u32, u32),
+    rotation: i8,
+    contacts: Vec<Point>,
+}
+
+impl TouchEncoder {
+    pub fn new(display: Display) -> TouchEncoder {
+        TouchEncoder {
+            dims: display.dims,
+            rotation: display.rotation,
+            contacts: Vec::new(),
+        }
+    }
+
+    pub fn rotate(&mut self, n: i8) -> InputEvent {
+        if (self.rotation - n).abs() % 2 == 1 {
+            mem::swap(&mut self.dims.0, &mut self.dims.1);
+        }
+        self.rotation = n;
+        display_rotate_event(n)
+    }
+
+    // Returns the events describing the transition from the current contacts to the given ones.
+    pub fn touch(&mut self, contacts: &[Point]) -> Vec<InputEvent> {
+        let proto = CURRENT_DEVICE.proto;
+        let mut tc = match proto {
+            TouchProto::Single => SINGLE_TOUCH_CODES,
+            TouchProto::MultiA => MULTI_TOUCH_CODES_A,
+            TouchProto::MultiB | TouchProto::MultiC => MULTI_TOUCH_CODES_B,
+        };
+        if CURRENT_DEVICE.should_swap_axes(self.rotation) {
+            mem::swap(&mut tc.x, &mut tc.y);
+        }
+        let (mirror_x, mirror_y) = CURRENT_DEVICE.should_mirror_axes(self.rotation);
+        let max_contacts = if proto == TouchProto::Single { 1 } else { contacts.len() };
+        let contacts = &contacts[..contacts.len().min(max_contacts)];
+        let (width, height) = (self.dims.0 as i32, self.dims.1 as i32);
+        let mut events = Vec::new();
+
+        for i in 0..self.contacts.len().max(contacts.len()) {
+            let (mut position, pressure) = match contacts.get(i) {
+                Some(&position) => (position, 1),
+                None => (self.contacts[i], 0),
+            };
+            // The parser compensates for the coordinates reported on release by these devices.
+            if proto == TouchProto::Single && CURRENT_DEVICE.mark() == 3 && pressure == 0 {
+                position = pt!(width - 1 - position.y, position.x);
+            }
+            if proto != TouchProto::Single {
+                events.push(input_event(EV_ABS, ABS_MT_TRACKING_ID, i as i32));
+            }
+            let x = if mirror_x { width - 1 - position.x } else { position.x };
+            let y = if mirror_y { height - 1 - position.y } else { position.y };
+            events.push(input_event(EV_ABS, tc.x, x));
+            events.push(input_event(EV_ABS, tc.y, y));
+            events.push(input_event(EV_ABS, tc.pressure, pressure));
+        }
+
+        events.push(input_event(EV_SYN, SYN_REPORT, 0));
+        self.contacts = contacts.to_vec();
+        events
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_touch_encoder() {
+        let (width, height) = CURRENT_DEVICE.dims;
+        let display = Display { dims: (width, height), rotation: CURRENT_DEVICE.startup_rotation() };
+        let mut encoder = TouchEncoder::new(display);
+        let (tx, rx) = mpsc::channel();
+        let position = pt!(width as i32 / 3, height as i32 / 5);
+        let mut expected = Vec::new();
+
+        for n in 0..4 {
+            tx.send(encoder.rotate(n)).unwrap();
+            for evt in encoder.touch(&[position]).into_iter().chain(encoder.touch(&[])) {
+                tx.send(evt).unwrap();
+            }
+            expected.push((FingerStatus::Down, position));
+            expected.push((FingerStatus::Up, position));
+        }
+
+        drop(tx);
+        let (ty, ry) = mpsc::channel();
+        parse_device_events(&rx, &ty, display, ButtonScheme::Natural);
+        let fingers: Vec<(FingerStatus, Point)> = ry.try_iter().filter_map(|evt| match evt {
+            DeviceEvent::Finger { status, position, .. } => Some((status, position)),
+            _ => None,
+        }).collect();
+
+        assert_eq!(fingers, expected);
+    }
+}
@@ -12,6 +12,9 @@ pub mod helpers;
 mod dictionary;
 pub mod document;
 pub mod library;
+pub mod duplicates;
+pub mod calibre;
+pub mod watcher;
 pub mod view;
 pub mod metadata;
 pub mod rtc;
@@ -19,6 +22,11 @@ pub mod settings;
 pub mod font;
 pub mod context;
 pub mod gesture;
+pub mod control;
+pub mod services;
+pub mod scheduler;
+#[cfg(test)]
+pub mod harness;
 
 pub use anyhow;
 pub use fxhash;
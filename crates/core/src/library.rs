@@ -1,24 +1,69 @@
-use std::fs::{self, File};
+use std::fs::{self, File, OpenOptions};
 use std::str::FromStr;
+use std::borrow::Cow;
 use std::time::{SystemTime, Duration};
 use std::path::{PathBuf, Path};
 use std::collections::BTreeSet;
-use std::io::{Error as IoError, ErrorKind};
+use std::io::{BufRead, BufReader, BufWriter, Read, Seek, SeekFrom, Write, ErrorKind};
 use walkdir::WalkDir;
-use indexmap::IndexMap;
+use indexmap::{IndexMap, IndexSet};
 use fxhash::{FxHashMap, FxHashSet, FxBuildHasher};
 use chrono::{Local, DateTime};
-use anyhow::{Error, bail, format_err};
+use serde::{Serialize, Deserialize};
+use anyhow::{Context, Error, bail, format_err};
 use crate::metadata::{Info, ReaderInfo, FileInfo, BookQuery, SimpleStatus, SortMethod};
 use crate::metadata::{sort, sorter, extract_metadata_from_document};
 use crate::settings::{LibraryMode, ImportSettings};
 use crate::document::file_kind;
-use crate::helpers::{Fingerprint, Fp, save_json, load_json, IsHidden};
+use crate::calibre::sidecar_opf;
+use crate::helpers::{Fingerprint, Fp, save_json, load_json, content_hash, IsHidden};
 
-pub const METADATA_FILENAME: &str = ".metadata.json";
+pub const METADATA_FILENAME: &str = ".metadata.jsonl";
+pub const LEGACY_METADATA_FILENAME: &str = ".metadata.json";
 pub const FAT32_EPOCH_FILENAME: &str = ".fat32-epoch";
 pub const READING_STATES_DIRNAME: &str = ".reading-states";
 pub const THUMBNAIL_PREVIEWS_DIRNAME: &str = ".thumbnail-previews";
+// The reading states of the missing documents, named after their content hashes.
+pub const ORPHANS_DIRNAME: &str = ".orphans";
+// The content hashes of the documents, indexed by fingerprint.
+pub const CONTENT_HASHES_FILENAME: &str = ".content-hashes.json";
+
+// The orphaned reading states expire after this duration,
+// and only the most recent ones are kept.
+const ORPHANS_MAX_AGE: Duration = Duration::from_secs(180 * 86_400);
+const ORPHANS_MAX_COUNT: usize = 256;
+
+// The journal is compacted when it holds more than
+// `COMPACTION_FACTOR * db.len() + COMPACTION_SLACK` records.
+const COMPACTION_FACTOR: usize = 2;
+const COMPACTION_SLACK: usize = 64;
+
+// A line of the database's journal.
+// The entry is removed when *info* is missing.
+// The reading state is cached so that the reading states directory
+// doesn't have to be parsed at startup: the cached copy is used as long
+// as the size and the modification time of its file are unchanged.
+#[derive(Serialize, Deserialize)]
+struct Record<'a> {
+    fp: Fp,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    info: Option<Cow<'a, Info>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    reader: Option<Cow<'a, ReaderInfo>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    stamp: Option<FileStamp>,
+}
+
+pub type Database = IndexMap<Fp, Info, FxBuildHasher>;
+
+// The size and the modification time, in nanoseconds since the UNIX epoch, of a file.
+type FileStamp = (u64, u64);
+
+fn file_stamp(metadata: &fs::Metadata) -> Option<FileStamp> {
+    let modified = metadata.modified().ok()?
+                           .duration_since(std::time::UNIX_EPOCH).ok()?;
+    Some((metadata.len(), modified.as_nanos() as u64))
+}
 
 pub struct Library {
     pub home: PathBuf,
@@ -27,7 +72,13 @@ pub struct Library {
     pub paths: FxHashMap<PathBuf, Fp>,
     pub reading_states: FxHashMap<Fp, ReaderInfo>,
     pub modified_reading_states: FxHashSet<Fp>,
-    pub has_db_changed: bool,
+    // The content hashes of the documents, computed on demand.
+    pub hashes: FxHashMap<Fp, Fp>,
+    pub modified_hashes: bool,
+    // The entries that need to be appended to the journal.
+    pub modified_entries: IndexSet<Fp, FxBuildHasher>,
+    // The number of records in the journal.
+    pub journal_len: usize,
     pub fat32_epoch: SystemTime,
     pub sort_method: SortMethod,
     pub reverse_order: bool,
@@ -42,25 +93,6 @@ impl Library {
             }
         }
 
-        let path = home.as_ref().join(METADATA_FILENAME);
-        let mut db;
-        if mode == LibraryMode::Database {
-            match load_json::<IndexMap<Fp, Info, FxBuildHasher>, _>(&path) {
-                Err(e) => {
-                    if e.downcast_ref::<IoError>().map(|e| e.kind()) != Some(ErrorKind::NotFound) {
-                        bail!(e);
-                    } else {
-                        db = IndexMap::with_capacity_and_hasher(0, FxBuildHasher::default());
-                    }
-                },
-                Ok(v) => db = v,
-            }
-        } else {
-            db = IndexMap::with_capacity_and_hasher(0, FxBuildHasher::default());
-        }
-
-        let mut reading_states = FxHashMap::default();
-
         let path = home.as_ref().join(READING_STATES_DIRNAME);
         if let Err(e) = fs::create_dir(&path) {
             if e.kind() != ErrorKind::AlreadyExists {
@@ -68,36 +100,11 @@ impl Library {
             }
         }
 
-        for entry in fs::read_dir(&path)? {
-            let entry = entry?;
-            let path = entry.path();
-            if let Some(fp) = path.file_stem().and_then(|v| v.to_str())
-                                  .and_then(|v| Fp::from_str(v).ok()) {
-                if let Ok(reader_info) = load_json(path).map_err(|e| eprintln!("Can't load reading state: {:#}.", e)) {
-                    if mode == LibraryMode::Database {
-                        if let Some(info) = db.get_mut(&fp) {
-                            info.reader = Some(reader_info);
-                        } else {
-                            eprintln!("Unknown fingerprint: {}.", fp);
-                        }
-                    } else {
-                        reading_states.insert(fp, reader_info);
-                    }
-                }
-            }
-        }
-
         let path = home.as_ref().join(THUMBNAIL_PREVIEWS_DIRNAME);
         if !path.exists() {
             fs::create_dir(&path).ok();
         }
 
-        let paths = if mode == LibraryMode::Database {
-            db.iter().map(|(fp, info)| (info.file.path.clone(), *fp)).collect()
-        } else {
-            FxHashMap::default()
-        };
-
         let path = home.as_ref().join(FAT32_EPOCH_FILENAME);
         if !path.exists() {
             let file = File::create(&path)?;
@@ -108,19 +115,26 @@ impl Library {
 
         let sort_method = SortMethod::Opened;
 
-        Ok(Library {
+        let mut library = Library {
             home: home.as_ref().to_path_buf(),
             mode,
-            db,
-            paths,
-            reading_states,
+            db: IndexMap::with_capacity_and_hasher(0, FxBuildHasher::default()),
+            paths: FxHashMap::default(),
+            reading_states: FxHashMap::default(),
             modified_reading_states: FxHashSet::default(),
-            has_db_changed: false,
+            hashes: FxHashMap::default(),
+            modified_hashes: false,
+            modified_entries: IndexSet::with_capacity_and_hasher(0, FxBuildHasher::default()),
+            journal_len: 0,
             fat32_epoch,
             sort_method,
             reverse_order: sort_method.reverse_order(),
             show_hidden: false,
-        })
+        };
+
+        library.load()?;
+
+        Ok(library)
     }
 
     pub fn list<P: AsRef<Path>>(&self, prefix: P, query: Option<&BookQuery>, skip_files: bool) -> (Vec<Info>, BTreeSet<PathBuf>) {
@@ -221,6 +235,8 @@ impl Library {
             return;
         }
 
+        let mut added = Vec::new();
+
         for entry in WalkDir::new(&self.home).min_depth(1).into_iter()
                              .filter_entry(|e| !e.is_hidden()) {
             if entry.is_err() {
@@ -246,13 +262,17 @@ impl Library {
                     self.paths.remove(&self.db[&fp].file.path);
                     self.paths.insert(relat.to_path_buf(), fp);
                     self.db[&fp].file.path = relat.to_path_buf();
-                    self.has_db_changed = true;
+                    self.modified_entries.insert(fp);
+                }
+                if self.db[&fp].reader.is_some() {
+                    self.hash_of(fp, path);
                 }
             // The path is known: update the fp.
             } else if let Some(fp2) = self.paths.get(relat).cloned() {
                 println!("Update fingerprint for {}: {} → {}.", relat.display(), fp2, fp);
                 let mut info = self.db.swap_remove(&fp2).unwrap();
-                if settings.sync_metadata && settings.metadata_kinds.contains(&info.file.kind) {
+                if settings.sync_metadata && (settings.metadata_kinds.contains(&info.file.kind) ||
+                                              sidecar_opf(path).is_some()) {
                     extract_metadata_from_document(&self.home, &mut info);
                 }
                 self.db.insert(fp, info);
@@ -265,7 +285,8 @@ impl Library {
                 if tpp.exists() {
                     fs::remove_file(tpp).ok();
                 }
-                self.has_db_changed = true;
+                self.modified_entries.insert(fp2);
+                self.modified_entries.insert(fp);
             } else {
                 let fp1 = self.fat32_epoch.checked_sub(Duration::from_secs(1))
                               .and_then(|epoch| md.fingerprint(epoch).ok()).unwrap_or(fp);
@@ -288,6 +309,7 @@ impl Library {
                     println!("Update fingerprint for {}: {} → {}.", self.db[&nfp].file.path.display(), nfp, fp);
                     let info = self.db.swap_remove(&nfp).unwrap();
                     self.db.insert(fp, info);
+                    self.modified_entries.insert(nfp);
                     let rp1 = self.reading_state_path(nfp);
                     let rp2 = self.reading_state_path(fp);
                     fs::rename(rp1, rp2).ok();
@@ -318,19 +340,22 @@ impl Library {
                         file,
                         .. Default::default()
                     };
-                    if settings.metadata_kinds.contains(&info.file.kind) {
+                    if settings.metadata_kinds.contains(&info.file.kind) || sidecar_opf(path).is_some() {
                         extract_metadata_from_document(&self.home, &mut info);
                     }
                     self.db.insert(fp, info);
                     self.paths.insert(relat.to_path_buf(), fp);
+                    added.push(fp);
                 }
 
-                self.has_db_changed = true;
+                self.modified_entries.insert(fp);
             }
         }
 
         let home = &self.home;
         let len = self.db.len();
+        let modified_entries = &mut self.modified_entries;
+        let mut orphans = Vec::new();
 
         self.db.retain(|fp, info| {
             let path = home.join(&info.file.path);
@@ -338,12 +363,24 @@ impl Library {
                 true
             } else {
                 println!("Remove entry: {}, {}.", fp, info.file.path.display());
+                modified_entries.insert(*fp);
+                if let Some(reader_info) = info.reader.take() {
+                    orphans.push((*fp, reader_info));
+                }
                 false
             }
         });
 
+        for (fp, reader_info) in orphans {
+            self.keep_orphan(fp, &reader_info);
+        }
+
+        let db = &self.db;
+        let count = self.hashes.len();
+        self.hashes.retain(|fp, _| db.contains_key(fp));
+        self.modified_hashes |= self.hashes.len() != count;
+
         if self.db.len() != len {
-            self.has_db_changed = true;
             let db = &self.db;
             self.paths.retain(|_, fp| db.contains_key(fp));
             self.modified_reading_states.retain(|fp| db.contains_key(fp));
@@ -360,11 +397,18 @@ impl Library {
                                        .and_then(|v| v.to_str())
                                        .and_then(|v| Fp::from_str(v).ok()) {
                     if !self.db.contains_key(&fp) {
-                        fs::remove_file(entry.path()).ok();
+                        remove_entry(&entry);
                     }
                 }
             }
         }
+
+        if self.prune_orphans() {
+            for fp in added {
+                let path = self.home.join(&self.db[&fp].file.path);
+                self.reattach(fp, &path);
+            }
+        }
     }
 
     pub fn add_document(&mut self, info: Info) {
@@ -379,7 +423,7 @@ impl Library {
         if self.mode == LibraryMode::Database {
             self.paths.insert(info.file.path.clone(), fp);
             self.db.insert(fp, info);
-            self.has_db_changed = true;
+            self.modified_entries.insert(fp);
         } else {
             if let Some(reader_info) = info.reader {
                 self.reading_states.insert(fp, reader_info);
@@ -387,6 +431,126 @@ impl Library {
         }
     }
 
+    // Returns the entry of the document at the given path, which is either
+    // absolute or relative to the library's home.
+    pub fn info<P: AsRef<Path>>(&self, path: P) -> Option<Info> {
+        let relat = path.as_ref().strip_prefix(&self.home)
+                        .unwrap_or_else(|_| path.as_ref());
+        match self.mode {
+            LibraryMode::Database => {
+                self.paths.get(relat)
+                    .and_then(|fp| self.db.get(fp))
+                    .cloned()
+            },
+            LibraryMode::Filesystem => {
+                let md = self.home.join(relat).metadata().ok()
+                             .filter(|md| md.is_file())?;
+                let fp = md.fingerprint(self.fat32_epoch).ok()?;
+                let secs = (*fp >> 32) as i64;
+                let nsecs = ((*fp & ((1<<32) - 1)) % 1_000_000_000) as u32;
+                let added = DateTime::from_timestamp(secs, nsecs)?.naive_utc();
+                Some(Info {
+                    file: FileInfo {
+                        path: relat.to_path_buf(),
+                        kind: file_kind(relat).unwrap_or_default(),
+                        size: md.len(),
+                    },
+                    added,
+                    reader: self.reading_states.get(&fp).cloned(),
+                    .. Default::default()
+                })
+            },
+        }
+    }
+
+    // Replaces the metadata of an existing entry.
+    // Only the reading state is kept in filesystem mode.
+    pub fn update(&mut self, info: Info) -> Result<(), Error> {
+        let path = self.home.join(&info.file.path);
+        let fp = self.paths.get(&info.file.path).cloned()
+                     .or_else(|| path.metadata().ok()
+                                     .and_then(|md| md.fingerprint(self.fat32_epoch).ok()))
+                     .ok_or_else(|| format_err!("can't get fingerprint of {}", info.file.path.display()))?;
+
+        if info.reader.is_some() {
+            self.stamp(fp, &path);
+            self.modified_reading_states.insert(fp);
+        }
+
+        match self.mode {
+            LibraryMode::Database => {
+                let entry = self.db.get_mut(&fp)
+                                .ok_or_else(|| format_err!("unknown document: {}", info.file.path.display()))?;
+                *entry = info;
+                self.modified_entries.insert(fp);
+            },
+            LibraryMode::Filesystem => {
+                if let Some(reader_info) = info.reader {
+                    self.reading_states.insert(fp, reader_info);
+                }
+            },
+        }
+
+        Ok(())
+    }
+
+    // Rewrites the document at the given path with *f*, and moves its entry, reading state
+    // and thumbnails to the new fingerprint of the document.
+    pub fn modify<P, F>(&mut self, path: P, f: F) -> Result<(), Error>
+    where P: AsRef<Path>, F: FnOnce(&Path) -> Result<(), Error> {
+        let full_path = self.home.join(path.as_ref());
+
+        let fp = self.paths.get(path.as_ref()).cloned().or_else(|| {
+            full_path.metadata().ok()
+                     .and_then(|md| md.fingerprint(self.fat32_epoch).ok())
+        }).ok_or_else(|| format_err!("can't get fingerprint of {}", path.as_ref().display()))?;
+
+        f(&full_path)?;
+
+        let md = full_path.metadata()?;
+        let new_fp = md.fingerprint(self.fat32_epoch)?;
+
+        if new_fp == fp {
+            return Ok(());
+        }
+
+        self.modified_reading_states.remove(&fp);
+        self.modified_hashes |= self.hashes.remove(&fp).is_some();
+
+        let has_reader = match self.mode {
+            LibraryMode::Database => {
+                let mut info = self.db.swap_remove(&fp)
+                                   .ok_or_else(|| format_err!("unknown document: {}", path.as_ref().display()))?;
+                info.file.size = md.len();
+                let has_reader = info.reader.is_some();
+                self.db.insert(new_fp, info);
+                self.paths.insert(path.as_ref().to_path_buf(), new_fp);
+                self.modified_entries.insert(fp);
+                self.modified_entries.insert(new_fp);
+                has_reader
+            },
+            LibraryMode::Filesystem => {
+                if let Some(reader_info) = self.reading_states.remove(&fp) {
+                    self.reading_states.insert(new_fp, reader_info);
+                    true
+                } else {
+                    false
+                }
+            },
+        };
+
+        if has_reader {
+            self.modified_reading_states.insert(new_fp);
+            self.hash_of(new_fp, &full_path);
+        }
+
+        fs::rename(self.reading_state_path(fp), self.reading_state_path(new_fp)).ok();
+        fs::rename(self.thumbnail_preview_path(fp), self.thumbnail_preview_path(new_fp)).ok();
+        fs::rename(self.page_thumbnails_path(fp), self.page_thumbnails_path(new_fp)).ok();
+
+        Ok(())
+    }
+
     pub fn rename<P: AsRef<Path>>(&mut self, path: P, file_name: &str) -> Result<(), Error> {
         let src = self.home.join(path.as_ref());
 
@@ -404,7 +568,7 @@ impl Library {
             self.paths.insert(new_path.to_path_buf(), fp);
             if let Some(info) = self.db.get_mut(&fp) {
                 info.file.path = new_path.to_path_buf();
-                self.has_db_changed = true;
+                self.modified_entries.insert(fp);
             }
         }
 
@@ -439,16 +603,22 @@ impl Library {
             fs::remove_file(tpp)?;
         }
 
+        let ptd = self.page_thumbnails_path(fp);
+        if ptd.exists() {
+            fs::remove_dir_all(ptd)?;
+        }
+
         if self.mode == LibraryMode::Database {
             self.paths.remove(path.as_ref());
             if self.db.shift_remove(&fp).is_some() {
-                self.has_db_changed = true;
+                self.modified_entries.insert(fp);
             }
         } else {
             self.reading_states.remove(&fp);
         }
 
         self.modified_reading_states.remove(&fp);
+        self.modified_hashes |= self.hashes.remove(&fp).is_some();
 
         Ok(())
     }
@@ -515,7 +685,7 @@ impl Library {
                 info.file.path = dest_path.to_path_buf();
                 other.db.insert(fp, info);
                 other.paths.insert(dest_path.to_path_buf(), fp);
-                other.has_db_changed = true;
+                other.modified_entries.insert(fp);
             }
         } else {
             let reader_info = self.reading_states.get(&fp).cloned()
@@ -526,7 +696,13 @@ impl Library {
             }
         }
 
+        if let Some(hash) = self.hashes.get(&fp) {
+            other.hashes.insert(fp, *hash);
+            other.modified_hashes = true;
+        }
+
         other.modified_reading_states.insert(fp);
+        other.reattach(fp, &dest);
 
         Ok(())
     }
@@ -571,6 +747,12 @@ impl Library {
             fs::rename(&tpp_src, &tpp_dest)?;
         }
 
+        let ptd_src = self.page_thumbnails_path(fp);
+        if ptd_src.exists() {
+            let ptd_dest = other.page_thumbnails_path(fp);
+            fs::rename(&ptd_src, &ptd_dest)?;
+        }
+
         if other.mode == LibraryMode::Database {
             let info = self.db.shift_remove(&fp)
                            .or_else(||
@@ -591,8 +773,8 @@ impl Library {
                 other.db.insert(fp, info);
                 self.paths.remove(path.as_ref());
                 other.paths.insert(dest_path.to_path_buf(), fp);
-                self.has_db_changed = true;
-                other.has_db_changed = true;
+                self.modified_entries.insert(fp);
+                other.modified_entries.insert(fp);
             }
         } else {
             let reader_info = self.reading_states.remove(&fp)
@@ -607,6 +789,14 @@ impl Library {
             other.modified_reading_states.insert(fp);
         }
 
+        if let Some(hash) = self.hashes.remove(&fp) {
+            self.modified_hashes = true;
+            other.hashes.insert(fp, hash);
+            other.modified_hashes = true;
+        }
+
+        other.reattach(fp, &dest);
+
         Ok(())
     }
 
@@ -628,16 +818,46 @@ impl Library {
                           })
                           .collect::<FxHashSet<Fp>>();
 
-        self.reading_states.retain(|fp, _| {
+        let mut orphans = Vec::new();
+        self.reading_states.retain(|fp, reader_info| {
             if fps.contains(fp) {
                 true
             } else {
                 println!("Remove reading state for {}.", fp);
+                orphans.push((*fp, reader_info.clone()));
                 false
             }
         });
         self.modified_reading_states.retain(|fp| fps.contains(fp));
 
+        for (fp, reader_info) in orphans {
+            self.keep_orphan(fp, &reader_info);
+        }
+
+        let count = self.hashes.len();
+        self.hashes.retain(|fp, _| fps.contains(fp));
+        self.modified_hashes |= self.hashes.len() != count;
+
+        // The hashes of the documents are cached: they're only computed once.
+        if self.prune_orphans() {
+            let documents = WalkDir::new(&self.home)
+                                    .min_depth(1).into_iter()
+                                    .filter_entry(|e| !e.is_hidden())
+                                    .filter_map(|entry| entry.ok())
+                                    .filter(|entry| entry.file_type().is_file() &&
+                                                    file_kind(entry.path()).is_some())
+                                    .filter_map(|entry| {
+                                        entry.metadata().ok()
+                                             .and_then(|md| md.fingerprint(self.fat32_epoch).ok())
+                                             .filter(|fp| !self.reading_states.contains_key(fp))
+                                             .map(|fp| (fp, entry.into_path()))
+                                    })
+                                    .collect::<Vec<(Fp, PathBuf)>>();
+            for (fp, path) in documents {
+                self.reattach(fp, &path);
+            }
+        }
+
         let reading_states_dir = self.home.join(READING_STATES_DIRNAME);
         let thumbnail_previews_dir = self.home.join(THUMBNAIL_PREVIEWS_DIRNAME);
         for entry in fs::read_dir(&reading_states_dir).unwrap()
@@ -650,7 +870,7 @@ impl Library {
                                    .and_then(|v| v.to_str())
                                    .and_then(|v| Fp::from_str(v).ok()) {
                 if !fps.contains(&fp) {
-                    fs::remove_file(entry.path()).ok();
+                    remove_entry(&entry);
                 }
             }
         }
@@ -682,7 +902,7 @@ impl Library {
             f(&self.home, info);
         }
 
-        self.has_db_changed = true;
+        self.modified_entries.extend(self.db.keys().cloned());
     }
 
     pub fn sync_reader_info<P: AsRef<Path>>(&mut self, path: P, reader: &ReaderInfo) {
@@ -691,15 +911,17 @@ impl Library {
                 .metadata().unwrap()
                 .fingerprint(self.fat32_epoch).unwrap()
         });
+        let reader = reader.clone();
+        self.stamp(fp, path.as_ref());
         self.modified_reading_states.insert(fp);
         match self.mode {
             LibraryMode::Database => {
                 if let Some(info) = self.db.get_mut(&fp) {
-                    info.reader = Some(reader.clone());
+                    info.reader = Some(reader);
                 }
             },
             LibraryMode::Filesystem => {
-                self.reading_states.insert(fp, reader.clone());
+                self.reading_states.insert(fp, reader);
             },
         }
     }
@@ -717,6 +939,15 @@ impl Library {
         }
     }
 
+    pub fn page_thumbnails<P: AsRef<Path>>(&self, path: P) -> PathBuf {
+        let fp = self.paths.get(path.as_ref()).cloned().unwrap_or_else(|| {
+            self.home.join(path.as_ref())
+                .metadata().unwrap()
+                .fingerprint(self.fat32_epoch).unwrap()
+        });
+        self.page_thumbnails_path(fp)
+    }
+
     pub fn set_status<P: AsRef<Path>>(&mut self, path: P, status: SimpleStatus) {
         let fp = self.paths.get(path.as_ref()).cloned().unwrap_or_else(|| {
             self.home.join(path.as_ref())
@@ -731,12 +962,14 @@ impl Library {
                     }
                     fs::remove_file(self.reading_state_path(fp)).ok();
                     self.modified_reading_states.remove(&fp);
+                    self.modified_entries.insert(fp);
                 },
                 SimpleStatus::Reading | SimpleStatus::Finished => {
-                    if let Some(info) = self.db.get_mut(&fp) {
-                        let reader_info = info.reader
-                                              .get_or_insert_with(ReaderInfo::default);
+                    if let Some(info) = self.db.get(&fp) {
+                        let mut reader_info = info.reader.clone().unwrap_or_default();
                         reader_info.finished = status == SimpleStatus::Finished;
+                        self.stamp(fp, path.as_ref());
+                        self.db[&fp].reader = Some(reader_info);
                         self.modified_reading_states.insert(fp);
                     }
                 },
@@ -749,9 +982,10 @@ impl Library {
                     self.modified_reading_states.remove(&fp);
                 },
                 SimpleStatus::Reading | SimpleStatus::Finished => {
-                    let reader_info = self.reading_states.entry(fp)
-                                          .or_insert_with(ReaderInfo::default);
+                    let mut reader_info = self.reading_states.get(&fp).cloned().unwrap_or_default();
                     reader_info.finished = status == SimpleStatus::Finished;
+                    self.stamp(fp, path.as_ref());
+                    self.reading_states.insert(fp, reader_info);
                     self.modified_reading_states.insert(fp);
                 },
             }
@@ -759,80 +993,329 @@ impl Library {
     }
 
     pub fn reload(&mut self) {
-        if self.mode == LibraryMode::Database {
-            let path = self.home.join(METADATA_FILENAME);
+        self.load().map_err(|e| eprintln!("Can't reload database: {:#}.", e)).ok();
+    }
 
-            match load_json(&path) {
-                Err(e) => {
-                    eprintln!("Can't reload database: {:#}.", e);
-                    return;
-                },
-                Ok(v) => {
-                    self.db = v;
-                    self.has_db_changed = false;
-                },
+    pub fn flush(&mut self) {
+        for fp in &self.modified_reading_states {
+            let reader_info = if self.mode == LibraryMode::Database {
+                self.db.get(fp).and_then(|info| info.reader.as_ref())
+            } else {
+                self.reading_states.get(fp)
+            };
+            if let Some(reader_info) = reader_info {
+                save_json(reader_info, self.reading_state_path(*fp))
+                         .map_err(|e| eprintln!("Can't save reading state: {:#}.", e)).ok();
             }
         }
 
-        let path = self.home.join(READING_STATES_DIRNAME);
+        // The journal caches the reading states: the records of the modified
+        // reading states need to be appended too.
+        if self.mode == LibraryMode::Database {
+            self.modified_entries.extend(self.modified_reading_states.iter().cloned());
+        }
 
         self.modified_reading_states.clear();
-        if self.mode == LibraryMode::Filesystem {
-            self.reading_states.clear();
+
+        if self.modified_hashes {
+            save_json(&self.hashes, self.home.join(CONTENT_HASHES_FILENAME))
+                     .map_err(|e| eprintln!("Can't save content hashes: {:#}.", e)).ok();
+            self.modified_hashes = false;
         }
 
-        for entry in fs::read_dir(&path).unwrap() {
-            let entry = entry.unwrap();
+        if !self.modified_entries.is_empty() {
+            let count = self.journal_len + self.modified_entries.len();
+            let result = if count > COMPACTION_FACTOR * self.db.len() + COMPACTION_SLACK ||
+                            self.modified_entries.len() >= self.db.len() {
+                self.compact()
+            } else {
+                self.append()
+            };
+            result.map_err(|e| eprintln!("Can't save database: {:#}.", e)).ok();
+            self.modified_entries.clear();
+        }
+    }
+
+    pub fn is_empty(&self) -> Option<bool> {
+        if self.mode == LibraryMode::Database {
+            Some(self.db.is_empty())
+        } else {
+            None
+        }
+    }
+
+    // Loads the database and the reading states.
+    // TODO: Replay the journal lazily.
+    fn load(&mut self) -> Result<(), Error> {
+        let path = self.home.join(METADATA_FILENAME);
+        let legacy_path = self.home.join(LEGACY_METADATA_FILENAME);
+        // The stamps of the reading state files whose content is cached in the journal.
+        let mut stamps = None;
+        let mut migrate = false;
+
+        let hashes_path = self.home.join(CONTENT_HASHES_FILENAME);
+        self.hashes = if hashes_path.exists() {
+            load_json(&hashes_path).map_err(|e| eprintln!("Can't load content hashes: {:#}.", e))
+                                   .unwrap_or_default()
+        } else {
+            FxHashMap::default()
+        };
+        self.modified_hashes = false;
+
+        if self.mode == LibraryMode::Database {
+            if path.exists() {
+                let (db, journal_stamps, count) = load_journal(&path)?;
+                stamps = Some(journal_stamps);
+                self.db = db;
+                self.journal_len = count;
+            } else if legacy_path.exists() {
+                self.db = load_json(&legacy_path)?;
+                migrate = true;
+            } else {
+                self.db.clear();
+                self.journal_len = 0;
+            }
+            self.paths = self.db.iter().map(|(fp, info)| (info.file.path.clone(), *fp)).collect();
+        }
+
+        self.modified_reading_states.clear();
+        self.modified_entries.clear();
+        self.reading_states.clear();
+
+        let mut found = FxHashSet::default();
+
+        for entry in fs::read_dir(self.home.join(READING_STATES_DIRNAME))? {
+            let entry = entry?;
             let path = entry.path();
             if let Some(fp) = path.file_stem().and_then(|v| v.to_str())
                                   .and_then(|v| Fp::from_str(v).ok()) {
-                if let Ok(reader_info) = load_json(path).map_err(|e| eprintln!("Can't load reading state: {:#}.", e)) {
-                    if self.mode == LibraryMode::Database {
-                        if let Some(info) = self.db.get_mut(&fp) {
+                found.insert(fp);
+                if self.mode == LibraryMode::Database {
+                    if let Some(info) = self.db.get_mut(&fp) {
+                        let cached = info.reader.is_some() && stamps.as_ref().and_then(|stamps| stamps.get(&fp)).is_some_and(|stamp| {
+                            entry.metadata().ok().and_then(|md| file_stamp(&md)) == Some(*stamp)
+                        });
+                        if cached {
+                            continue;
+                        }
+                        if let Ok(reader_info) = load_json(&path).map_err(|e| eprintln!("Can't load reading state: {:#}.", e)) {
                             info.reader = Some(reader_info);
-                        } else {
-                            eprintln!("Unknown fingerprint: {}.", fp);
+                            if stamps.is_some() {
+                                self.modified_entries.insert(fp);
+                            }
                         }
                     } else {
-                        self.reading_states.insert(fp, reader_info);
+                        eprintln!("Unknown fingerprint: {}.", fp);
                     }
+                } else if let Ok(reader_info) = load_json(&path).map_err(|e| eprintln!("Can't load reading state: {:#}.", e)) {
+                    self.reading_states.insert(fp, reader_info);
                 }
             }
         }
 
         if self.mode == LibraryMode::Database {
-            self.paths = self.db.iter().map(|(fp, info)| (info.file.path.clone(), *fp)).collect();
+            // Forget the cached reading states whose files were removed.
+            for (fp, info) in &mut self.db {
+                if info.reader.is_some() && !found.contains(fp) {
+                    info.reader = None;
+                    self.modified_entries.insert(*fp);
+                }
+            }
+
+            if migrate {
+                println!("Migrate {} to {}.", LEGACY_METADATA_FILENAME, METADATA_FILENAME);
+                self.compact()?;
+                fs::rename(&legacy_path, self.home.join(format!("{}.bak", LEGACY_METADATA_FILENAME)))?;
+            }
         }
+
+        Ok(())
     }
 
-    pub fn flush(&mut self) {
-        for fp in &self.modified_reading_states {
-            let reader_info = if self.mode == LibraryMode::Database {
-                self.db.get(fp).and_then(|info| info.reader.as_ref())
+    fn record(&self, fp: Fp) -> Record<'_> {
+        let info = self.db.get(&fp);
+        let reader = info.and_then(|info| info.reader.as_ref());
+        let stamp = reader.and_then(|_| fs::metadata(self.reading_state_path(fp)).ok())
+                          .and_then(|md| file_stamp(&md));
+        Record {
+            fp,
+            info: info.map(Cow::Borrowed),
+            reader: reader.map(Cow::Borrowed),
+            stamp,
+        }
+    }
+
+    // Appends the records of the modified entries to the journal.
+    fn append(&mut self) -> Result<(), Error> {
+        let path = self.home.join(METADATA_FILENAME);
+        let mut file = OpenOptions::new().read(true).append(true).create(true).open(&path)
+                                         .with_context(|| format!("can't open file {}", path.display()))?;
+
+        // Terminate the last record if it was interrupted.
+        let mut last_byte = [b'\n'];
+        if file.metadata()?.len() > 0 {
+            file.seek(SeekFrom::End(-1))?;
+            file.read_exact(&mut last_byte)?;
+        }
+
+        let mut writer = BufWriter::new(file);
+        if last_byte[0] != b'\n' {
+            writer.write_all(b"\n")?;
+        }
+
+        for fp in &self.modified_entries {
+            serde_json::to_writer(&mut writer, &self.record(*fp))
+                       .with_context(|| format!("can't serialize record {}", fp))?;
+            writer.write_all(b"\n")?;
+        }
+
+        writer.flush()?;
+        self.journal_len += self.modified_entries.len();
+
+        Ok(())
+    }
+
+    // Rewrites the journal with one record per entry.
+    fn compact(&mut self) -> Result<(), Error> {
+        let path = self.home.join(METADATA_FILENAME);
+        let tmp_path = self.home.join(format!("{}.tmp", METADATA_FILENAME));
+        let file = File::create(&tmp_path)
+                        .with_context(|| format!("can't create file {}", tmp_path.display()))?;
+        let mut writer = BufWriter::new(file);
+
+        for fp in self.db.keys() {
+            serde_json::to_writer(&mut writer, &self.record(*fp))
+                       .with_context(|| format!("can't serialize record {}", fp))?;
+            writer.write_all(b"\n")?;
+        }
+
+        writer.into_inner().map_err(|e| e.into_error())?.sync_all()?;
+        fs::rename(&tmp_path, &path)?;
+        self.journal_len = self.db.len();
+
+        Ok(())
+    }
+
+    // Returns the content hash of the document at *path*, computing it if it isn't cached.
+    pub fn content_hash<P: AsRef<Path>>(&mut self, path: P) -> Option<Fp> {
+        let path = self.home.join(path.as_ref());
+        let fp = self.paths.get(path.strip_prefix(&self.home).unwrap_or(&path)).cloned().or_else(|| {
+            path.metadata().ok()
+                .and_then(|md| md.fingerprint(self.fat32_epoch).ok())
+        })?;
+        self.hash_of(fp, &path)
+    }
+
+    // Computes the content hash of a document that has a reading state, so that
+    // the reading state can be kept if the document goes missing.
+    fn stamp<P: AsRef<Path>>(&mut self, fp: Fp, path: P) {
+        let path = self.home.join(path.as_ref());
+        self.hash_of(fp, &path);
+    }
+
+    // Returns the content hash of the document *fp*, located at *path*.
+    fn hash_of(&mut self, fp: Fp, path: &Path) -> Option<Fp> {
+        if let Some(hash) = self.hashes.get(&fp) {
+            return Some(*hash);
+        }
+        let hash = content_hash(path).map_err(|e| eprintln!("Can't hash {}: {:#}.", path.display(), e)).ok()?;
+        self.hashes.insert(fp, hash);
+        self.modified_hashes = true;
+        Some(hash)
+    }
+
+    // Keeps the reading state of a missing document, so that it can be reattached
+    // if the document comes back with another fingerprint.
+    fn keep_orphan(&self, fp: Fp, reader_info: &ReaderInfo) {
+        if let Some(hash) = self.hashes.get(&fp) {
+            let dir = self.home.join(READING_STATES_DIRNAME).join(ORPHANS_DIRNAME);
+            fs::create_dir_all(&dir).ok();
+            println!("Keep orphaned reading state for {}: {}.", fp, hash);
+            save_json(reader_info, self.orphan_path(*hash))
+                     .map_err(|e| eprintln!("Can't save orphaned reading state: {:#}.", e)).ok();
+        }
+    }
+
+    // Removes the expired orphaned reading states and the oldest ones beyond the maximum count.
+    // Returns whether orphaned reading states remain.
+    fn prune_orphans(&self) -> bool {
+        let entries = match fs::read_dir(self.home.join(READING_STATES_DIRNAME).join(ORPHANS_DIRNAME)) {
+            Ok(entries) => entries,
+            Err(..) => return false,
+        };
+        let now = SystemTime::now();
+        let mut orphans = entries.filter_map(|entry| entry.ok())
+                                 .filter_map(|entry| {
+                                     let modified = entry.metadata().and_then(|md| md.modified()).ok()?;
+                                     Some((modified, entry.path()))
+                                 })
+                                 .collect::<Vec<(SystemTime, PathBuf)>>();
+        orphans.sort_by_key(|orphan| std::cmp::Reverse(orphan.0));
+        let mut count = 0;
+        for (modified, path) in orphans {
+            let expired = now.duration_since(modified).is_ok_and(|age| age > ORPHANS_MAX_AGE);
+            if expired || count >= ORPHANS_MAX_COUNT {
+                println!("Forget orphaned reading state: {}.", path.display());
+                fs::remove_file(path).ok();
             } else {
-                self.reading_states.get(fp)
-            };
-            if let Some(reader_info) = reader_info {
-                save_json(reader_info, self.reading_state_path(*fp))
-                         .map_err(|e| eprintln!("Can't save reading state: {:#}.", e)).ok();
+                count += 1;
             }
         }
+        count > 0
+    }
 
-        self.modified_reading_states.clear();
+    // Gives the orphaned reading state matching the content of the document
+    // at *path* to the entry *fp*, if it doesn't have a reading state.
+    fn reattach(&mut self, fp: Fp, path: &Path) -> bool {
+        let has_reader = match self.mode {
+            LibraryMode::Database => self.db.get(&fp).is_none_or(|info| info.reader.is_some()),
+            LibraryMode::Filesystem => self.reading_states.contains_key(&fp),
+        };
 
-        if self.has_db_changed {
-            save_json(&self.db, self.home.join(METADATA_FILENAME))
-                     .map_err(|e| eprintln!("Can't save database: {:#}.", e)).ok();
-            self.has_db_changed = false;
+        if has_reader {
+            return false;
         }
-    }
 
-    pub fn is_empty(&self) -> Option<bool> {
-        if self.mode == LibraryMode::Database {
-            Some(self.db.is_empty())
-        } else {
-            None
+        let orphan_path = match self.hash_of(fp, path) {
+            Some(hash) => self.orphan_path(hash),
+            None => return false,
+        };
+
+        if !orphan_path.exists() {
+            return false;
         }
+
+        let reader_info: ReaderInfo = match load_json(&orphan_path) {
+            Ok(reader_info) => reader_info,
+            Err(e) => {
+                eprintln!("Can't load orphaned reading state: {:#}.", e);
+                return false;
+            },
+        };
+
+        println!("Reattach reading state to {}: {}.", fp, path.display());
+
+        match self.mode {
+            LibraryMode::Database => {
+                self.db[&fp].reader = Some(reader_info);
+                self.modified_entries.insert(fp);
+            },
+            LibraryMode::Filesystem => {
+                self.reading_states.insert(fp, reader_info);
+            },
+        }
+
+        self.modified_reading_states.insert(fp);
+        fs::remove_file(orphan_path).ok();
+
+        true
+    }
+
+    fn orphan_path(&self, hash: Fp) -> PathBuf {
+        self.home
+            .join(READING_STATES_DIRNAME)
+            .join(ORPHANS_DIRNAME)
+            .join(format!("{}.json", hash))
     }
 
     fn reading_state_path(&self, fp: Fp) -> PathBuf {
@@ -846,4 +1329,194 @@ impl Library {
             .join(THUMBNAIL_PREVIEWS_DIRNAME)
             .join(format!("{}.png", fp))
     }
+
+    // The thumbnails of the pages of a document are stored in a directory named after its fingerprint.
+    fn page_thumbnails_path(&self, fp: Fp) -> PathBuf {
+        self.home
+            .join(THUMBNAIL_PREVIEWS_DIRNAME)
+            .join(format!("{}", fp))
+    }
+}
+
+fn remove_entry(entry: &fs::DirEntry) {
+    if entry.file_type().is_ok_and(|ft| ft.is_dir()) {
+        fs::remove_dir_all(entry.path()).ok();
+    } else {
+        fs::remove_file(entry.path()).ok();
+    }
+}
+
+// Replays the journal of the database.
+// Returns the entries, the stamps of their reading state files and the number of records.
+fn load_journal(path: &Path) -> Result<(Database, FxHashMap<Fp, FileStamp>, usize), Error> {
+    let file = File::open(path)
+                    .with_context(|| format!("can't open file {}", path.display()))?;
+    let mut db = IndexMap::with_capacity_and_hasher(0, FxBuildHasher::default());
+    let mut stamps = FxHashMap::default();
+    let mut count = 0;
+
+    for (index, line) in BufReader::new(file).split(b'\n').enumerate() {
+        let line = line?;
+        if line.iter().all(u8::is_ascii_whitespace) {
+            continue;
+        }
+        // A record might have been interrupted by a crash or a power loss.
+        match serde_json::from_slice::<Record>(&line) {
+            Ok(record) => {
+                if let Some(info) = record.info {
+                    let mut info = info.into_owned();
+                    info.reader = record.reader.map(Cow::into_owned);
+                    db.insert(record.fp, info);
+                } else {
+                    db.shift_remove(&record.fp);
+                }
+                if let Some(stamp) = record.stamp {
+                    stamps.insert(record.fp, stamp);
+                } else {
+                    stamps.remove(&record.fp);
+                }
+                count += 1;
+            },
+            Err(e) => eprintln!("Can't parse record {} of {}: {:#}.", index + 1, path.display(), e),
+        }
+    }
+
+    Ok((db, stamps, count))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+
+    fn journal_lines(home: &Path) -> usize {
+        fs::read_to_string(home.join(METADATA_FILENAME)).unwrap().lines().count()
+    }
+
+    #[test]
+    fn test_journal() {
+        let home = env::temp_dir().join(format!("plato-library-{}", std::process::id()));
+        fs::create_dir_all(home.join(READING_STATES_DIRNAME)).unwrap();
+        let fps: Vec<Fp> = (1..=4).map(|i| Fp::from_str(&format!("{:016X}", i)).unwrap()).collect();
+        let legacy_db: IndexMap<Fp, Info, FxBuildHasher> = fps.iter().enumerate().map(|(i, fp)| {
+            (*fp, Info {
+                title: format!("Book {}", i),
+                file: FileInfo { path: PathBuf::from(format!("{}.epub", i)), .. Default::default() },
+                .. Default::default()
+            })
+        }).collect();
+        save_json(&legacy_db, home.join(LEGACY_METADATA_FILENAME)).unwrap();
+        save_json(&ReaderInfo { current_page: 7, .. Default::default() },
+                  home.join(READING_STATES_DIRNAME).join(format!("{}.json", fps[0]))).unwrap();
+
+        // The legacy database is migrated.
+        let mut library = Library::new(&home, LibraryMode::Database).unwrap();
+        assert!(!home.join(LEGACY_METADATA_FILENAME).exists());
+        assert!(home.join(format!("{}.bak", LEGACY_METADATA_FILENAME)).exists());
+        assert_eq!(journal_lines(&home), 4);
+        assert_eq!(library.db[&fps[0]].reader.as_ref().map(|r| r.current_page), Some(7));
+
+        // Only the modified entries are appended.
+        library.set_status("1.epub", SimpleStatus::Finished);
+        library.remove("2.epub").unwrap();
+        library.flush();
+        assert_eq!(journal_lines(&home), 6);
+
+        // The reading states are read from the journal.
+        fs::write(home.join(METADATA_FILENAME),
+                  fs::read_to_string(home.join(METADATA_FILENAME)).unwrap() + "{\"fp\":").unwrap();
+        let mut library = Library::new(&home, LibraryMode::Database).unwrap();
+        assert_eq!(library.db.keys().cloned().collect::<Vec<Fp>>(), [fps[0], fps[1], fps[3]]);
+        assert_eq!(library.db[&fps[0]].reader.as_ref().map(|r| r.current_page), Some(7));
+        assert!(library.db[&fps[1]].reader.as_ref().is_some_and(|r| r.finished));
+        assert_eq!(library.paths.get(Path::new("3.epub")), Some(&fps[3]));
+
+        // The interrupted record is terminated before appending.
+        library.set_status("0.epub", SimpleStatus::New);
+        library.flush();
+        let library = Library::new(&home, LibraryMode::Database).unwrap();
+        assert!(library.db[&fps[0]].reader.is_none());
+        assert_eq!(library.journal_len, 7);
+
+        // A reading state modified behind the library's back is reloaded, whatever its modification time.
+        let path = home.join(READING_STATES_DIRNAME).join(format!("{}.json", fps[1]));
+        save_json(&ReaderInfo { current_page: 12, .. Default::default() }, &path).unwrap();
+        File::options().write(true).open(&path).unwrap()
+             .set_modified(std::time::UNIX_EPOCH + Duration::from_secs(86_400)).unwrap();
+        let library = Library::new(&home, LibraryMode::Database).unwrap();
+        assert_eq!(library.db[&fps[1]].reader.as_ref().map(|r| r.current_page), Some(12));
+
+        // Modifying every entry compacts the journal.
+        let mut library = library;
+        library.apply(|_, info| info.title.push('!'));
+        library.flush();
+        assert_eq!(journal_lines(&home), 3);
+        let library = Library::new(&home, LibraryMode::Database).unwrap();
+        assert_eq!(library.db[&fps[3]].title, "Book 3!");
+
+        fs::remove_dir_all(&home).ok();
+    }
+
+    #[test]
+    fn test_orphans() {
+        let home = env::temp_dir().join(format!("plato-orphans-{}", std::process::id()));
+        fs::create_dir_all(&home).unwrap();
+        fs::write(home.join("book.epub"), "content").unwrap();
+
+        let mut library = Library::new(&home, LibraryMode::Filesystem).unwrap();
+        library.set_status("book.epub", SimpleStatus::Finished);
+        let hash = content_hash(home.join("book.epub")).unwrap();
+        assert_eq!(library.hashes.values().collect::<Vec<&Fp>>(), [&hash]);
+        library.flush();
+        let reloaded = Library::new(&home, LibraryMode::Filesystem).unwrap();
+        assert_eq!(reloaded.hashes, library.hashes);
+
+        // The book is restored from a backup: its fingerprint changes.
+        fs::remove_file(home.join("book.epub")).unwrap();
+        library.clean_up();
+        assert!(library.reading_states.is_empty());
+        assert!(library.orphan_path(hash).exists());
+        fs::create_dir(home.join("backup")).unwrap();
+        fs::write(home.join("backup/book.epub"), "content").unwrap();
+        File::open(home.join("backup/book.epub")).unwrap()
+             .set_modified(SystemTime::now() - Duration::from_secs(3600)).unwrap();
+        library.clean_up();
+        assert!(library.info("backup/book.epub").and_then(|info| info.reader).is_some_and(|r| r.finished));
+        assert!(!library.orphan_path(hash).exists());
+
+        // The expired orphans are forgotten.
+        let stale = Fp::from_str("0123456789ABCDEF").unwrap();
+        fs::write(library.orphan_path(stale), "{}").unwrap();
+        File::open(library.orphan_path(stale)).unwrap()
+             .set_modified(SystemTime::now() - ORPHANS_MAX_AGE - Duration::from_secs(60)).unwrap();
+        assert!(!library.prune_orphans());
+        assert!(!library.orphan_path(stale).exists());
+
+        fs::remove_dir_all(&home).ok();
+    }
+
+    #[test]
+    fn test_modify() {
+        let home = env::temp_dir().join(format!("plato-modify-{}", std::process::id()));
+        fs::create_dir_all(&home).unwrap();
+        fs::write(home.join("book.epub"), "content").unwrap();
+
+        let mut library = Library::new(&home, LibraryMode::Filesystem).unwrap();
+        library.set_status("book.epub", SimpleStatus::Reading);
+        library.flush();
+        let hashes: FxHashMap<Fp, Fp> = load_json(home.join(CONTENT_HASHES_FILENAME)).unwrap();
+        assert_eq!(hashes.len(), 1);
+
+        library.modify("book.epub", |path| fs::write(path, "new content").map_err(Into::into)).unwrap();
+        let hash = content_hash(home.join("book.epub")).unwrap();
+        let fp = home.join("book.epub").metadata().unwrap().fingerprint(library.fat32_epoch).unwrap();
+        assert_eq!(library.hashes.values().collect::<Vec<&Fp>>(), [&hash]);
+        library.flush();
+        assert_eq!(fs::read_dir(home.join(READING_STATES_DIRNAME)).unwrap().count(), 1);
+        // The content hash of the previous version is forgotten.
+        let hashes: FxHashMap<Fp, Fp> = load_json(home.join(CONTENT_HASHES_FILENAME)).unwrap();
+        assert_eq!(hashes.into_iter().collect::<Vec<(Fp, Fp)>>(), [(fp, hash)]);
+
+        fs::remove_dir_all(&home).ok();
+    }
 }
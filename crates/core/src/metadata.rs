@@ -17,6 +17,8 @@ use crate::document::epub::EpubDocument;
 use crate::document::html::HtmlDocument;
 use crate::document::pdf::PdfOpener;
 use crate::document::djvu::DjvuOpener;
+use crate::document::{mobi, fb2, cbz};
+use crate::calibre::{self, sidecar_opf, plain_text};
 use crate::helpers::datetime_format;
 
 pub const DEFAULT_CONTRAST_EXPONENT: f32 = 1.0;
@@ -49,6 +51,8 @@ pub struct Info {
     pub number: String,
     #[serde(skip_serializing_if = "String::is_empty")]
     pub identifier: String,
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub description: String,
     #[serde(skip_serializing_if = "BTreeSet::is_empty")]
     pub categories: BTreeSet<String>,
     pub file: FileInfo,
@@ -206,6 +210,10 @@ pub struct ReaderInfo {
     pub scroll_mode: Option<ScrollMode>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub page_offset: Option<Point>,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub right_to_left: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub two_pages: Option<bool>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub rotation: Option<i8>,
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -238,6 +246,7 @@ pub struct ReaderInfo {
 pub enum ZoomMode {
     FitToPage,
     FitToWidth,
+    Panel,
     Custom(f32),
 }
 
@@ -252,6 +261,7 @@ impl PartialEq for ZoomMode {
         match (self, other) {
             (ZoomMode::FitToPage, ZoomMode::FitToPage) => true,
             (ZoomMode::FitToWidth, ZoomMode::FitToWidth) => true,
+            (ZoomMode::Panel, ZoomMode::Panel) => true,
             (ZoomMode::Custom(z1), ZoomMode::Custom(z2)) => (z1 - z2).abs() < f32::EPSILON,
             _ => false,
         }
@@ -277,6 +287,8 @@ impl Default for ReaderInfo {
             zoom_mode: None,
             scroll_mode: None,
             page_offset: None,
+            right_to_left: false,
+            two_pages: None,
             rotation: None,
             cropping_margins: None,
             margin_width: None,
@@ -308,6 +320,7 @@ impl Default for Info {
             volume: String::default(),
             number: String::default(),
             identifier: String::default(),
+            description: String::default(),
             categories: BTreeSet::new(),
             file: FileInfo::default(),
             added: Local::now().naive_local(),
@@ -765,6 +778,14 @@ lazy_static! {
 pub fn extract_metadata_from_document(prefix: &Path, info: &mut Info) {
     let path = prefix.join(&info.file.path);
 
+    // The metadata curated with Calibre is preferred.
+    if let Some(opf) = sidecar_opf(&path) {
+        match calibre::read_opf(&opf, info) {
+            Ok(()) => return,
+            Err(e) => eprintln!("Can't read {}: {:#}.", opf.display(), e),
+        }
+    }
+
     match info.file.kind.as_ref() {
         "epub" => {
             match EpubDocument::new(&path) {
@@ -778,6 +799,8 @@ pub fn extract_metadata_from_document(prefix: &Path, info: &mut Info) {
                         info.number = index;
                     }
                     info.language = doc.language().unwrap_or_default();
+                    info.description = doc.description().map(|text| plain_text(&text))
+                                          .unwrap_or_default();
                     info.categories.append(&mut doc.categories());
                 },
                 Err(e) => eprintln!("Can't open {}: {:#}.", info.file.path.display(), e),
@@ -814,6 +837,21 @@ pub fn extract_metadata_from_document(prefix: &Path, info: &mut Info) {
                 None => eprintln!("Can't open {}.", info.file.path.display()),
             }
         },
+        "mobi" | "azw" | "azw3" => {
+            mobi::extract_metadata(&path, info)
+                 .map_err(|e| eprintln!("Can't extract metadata from {}: {:#}.", info.file.path.display(), e))
+                 .ok();
+        },
+        "fb2" => {
+            fb2::extract_metadata(&path, info)
+                .map_err(|e| eprintln!("Can't extract metadata from {}: {:#}.", info.file.path.display(), e))
+                .ok();
+        },
+        "cbz" => {
+            cbz::extract_metadata(&path, info)
+                .map_err(|e| eprintln!("Can't extract metadata from {}: {:#}.", info.file.path.display(), e))
+                .ok();
+        },
         _ => {
                 eprintln!("Don't know how to extract metadata from {}.", &info.file.kind);
         },
@@ -4,7 +4,7 @@ use std::path::Path;
 use std::os::unix::io::AsRawFd;
 use anyhow::Error;
 use nix::{ioctl_read, ioctl_write_ptr, ioctl_none};
-use chrono::{Duration, Utc, Datelike, Timelike};
+use chrono::{DateTime, Duration, Utc, Datelike, Timelike};
 
 ioctl_read!(rtc_read_alarm, b'p', 0x10, RtcWkalrm);
 ioctl_write_ptr!(rtc_write_alarm, b'p', 0x0f, RtcWkalrm);
@@ -73,6 +73,10 @@ impl Rtc {
 
     pub fn set_alarm(&self, days: f32) -> Result<i32, Error> {
         let wt = Utc::now() + Duration::seconds((86_400.0 * days) as i64);
+        self.set_alarm_at(wt)
+    }
+
+    pub fn set_alarm_at(&self, wt: DateTime<Utc>) -> Result<i32, Error> {
         let rwa = RtcWkalrm {
             enabled: 1,
             pending: 0,
@@ -95,3 +99,26 @@ impl Rtc {
         unsafe { rtc_disable_alarm(self.0.as_raw_fd()).map_err(|e| e.into()) }
     }
 }
+
+// The alarm functions needed by the scheduler.
+pub trait AlarmClock {
+    fn arm(&self, time: DateTime<Utc>) -> Result<(), Error>;
+    // Returns whether the alarm set for *time* went off.
+    fn fired(&self, time: DateTime<Utc>, now: DateTime<Utc>) -> Result<bool, Error>;
+    fn disarm(&self) -> Result<(), Error>;
+}
+
+impl AlarmClock for Rtc {
+    fn arm(&self, time: DateTime<Utc>) -> Result<(), Error> {
+        self.set_alarm_at(time).map(|_| ())
+    }
+
+    fn fired(&self, time: DateTime<Utc>, now: DateTime<Utc>) -> Result<bool, Error> {
+        self.alarm().map(|rwa| !rwa.enabled() ||
+                               (rwa.year() <= 1970 && (now - time).num_seconds().abs() < 3))
+    }
+
+    fn disarm(&self) -> Result<(), Error> {
+        self.disable_alarm().map(|_| ())
+    }
+}
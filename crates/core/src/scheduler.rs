@@ -0,0 +1,299 @@
+// Scheduled wake-ups.
+//
+// Before going to sleep, the RTC alarm is set to the next scheduled wake-up, or to the
+// automatic power off, whichever comes first. When the device is woken up by a scheduled
+// alarm, the wake-up hooks are run until they exit, and the device goes back to sleep.
+// The automatic power off is counted from the last time the user put the device to sleep:
+// scheduled wake-ups don't postpone it.
+
+use std::io::Write;
+use std::process::Child;
+use chrono::{DateTime, Duration, NaiveTime, TimeZone, Utc};
+use serde_json::{json, Value as JsonValue};
+use crate::rtc::AlarmClock;
+use crate::services::spawn_hook;
+use crate::settings::Settings;
+use crate::context::Context;
+use crate::view::Hub;
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum AlarmKind {
+    WakeUp,
+    PowerOff,
+}
+
+#[derive(Debug, Copy, Clone)]
+pub struct Alarm {
+    pub kind: AlarmKind,
+    pub time: DateTime<Utc>,
+}
+
+#[derive(Default)]
+pub struct Scheduler {
+    alarm: Option<Alarm>,
+    // The time of the automatic power off, kept across scheduled wake-ups.
+    power_off: Option<DateTime<Utc>>,
+    hooks: Vec<Child>,
+    // The WiFi setting before the wake-up.
+    wifi: bool,
+}
+
+impl Scheduler {
+    // Sets the alarm before going to sleep.
+    pub fn arm<C: AlarmClock + ?Sized, Tz: TimeZone>(&mut self, clock: &C, now: &DateTime<Tz>, settings: &Settings) -> Option<Alarm> {
+        let wake_up = next_wake_up(&settings.wake_up.times, now)
+                                  .map(|time| Alarm { kind: AlarmKind::WakeUp, time: time.with_timezone(&Utc) });
+        self.power_off = self.power_off.or_else(|| {
+            Some(settings.auto_power_off).filter(|&days| days > 0.0)
+                .map(|days| now.with_timezone(&Utc) + Duration::seconds((86_400.0 * days) as i64))
+        });
+        let power_off = self.power_off.map(|time| Alarm { kind: AlarmKind::PowerOff, time });
+        self.alarm = match (wake_up, power_off) {
+            (Some(a), Some(b)) => Some(if a.time < b.time { a } else { b }),
+            (a, b) => a.or(b),
+        };
+        if let Some(alarm) = self.alarm {
+            if let Err(e) = clock.arm(alarm.time) {
+                eprintln!("Can't set alarm: {:#}.", e);
+                self.alarm = None;
+            }
+        }
+        self.alarm
+    }
+
+    // Returns the kind of the alarm that woke the device up, if any.
+    // The power off time is forgotten unless the device was woken up by a scheduled wake-up.
+    pub fn check<C: AlarmClock + ?Sized>(&mut self, clock: &C, now: DateTime<Utc>) -> Option<AlarmKind> {
+        let power_off = self.power_off.take();
+        let alarm = self.alarm.take()?;
+        match clock.fired(alarm.time, now) {
+            Ok(true) => {
+                if alarm.kind == AlarmKind::WakeUp {
+                    self.power_off = power_off;
+                }
+                Some(alarm.kind)
+            },
+            Ok(false) => {
+                clock.disarm()
+                     .map_err(|e| eprintln!("Can't disable alarm: {:#}.", e))
+                     .ok();
+                None
+            },
+            Err(e) => {
+                eprintln!("Can't get alarm: {:#}.", e);
+                None
+            },
+        }
+    }
+
+    // Starts the wake-up hooks. Returns the number of hooks started.
+    pub fn start(&mut self, wifi: bool, hub: &Hub, context: &Context) -> usize {
+        self.wifi = wifi;
+        let selected_library = context.settings.selected_library;
+        let library_path = &context.library.home;
+        for path in &context.settings.wake_up.hooks {
+            let hook = context.settings.libraries[selected_library].hooks.iter()
+                              .find(|hook| &hook.path == path);
+            if let Some(hook) = hook {
+                let save_path = library_path.join(&hook.path);
+                match spawn_hook(library_path, &save_path, &hook.program,
+                                 context.settings.wifi, context.online, hub) {
+                    Ok(child) => self.hooks.push(child),
+                    Err(e) => eprintln!("Can't spawn hook {}: {:#}.", path.display(), e),
+                }
+            } else {
+                eprintln!("Unknown hook: {}.", path.display());
+            }
+        }
+        self.hooks.len()
+    }
+
+    pub fn owns(&self, id: u32) -> bool {
+        self.hooks.iter().any(|child| child.id() == id)
+    }
+
+    // Called when the standard output of a hook is closed.
+    pub fn check_hook(&mut self, id: u32) {
+        if let Some(index) = self.hooks.iter().position(|child| child.id() == id) {
+            let mut child = self.hooks.remove(index);
+            child.wait().ok();
+        }
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.hooks.is_empty()
+    }
+
+    pub fn answer(&mut self, id: u32, value: bool) {
+        let child = self.hooks.iter_mut().find(|child| child.id() == id);
+        if let Some(stdin) = child.and_then(|child| child.stdin.as_mut()) {
+            writeln!(stdin, "{}", json!({"type": "answer", "value": value})).ok();
+        }
+    }
+
+    fn broadcast(&mut self, event: &JsonValue) {
+        for child in &mut self.hooks {
+            if let Some(stdin) = child.stdin.as_mut() {
+                writeln!(stdin, "{}", event).ok();
+            }
+        }
+    }
+
+    pub fn notify_network_up(&mut self) {
+        self.broadcast(&json!({"type": "network", "status": "up"}));
+    }
+
+    // Forgets the power off time, when the user ends a scheduled wake-up.
+    pub fn reset(&mut self) {
+        self.power_off = None;
+    }
+
+    // Terminates the remaining hooks and returns the WiFi setting before the wake-up.
+    pub fn stop(&mut self) -> bool {
+        for mut child in self.hooks.drain(..) {
+            unsafe { libc::kill(child.id() as libc::pid_t, libc::SIGTERM) };
+            child.wait().ok();
+        }
+        self.wifi
+    }
+}
+
+// Returns the first of the given times of the day, formatted as *HH:MM*, after *now*.
+pub fn next_wake_up<Tz: TimeZone>(times: &[String], now: &DateTime<Tz>) -> Option<DateTime<Tz>> {
+    let today = now.date_naive();
+    times.iter().filter_map(|text| {
+        NaiveTime::parse_from_str(text, "%H:%M")
+                  .map_err(|e| eprintln!("Can't parse wake-up time {}: {:#}.", text, e))
+                  .ok()
+    }).filter_map(|time| {
+        [today, today.succ_opt()?].iter()
+            .filter_map(|day| now.timezone().from_local_datetime(&day.and_time(time)).earliest())
+            .find(|date| date > now)
+    }).min()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+    use anyhow::Error;
+
+    // An alarm clock that goes off once the current time reaches the alarm.
+    #[derive(Default)]
+    struct FakeRtc {
+        alarm: Cell<Option<DateTime<Utc>>>,
+    }
+
+    impl AlarmClock for FakeRtc {
+        fn arm(&self, time: DateTime<Utc>) -> Result<(), Error> {
+            self.alarm.set(Some(time));
+            Ok(())
+        }
+
+        fn fired(&self, _time: DateTime<Utc>, now: DateTime<Utc>) -> Result<bool, Error> {
+            Ok(self.alarm.get().is_some_and(|time| time <= now))
+        }
+
+        fn disarm(&self) -> Result<(), Error> {
+            self.alarm.set(None);
+            Ok(())
+        }
+    }
+
+    fn date(text: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(text).unwrap().with_timezone(&Utc)
+    }
+
+    #[test]
+    fn test_next_wake_up() {
+        let times = vec!["06:00".to_string(), "18:30".to_string(), "bogus".to_string()];
+        let now = date("2024-03-01T05:00:00Z");
+        assert_eq!(next_wake_up(&times, &now), Some(date("2024-03-01T06:00:00Z")));
+        let now = date("2024-03-01T06:00:00Z");
+        assert_eq!(next_wake_up(&times, &now), Some(date("2024-03-01T18:30:00Z")));
+        let now = date("2024-03-01T20:00:00Z");
+        assert_eq!(next_wake_up(&times, &now), Some(date("2024-03-02T06:00:00Z")));
+        assert_eq!(next_wake_up(&[], &now), None);
+    }
+
+    #[test]
+    fn test_scheduler() {
+        let rtc = FakeRtc::default();
+        let mut scheduler = Scheduler::default();
+        let mut settings = Settings { auto_power_off: 0.0, ..Default::default() };
+        let now = date("2024-03-01T22:00:00Z");
+        assert!(scheduler.arm(&rtc, &now, &settings).is_none());
+        assert!(rtc.alarm.get().is_none());
+
+        settings.wake_up.times = vec!["06:00".to_string()];
+        settings.auto_power_off = 3.0;
+        let alarm = scheduler.arm(&rtc, &now, &settings).unwrap();
+        assert_eq!(alarm.kind, AlarmKind::WakeUp);
+        assert_eq!(rtc.alarm.get(), Some(date("2024-03-02T06:00:00Z")));
+        // Woken up by the user.
+        assert_eq!(scheduler.check(&rtc, date("2024-03-02T01:00:00Z")), None);
+        assert!(rtc.alarm.get().is_none());
+
+        scheduler.arm(&rtc, &now, &settings);
+        assert_eq!(scheduler.check(&rtc, date("2024-03-02T06:00:01Z")), Some(AlarmKind::WakeUp));
+        scheduler.arm(&rtc, &now, &settings);
+        assert_eq!(scheduler.check(&rtc, date("2024-03-02T01:00:00Z")), None);
+
+        settings.auto_power_off = 0.25;
+        let alarm = scheduler.arm(&rtc, &now, &settings).unwrap();
+        assert_eq!(alarm.kind, AlarmKind::PowerOff);
+        assert_eq!(scheduler.check(&rtc, date("2024-03-02T04:00:00Z")), Some(AlarmKind::PowerOff));
+        assert_eq!(scheduler.check(&rtc, date("2024-03-02T04:00:00Z")), None);
+    }
+
+    #[test]
+    fn test_power_off_across_wake_ups() {
+        let rtc = FakeRtc::default();
+        let mut scheduler = Scheduler::default();
+        let mut settings = Settings { auto_power_off: 1.0, ..Default::default() };
+        settings.wake_up.times = vec!["06:00".to_string(), "18:00".to_string()];
+        let now = date("2024-03-01T22:00:00Z");
+        scheduler.arm(&rtc, &now, &settings);
+        assert_eq!(scheduler.check(&rtc, date("2024-03-02T06:00:01Z")), Some(AlarmKind::WakeUp));
+
+        // Going back to sleep after a scheduled wake-up keeps the power off time.
+        scheduler.arm(&rtc, &date("2024-03-02T06:10:00Z"), &settings);
+        assert_eq!(scheduler.check(&rtc, date("2024-03-02T18:00:01Z")), Some(AlarmKind::WakeUp));
+        let alarm = scheduler.arm(&rtc, &date("2024-03-02T18:10:00Z"), &settings).unwrap();
+        assert_eq!(alarm.kind, AlarmKind::PowerOff);
+        assert_eq!(alarm.time, date("2024-03-02T22:00:00Z"));
+
+        // Woken up by the user: the next suspend starts a new countdown.
+        assert_eq!(scheduler.check(&rtc, date("2024-03-02T20:00:00Z")), None);
+        let alarm = scheduler.arm(&rtc, &date("2024-03-02T20:00:00Z"), &settings).unwrap();
+        assert_eq!(alarm.kind, AlarmKind::WakeUp);
+        assert_eq!(alarm.time, date("2024-03-03T06:00:00Z"));
+        assert_eq!(scheduler.check(&rtc, date("2024-03-03T06:00:01Z")), Some(AlarmKind::WakeUp));
+        let alarm = scheduler.arm(&rtc, &date("2024-03-03T06:10:00Z"), &settings).unwrap();
+        assert_eq!(alarm.time, date("2024-03-03T18:00:00Z"));
+        assert_eq!(scheduler.check(&rtc, date("2024-03-03T18:00:01Z")), Some(AlarmKind::WakeUp));
+        let alarm = scheduler.arm(&rtc, &date("2024-03-03T18:10:00Z"), &settings).unwrap();
+        assert_eq!(alarm.kind, AlarmKind::PowerOff);
+        assert_eq!(alarm.time, date("2024-03-03T20:00:00Z"));
+    }
+
+    #[test]
+    fn test_user_wake_up_during_wake_up() {
+        let rtc = FakeRtc::default();
+        let mut scheduler = Scheduler::default();
+        let mut settings = Settings { auto_power_off: 1.0, ..Default::default() };
+        settings.wake_up.times = vec!["06:00".to_string()];
+        scheduler.arm(&rtc, &date("2024-03-01T22:00:00Z"), &settings);
+        assert_eq!(scheduler.check(&rtc, date("2024-03-02T06:00:01Z")), Some(AlarmKind::WakeUp));
+
+        // The user wakes the device up before the hooks are done.
+        scheduler.reset();
+        let alarm = scheduler.arm(&rtc, &date("2024-03-02T09:00:00Z"), &settings).unwrap();
+        assert_eq!(alarm.kind, AlarmKind::WakeUp);
+        assert_eq!(alarm.time, date("2024-03-03T06:00:00Z"));
+        assert_eq!(scheduler.check(&rtc, date("2024-03-03T06:00:01Z")), Some(AlarmKind::WakeUp));
+        let alarm = scheduler.arm(&rtc, &date("2024-03-03T06:10:00Z"), &settings).unwrap();
+        assert_eq!(alarm.kind, AlarmKind::PowerOff);
+        assert_eq!(alarm.time, date("2024-03-03T09:00:00Z"));
+    }
+}
@@ -0,0 +1,298 @@
+// The programs started by Plato, hooks and services, communicate with it through their
+// standard streams: they write events to their standard output and read events from
+// their standard input, one JSON object per line.
+//
+// The services are started with the application and restarted when they fail (a non-zero
+// exit status or a signal). The delay before a restart doubles each time a service fails
+// shortly after being started.
+
+use std::thread;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Child, Stdio};
+use std::io::{BufRead, BufReader, Read, Write};
+use std::time::{Duration, Instant};
+use anyhow::{Error, format_err};
+use serde_json::{json, Value as JsonValue};
+use crate::view::{Event, Hub, ViewId};
+use crate::control::{self, Command as ControlCommand};
+use crate::settings::Service;
+
+const RESTART_BASE_DELAY: Duration = Duration::from_secs(2);
+const RESTART_MAX_DELAY: Duration = Duration::from_secs(600);
+// The restart delay is reset when a service ran at least this long.
+const STABLE_UPTIME: Duration = Duration::from_secs(60);
+
+// Forwards the events written by a hook or a service to the hub, until its standard output is closed.
+pub fn read_events<R: Read>(stdout: R, id: u32, hub: &Hub) {
+    let reader = BufReader::new(stdout);
+    for line_res in reader.lines() {
+        if let Ok(line) = line_res {
+            if let Ok(event) = serde_json::from_str::<JsonValue>(&line) {
+                match event.get("type")
+                           .and_then(JsonValue::as_str) {
+                    Some("notify") => {
+                        if let Some(msg) = event.get("message")
+                                                .and_then(JsonValue::as_str) {
+                            hub.send(Event::Notify(msg.to_string())).ok();
+                        }
+                    },
+                    Some("setWifi") => {
+                        if let Some(enable) = event.get("enable")
+                                                   .and_then(JsonValue::as_bool) {
+                            hub.send(Event::SetWifi(enable)).ok();
+                        }
+                    },
+                    Some("addDocument") => {
+                        if let Some(info) = event.get("info")
+                                                 .map(ToString::to_string)
+                                                 .and_then(|v| serde_json::from_str(&v).ok()) {
+                            hub.send(Event::FetcherAddDocument(id, Box::new(info))).ok();
+                        }
+                    },
+                    Some("removeDocument") => {
+                        if let Some(path) = event.get("path")
+                                                 .and_then(JsonValue::as_str) {
+                            hub.send(Event::FetcherRemoveDocument(id, PathBuf::from(path))).ok();
+                        }
+                    },
+                    Some("search") => {
+                        let path = event.get("path")
+                                        .and_then(JsonValue::as_str)
+                                        .map(PathBuf::from);
+                        let query = event.get("query")
+                                         .and_then(JsonValue::as_str)
+                                         .map(String::from);
+                        let sort_by = event.get("sortBy")
+                                           .map(ToString::to_string)
+                                           .and_then(|v| serde_json::from_str(&v).ok());
+                        hub.send(Event::FetcherSearch { id, path, query, sort_by }).ok();
+                    },
+                    Some("openDocument") => {
+                        if let Some(path) = event.get("path")
+                                                 .and_then(JsonValue::as_str) {
+                            let location = event.get("location")
+                                                .and_then(JsonValue::as_u64)
+                                                .map(|v| v as usize);
+                            let cmd = ControlCommand::Open { path: PathBuf::from(path), location };
+                            let response = control::request(cmd, hub);
+                            if let Some(msg) = response.get("message")
+                                                       .and_then(JsonValue::as_str) {
+                                hub.send(Event::Notify(format!("Can't open {}: {}.", path, msg))).ok();
+                            }
+                        }
+                    },
+                    Some("updateDocument") => {
+                        if let (Some(path), Some(info)) = (event.get("path").and_then(JsonValue::as_str),
+                                                           event.get("info").filter(|v| v.is_object())) {
+                            hub.send(Event::FetcherUpdateDocument(id, PathBuf::from(path), info.clone())).ok();
+                        }
+                    },
+                    Some("getReaderInfo") => {
+                        let response = control::request(ControlCommand::GetReaderInfo, hub);
+                        let info = response.get("result").cloned()
+                                           .unwrap_or(JsonValue::Null);
+                        hub.send(Event::FetcherReaderInfo(id, info)).ok();
+                    },
+                    Some("progress") => {
+                        if let (Some(msg), Some(value)) = (event.get("message").and_then(JsonValue::as_str),
+                                                           event.get("value").and_then(JsonValue::as_f64)) {
+                            hub.send(Event::FetcherProgress(id, msg.to_string(), value as f32)).ok();
+                        }
+                    },
+                    Some("ask") => {
+                        if let Some(msg) = event.get("message")
+                                                .and_then(JsonValue::as_str) {
+                            hub.send(Event::FetcherAsk(id, msg.to_string())).ok();
+                        }
+                    },
+                    _ => (),
+                }
+            }
+        } else {
+            break;
+        }
+    }
+    hub.send(Event::Close(ViewId::ProgressNotif(id))).ok();
+}
+
+struct Process {
+    service: Service,
+    child: Option<Child>,
+    started: Instant,
+    // Number of consecutive premature exits.
+    failures: u32,
+}
+
+#[derive(Default)]
+pub struct Services {
+    processes: Vec<Process>,
+}
+
+impl Services {
+    pub fn start(&mut self, services: &[Service], wifi: bool, online: bool, hub: &Hub) {
+        self.processes = services.iter().map(|service| {
+            Process {
+                service: service.clone(),
+                child: None,
+                started: Instant::now(),
+                failures: 0,
+            }
+        }).collect();
+        for index in 0..self.processes.len() {
+            self.spawn(index, wifi, online, hub);
+        }
+    }
+
+    pub fn spawn(&mut self, index: usize, wifi: bool, online: bool, hub: &Hub) {
+        let process = match self.processes.get_mut(index) {
+            Some(process) if process.child.is_none() => process,
+            _ => return,
+        };
+        process.started = Instant::now();
+        match spawn_service(&process.service, index, wifi, online, hub) {
+            Ok(child) => process.child = Some(child),
+            Err(e) => {
+                eprintln!("Can't spawn service {}: {:#}.", process.service.name, e);
+                process.failures = process.failures.saturating_add(1);
+                schedule_restart(index, restart_delay(process.failures), hub);
+            },
+        }
+    }
+
+    // Called when the standard output of a service is closed.
+    pub fn check(&mut self, index: usize, hub: &Hub) {
+        let process = match self.processes.get_mut(index) {
+            Some(process) => process,
+            None => return,
+        };
+        if let Some(mut child) = process.child.take() {
+            // A service that exits successfully is done and stays stopped.
+            if child.wait().is_ok_and(|exit_status| exit_status.success()) {
+                return;
+            }
+            eprintln!("Service {}: abnormal process termination.", process.service.name);
+            if process.started.elapsed() >= STABLE_UPTIME {
+                process.failures = 0;
+            } else {
+                process.failures = process.failures.saturating_add(1);
+            }
+            schedule_restart(index, restart_delay(process.failures), hub);
+        }
+    }
+
+    // Sends an event to the service with the given process id.
+    // Returns `false` if there's no such service.
+    pub fn send(&mut self, id: u32, event: &JsonValue) -> bool {
+        let child = self.processes.iter_mut()
+                        .filter_map(|process| process.child.as_mut())
+                        .find(|child| child.id() == id);
+        if let Some(stdin) = child.and_then(|child| child.stdin.as_mut()) {
+            writeln!(stdin, "{}", event).ok();
+            true
+        } else {
+            false
+        }
+    }
+
+    pub fn broadcast(&mut self, event: &JsonValue) {
+        for child in self.processes.iter_mut().filter_map(|process| process.child.as_mut()) {
+            if let Some(stdin) = child.stdin.as_mut() {
+                writeln!(stdin, "{}", event).ok();
+            }
+        }
+    }
+
+    pub fn notify_network_up(&mut self) {
+        self.broadcast(&json!({"type": "network", "status": "up"}));
+    }
+
+    pub fn notify_suspend(&mut self) {
+        self.broadcast(&json!({"type": "suspend"}));
+    }
+
+    pub fn notify_resume(&mut self) {
+        self.broadcast(&json!({"type": "resume"}));
+    }
+
+    // Terminates the services, they won't be restarted.
+    pub fn stop(&mut self) {
+        for mut child in self.processes.drain(..).filter_map(|process| process.child) {
+            unsafe { libc::kill(child.id() as libc::pid_t, libc::SIGTERM) };
+            child.wait().ok();
+        }
+    }
+}
+
+// Starts a hook: *CheckFetcher* is sent when its standard output is closed.
+pub fn spawn_hook(library_path: &Path, save_path: &Path, program: &Path, wifi: bool, online: bool, hub: &Hub) -> Result<Child, Error> {
+    let path = program.canonicalize()?;
+    let parent = path.parent()
+                     .unwrap_or_else(|| Path::new(""));
+    let mut process = Command::new(&path)
+                             .current_dir(parent)
+                             .arg(library_path)
+                             .arg(save_path)
+                             .arg(wifi.to_string())
+                             .arg(online.to_string())
+                             .stdin(Stdio::piped())
+                             .stdout(Stdio::piped())
+                             .spawn()?;
+    let stdout = process.stdout.take()
+                        .ok_or_else(|| format_err!("can't take stdout"))?;
+    let id = process.id();
+    let hub2 = hub.clone();
+    thread::spawn(move || {
+        read_events(stdout, id, &hub2);
+        hub2.send(Event::CheckFetcher(id)).ok();
+    });
+    Ok(process)
+}
+
+fn spawn_service(service: &Service, index: usize, wifi: bool, online: bool, hub: &Hub) -> Result<Child, Error> {
+    let path = service.program.canonicalize()?;
+    let parent = path.parent()
+                     .unwrap_or_else(|| Path::new(""));
+    let mut child = Command::new(&path)
+                           .current_dir(parent)
+                           .arg(wifi.to_string())
+                           .arg(online.to_string())
+                           .stdin(Stdio::piped())
+                           .stdout(Stdio::piped())
+                           .spawn()?;
+    let stdout = child.stdout.take()
+                      .ok_or_else(|| format_err!("can't take stdout"))?;
+    let id = child.id();
+    let hub2 = hub.clone();
+    thread::spawn(move || {
+        read_events(stdout, id, &hub2);
+        hub2.send(Event::CheckService(index)).ok();
+    });
+    Ok(child)
+}
+
+fn schedule_restart(index: usize, delay: Duration, hub: &Hub) {
+    let hub2 = hub.clone();
+    thread::spawn(move || {
+        thread::sleep(delay);
+        hub2.send(Event::StartService(index)).ok();
+    });
+}
+
+fn restart_delay(failures: u32) -> Duration {
+    RESTART_BASE_DELAY.saturating_mul(2u32.saturating_pow(failures.min(16)))
+                      .min(RESTART_MAX_DELAY)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_restart_delay() {
+        assert_eq!(restart_delay(0), RESTART_BASE_DELAY);
+        assert_eq!(restart_delay(1), 2 * RESTART_BASE_DELAY);
+        assert_eq!(restart_delay(3), 8 * RESTART_BASE_DELAY);
+        assert_eq!(restart_delay(12), RESTART_MAX_DELAY);
+        assert_eq!(restart_delay(u32::MAX), RESTART_MAX_DELAY);
+    }
+}
@@ -112,8 +112,12 @@ pub struct Settings {
     pub date_format: String,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub external_urls_queue: Option<PathBuf>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub control_socket: Option<PathBuf>,
     #[serde(skip_serializing_if = "Vec::is_empty")]
     pub libraries: Vec<LibrarySettings>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub services: Vec<Service>,
     pub intermissions: Intermissions,
     #[serde(skip_serializing_if = "Vec::is_empty")]
     pub frontlight_presets: Vec<LightPreset>,
@@ -124,6 +128,7 @@ pub struct Settings {
     pub sketch: SketchSettings,
     pub calculator: CalculatorSettings,
     pub battery: BatterySettings,
+    pub wake_up: WakeUpSettings,
     pub frontlight_levels: LightLevels,
 }
 
@@ -274,6 +279,13 @@ pub enum SecondColumn {
     Year,
 }
 
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct Service {
+    pub name: String,
+    pub program: PathBuf,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(default, rename_all = "kebab-case")]
 pub struct Hook {
@@ -364,6 +376,17 @@ pub struct BatterySettings {
     pub power_off: f32,
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct WakeUpSettings {
+    // Times of the day, formatted as *HH:MM*, at which the device wakes up to run the hooks.
+    pub times: Vec<String>,
+    // Paths of the hooks of the selected library.
+    pub hooks: Vec<PathBuf>,
+    // The device goes back to sleep after this many seconds, even if some hooks are still running.
+    pub max_duration: u64,
+}
+
 #[derive(Debug, Copy, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "kebab-case")]
 pub enum FinishedAction {
@@ -473,7 +496,8 @@ impl Default for ImportSettings {
             unshare_trigger: true,
             startup_trigger: true,
             sync_metadata: true,
-            metadata_kinds: ["epub", "pdf", "djvu"].iter().map(|k| k.to_string()).collect(),
+            metadata_kinds: ["epub", "pdf", "djvu", "mobi",
+                             "fb2", "cbz"].iter().map(|k| k.to_string()).collect(),
             allowed_kinds: ["pdf", "djvu", "epub", "fb2", "txt",
                             "xps", "oxps", "mobi", "cbz"].iter().map(|k| k.to_string()).collect(),
         }
@@ -489,6 +513,16 @@ impl Default for BatterySettings {
     }
 }
 
+impl Default for WakeUpSettings {
+    fn default() -> Self {
+        WakeUpSettings {
+            times: Vec::new(),
+            hooks: Vec::new(),
+            max_duration: 600,
+        }
+    }
+}
+
 impl Default for Settings {
     fn default() -> Self {
         Settings {
@@ -524,7 +558,9 @@ impl Default for Settings {
                     .. Default::default()
                 },
             ],
+            services: Vec::new(),
             external_urls_queue: Some(PathBuf::from("bin/article_fetcher/urls.txt")),
+            control_socket: None,
             keyboard_layout: "English".to_string(),
             frontlight: true,
             wifi: false,
@@ -549,6 +585,7 @@ impl Default for Settings {
             sketch: SketchSettings::default(),
             calculator: CalculatorSettings::default(),
             battery: BatterySettings::default(),
+            wake_up: WakeUpSettings::default(),
             frontlight_levels: LightLevels::default(),
             frontlight_presets: Vec::new(),
         }
@@ -0,0 +1,681 @@
+mod bottom_bar;
+
+use std::path::{Path, PathBuf};
+use crate::device::CURRENT_DEVICE;
+use crate::framebuffer::{Framebuffer, UpdateMode, Pixmap};
+use crate::geom::{Rectangle, Point, Dir, CycleDir, halves};
+use crate::unit::scale_by_dpi;
+use crate::font::Fonts;
+use crate::input::{DeviceEvent, ButtonCode, ButtonStatus};
+use crate::view::{View, Event, Hub, Bus, RenderQueue, RenderData};
+use crate::view::{ViewId, Id, ID_FEEDER, EntryId, EntryKind};
+use crate::view::{SMALL_BAR_HEIGHT, BIG_BAR_HEIGHT, THICKNESS_MEDIUM};
+use crate::document::{Document, Location, HumanSize};
+use crate::document::html::HtmlDocument;
+use crate::document::epub::EpubDocument;
+use crate::document::epub::opf::{write_metadata, write_opf};
+use crate::metadata::{Info, SimpleStatus, extract_metadata_from_document};
+use crate::settings::LibraryMode;
+use crate::helpers::escape;
+use crate::calibre::{sidecar_opf, sidecar_cover, plain_text};
+use crate::view::common::{locate_by_id, locate};
+use crate::view::common::{toggle_main_menu, toggle_battery_menu, toggle_clock_menu};
+use crate::gesture::GestureEvent;
+use crate::color::BLACK;
+use crate::context::Context;
+use crate::view::filler::Filler;
+use crate::view::named_input::NamedInput;
+use crate::view::image::Image;
+use crate::view::keyboard::Keyboard;
+use crate::view::menu::{Menu, MenuKind};
+use crate::view::top_bar::TopBar;
+use self::bottom_bar::BottomBar;
+
+const VIEWER_STYLESHEET: &str = "css/book-details.css";
+const USER_STYLESHEET: &str = "css/book-details-user.css";
+const FONT_SIZE: f32 = 11.0;
+const MARGIN_WIDTH: i32 = 4;
+const DATE_FORMAT: &str = "%Y-%m-%d %H:%M";
+const EDIT_PREFIX: &str = "edit:";
+
+// The fields that can be edited from the details view.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+enum Field {
+    Title,
+    Author,
+    Series,
+    Number,
+    Categories,
+    Language,
+}
+
+impl Field {
+    fn from_name(name: &str) -> Option<Field> {
+        match name {
+            "title" => Some(Field::Title),
+            "author" => Some(Field::Author),
+            "series" => Some(Field::Series),
+            "number" => Some(Field::Number),
+            "categories" => Some(Field::Categories),
+            "language" => Some(Field::Language),
+            _ => None,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Field::Title => "title",
+            Field::Author => "author",
+            Field::Series => "series",
+            Field::Number => "number",
+            Field::Categories => "categories",
+            Field::Language => "language",
+        }
+    }
+
+    fn label(self) -> &'static str {
+        match self {
+            Field::Title => "Title",
+            Field::Author => "Author",
+            Field::Series => "Series",
+            Field::Number => "Number",
+            Field::Categories => "Categories",
+            Field::Language => "Language",
+        }
+    }
+
+    fn value(self, info: &Info) -> String {
+        match self {
+            Field::Title => info.title.clone(),
+            Field::Author => info.author.clone(),
+            Field::Series => info.series.clone(),
+            Field::Number => info.number.clone(),
+            Field::Categories => info.categories.iter().cloned().collect::<Vec<String>>().join(", "),
+            Field::Language => info.language.clone(),
+        }
+    }
+
+    fn set(self, info: &mut Info, text: &str) {
+        let text = text.trim().to_string();
+        match self {
+            Field::Title => info.title = text,
+            Field::Author => info.author = text,
+            Field::Series => info.series = text,
+            Field::Number => info.number = text,
+            Field::Categories => {
+                info.categories = text.split(',')
+                                      .map(|s| s.trim().to_string())
+                                      .filter(|s| !s.is_empty())
+                                      .collect();
+            },
+            Field::Language => info.language = text,
+        }
+    }
+}
+
+pub struct BookDetails {
+    id: Id,
+    rect: Rectangle,
+    children: Vec<Box<dyn View>>,
+    doc: HtmlDocument,
+    location: usize,
+    info: Info,
+    field: Option<Field>,
+    focus: Option<ViewId>,
+}
+
+fn push_row(buf: &mut String, key: &str, value: &str) {
+    buf.push_str("\t\t\t<tr>\n");
+    buf.push_str(&format!("\t\t\t\t<td class=\"key\">{}</td>\n", key));
+    buf.push_str(&format!("\t\t\t\t<td class=\"value\">{}</td>\n", escape(value)));
+    buf.push_str("\t\t\t</tr>\n");
+}
+
+fn push_field(buf: &mut String, field: Field, info: &Info, editable: bool) {
+    let key = if editable {
+        format!("<a href=\"{}{}\">{}</a>", EDIT_PREFIX, field.name(), field.label())
+    } else {
+        field.label().to_string()
+    };
+    push_row(buf, &key, &field.value(info));
+}
+
+fn info_to_html(info: &Info, cover: Option<&Path>, editable: bool) -> String {
+    let mut buf = "<html>\n\t<head>\n\t\t<title>Book Details</title>\n\t</head>\n\t<body>\n".to_string();
+
+    if let Some(path) = cover.and_then(|path| path.to_str()) {
+        buf.push_str(&format!("\t\t<p class=\"cover\"><img src=\"{}\"/></p>\n",
+                              escape(&path.replace('%', "%25"))));
+    }
+
+    buf.push_str(&format!("\t\t<h1 class=\"title\">{}</h1>\n", escape(&info.title())));
+
+    if !info.author.is_empty() {
+        buf.push_str(&format!("\t\t<p class=\"author\">{}</p>\n", escape(&info.author)));
+    }
+
+    buf.push_str("\t\t<h2>Metadata</h2>\n");
+    buf.push_str("\t\t<table>\n");
+    push_field(&mut buf, Field::Title, info, editable);
+    if !info.subtitle.is_empty() {
+        push_row(&mut buf, "Subtitle", &info.subtitle);
+    }
+    push_field(&mut buf, Field::Author, info, editable);
+    push_field(&mut buf, Field::Series, info, editable);
+    push_field(&mut buf, Field::Number, info, editable);
+    for (key, value) in [("Volume", &info.volume),
+                         ("Edition", &info.edition),
+                         ("Year", &info.year),
+                         ("Publisher", &info.publisher)] {
+        if !value.is_empty() {
+            push_row(&mut buf, key, value);
+        }
+    }
+    push_field(&mut buf, Field::Language, info, editable);
+    if !info.identifier.is_empty() {
+        push_row(&mut buf, "Identifier", &info.identifier);
+    }
+    push_field(&mut buf, Field::Categories, info, editable);
+    buf.push_str("\t\t</table>\n");
+
+    if !editable {
+        buf.push_str("\t\t<p class=\"note\">The metadata of this document can't be edited in a filesystem library.</p>\n");
+    }
+
+    if !info.description.is_empty() {
+        buf.push_str("\t\t<h2>Description</h2>\n");
+        for line in info.description.lines().filter(|line| !line.trim().is_empty()) {
+            buf.push_str(&format!("\t\t<p class=\"description\">{}</p>\n", escape(line)));
+        }
+    }
+
+    buf.push_str("\t\t<h2>File</h2>\n");
+    buf.push_str("\t\t<table>\n");
+    push_row(&mut buf, "Path", &info.file.path.to_string_lossy());
+    push_row(&mut buf, "Kind", &info.file.kind.to_uppercase());
+    push_row(&mut buf, "Size", &info.file.size.human_size());
+    push_row(&mut buf, "Added", &info.added.format(DATE_FORMAT).to_string());
+    buf.push_str("\t\t</table>\n");
+
+    buf.push_str("\t\t<h2>Reading</h2>\n");
+    buf.push_str("\t\t<table>\n");
+    match info.reader.as_ref() {
+        Some(reader) => {
+            let status = match info.simple_status() {
+                SimpleStatus::Reading => {
+                    let progress = reader.current_page as f32 / reader.pages_count.max(1) as f32;
+                    format!("Reading ({:.0}%)", 100.0 * progress)
+                },
+                status => status.to_string(),
+            };
+            push_row(&mut buf, "Status", &status);
+            push_row(&mut buf, "Opened", &reader.opened.format(DATE_FORMAT).to_string());
+            push_row(&mut buf, "Page", &format!("{} / {}", reader.current_page + 1, reader.pages_count));
+            push_row(&mut buf, "Bookmarks", &reader.bookmarks.len().to_string());
+            push_row(&mut buf, "Annotations", &reader.annotations.len().to_string());
+        },
+        None => push_row(&mut buf, "Status", &SimpleStatus::New.to_string()),
+    }
+    buf.push_str("\t\t</table>\n");
+
+    buf.push_str("\t</body>\n</html>\n");
+    buf
+}
+
+impl BookDetails {
+    pub fn new(rect: Rectangle, path: &Path, hub: &Hub, rq: &mut RenderQueue, context: &mut Context) -> BookDetails {
+        let id = ID_FEEDER.next();
+        let mut children = Vec::new();
+        let dpi = CURRENT_DEVICE.dpi;
+        let small_height = scale_by_dpi(SMALL_BAR_HEIGHT, dpi) as i32;
+        let thickness = scale_by_dpi(THICKNESS_MEDIUM, dpi) as i32;
+        let (small_thickness, big_thickness) = halves(thickness);
+
+        let top_bar = TopBar::new(rect![rect.min.x, rect.min.y,
+                                        rect.max.x, rect.min.y + small_height - small_thickness],
+                                  Event::Back,
+                                  "Book Details".to_string(),
+                                  context);
+        children.push(Box::new(top_bar) as Box<dyn View>);
+
+        let separator = Filler::new(rect![rect.min.x, rect.min.y + small_height - small_thickness,
+                                          rect.max.x, rect.min.y + small_height + big_thickness],
+                                    BLACK);
+        children.push(Box::new(separator) as Box<dyn View>);
+
+        let image_rect = rect![rect.min.x, rect.min.y + small_height + big_thickness,
+                               rect.max.x, rect.max.y - small_height - small_thickness];
+
+        let image = Image::new(image_rect, Pixmap::new(1, 1, 1));
+        children.push(Box::new(image) as Box<dyn View>);
+
+        let separator = Filler::new(rect![rect.min.x, rect.max.y - small_height - small_thickness,
+                                          rect.max.x, rect.max.y - small_height + big_thickness],
+                                    BLACK);
+        children.push(Box::new(separator) as Box<dyn View>);
+
+        let name = path.file_name().map(|name| name.to_string_lossy().into_owned())
+                       .unwrap_or_default();
+        let bottom_bar = BottomBar::new(rect![rect.min.x, rect.max.y - small_height + big_thickness,
+                                              rect.max.x, rect.max.y],
+                                        &name, false, false);
+        children.push(Box::new(bottom_bar) as Box<dyn View>);
+
+        let mut info = context.library.info(path).unwrap_or_default();
+
+        // The metadata is only stored in database mode.
+        if context.library.mode == LibraryMode::Filesystem {
+            extract_metadata_from_document(&context.library.home, &mut info);
+        }
+
+        if info.description.is_empty() && info.file.kind == "epub" {
+            let full_path = context.library.home.join(&info.file.path);
+            info.description = EpubDocument::new(&full_path)
+                                            .map_err(|e| eprintln!("Can't open {}: {:#}.", full_path.display(), e))
+                                            .ok().and_then(|doc| doc.description())
+                                            .map(|text| plain_text(&text))
+                                            .unwrap_or_default();
+        }
+
+        let mut doc = HtmlDocument::new_from_memory("");
+        doc.layout(image_rect.width(), image_rect.height(), FONT_SIZE, dpi);
+        doc.set_margin_width(MARGIN_WIDTH);
+        doc.set_viewer_stylesheet(VIEWER_STYLESHEET);
+        doc.set_user_stylesheet(USER_STYLESHEET);
+
+        rq.add(RenderData::new(id, rect, UpdateMode::Gui));
+
+        if info.file.path.as_os_str().is_empty() {
+            hub.send(Event::Notify(format!("Can't find {}.", path.display()))).ok();
+        }
+
+        let mut book_details = BookDetails {
+            id,
+            rect,
+            children,
+            doc,
+            location: 0,
+            info,
+            field: None,
+            focus: None,
+        };
+
+        book_details.update(&mut RenderQueue::new(), context);
+        book_details
+    }
+
+    fn toggle_title_menu(&mut self, rect: Rectangle, enable: Option<bool>, rq: &mut RenderQueue, context: &mut Context) {
+        if let Some(index) = locate_by_id(self, ViewId::TitleMenu) {
+            if let Some(true) = enable {
+                return;
+            }
+
+            rq.add(RenderData::expose(*self.child(index).rect(), UpdateMode::Gui));
+            self.children.remove(index);
+        } else {
+            if let Some(false) = enable {
+                return;
+            }
+            if self.info.file.kind != "epub" {
+                return;
+            }
+            let entries = vec![EntryKind::Command("Write Into Document".to_string(), EntryId::WriteMetadata)];
+            let title_menu = Menu::new(rect, ViewId::TitleMenu, MenuKind::DropDown, entries, context);
+            rq.add(RenderData::new(title_menu.id(), *title_menu.rect(), UpdateMode::Gui));
+            self.children.push(Box::new(title_menu) as Box<dyn View>);
+        }
+    }
+
+    fn toggle_keyboard(&mut self, enable: bool, hub: &Hub, rq: &mut RenderQueue, context: &mut Context) {
+        if let Some(index) = locate::<Keyboard>(self) {
+            if enable {
+                return;
+            }
+
+            let mut rect = *self.child(index).rect();
+            rect.absorb(self.child(index-1).rect());
+            self.children.drain(index - 1 ..= index);
+
+            context.kb_rect = Rectangle::default();
+            rq.add(RenderData::expose(rect, UpdateMode::Gui));
+            hub.send(Event::Focus(None)).ok();
+        } else {
+            if !enable {
+                return;
+            }
+
+            let dpi = CURRENT_DEVICE.dpi;
+            let (small_height, big_height) = (scale_by_dpi(SMALL_BAR_HEIGHT, dpi) as i32,
+                                              scale_by_dpi(BIG_BAR_HEIGHT, dpi) as i32);
+            let thickness = scale_by_dpi(THICKNESS_MEDIUM, dpi) as i32;
+            let (small_thickness, big_thickness) = halves(thickness);
+
+            let mut kb_rect = rect![self.rect.min.x,
+                                    self.rect.max.y - (small_height + 3 * big_height) + big_thickness,
+                                    self.rect.max.x,
+                                    self.rect.max.y - small_height - small_thickness];
+
+            let number = self.field == Some(Field::Number);
+            let index = locate::<BottomBar>(self).unwrap() + 1;
+
+            let keyboard = Keyboard::new(&mut kb_rect, number, context);
+            self.children.insert(index, Box::new(keyboard) as Box<dyn View>);
+
+            let separator = Filler::new(rect![self.rect.min.x, kb_rect.min.y - thickness,
+                                              self.rect.max.x, kb_rect.min.y],
+                                        BLACK);
+            self.children.insert(index, Box::new(separator) as Box<dyn View>);
+
+            for i in index..=index+1 {
+                rq.add(RenderData::new(self.child(i).id(), *self.child(i).rect(), UpdateMode::Gui));
+            }
+        }
+    }
+
+    fn toggle_edit_metadata(&mut self, field: Option<Field>, hub: &Hub, rq: &mut RenderQueue, context: &mut Context) {
+        if let Some(index) = locate_by_id(self, ViewId::EditMetadata) {
+            rq.add(RenderData::expose(*self.child(index).rect(), UpdateMode::Gui));
+            self.children.remove(index);
+
+            if self.focus == Some(ViewId::EditMetadataInput) {
+                self.toggle_keyboard(false, hub, rq, context);
+            }
+
+            self.field = None;
+        }
+
+        if let Some(field) = field {
+            self.field = Some(field);
+
+            let mut edit_metadata = NamedInput::new(field.label().to_string(), ViewId::EditMetadata,
+                                                    ViewId::EditMetadataInput, 24, context);
+            edit_metadata.set_text(&field.value(&self.info), &mut RenderQueue::new(), context);
+
+            rq.add(RenderData::new(edit_metadata.id(), *edit_metadata.rect(), UpdateMode::Gui));
+            hub.send(Event::Focus(Some(ViewId::EditMetadataInput))).ok();
+
+            self.children.push(Box::new(edit_metadata) as Box<dyn View>);
+        }
+    }
+
+    fn cover_path(&self, context: &Context) -> Option<PathBuf> {
+        let full_path = context.library.home.join(&self.info.file.path);
+        sidecar_cover(&full_path).or_else(|| {
+            let path = context.library.thumbnail_preview(&self.info.file.path);
+            Some(path).filter(|path| path.exists())
+        })
+    }
+
+    // In filesystem mode, the metadata is read from the document or its sidecar:
+    // the edits are written there.
+    fn is_editable(&self, context: &Context) -> bool {
+        context.library.mode == LibraryMode::Database ||
+        self.info.file.kind == "epub" ||
+        sidecar_opf(context.library.home.join(&self.info.file.path)).is_some()
+    }
+
+    fn update(&mut self, rq: &mut RenderQueue, context: &mut Context) {
+        let cover = self.cover_path(context);
+        let content = info_to_html(&self.info, cover.as_deref(), self.is_editable(context));
+        self.doc.update(&content);
+        if let Some(image) = self.children[2].downcast_mut::<Image>() {
+            if let Some((pixmap, loc)) = self.doc.pixmap(Location::Exact(self.location), 1.0, CURRENT_DEVICE.color_samples()) {
+                image.update(pixmap, rq);
+                self.location = loc;
+            }
+        }
+        self.update_bottom_bar(rq);
+    }
+
+    fn update_bottom_bar(&mut self, rq: &mut RenderQueue) {
+        let has_prev = self.doc.resolve_location(Location::Previous(self.location)).is_some();
+        let has_next = self.doc.resolve_location(Location::Next(self.location)).is_some();
+        if let Some(bottom_bar) = self.children[4].downcast_mut::<BottomBar>() {
+            bottom_bar.update_icons(has_prev, has_next, rq);
+        }
+    }
+
+    fn go_to_neighbor(&mut self, dir: CycleDir, rq: &mut RenderQueue) {
+        let location = match dir {
+            CycleDir::Previous => Location::Previous(self.location),
+            CycleDir::Next => Location::Next(self.location),
+        };
+        if let Some(image) = self.children[2].downcast_mut::<Image>() {
+            if let Some((pixmap, loc)) = self.doc.pixmap(location, 1.0, CURRENT_DEVICE.color_samples()) {
+                image.update(pixmap, rq);
+                self.location = loc;
+            }
+        }
+        self.update_bottom_bar(rq);
+    }
+
+    fn set_field(&mut self, field: Field, text: &str, hub: &Hub, rq: &mut RenderQueue, context: &mut Context) {
+        field.set(&mut self.info, text);
+
+        if context.library.mode == LibraryMode::Database {
+            context.library.update(self.info.clone())
+                   .map_err(|e| eprintln!("Can't update entry: {:#}.", e))
+                   .ok();
+        } else if let Some(opf_path) = sidecar_opf(context.library.home.join(&self.info.file.path)) {
+            if let Err(e) = write_opf(&opf_path, &self.info) {
+                eprintln!("Can't write metadata: {:#}.", e);
+                hub.send(Event::Notify(format!("Can't write metadata: {}.", e))).ok();
+            }
+        } else {
+            self.write_metadata(hub, rq, context);
+            return;
+        }
+
+        self.update(rq, context);
+    }
+
+    fn write_metadata(&mut self, hub: &Hub, rq: &mut RenderQueue, context: &mut Context) {
+        let info = &self.info;
+        let path = info.file.path.clone();
+        match context.library.modify(&path, |full_path| write_metadata(full_path, info)) {
+            Ok(()) => {
+                if let Some(entry) = context.library.info(&path) {
+                    self.info.file.size = entry.file.size;
+                    self.info.reader = entry.reader;
+                }
+                self.update(rq, context);
+                hub.send(Event::Notify("Metadata written into the document.".to_string())).ok();
+            },
+            Err(e) => {
+                eprintln!("Can't write metadata: {:#}.", e);
+                hub.send(Event::Notify(format!("Can't write metadata: {}.", e))).ok();
+            },
+        }
+    }
+
+    fn follow_link(&mut self, pt: Point, hub: &Hub, rq: &mut RenderQueue, context: &mut Context) {
+        let offset = *self.children[2].rect();
+
+        if let Some((links, _)) = self.doc.links(Location::Exact(self.location)) {
+            for link in links {
+                let rect = link.rect.to_rect() + offset.min;
+                if rect.includes(pt) {
+                    if let Some(field) = link.text.strip_prefix(EDIT_PREFIX).and_then(Field::from_name) {
+                        self.toggle_edit_metadata(Some(field), hub, rq, context);
+                        return;
+                    }
+                }
+            }
+        }
+
+        let half_width = self.rect.width() as i32 / 2;
+        if pt.x - offset.min.x < half_width {
+            self.go_to_neighbor(CycleDir::Previous, rq);
+        } else {
+            self.go_to_neighbor(CycleDir::Next, rq);
+        }
+    }
+
+    fn reseed(&mut self, rq: &mut RenderQueue, context: &mut Context) {
+        if let Some(top_bar) = self.child_mut(0).downcast_mut::<TopBar>() {
+            top_bar.reseed(rq, context);
+        }
+
+        rq.add(RenderData::new(self.id, self.rect, UpdateMode::Gui));
+    }
+}
+
+impl View for BookDetails {
+    fn handle_event(&mut self, evt: &Event, hub: &Hub, _bus: &mut Bus, rq: &mut RenderQueue, context: &mut Context) -> bool {
+        match *evt {
+            Event::Page(dir) => {
+                self.go_to_neighbor(dir, rq);
+                true
+            },
+            Event::Gesture(GestureEvent::Swipe { dir, start, .. }) if self.rect.includes(start) => {
+                match dir {
+                    Dir::West => self.go_to_neighbor(CycleDir::Next, rq),
+                    Dir::East => self.go_to_neighbor(CycleDir::Previous, rq),
+                    _ => (),
+                }
+                true
+            },
+            Event::Device(DeviceEvent::Button { code, status: ButtonStatus::Released, .. }) => {
+                let cd = match code {
+                    ButtonCode::Backward => Some(CycleDir::Previous),
+                    ButtonCode::Forward => Some(CycleDir::Next),
+                    _ => None,
+                };
+                if let Some(cd) = cd {
+                    self.go_to_neighbor(cd, rq);
+                }
+                true
+            },
+            Event::Gesture(GestureEvent::Tap(center)) if self.rect.includes(center) => {
+                self.follow_link(center, hub, rq, context);
+                true
+            },
+            Event::Submit(ViewId::EditMetadataInput, ref text) => {
+                if let Some(field) = self.field {
+                    self.set_field(field, text, hub, rq, context);
+                }
+                true
+            },
+            Event::Close(ViewId::EditMetadata) => {
+                self.toggle_edit_metadata(None, hub, rq, context);
+                true
+            },
+            Event::Select(EntryId::WriteMetadata) => {
+                self.write_metadata(hub, rq, context);
+                true
+            },
+            Event::Focus(v) => {
+                self.focus = v;
+                if v.is_some() {
+                    self.toggle_keyboard(true, hub, rq, context);
+                }
+                true
+            },
+            Event::ToggleNear(ViewId::TitleMenu, rect) => {
+                self.toggle_title_menu(rect, None, rq, context);
+                true
+            },
+            Event::ToggleNear(ViewId::MainMenu, rect) => {
+                toggle_main_menu(self, rect, None, rq, context);
+                true
+            },
+            Event::ToggleNear(ViewId::BatteryMenu, rect) => {
+                toggle_battery_menu(self, rect, None, rq, context);
+                true
+            },
+            Event::ToggleNear(ViewId::ClockMenu, rect) => {
+                toggle_clock_menu(self, rect, None, rq, context);
+                true
+            },
+            Event::Reseed => {
+                self.reseed(rq, context);
+                true
+            },
+            Event::Gesture(GestureEvent::Cross(_)) => {
+                hub.send(Event::Back).ok();
+                true
+            },
+            _ => false,
+        }
+    }
+
+    fn render(&self, _fb: &mut dyn Framebuffer, _rect: Rectangle, _fonts: &mut Fonts) {
+    }
+
+    fn resize(&mut self, rect: Rectangle, hub: &Hub, rq: &mut RenderQueue, context: &mut Context) {
+        let dpi = CURRENT_DEVICE.dpi;
+        let (small_height, big_height) = (scale_by_dpi(SMALL_BAR_HEIGHT, dpi) as i32,
+                                          scale_by_dpi(BIG_BAR_HEIGHT, dpi) as i32);
+        let thickness = scale_by_dpi(THICKNESS_MEDIUM, dpi) as i32;
+        let (small_thickness, big_thickness) = halves(thickness);
+
+        self.children[0].resize(rect![rect.min.x, rect.min.y,
+                                      rect.max.x, rect.min.y + small_height - small_thickness],
+                                hub, rq, context);
+
+        self.children[1].resize(rect![rect.min.x, rect.min.y + small_height - small_thickness,
+                                      rect.max.x, rect.min.y + small_height + big_thickness],
+                                hub, rq, context);
+
+        let image_rect = rect![rect.min.x, rect.min.y + small_height + big_thickness,
+                               rect.max.x, rect.max.y - small_height - small_thickness];
+        self.doc.layout(image_rect.width(), image_rect.height(), FONT_SIZE, dpi);
+        if let Some(image) = self.children[2].downcast_mut::<Image>() {
+            if let Some((pixmap, loc)) = self.doc.pixmap(Location::Exact(self.location), 1.0, CURRENT_DEVICE.color_samples()) {
+                image.update(pixmap, &mut RenderQueue::new());
+                self.location = loc;
+            }
+        }
+        self.children[2].resize(image_rect, hub, rq, context);
+
+        self.children[3].resize(rect![rect.min.x, rect.max.y - small_height - small_thickness,
+                                      rect.max.x, rect.max.y - small_height + big_thickness],
+                                hub, rq, context);
+
+        self.children[4].resize(rect![rect.min.x, rect.max.y - small_height + big_thickness,
+                                      rect.max.x, rect.max.y],
+                                hub, rq, context);
+        self.update_bottom_bar(&mut RenderQueue::new());
+
+        let mut index = 5;
+        if self.len() >= 7 && self.children[6].is::<Keyboard>() {
+            let kb_rect = rect![rect.min.x,
+                                rect.max.y - (small_height + 3 * big_height) + big_thickness,
+                                rect.max.x,
+                                rect.max.y - small_height - small_thickness];
+            self.children[6].resize(kb_rect, hub, rq, context);
+            let kb_rect = *self.children[6].rect();
+            self.children[5].resize(rect![rect.min.x, kb_rect.min.y - thickness,
+                                          rect.max.x, kb_rect.min.y],
+                                    hub, rq, context);
+            index = 7;
+        }
+
+        for i in index..self.children.len() {
+            self.children[i].resize(rect, hub, rq, context);
+        }
+
+        self.rect = rect;
+        rq.add(RenderData::new(self.id, self.rect, UpdateMode::Full));
+    }
+
+    fn rect(&self) -> &Rectangle {
+        &self.rect
+    }
+
+    fn rect_mut(&mut self) -> &mut Rectangle {
+        &mut self.rect
+    }
+
+    fn children(&self) -> &Vec<Box<dyn View>> {
+        &self.children
+    }
+
+    fn children_mut(&mut self) -> &mut Vec<Box<dyn View>> {
+        &mut self.children
+    }
+
+    fn id(&self) -> Id {
+        self.id
+    }
+}
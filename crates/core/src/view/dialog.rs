@@ -21,6 +21,8 @@ pub struct Dialog {
     children: Vec<Box<dyn View>>,
     view_id: ViewId,
     event: Option<Event>,
+    // Sent when the dialog is cancelled or dismissed.
+    cancel_event: Option<Event>,
     will_close: bool,
 }
 
@@ -86,9 +88,14 @@ impl Dialog {
             children,
             view_id,
             event,
+            cancel_event: None,
             will_close: false,
         }
     }
+
+    pub fn set_cancel_event(&mut self, event: Event) {
+        self.cancel_event = Some(event);
+    }
 }
 
 impl View for Dialog {
@@ -104,15 +111,24 @@ impl View for Dialog {
                     thread::sleep(CLOSE_IGNITION_DELAY);
                     hub2.send(Event::Close(view_id)).ok();
                 });
-                if let Event::Validate = *evt {
-                    if let Some(event) = self.event.as_ref() {
-                        bus.push_back(event.clone());
-                    }
+                let event = if let Event::Validate = *evt {
+                    self.event.as_ref()
+                } else {
+                    self.cancel_event.as_ref()
+                };
+                if let Some(event) = event {
+                    bus.push_back(event.clone());
                 }
                 self.will_close = true;
                 true
             },
             Event::Gesture(GestureEvent::Tap(center)) if !self.rect.includes(center) => {
+                if !self.will_close {
+                    if let Some(event) = self.cancel_event.as_ref() {
+                        bus.push_back(event.clone());
+                    }
+                    self.will_close = true;
+                }
                 hub.send(Event::Close(self.view_id)).ok();
                 true
             },
@@ -9,20 +9,25 @@ mod bottom_bar;
 
 use std::fs;
 use std::mem;
-use std::thread;
 use std::io::Write;
 use std::path::{Path, PathBuf};
-use std::process::{Command, Child, Stdio};
-use std::io::{BufRead, BufReader};
+use std::process::Child;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::Duration;
 use fxhash::FxHashMap;
 use rand_core::RngCore;
 use serde_json::{json, Value as JsonValue};
-use anyhow::{Error, format_err};
+use anyhow::Error;
 use crate::library::Library;
+use crate::duplicates::{search_duplicates, merge, Duplicates};
+use crate::services::spawn_hook;
+use crate::watcher::Watcher;
 use crate::framebuffer::{Framebuffer, UpdateMode};
 use crate::metadata::{Info, Metadata, SortMethod, BookQuery, SimpleStatus, sort};
-use crate::view::{View, Event, Hub, Bus, RenderQueue, RenderData};
-use crate::view::{Id, ID_FEEDER, ViewId, EntryId, EntryKind};
+use crate::view::{View, Event, Activity, Hub, Bus, RenderQueue, RenderData};
+use crate::view::{Id, ID_FEEDER, ViewId, EntryId, EntryKind, AppCmd};
 use crate::view::{SMALL_BAR_HEIGHT, BIG_BAR_HEIGHT, THICKNESS_MEDIUM};
 use crate::settings::{Hook, LibraryMode, FirstColumn, SecondColumn};
 use crate::view::common::{toggle_main_menu, toggle_battery_menu, toggle_clock_menu};
@@ -49,6 +54,8 @@ use crate::font::Fonts;
 use crate::context::Context;
 
 pub const TRASH_DIRNAME: &str = ".trash";
+// The interval at which the watcher thread checks whether it should stop.
+const WATCHER_CHECK_INTERVAL: Duration = Duration::from_secs(10);
 
 #[derive(Debug)]
 pub struct Home {
@@ -66,6 +73,54 @@ pub struct Home {
     current_directory: PathBuf,
     target_document: Option<PathBuf>,
     background_fetchers: FxHashMap<u32, Fetcher>,
+    watcher: Option<LibraryWatcher>,
+}
+
+// Watches a filesystem library in its own thread, and sends
+// `Event::LibraryChanged` when its files have changed.
+#[derive(Debug)]
+struct LibraryWatcher {
+    home: PathBuf,
+    stop: Arc<AtomicBool>,
+}
+
+impl LibraryWatcher {
+    fn new(home: &Path, hub: &Hub) -> Option<LibraryWatcher> {
+        let mut watcher = Watcher::new(home)
+                                  .map_err(|e| eprintln!("Can't watch library: {:#}.", e))
+                                  .ok()?;
+        let stop = Arc::new(AtomicBool::new(false));
+        let stop2 = stop.clone();
+        let hub2 = hub.clone();
+        let home2 = home.to_path_buf();
+        thread::spawn(move || {
+            // The watches are lost when the file system is unmounted.
+            while !stop2.load(Ordering::Relaxed) && watcher.is_active() {
+                match watcher.wait(Some(WATCHER_CHECK_INTERVAL)) {
+                    Ok(true) => {
+                        hub2.send(Event::LibraryChanged(home2.clone())).ok();
+                    },
+                    Ok(false) => (),
+                    Err(e) => {
+                        eprintln!("Can't watch library: {:#}.", e);
+                        break;
+                    },
+                }
+            }
+            stop2.store(true, Ordering::Relaxed);
+        });
+        Some(LibraryWatcher { home: home.to_path_buf(), stop })
+    }
+
+    fn is_running(&self) -> bool {
+        !self.stop.load(Ordering::Relaxed)
+    }
+}
+
+impl Drop for LibraryWatcher {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+    }
 }
 
 #[derive(Debug)]
@@ -188,6 +243,12 @@ impl Home {
 
         rq.add(RenderData::new(id, rect, UpdateMode::Full));
 
+        let watcher = if context.library.mode == LibraryMode::Filesystem {
+            LibraryWatcher::new(&context.library.home, hub)
+        } else {
+            None
+        };
+
         Ok(Home {
             id,
             rect,
@@ -203,9 +264,24 @@ impl Home {
             current_directory,
             target_document: None,
             background_fetchers: FxHashMap::default(),
+            watcher,
         })
     }
 
+    // Starts watching the current library if it's a filesystem library.
+    fn update_watcher(&mut self, hub: &Hub, context: &Context) {
+        if context.library.mode != LibraryMode::Filesystem {
+            self.watcher = None;
+            return;
+        }
+
+        if self.watcher.as_ref().is_some_and(|w| w.is_running() && w.home == context.library.home) {
+            return;
+        }
+
+        self.watcher = LibraryWatcher::new(&context.library.home, hub);
+    }
+
     fn select_directory(&mut self, path: &Path, hub: &Hub, rq: &mut RenderQueue, context: &mut Context) {
         if self.current_directory == path {
             return;
@@ -901,7 +977,9 @@ impl Home {
             let info = &self.visible_books[book_index];
             let path = &info.file.path;
 
-            let mut entries = Vec::new();
+            let mut entries = vec![EntryKind::Command("Details".to_string(),
+                                                      EntryId::Launch(AppCmd::BookDetails { path: path.clone() })),
+                                   EntryKind::Separator];
 
             if let Some(parent) = path.parent() {
                 entries.push(EntryKind::Command("Select Parent".to_string(),
@@ -913,7 +991,7 @@ impl Home {
                                                 EntryId::SearchAuthor(info.author.clone())));
             }
 
-            if !entries.is_empty() {
+            if entries.len() > 2 {
                 entries.push(EntryKind::Separator);
             }
 
@@ -981,6 +1059,7 @@ impl Home {
 
             let database = if library_settings.mode == LibraryMode::Database {
                 vec![EntryKind::Command("Import".to_string(), EntryId::Import),
+                     EntryKind::Command("Find Duplicates".to_string(), EntryId::FindDuplicates),
                      EntryKind::Command("Flush".to_string(), EntryId::Flush)]
             } else {
                 Vec::new()
@@ -1063,6 +1142,87 @@ impl Home {
         self.refresh_visibles(true, false, hub, rq, context);
     }
 
+    fn toggle_duplicates_menu(&mut self, enable: Option<bool>, hub: &Hub, rq: &mut RenderQueue, context: &mut Context) {
+        if let Some(index) = locate_by_id(self, ViewId::DuplicatesMenu) {
+            if let Some(true) = enable {
+                return;
+            }
+
+            rq.add(RenderData::expose(*self.child(index).rect(), UpdateMode::Gui));
+            self.children.remove(index);
+        } else {
+            if let Some(false) = enable {
+                return;
+            }
+
+            let notif = Notification::new("Searching for duplicates.".to_string(), hub, rq, context);
+            self.children.push(Box::new(notif) as Box<dyn View>);
+
+            let hub2 = hub.clone();
+            let home = context.library.home.clone();
+            let db = context.library.db.clone();
+            let hashes = context.library.hashes.clone();
+            thread::spawn(move || {
+                let duplicates = search_duplicates(&home, &db, &hashes);
+                hub2.send(Event::DuplicatesFound(home, duplicates)).ok();
+            });
+        }
+    }
+
+    fn show_duplicates(&mut self, duplicates: Vec<Duplicates>, hub: &Hub, rq: &mut RenderQueue, context: &mut Context) {
+        if duplicates.is_empty() {
+            let notif = Notification::new("No duplicates found.".to_string(), hub, rq, context);
+            self.children.push(Box::new(notif) as Box<dyn View>);
+            return;
+        }
+
+        let message = format!("Found {} group{} of duplicates.", duplicates.len(),
+                              if duplicates.len() != 1 { "s" } else { "" });
+        let notif = Notification::new(message, hub, rq, context);
+        self.children.push(Box::new(notif) as Box<dyn View>);
+
+        if let Some(index) = locate_by_id(self, ViewId::DuplicatesMenu) {
+            rq.add(RenderData::expose(*self.child(index).rect(), UpdateMode::Gui));
+            self.children.remove(index);
+        }
+
+        let entries = duplicates.into_iter().map(|group| {
+            let copies = group.paths.iter().map(|keep| {
+                let others = group.paths.iter().filter(|path| *path != keep).cloned().collect();
+                EntryKind::Command(format!("Keep {}", keep.display()),
+                                   EntryId::MergeDuplicates(keep.clone(), others))
+            }).collect();
+            let name = if group.author.is_empty() {
+                group.title
+            } else {
+                format!("{} · {}", group.title, group.author)
+            };
+            EntryKind::SubMenu(name, copies)
+        }).collect();
+
+        let rect = rlocate::<BottomBar>(self).map(|index| *self.child(index).rect())
+                                             .unwrap_or(self.rect);
+        let duplicates_menu = Menu::new(rect, ViewId::DuplicatesMenu, MenuKind::DropDown, entries, context);
+        rq.add(RenderData::new(duplicates_menu.id(), *duplicates_menu.rect(), UpdateMode::Gui));
+        self.children.push(Box::new(duplicates_menu) as Box<dyn View>);
+    }
+
+    // Merges the given copies into *keep*, and moves them to the trash.
+    fn merge_duplicates(&mut self, keep: &Path, others: &[PathBuf], hub: &Hub, rq: &mut RenderQueue, context: &mut Context) {
+        let mut count = 0;
+        for path in others {
+            if let Err(e) = merge(&mut context.library, keep, path)
+                                 .and_then(|_| self.remove(path, hub, rq, context)) {
+                eprintln!("Can't merge {}: {:#}.", path.display(), e);
+            } else {
+                count += 1;
+            }
+        }
+        let message = format!("Merged {} cop{}.", count, if count != 1 { "ies" } else { "y" });
+        let notif = Notification::new(message, hub, rq, context);
+        self.children.push(Box::new(notif) as Box<dyn View>);
+    }
+
     fn empty_trash(&mut self, hub: &Hub, rq: &mut RenderQueue, context: &mut Context) {
         let trash_path = context.library.home.join(TRASH_DIRNAME);
 
@@ -1230,6 +1390,7 @@ impl Home {
 
         let home = context.library.home.clone();
         self.select_directory(&home, hub, rq, context);
+        self.update_watcher(hub, context);
     }
 
     fn import(&mut self, hub: &Hub, rq: &mut RenderQueue, context: &mut Context) {
@@ -1284,7 +1445,7 @@ impl Home {
     fn insert_fetcher(&mut self, hook: &Hook, hub: &Hub, context: &Context) {
         let library_path = &context.library.home;
         let save_path = context.library.home.join(&hook.path);
-        match self.spawn_child(library_path, &save_path, &hook.program, context.settings.wifi, context.online, hub) {
+        match spawn_hook(library_path, &save_path, &hook.program, context.settings.wifi, context.online, hub) {
             Ok(process) => {
                 let mut sort_method = hook.sort_method;
                 let mut first_column = hook.first_column;
@@ -1307,80 +1468,50 @@ impl Home {
         }
     }
 
-    fn spawn_child(&mut self, library_path: &Path, save_path: &Path, program: &Path, wifi: bool, online: bool, hub: &Hub) -> Result<Child, Error> {
-        let path = program.canonicalize()?;
-        let parent = path.parent()
-                         .unwrap_or_else(|| Path::new(""));
-        let mut process = Command::new(&path)
-                                 .current_dir(parent)
-                                 .arg(library_path)
-                                 .arg(save_path)
-                                 .arg(wifi.to_string())
-                                 .arg(online.to_string())
-                                 .stdin(Stdio::piped())
-                                 .stdout(Stdio::piped())
-                                 .spawn()?;
-        let stdout = process.stdout.take()
-                            .ok_or_else(|| format_err!("can't take stdout"))?;
-        let id = process.id();
-        let hub2 = hub.clone();
-        thread::spawn(move || {
-            let reader = BufReader::new(stdout);
-            for line_res in reader.lines() {
-                if let Ok(line) = line_res {
-                    if let Ok(event) = serde_json::from_str::<JsonValue>(&line) {
-                        match event.get("type")
-                                   .and_then(JsonValue::as_str) {
-                            Some("notify") => {
-                                if let Some(msg) = event.get("message")
-                                                        .and_then(JsonValue::as_str) {
-                                    hub2.send(Event::Notify(msg.to_string())).ok();
-                                }
-                            },
-                            Some("setWifi") => {
-                                if let Some(enable) = event.get("enable")
-                                                           .and_then(JsonValue::as_bool) {
-                                    hub2.send(Event::SetWifi(enable)).ok();
-                                }
-                            },
-                            Some("addDocument") => {
-                                if let Some(info) = event.get("info")
-                                                         .map(ToString::to_string)
-                                                         .and_then(|v| serde_json::from_str(&v).ok()) {
-                                    hub2.send(Event::FetcherAddDocument(id, Box::new(info))).ok();
-                                }
-                            },
-                            Some("removeDocument") => {
-                                if let Some(path) = event.get("path")
-                                                         .and_then(JsonValue::as_str) {
-                                    hub2.send(Event::FetcherRemoveDocument(id, PathBuf::from(path))).ok();
-                                }
-                            },
-                            Some("search") => {
-                                let path = event.get("path")
-                                                .and_then(JsonValue::as_str)
-                                                .map(PathBuf::from);
-                                let query = event.get("query")
-                                                 .and_then(JsonValue::as_str)
-                                                 .map(String::from);
-                                let sort_by = event.get("sortBy")
-                                                   .map(ToString::to_string)
-                                                   .and_then(|v| serde_json::from_str(&v).ok());
-                                hub2.send(Event::FetcherSearch { id, path, query, sort_by }).ok();
-                            },
-                            _ => (),
-                        }
-                    }
-                } else {
-                    break;
-                }
+    // Sends an event to the fetcher or service with the given process id.
+    fn send_to(&mut self, id: u32, event: &JsonValue, context: &mut Context) {
+        if let Some(fetcher) = self.background_fetchers.get_mut(&id) {
+            if let Some(stdin) = fetcher.process.stdin.as_mut() {
+                writeln!(stdin, "{}", event).ok();
             }
-            hub2.send(Event::CheckFetcher(id)).ok();
-        });
-        Ok(process)
+        } else {
+            context.services.send(id, event);
+        }
+    }
+
+    // Merges the given fields into the metadata of the document at *path*.
+    // The path and the reading state can't be modified.
+    fn update_document(&mut self, path: &Path, value: &JsonValue, hub: &Hub, rq: &mut RenderQueue, context: &mut Context) {
+        let info = match context.library.info(path) {
+            Some(info) => info,
+            None => {
+                eprintln!("Can't update {}: unknown document.", path.display());
+                return;
+            },
+        };
+        let mut fields = json!(info);
+        if let (Some(fields), Some(updates)) = (fields.as_object_mut(), value.as_object()) {
+            for (key, value) in updates {
+                fields.insert(key.clone(), value.clone());
+            }
+        }
+        match serde_json::from_value::<Info>(fields) {
+            Ok(mut next_info) => {
+                next_info.file = info.file;
+                next_info.reader = info.reader;
+                next_info.reader_info = None;
+                if let Err(e) = context.library.update(next_info) {
+                    eprintln!("Can't update {}: {:#}.", path.display(), e);
+                    return;
+                }
+                self.refresh_visibles(true, false, hub, rq, context);
+            },
+            Err(e) => eprintln!("Can't update {}: {:#}.", path.display(), e),
+        }
     }
 
     fn reseed(&mut self, hub: &Hub, rq: &mut RenderQueue, context: &mut Context) {
+        self.update_watcher(hub, context);
         context.library.sort(self.sort_method, self.reverse_order);
         self.refresh_visibles(true, false, hub, &mut RenderQueue::new(), context);
 
@@ -1543,6 +1674,14 @@ impl View for Home {
                 self.clean_up(hub, rq, context);
                 true
             },
+            Event::Select(EntryId::FindDuplicates) => {
+                self.toggle_duplicates_menu(None, hub, rq, context);
+                true
+            },
+            Event::Select(EntryId::MergeDuplicates(ref keep, ref others)) => {
+                self.merge_duplicates(keep, others, hub, rq, context);
+                true
+            },
             Event::Select(EntryId::Flush) => {
                 self.flush(context);
                 true
@@ -1727,12 +1866,36 @@ impl View for Home {
                     // Let the *reader* field pass through.
                     mem::swap(&mut entry.reader, &mut entry.reader_info);
                 }
-                if let Some(fetcher) = self.background_fetchers.get_mut(&id) {
+                self.send_to(id, &json!({"type": "search", "results": files}), context);
+                true
+            },
+            Event::FetcherUpdateDocument(_, ref path, ref value) => {
+                self.update_document(path, value, hub, rq, context);
+                true
+            },
+            Event::FetcherReaderInfo(id, ref info) => {
+                self.send_to(id, &json!({"type": "readerInfo", "info": info}), context);
+                true
+            },
+            Event::FetcherAnswer(id, answer) => {
+                self.send_to(id, &json!({"type": "answer", "value": answer}), context);
+                true
+            },
+            Event::Activity(ref activity, ref info) => {
+                let mut info = info.as_ref().clone();
+                mem::swap(&mut info.reader, &mut info.reader_info);
+                let event = match activity {
+                    Activity::Opened => json!({"type": "documentOpened", "info": info}),
+                    Activity::Finished => json!({"type": "documentFinished", "info": info}),
+                    Activity::Annotated(annot) => json!({"type": "documentAnnotated", "info": info,
+                                                         "annotation": annot}),
+                };
+                for fetcher in self.background_fetchers.values_mut() {
                     if let Some(stdin) = fetcher.process.stdin.as_mut() {
-                        writeln!(stdin, "{}", json!({"type": "search",
-                                                     "results": files})).ok();
+                        writeln!(stdin, "{}", event).ok();
                     }
                 }
+                context.services.broadcast(&event);
                 true
             },
             Event::CheckFetcher(id) => {
@@ -1758,6 +1921,19 @@ impl View for Home {
                 self.reseed(hub, rq, context);
                 true
             },
+            Event::DuplicatesFound(ref path, ref duplicates) => {
+                // The library might have been switched in the meantime.
+                if *path == context.library.home {
+                    self.show_duplicates(duplicates.clone(), hub, rq, context);
+                }
+                true
+            },
+            Event::LibraryChanged(ref path) => {
+                if context.library.mode == LibraryMode::Filesystem && *path == context.library.home {
+                    self.refresh_visibles(true, false, hub, rq, context);
+                }
+                true
+            },
             _ => false,
         }
     }
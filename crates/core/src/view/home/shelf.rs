@@ -8,6 +8,7 @@ use crate::view::{View, Event, Hub, Bus, Id, ID_FEEDER, RenderQueue, RenderData}
 use crate::view::{BIG_BAR_HEIGHT, THICKNESS_MEDIUM};
 use crate::view::filler::Filler;
 use crate::document::open;
+use crate::calibre::sidecar_cover;
 use crate::framebuffer::{Framebuffer, UpdateMode};
 use crate::settings::{FirstColumn, SecondColumn};
 use crate::geom::{Rectangle, Dir, CycleDir, halves};
@@ -85,6 +86,8 @@ impl Shelf {
                     let thumb_path2 = thumb_path.to_string_lossy().into_owned();
                     let path = info.file.path.clone();
                     let full_path = context.library.home.join(&info.file.path);
+                    // The cover saved by Calibre is preferred.
+                    let full_path = sidecar_cover(&full_path).unwrap_or(full_path);
                     thread::spawn(move || {
                         // This is a hack to circumvent a segfault (EXC_BAD_ACCESS)
                         // triggered by loading multiple jp2 pixmaps in parallel.
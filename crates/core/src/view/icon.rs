@@ -1,7 +1,7 @@
-use std::path::Path;
 use fxhash::FxHashMap;
 use lazy_static::lazy_static;
 use crate::device::CURRENT_DEVICE;
+use crate::helpers::asset_path;
 use crate::framebuffer::{Framebuffer, Pixmap, UpdateMode};
 use super::{View, Event, Hub, Bus, Id, ID_FEEDER, RenderQueue, RenderData, ViewId, Align};
 use crate::gesture::GestureEvent;
@@ -19,7 +19,7 @@ lazy_static! {
     pub static ref ICONS_PIXMAPS: FxHashMap<&'static str, Pixmap> = {
         let mut m = FxHashMap::default();
         let scale = scale_by_dpi_raw(ICON_SCALE, CURRENT_DEVICE.dpi);
-        let dir = Path::new("icons");
+        let dir = asset_path("icons");
         for name in ["home", "search", "back", "frontlight", "frontlight-disabled", "menu",
                      "angle-left", "angle-right", "angle-left-small", "angle-right-small",
                      "return", "shift", "combine", "alternate", "delete-backward", "delete-forward",
@@ -1,5 +1,6 @@
 use std::path::PathBuf;
 use crate::device::CURRENT_DEVICE;
+use crate::helpers::asset_path;
 use crate::document::{Location, open};
 use crate::geom::Rectangle;
 use crate::font::{Fonts, font_from_style, DISPLAY_STYLE};
@@ -91,7 +92,7 @@ impl View for Intermission {
 
                 font.render(fb, scheme[1], &plan, pt!(dx, dy));
 
-                let mut doc = open("icons/dodecahedron.svg").unwrap();
+                let mut doc = open(asset_path("icons/dodecahedron.svg")).unwrap();
                 let (width, height) = doc.dims(0).unwrap();
                 let scale = (plan.width as f32 / width.max(height) as f32) / 4.0;
                 let (pixmap, _) = doc.pixmap(Location::Exact(0), scale, 1).unwrap();
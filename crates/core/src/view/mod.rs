@@ -38,6 +38,7 @@ pub mod key;
 pub mod home;
 pub mod reader;
 pub mod dictionary;
+pub mod book_details;
 pub mod calculator;
 pub mod sketch;
 pub mod touch_events;
@@ -51,16 +52,19 @@ use std::sync::atomic::{AtomicU64, Ordering};
 use std::collections::VecDeque;
 use std::fmt::{self, Debug};
 use fxhash::FxHashMap;
+use serde_json::Value as JsonValue;
 use downcast_rs::{Downcast, impl_downcast};
 use crate::font::Fonts;
 use crate::color::Color;
 use crate::document::{Location, TextLocation};
 use crate::settings::{ButtonScheme, FirstColumn, SecondColumn, RotationLock};
-use crate::metadata::{Info, ZoomMode, ScrollMode, SortMethod, TextAlign, SimpleStatus, PageScheme, Margin};
+use crate::metadata::{Info, Annotation, ZoomMode, ScrollMode, SortMethod, TextAlign, SimpleStatus, PageScheme, Margin};
 use crate::geom::{LinearDir, CycleDir, Rectangle, Boundary};
 use crate::framebuffer::{Framebuffer, UpdateMode};
 use crate::input::{DeviceEvent, FingerStatus};
 use crate::gesture::GestureEvent;
+use crate::control::{Command, Responder};
+use crate::duplicates::Duplicates;
 use self::calculator::LineOrigin;
 use self::key::KeyKind;
 use crate::context::Context;
@@ -334,6 +338,9 @@ pub enum Event {
     SearchResult(usize, Vec<Boundary>),
     FetcherAddDocument(u32, Box<Info>),
     FetcherRemoveDocument(u32, PathBuf),
+    LibraryChanged(PathBuf),
+    // The duplicates found in the library at the given path.
+    DuplicatesFound(PathBuf, Vec<Duplicates>),
     FetcherSearch {
         id: u32,
         path: Option<PathBuf>,
@@ -341,6 +348,16 @@ pub enum Event {
         sort_by: Option<(SortMethod, bool)>,
     },
     CheckFetcher(u32),
+    CheckService(usize),
+    StartService(usize),
+    FinishWakeUp,
+    FetcherUpdateDocument(u32, PathBuf, JsonValue),
+    FetcherReaderInfo(u32, JsonValue),
+    FetcherProgress(u32, String, f32),
+    FetcherAsk(u32, String),
+    FetcherAnswer(u32, bool),
+    Activity(Activity, Box<Info>),
+    Control(Command, Responder),
     EndOfSearch,
     Finished,
     ClockTick,
@@ -376,6 +393,9 @@ pub enum AppCmd {
     },
     TouchEvents,
     RotationValues,
+    BookDetails {
+        path: PathBuf,
+    },
 }
 
 #[derive(Debug, Copy, Clone, Hash, Eq, PartialEq)]
@@ -404,6 +424,7 @@ pub enum ViewId {
     DirectoryMenu,
     BookMenu,
     LibraryMenu,
+    DuplicatesMenu,
     PageMenu,
     PresetMenu,
     MarginCropperMenu,
@@ -421,6 +442,8 @@ pub enum ViewId {
     EditNoteInput,
     EditLanguages,
     EditLanguagesInput,
+    EditMetadata,
+    EditMetadataInput,
     HomeSearchInput,
     ReaderSearchInput,
     DictionarySearchInput,
@@ -432,12 +455,24 @@ pub enum ViewId {
     AboutDialog,
     ShareDialog,
     MarginCropper,
+    PageGrid,
+    ImageViewer,
     TopBottomBars,
     TableOfContents,
     MessageNotif(Id),
+    ProgressNotif(u32),
+    FetcherDialog(u32),
     SubMenu(u8),
 }
 
+// The reading activity reported to the hooks.
+#[derive(Debug, Clone)]
+pub enum Activity {
+    Opened,
+    Finished,
+    Annotated(Box<Annotation>),
+}
+
 #[derive(Debug, Copy, Clone, Eq, PartialEq)]
 pub enum SliderId {
     FontSize,
@@ -514,6 +549,8 @@ pub enum EntryId {
     Save,
     Import,
     CleanUp,
+    FindDuplicates,
+    MergeDuplicates(PathBuf, Vec<PathBuf>),
     Sort(SortMethod),
     ReverseOrder,
     EmptyTrash,
@@ -543,6 +580,7 @@ pub enum EntryId {
     AdjustSelection,
     Annotations,
     Bookmarks,
+    PageGrid,
     RemoveAnnotation([TextLocation; 2]),
     EditAnnotationNote([TextLocation; 2]),
     RemoveAnnotationNote([TextLocation; 2]),
@@ -565,6 +603,8 @@ pub enum EntryId {
     ToggleFuzzy,
     ToggleInverted,
     ToggleDithered,
+    ToggleRightToLeft,
+    ToggleTwoPages,
     ToggleWifi,
     Rotate(i8),
     Launch(AppCmd),
@@ -572,6 +612,7 @@ pub enum EntryId {
     SetPenColor(Color),
     TogglePenDynamism,
     ReloadDictionaries,
+    WriteMetadata,
     New,
     Refresh,
     TakeScreenshot,
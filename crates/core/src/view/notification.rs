@@ -4,7 +4,7 @@ use crate::device::CURRENT_DEVICE;
 use crate::framebuffer::{Framebuffer, UpdateMode};
 use crate::geom::{Rectangle, CornerSpec, BorderSpec};
 use crate::font::{Fonts, font_from_style, NORMAL_STYLE};
-use crate::color::{BLACK, WHITE, TEXT_NORMAL};
+use crate::color::{BLACK, WHITE, TEXT_NORMAL, PROGRESS_EMPTY, PROGRESS_FULL};
 use super::{View, Event, Hub, Bus, Id, ID_FEEDER, RenderQueue, RenderData, ViewId};
 use super::{SMALL_BAR_HEIGHT, THICKNESS_LARGE, BORDER_RADIUS_MEDIUM};
 use crate::gesture::GestureEvent;
@@ -22,19 +22,39 @@ pub struct Notification {
     max_width: i32,
     index: u8,
     view_id: ViewId,
+    progress: Option<f32>,
+}
+
+// Closes the notification after a delay.
+fn schedule_close(view_id: ViewId, hub: &Hub) {
+    let hub2 = hub.clone();
+    thread::spawn(move || {
+        thread::sleep(NOTIFICATION_CLOSE_DELAY);
+        hub2.send(Event::Close(view_id)).ok();
+    });
 }
 
 impl Notification {
     pub fn new(text: String, hub: &Hub, rq: &mut RenderQueue, context: &mut Context) -> Notification {
         let id = ID_FEEDER.next();
         let view_id = ViewId::MessageNotif(id);
-        let hub2 = hub.clone();
-        let index = context.notification_index;
+        schedule_close(view_id, hub);
+        Notification::build(id, view_id, text, None, rq, context)
+    }
+
+    // Creates a notification with a progress bar. It stays on screen until
+    // the progress is complete, or until it's explicitly closed.
+    pub fn with_progress(view_id: ViewId, text: String, progress: f32, hub: &Hub, rq: &mut RenderQueue, context: &mut Context) -> Notification {
+        let id = ID_FEEDER.next();
+        let progress = progress.clamp(0.0, 1.0);
+        if progress >= 1.0 {
+            schedule_close(view_id, hub);
+        }
+        Notification::build(id, view_id, text, Some(progress), rq, context)
+    }
 
-        thread::spawn(move || {
-            thread::sleep(NOTIFICATION_CLOSE_DELAY);
-            hub2.send(Event::Close(view_id)).ok();
-        });
+    fn build(id: Id, view_id: ViewId, text: String, progress: Option<f32>, rq: &mut RenderQueue, context: &mut Context) -> Notification {
+        let index = context.notification_index;
 
         let dpi = CURRENT_DEVICE.dpi;
         let (width, _) = context.display.dims;
@@ -47,8 +67,17 @@ impl Notification {
         let max_message_width = width as i32 - 5 * padding;
         let plan = font.plan(&text, Some(max_message_width), None);
 
-        let dialog_width = plan.width + 3 * padding;
-        let dialog_height = 7 * x_height;
+        // The width of a progress notification doesn't depend on its text, since the text can change.
+        let dialog_width = if progress.is_some() {
+            max_message_width + 3 * padding
+        } else {
+            plan.width + 3 * padding
+        };
+        let dialog_height = if progress.is_some() {
+            10 * x_height
+        } else {
+            7 * x_height
+        };
 
         let side = (index / 3) % 2;
         let dx = if side == 0 {
@@ -72,8 +101,19 @@ impl Notification {
             max_width: max_message_width,
             index,
             view_id,
+            progress,
         }
     }
+
+    pub fn update_progress(&mut self, text: String, progress: f32, hub: &Hub, rq: &mut RenderQueue) {
+        let progress = progress.clamp(0.0, 1.0);
+        if progress >= 1.0 && self.progress.is_some_and(|p| p < 1.0) {
+            schedule_close(self.view_id, hub);
+        }
+        self.text = text;
+        self.progress = Some(progress);
+        rq.add(RenderData::new(self.id, self.rect, UpdateMode::Gui));
+    }
 }
 
 impl View for Notification {
@@ -103,10 +143,22 @@ impl View for Notification {
         let x_height = font.x_heights.0 as i32;
 
         let dx = (self.rect.width() as i32 - plan.width) as i32 / 2;
-        let dy = (self.rect.height() as i32 - x_height) / 2;
-        let pt = pt!(self.rect.min.x + dx, self.rect.max.y - dy);
 
-        font.render(fb, TEXT_NORMAL[1], &plan, pt);
+        if let Some(progress) = self.progress {
+            let padding = font.em() as i32;
+            let pt = pt!(self.rect.min.x + dx, self.rect.min.y + 4 * x_height);
+            font.render(fb, TEXT_NORMAL[1], &plan, pt);
+
+            let bar_rect = rect![self.rect.min.x + padding, self.rect.max.y - 4 * x_height,
+                                 self.rect.max.x - padding, self.rect.max.y - 3 * x_height];
+            let done_width = (progress * bar_rect.width() as f32).round() as i32;
+            fb.draw_rectangle(&bar_rect, PROGRESS_EMPTY);
+            fb.draw_rectangle(&rect![bar_rect.min, pt!(bar_rect.min.x + done_width, bar_rect.max.y)], PROGRESS_FULL);
+        } else {
+            let dy = (self.rect.height() as i32 - x_height) / 2;
+            let pt = pt!(self.rect.min.x + dx, self.rect.max.y - dy);
+            font.render(fb, TEXT_NORMAL[1], &plan, pt);
+        }
     }
 
     fn resize(&mut self, _rect: Rectangle, _hub: &Hub, _rq: &mut RenderQueue, context: &mut Context) {
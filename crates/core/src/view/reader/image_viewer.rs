@@ -0,0 +1,274 @@
+use std::sync::{Arc, Mutex};
+use crate::device::CURRENT_DEVICE;
+use crate::document::{Document, Location};
+use crate::framebuffer::{Framebuffer, UpdateMode, Pixmap};
+use crate::font::Fonts;
+use crate::gesture::GestureEvent;
+use crate::geom::{Point, Rectangle, Boundary};
+use crate::view::{View, Event, Hub, Bus, Id, ID_FEEDER, RenderQueue, RenderData, ViewId};
+use crate::view::SMALL_BAR_HEIGHT;
+use crate::view::rounded_button::RoundedButton;
+use crate::unit::scale_by_dpi;
+use crate::color::WHITE;
+use crate::context::Context;
+
+const MAX_ZOOM: f32 = 8.0;
+// Upper bound on the number of pixels of the rendered page.
+const MAX_PAGE_PIXELS: f32 = 16_777_216.0;
+
+pub struct ImageViewer {
+    id: Id,
+    rect: Rectangle,
+    children: Vec<Box<dyn View>>,
+    doc: Arc<Mutex<Box<dyn Document>>>,
+    location: usize,
+    image: Boundary,      // The image's rectangle, in page coordinates.
+    pixmap: Pixmap,
+    position: Point,      // Position of the pixmap, relative to the top left corner of the view.
+    zoom: f32,            // Zoom factor, relative to the fit-to-screen scale.
+    rotated: bool,
+    dithered: bool,       // The dithering state to restore when the viewer is closed.
+}
+
+impl ImageViewer {
+    pub fn new(rect: Rectangle, doc: Arc<Mutex<Box<dyn Document>>>, location: usize,
+               image: Boundary, dithered: bool, context: &mut Context) -> ImageViewer {
+        let id = ID_FEEDER.next();
+        let previous_dithered = context.fb.dithered();
+        context.fb.set_dithered(dithered);
+
+        let mut viewer = ImageViewer {
+            id,
+            rect,
+            children: Vec::new(),
+            doc,
+            location,
+            image,
+            pixmap: Pixmap::empty(1, 1, 1),
+            position: Point::default(),
+            zoom: 1.0,
+            rotated: false,
+            dithered: previous_dithered,
+        };
+
+        viewer.rotated = viewer.fit_scale(true) > viewer.fit_scale(false);
+        viewer.layout_children();
+        viewer.load_pixmap();
+        viewer.center();
+        viewer
+    }
+
+    fn layout_children(&mut self) {
+        self.children.clear();
+        let dpi = CURRENT_DEVICE.dpi;
+        let button_diameter = scale_by_dpi(SMALL_BAR_HEIGHT, dpi) as i32;
+        let padding = button_diameter / 2;
+        let close_button = RoundedButton::new("close",
+                                              rect![self.rect.min.x + padding,
+                                                    self.rect.max.y - padding - button_diameter,
+                                                    self.rect.min.x + padding + button_diameter,
+                                                    self.rect.max.y - padding],
+                                              Event::Cancel);
+        self.children.push(Box::new(close_button) as Box<dyn View>);
+    }
+
+    // The scale at which the image fits the screen.
+    fn fit_scale(&self, rotated: bool) -> f32 {
+        let (width, height) = if rotated {
+            (self.image.height(), self.image.width())
+        } else {
+            (self.image.width(), self.image.height())
+        };
+        (self.rect.width() as f32 / width).min(self.rect.height() as f32 / height)
+    }
+
+    fn max_zoom(&self) -> f32 {
+        let fit_scale = self.fit_scale(self.rotated);
+        self.doc.lock().ok()
+            .and_then(|doc| doc.dims(self.location))
+            .map_or(1.0, |(width, height)| {
+                (MAX_PAGE_PIXELS / (width * height)).sqrt() / fit_scale
+            })
+            .clamp(1.0, MAX_ZOOM)
+    }
+
+    fn load_pixmap(&mut self) {
+        let scale = self.zoom * self.fit_scale(self.rotated);
+        let samples = CURRENT_DEVICE.color_samples();
+        let pixmap_opt = self.doc.lock().ok().and_then(|mut doc| {
+            doc.pixmap(Location::Exact(self.location), scale, samples)
+        }).and_then(|(page, _)| {
+            let frame = (self.image * scale).to_rect()
+                                            .intersection(&page.rect())?;
+            let mut pixmap = Pixmap::try_new(frame.width(), frame.height(), page.samples)?;
+            pixmap.draw_framed_pixmap(&page, &frame, pt!(0, 0));
+            Some(pixmap)
+        });
+
+        if let Some(pixmap) = pixmap_opt {
+            self.pixmap = if self.rotated {
+                rotate(&pixmap)
+            } else {
+                pixmap
+            };
+        }
+    }
+
+    // Centers the pixmap along the axes where it's smaller than the view,
+    // and prevents the view from going beyond the pixmap's edges along the others.
+    fn clamp_position(&mut self) {
+        let (width, height) = (self.rect.width() as i32, self.rect.height() as i32);
+        let (pw, ph) = (self.pixmap.width as i32, self.pixmap.height as i32);
+        self.position.x = if pw <= width { (width - pw) / 2 } else { self.position.x.clamp(width - pw, 0) };
+        self.position.y = if ph <= height { (height - ph) / 2 } else { self.position.y.clamp(height - ph, 0) };
+    }
+
+    fn center(&mut self) {
+        self.position = pt!(self.rect.width() as i32 - self.pixmap.width as i32,
+                            self.rect.height() as i32 - self.pixmap.height as i32) / 2;
+        self.clamp_position();
+    }
+
+    fn set_zoom(&mut self, center: Point, factor: f32, rq: &mut RenderQueue) {
+        let zoom = (self.zoom * factor).clamp(1.0, self.max_zoom());
+        if (zoom - self.zoom).abs() < f32::EPSILON {
+            return;
+        }
+        // Keep the point under the center of the gesture still.
+        let anchor = center - self.rect.min;
+        let point = anchor - self.position;
+        let ratio = zoom / self.zoom;
+        self.zoom = zoom;
+        self.load_pixmap();
+        self.position = anchor - pt!((point.x as f32 * ratio) as i32, (point.y as f32 * ratio) as i32);
+        self.clamp_position();
+        rq.add(RenderData::new(self.id, self.rect, UpdateMode::Partial));
+    }
+
+    fn close(&mut self, bus: &mut Bus, context: &mut Context) {
+        context.fb.set_dithered(self.dithered);
+        bus.push_back(Event::Close(ViewId::ImageViewer));
+    }
+}
+
+// Rotates the given pixmap by a quarter turn clockwise.
+fn rotate(pixmap: &Pixmap) -> Pixmap {
+    let samples = pixmap.samples;
+    let mut rotated = Pixmap::new(pixmap.height, pixmap.width, samples);
+    for y in 0..pixmap.height {
+        for x in 0..pixmap.width {
+            let src = samples * (y * pixmap.width + x) as usize;
+            let dst = samples * (x * rotated.width + (pixmap.height - 1 - y)) as usize;
+            rotated.data[dst..dst+samples].copy_from_slice(&pixmap.data[src..src+samples]);
+        }
+    }
+    rotated
+}
+
+impl View for ImageViewer {
+    fn handle_event(&mut self, evt: &Event, _hub: &Hub, bus: &mut Bus, rq: &mut RenderQueue, context: &mut Context) -> bool {
+        match *evt {
+            Event::Gesture(GestureEvent::Swipe { start, end, .. }) |
+            Event::Gesture(GestureEvent::SlantedSwipe { start, end, .. }) if self.rect.includes(start) => {
+                let position = self.position;
+                self.position += end - start;
+                self.clamp_position();
+                if self.position != position {
+                    rq.add(RenderData::new(self.id, self.rect, UpdateMode::Partial));
+                }
+                true
+            },
+            Event::Gesture(GestureEvent::Spread { center, factor, .. }) |
+            Event::Gesture(GestureEvent::Pinch { center, factor, .. }) if factor.is_finite() &&
+                                                                           self.rect.includes(center) => {
+                self.set_zoom(center, factor, rq);
+                true
+            },
+            Event::Gesture(GestureEvent::Rotate { center, quarter_turns, .. }) if quarter_turns != 0 &&
+                                                                                self.rect.includes(center) => {
+                self.rotated = !self.rotated;
+                self.zoom = 1.0;
+                self.load_pixmap();
+                self.center();
+                rq.add(RenderData::new(self.id, self.rect, UpdateMode::Full));
+                true
+            },
+            Event::Gesture(GestureEvent::Tap(center)) |
+            Event::Gesture(GestureEvent::HoldFingerShort(center, ..)) |
+            Event::Gesture(GestureEvent::HoldFingerLong(center, ..)) if self.rect.includes(center) => true,
+            Event::Cancel => {
+                self.close(bus, context);
+                true
+            },
+            _ => false,
+        }
+    }
+
+    fn render(&self, fb: &mut dyn Framebuffer, rect: Rectangle, _fonts: &mut Fonts) {
+        let origin = self.rect.min + self.position;
+        let pixmap_rect = rect![origin, origin + pt!(self.pixmap.width as i32, self.pixmap.height as i32)];
+
+        if let Some(r) = self.rect.intersection(&rect) {
+            fb.draw_rectangle(&r, WHITE);
+        }
+
+        if let Some(r) = pixmap_rect.intersection(&rect) {
+            let frame = r - origin;
+            fb.draw_framed_pixmap(&self.pixmap, &frame, r.min);
+        }
+    }
+
+    fn resize(&mut self, rect: Rectangle, _hub: &Hub, _rq: &mut RenderQueue, _context: &mut Context) {
+        self.rect = rect;
+        self.zoom = 1.0;
+        self.rotated = self.fit_scale(true) > self.fit_scale(false);
+        self.layout_children();
+        self.load_pixmap();
+        self.center();
+    }
+
+    fn is_background(&self) -> bool {
+        true
+    }
+
+    fn view_id(&self) -> Option<ViewId> {
+        Some(ViewId::ImageViewer)
+    }
+
+    fn rect(&self) -> &Rectangle {
+        &self.rect
+    }
+
+    fn rect_mut(&mut self) -> &mut Rectangle {
+        &mut self.rect
+    }
+
+    fn children(&self) -> &Vec<Box<dyn View>> {
+        &self.children
+    }
+
+    fn children_mut(&mut self) -> &mut Vec<Box<dyn View>> {
+        &mut self.children
+    }
+
+    fn id(&self) -> Id {
+        self.id
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rotate() {
+        let mut pixmap = Pixmap::new(3, 2, 1);
+        pixmap.data.copy_from_slice(&[1, 2, 3,
+                                      4, 5, 6]);
+        let rotated = rotate(&pixmap);
+        assert_eq!((rotated.width, rotated.height), (2, 3));
+        assert_eq!(rotated.data, vec![4, 1,
+                                      5, 2,
+                                      6, 3]);
+    }
+}
@@ -4,6 +4,9 @@ mod results_bar;
 mod margin_cropper;
 mod chapter_label;
 mod results_label;
+mod panels;
+mod page_grid;
+mod image_viewer;
 
 use std::thread;
 use std::sync::{Arc, Mutex};
@@ -21,7 +24,7 @@ use septem::{Roman, Digit};
 use rand_core::RngCore;
 use crate::input::{DeviceEvent, FingerStatus, ButtonCode, ButtonStatus};
 use crate::framebuffer::{Framebuffer, UpdateMode, Pixmap};
-use crate::view::{View, Event, AppCmd, Hub, Bus, RenderQueue, RenderData};
+use crate::view::{View, Event, Activity, AppCmd, Hub, Bus, RenderQueue, RenderData};
 use crate::view::{ViewId, Id, ID_FEEDER, EntryKind, EntryId, SliderId};
 use crate::view::{SMALL_BAR_HEIGHT, BIG_BAR_HEIGHT, THICKNESS_MEDIUM};
 use crate::unit::{scale_by_dpi, mm_to_px};
@@ -34,6 +37,9 @@ use super::top_bar::TopBar;
 use self::tool_bar::ToolBar;
 use self::bottom_bar::BottomBar;
 use self::results_bar::ResultsBar;
+use self::panels::detect_panels;
+use self::page_grid::PageGrid;
+use self::image_viewer::ImageViewer;
 use crate::view::common::{locate, rlocate, locate_by_id};
 use crate::view::common::{toggle_main_menu, toggle_battery_menu, toggle_clock_menu};
 use crate::view::filler::Filler;
@@ -64,6 +70,9 @@ const RECT_DIST_JITTER: f32 = 24.0;
 const ANNOTATION_DRIFT: u8 =  0x44;
 const HIGHLIGHT_DRIFT: u8 =  0x22;
 const MEM_SCHEME: &str = "mem:";
+const MAX_PANEL_ZOOM: f32 = 3.0;
+// The kinds of the images embedded in documents.
+const IMAGE_KINDS: [&str; 3] = ["png", "jpg", "jpeg"];
 
 pub struct Reader {
     id: Id,
@@ -75,6 +84,7 @@ pub struct Reader {
     text: FxHashMap<usize, Vec<BoundedText>>,        // Text of the current chunks.
     annotations: FxHashMap<usize, Vec<Annotation>>,  // Annotations for the current chunks.
     noninverted_regions: FxHashMap<usize, Vec<Boundary>>,
+    panels: FxHashMap<usize, Vec<Boundary>>,        // Panels of the pages, relative to their dimensions.
     focus: Option<ViewId>,
     search: Option<Search>,
     search_direction: LinearDir,
@@ -91,6 +101,7 @@ pub struct Reader {
     synthetic: bool,
     page_turns: usize,
     reflowable: bool,
+    two_pages: bool,
     ephemeral: bool,
     finished: bool,
 }
@@ -100,7 +111,9 @@ struct ViewPort {
     zoom_mode: ZoomMode,
     scroll_mode: ScrollMode,
     page_offset: Point,   // Offset relative to the top left corner of a resource's frame.
+    panel: usize,         // Index of the current panel.
     margin_width: i32,
+    text_margin: i32,     // Vertical margin of the pages of reflowable documents.
 }
 
 impl Default for ViewPort {
@@ -109,7 +122,9 @@ impl Default for ViewPort {
             zoom_mode: ZoomMode::FitToPage,
             scroll_mode: ScrollMode::Screen,
             page_offset: pt!(0, 0),
+            panel: 0,
             margin_width: 0,
+            text_margin: 0,
         }
     }
 }
@@ -189,7 +204,7 @@ fn scaling_factor(rect: &Rectangle, cropping_margin: &Margin, screen_margin_widt
     let frame_width = (1.0 - (cropping_margin.left + cropping_margin.right)) * page_width;
     let width_ratio = surface_width / frame_width;
     match zoom_mode {
-        ZoomMode::FitToPage => {
+        ZoomMode::FitToPage | ZoomMode::Panel => {
             let surface_height = (rect.height() as i32 - 2 * screen_margin_width) as f32;
             let frame_height = (1.0 - (cropping_margin.top + cropping_margin.bottom)) * page_height;
             let height_ratio = surface_height / frame_height;
@@ -358,9 +373,17 @@ impl Reader {
             let synthetic = doc.has_synthetic_page_numbers();
             let reflowable = doc.is_reflowable();
 
+            if reflowable {
+                view_port.text_margin = mm_to_px(margin_width as f32, CURRENT_DEVICE.dpi).round() as i32;
+            }
+            let two_pages = info.reader.as_ref().and_then(|r| r.two_pages)
+                                .or_else(|| doc.spread())
+                                .unwrap_or(false);
+
             println!("{}", info.file.path.display());
 
             hub.send(Event::Update(UpdateMode::Partial)).ok();
+            hub.send(Event::Activity(Activity::Opened, Box::new(info.clone()))).ok();
 
             Some(Reader {
                 id,
@@ -372,6 +395,7 @@ impl Reader {
                 text: FxHashMap::default(),
                 annotations: FxHashMap::default(),
                 noninverted_regions: FxHashMap::default(),
+                panels: FxHashMap::default(),
                 focus: None,
                 search: None,
                 search_direction: LinearDir::Forward,
@@ -389,6 +413,7 @@ impl Reader {
                 contrast,
                 ephemeral: false,
                 reflowable,
+                two_pages,
                 finished: false,
             })
         })
@@ -437,6 +462,7 @@ impl Reader {
             text: FxHashMap::default(),
             annotations: FxHashMap::default(),
             noninverted_regions: FxHashMap::default(),
+            panels: FxHashMap::default(),
             focus: None,
             search: None,
             search_direction: LinearDir::Forward,
@@ -454,12 +480,13 @@ impl Reader {
             contrast: Contrast::default(),
             ephemeral: true,
             reflowable: true,
+            two_pages: false,
             finished: false,
         }
     }
 
     fn load_pixmap(&mut self, location: usize) {
-        if self.cache.contains_key(&location) {
+        if self.cache.contains_key(&location) && self.view_port.zoom_mode != ZoomMode::Panel {
             return;
         }
 
@@ -470,12 +497,30 @@ impl Reader {
                                   .cloned().unwrap_or_default();
         let dims = doc.dims(location).unwrap_or((3.0, 4.0));
         let screen_margin_width = self.view_port.margin_width;
-        let scale = scaling_factor(&self.rect, &cropping_margin, screen_margin_width, dims, self.view_port.zoom_mode);
+        let scale = if self.view_port.zoom_mode == ZoomMode::Panel {
+            self.panel_scaling_factor(location, dims)
+        } else if self.spread_active() {
+            // Each page gets half of the surface.
+            let rect = rect![0, 0, self.rect.width() as i32 / 2 + screen_margin_width, self.rect.height() as i32];
+            scaling_factor(&rect, &cropping_margin, screen_margin_width, dims, self.view_port.zoom_mode)
+        } else {
+            scaling_factor(&self.rect, &cropping_margin, screen_margin_width, dims, self.view_port.zoom_mode)
+        };
+        // In panel mode, the scale depends on the current panel.
+        if self.cache.get(&location).is_some_and(|r| (r.scale - scale).abs() < f32::EPSILON) {
+            return;
+        }
         if let Some((pixmap, _)) = doc.pixmap(Location::Exact(location), scale, CURRENT_DEVICE.color_samples()) {
-            let frame = rect![(cropping_margin.left * pixmap.width as f32).ceil() as i32,
-                              (cropping_margin.top * pixmap.height as f32).ceil() as i32,
-                              ((1.0 - cropping_margin.right) * pixmap.width as f32).floor() as i32,
-                              ((1.0 - cropping_margin.bottom) * pixmap.height as f32).floor() as i32];
+            let mut frame = rect![(cropping_margin.left * pixmap.width as f32).ceil() as i32,
+                                  (cropping_margin.top * pixmap.height as f32).ceil() as i32,
+                                  ((1.0 - cropping_margin.right) * pixmap.width as f32).floor() as i32,
+                                  ((1.0 - cropping_margin.bottom) * pixmap.height as f32).floor() as i32];
+            // The pages of reflowable documents are stacked without their vertical margins.
+            if self.reflowable && self.view_port.zoom_mode == ZoomMode::FitToWidth &&
+               frame.height() as i32 > 2 * self.view_port.text_margin {
+                frame.min.y += self.view_port.text_margin;
+                frame.max.y -= self.view_port.text_margin;
+            }
             self.cache.insert(location, Resource { pixmap, frame, scale });
         } else {
             let width = (dims.0 as f32 * scale).max(1.0) as u32;
@@ -486,6 +531,61 @@ impl Reader {
         }
     }
 
+    // Facing pages are displayed side by side in landscape.
+    fn spread_active(&self) -> bool {
+        self.two_pages && !self.reflowable &&
+        self.view_port.zoom_mode == ZoomMode::FitToPage &&
+        self.rect.width() > self.rect.height()
+    }
+
+    // The first page is displayed alone, the following pages are grouped by pairs.
+    fn spread_locations(&self, location: usize) -> Vec<usize> {
+        if location == 0 {
+            return vec![0];
+        }
+        let first = if location % 2 == 1 { location } else { location - 1 };
+        (first..(first + 2).min(self.pages_count)).collect()
+    }
+
+    fn load_panels(&mut self, location: usize) {
+        if self.panels.contains_key(&location) {
+            return;
+        }
+
+        let right_to_left = self.info.reader.as_ref().is_some_and(|r| r.right_to_left);
+        let mut doc = self.doc.lock().unwrap();
+        let mut panels: Vec<Boundary> = doc.dims(location).and_then(|dims| {
+            let scale = scaling_factor(&self.rect, &Margin::default(), 0, dims, ZoomMode::FitToPage);
+            doc.pixmap(Location::Exact(location), scale, 1)
+        }).map(|(pixmap, _)| {
+            let size = vec2!(pixmap.width as f32, pixmap.height as f32);
+            detect_panels(&pixmap, right_to_left).into_iter()
+                                                 .map(|rect| {
+                                                     let bnd: Boundary = rect.into();
+                                                     bndr!(bnd.min / size, bnd.max / size)
+                                                 }).collect()
+        }).unwrap_or_default();
+
+        if panels.is_empty() {
+            panels.push(bndr!(0.0, 0.0, 1.0, 1.0));
+        }
+
+        self.panels.insert(location, panels);
+    }
+
+    fn panel_scaling_factor(&self, location: usize, dims: (f32, f32)) -> f32 {
+        let smw = self.view_port.margin_width;
+        let page_scale = scaling_factor(&self.rect, &Margin::default(), smw, dims, ZoomMode::FitToPage);
+        let index = if location == self.current_page { self.view_port.panel } else { 0 };
+        self.panels.get(&location).and_then(|panels| panels.get(index)).map_or(page_scale, |panel| {
+            let surface_width = (self.rect.width() as i32 - 2 * smw) as f32;
+            let surface_height = (self.rect.height() as i32 - 2 * smw) as f32;
+            let width_ratio = surface_width / (panel.width() * dims.0);
+            let height_ratio = surface_height / (panel.height() * dims.1);
+            width_ratio.min(height_ratio).min(MAX_PANEL_ZOOM * page_scale)
+        })
+    }
+
     fn load_text(&mut self, location: usize) {
         if self.text.contains_key(&location) {
             return;
@@ -499,6 +599,15 @@ impl Reader {
         self.text.insert(location, words);
     }
 
+    // The lines used to avoid cutting through text when scrolling.
+    // Reflowable documents don't provide lines, their words are used instead.
+    fn cut_lines(&self, location: usize) -> Option<Vec<BoundedText>> {
+        self.doc.lock().unwrap()
+            .lines(Location::Exact(location))
+            .map(|(lines, _)| lines)
+            .or_else(|| self.text.get(&location).cloned())
+    }
+
     fn go_to_page(&mut self, location: usize, record: bool, hub: &Hub, rq: &mut RenderQueue, context: &Context) {
         let loc = {
             let mut doc = self.doc.lock().unwrap();
@@ -519,6 +628,7 @@ impl Reader {
 
             self.current_page = location;
             self.view_port.page_offset = pt!(0);
+            self.view_port.panel = 0;
             self.selection = None;
             self.state = State::Idle;
             self.update(None, hub, rq, context);
@@ -628,7 +738,8 @@ impl Reader {
     }
 
     fn vertical_scroll(&mut self, delta_y: i32, hub: &Hub, rq: &mut RenderQueue, context: &mut Context) {
-        if delta_y == 0 || self.view_port.zoom_mode == ZoomMode::FitToPage || self.cache.is_empty() {
+        if delta_y == 0 || self.view_port.zoom_mode == ZoomMode::FitToPage ||
+           self.view_port.zoom_mode == ZoomMode::Panel || self.cache.is_empty() {
             return;
         }
 
@@ -668,8 +779,7 @@ impl Reader {
 
                 {
                     let Resource { frame, scale, .. } = *self.cache.get(&location).unwrap();
-                    let mut doc = self.doc.lock().unwrap();
-                    if let Some((lines, _)) = doc.lines(Location::Exact(location)) {
+                    if let Some(lines) = self.cut_lines(location) {
                         if let Some(mut y_pos) = find_cut(&frame, frame.min.y + next_top_offset,
                                                           scale, LinearDir::Forward, &lines) {
                             y_pos = y_pos.clamp(frame.min.y, frame.max.y - 1);
@@ -733,6 +843,7 @@ impl Reader {
 
         let current_page = self.current_page;
         let page_offset = self.view_port.page_offset;
+        let panel = self.view_port.panel;
 
         let loc = {
             let neighloc = match dir { 
@@ -768,8 +879,7 @@ impl Reader {
                                 let mut next_top_offset = (height - available_height).max(0);
                                 if height > available_height {
                                     let Resource { frame, scale, .. } = self.cache[&location];
-                                    let mut doc = self.doc.lock().unwrap();
-                                    if let Some((lines, _)) = doc.lines(Location::Exact(location)) {
+                                    if let Some(lines) = self.cut_lines(location) {
                                         if let Some(mut y_pos) = find_cut(&frame, frame.min.y + next_top_offset,
                                             scale, LinearDir::Forward, &lines) {
                                             y_pos = y_pos.clamp(frame.min.y, frame.max.y - 1);
@@ -798,6 +908,20 @@ impl Reader {
                                 }
                             },
                         },
+                        ZoomMode::Panel => {
+                            if self.view_port.panel > 0 {
+                                self.view_port.panel -= 1;
+                                Location::Exact(current_page)
+                            } else {
+                                let previous_location = self.doc.lock().unwrap()
+                                                            .resolve_location(Location::Previous(current_page));
+                                if let Some(location) = previous_location {
+                                    self.load_panels(location);
+                                    self.view_port.panel = self.panels[&location].len().saturating_sub(1);
+                                }
+                                Location::Previous(current_page)
+                            }
+                        },
                         ZoomMode::Custom(_) => {
                             self.view_port.page_offset = pt!(0);
                             Location::Previous(current_page)
@@ -806,7 +930,7 @@ impl Reader {
                 },
                 CycleDir::Next => {
                     match self.view_port.zoom_mode {
-                        ZoomMode::FitToPage => Location::Next(current_page),
+                        ZoomMode::FitToPage => Location::Next(self.chunks.iter().map(|c| c.location).max().unwrap()),
                         ZoomMode::FitToWidth => match self.view_port.scroll_mode {
                             ScrollMode::Screen => {
                                 let &RenderChunk { location, frame, .. } = self.chunks.last().unwrap();
@@ -835,6 +959,16 @@ impl Reader {
                                 }
                             },
                         },
+                        ZoomMode::Panel => {
+                            let panels_count = self.panels.get(&current_page).map_or(0, Vec::len);
+                            if self.view_port.panel + 1 < panels_count {
+                                self.view_port.panel += 1;
+                                Location::Exact(current_page)
+                            } else {
+                                self.view_port.panel = 0;
+                                Location::Next(current_page)
+                            }
+                        },
                         ZoomMode::Custom(_) => {
                             self.view_port.page_offset = pt!(0);
                             Location::Next(current_page)
@@ -846,7 +980,8 @@ impl Reader {
             doc.resolve_location(neighloc)
         };
         match loc {
-            Some(location) if location != current_page || self.view_port.page_offset != page_offset => {
+            Some(location) if location != current_page || self.view_port.page_offset != page_offset ||
+                               self.view_port.panel != panel => {
                 if let Some(ref mut s) = self.search {
                     s.current_page = s.highlights.range(..=location).count().saturating_sub(1);
                 }
@@ -862,9 +997,13 @@ impl Reader {
                 }
             },
             _ => {
+                self.view_port.panel = panel;
                 match dir {
                     CycleDir::Next => {
-                        self.finished = true;
+                        if !self.finished {
+                            self.finished = true;
+                            self.report(Activity::Finished, hub);
+                        }
                         let action = if self.ephemeral {
                             FinishedAction::Notify
                         } else {
@@ -903,6 +1042,7 @@ impl Reader {
         if let Some(location) = loc {
             self.current_page = location;
             self.view_port.page_offset = pt!(0, 0);
+            self.view_port.panel = 0;
             self.selection = None;
             self.state = State::Idle;
             self.update_results_bar(rq);
@@ -925,6 +1065,7 @@ impl Reader {
                 s.current_page = s.highlights.range(..=location).count().saturating_sub(1);
             }
             self.view_port.page_offset = pt!(0, 0);
+            self.view_port.panel = 0;
             self.current_page = location;
             self.update_results_bar(rq);
             self.update_bottom_bar(rq);
@@ -1055,6 +1196,25 @@ impl Reader {
         let smw = self.view_port.margin_width;
 
         match self.view_port.zoom_mode {
+            ZoomMode::FitToPage if self.spread_active() => {
+                let mut locations = self.spread_locations(location);
+                self.current_page = locations[0];
+                if self.info.reader.as_ref().is_some_and(|r| r.right_to_left) {
+                    locations.reverse();
+                }
+                for &location in &locations {
+                    self.load_pixmap(location);
+                    self.load_text(location);
+                }
+                let width: i32 = locations.iter().map(|l| self.cache[l].frame.width() as i32).sum();
+                let mut dx = smw + (self.rect.width() as i32 - width - 2 * smw) / 2;
+                for location in locations {
+                    let Resource { frame, scale, .. } = self.cache[&location];
+                    let dy = smw + ((self.rect.height() - frame.height()) as i32 - 2 * smw) / 2;
+                    self.chunks.push(RenderChunk { frame, location, position: pt!(dx, dy), scale });
+                    dx += frame.width() as i32;
+                }
+            },
             ZoomMode::FitToPage => {
                 self.load_pixmap(location);
                 self.load_text(location);
@@ -1072,6 +1232,8 @@ impl Reader {
                         self.load_text(location);
                         let Resource { mut frame, scale, .. } = self.cache[&location];
                         if location == self.current_page {
+                            self.view_port.page_offset.y = self.view_port.page_offset.y
+                                                               .min(frame.height().saturating_sub(1) as i32);
                             frame.min.y += self.view_port.page_offset.y;
                         }
                         let position = pt!(smw, smw + height);
@@ -1086,10 +1248,10 @@ impl Reader {
                         }
                     }
                     if height > available_height {
+                        let lines = self.chunks.last().and_then(|c| self.cut_lines(c.location));
                         if let Some(last_chunk) = self.chunks.last_mut() {
                             last_chunk.frame.max.y -= height - available_height;
-                            let mut doc = self.doc.lock().unwrap();
-                            if let Some((lines, _)) = doc.lines(Location::Exact(last_chunk.location)) {
+                            if let Some(lines) = lines {
                                 let pixmap_frame = self.cache[&last_chunk.location].frame;
                                 if let Some(mut y_pos) = find_cut(&pixmap_frame, last_chunk.frame.max.y, last_chunk.scale, LinearDir::Backward, &lines) {
                                     y_pos = y_pos.clamp(pixmap_frame.min.y, pixmap_frame.max.y - 1);
@@ -1115,6 +1277,22 @@ impl Reader {
                     self.chunks.push(RenderChunk { frame, location, position, scale });
                 },
             },
+            ZoomMode::Panel => {
+                self.load_panels(location);
+                let panels_count = self.panels[&location].len();
+                self.view_port.panel = self.view_port.panel.min(panels_count - 1);
+                self.load_pixmap(location);
+                self.load_text(location);
+                let Resource { ref pixmap, frame, scale } = self.cache[&location];
+                let panel = self.panels[&location][self.view_port.panel];
+                let size = vec2!(pixmap.width as f32, pixmap.height as f32);
+                let frame = bndr!(panel.min * size, panel.max * size).to_rect()
+                                                                      .intersection(&frame)
+                                                                      .unwrap_or(frame);
+                let dx = smw + (self.rect.width() as i32 - frame.width() as i32 - 2 * smw) / 2;
+                let dy = smw + (self.rect.height() as i32 - frame.height() as i32 - 2 * smw) / 2;
+                self.chunks.push(RenderChunk { frame, location, position: pt!(dx, dy), scale });
+            },
             ZoomMode::Custom(_) => {
                 self.load_pixmap(location);
                 self.load_text(location);
@@ -1130,10 +1308,11 @@ impl Reader {
         }
 
         rq.add(RenderData::new(self.id, self.rect, update_mode));
-        let first_location = self.chunks.first().map(|c| c.location).unwrap();
-        let last_location = self.chunks.last().map(|c| c.location).unwrap();
+        let first_location = self.chunks.iter().map(|c| c.location).min().unwrap();
+        let last_location = self.chunks.iter().map(|c| c.location).max().unwrap();
+        let cache_size = if self.spread_active() { 4 } else { 3 };
 
-        while self.cache.len() > 3 {
+        while self.cache.len() > cache_size {
             let left_count = self.cache.range(..first_location).count();
             let right_count = self.cache.range(last_location+1..).count();
             let extremum = if left_count >= right_count {
@@ -1614,6 +1793,69 @@ impl Reader {
         }
     }
 
+    fn toggle_page_grid(&mut self, enable: bool, hub: &Hub, rq: &mut RenderQueue, context: &mut Context) {
+        if let Some(index) = locate::<PageGrid>(self) {
+            if enable {
+                return;
+            }
+
+            rq.add(RenderData::expose(*self.child(index).rect(), UpdateMode::Gui));
+            self.children.remove(index);
+        } else {
+            if !enable {
+                return;
+            }
+
+            self.toggle_bars(Some(false), hub, rq, context);
+
+            let thumbnails_dir = if self.ephemeral {
+                None
+            } else {
+                Some(context.library.page_thumbnails(&self.info.file.path))
+            };
+            let bookmarks = self.info.reader.as_ref()
+                                .map(|r| r.bookmarks.clone())
+                                .unwrap_or_default();
+            let annotated = self.info.reader.as_ref().map(|r| {
+                r.annotations.iter()
+                 .flat_map(|annot| annot.selection[0].location()..=annot.selection[1].location())
+                 .collect()
+            }).unwrap_or_default();
+
+            let page_grid = PageGrid::new(self.rect, Arc::clone(&self.doc), thumbnails_dir,
+                                          self.current_page, bookmarks, annotated);
+            rq.add(RenderData::new(page_grid.id(), *page_grid.rect(), UpdateMode::Full));
+            self.children.push(Box::new(page_grid) as Box<dyn View>);
+        }
+    }
+
+    fn toggle_image_viewer(&mut self, image: Option<(usize, Boundary)>, hub: &Hub, rq: &mut RenderQueue, context: &mut Context) {
+        if let Some(index) = locate::<ImageViewer>(self) {
+            if image.is_some() {
+                return;
+            }
+
+            rq.add(RenderData::expose(*self.child(index).rect(), UpdateMode::Full));
+            self.children.remove(index);
+        } else {
+            let (location, image) = if let Some(image) = image {
+                image
+            } else {
+                return;
+            };
+
+            self.toggle_bars(Some(false), hub, rq, context);
+
+            let dithered_kinds = &context.settings.reader.dithered_kinds;
+            let dithered = dithered_kinds.contains(&self.info.file.kind) ||
+                           IMAGE_KINDS.iter().any(|kind| dithered_kinds.contains(*kind));
+            let image_viewer = ImageViewer::new(self.rect, Arc::clone(&self.doc), location,
+                                                image, dithered, context);
+            rq.add(RenderData::new(image_viewer.id(), *image_viewer.rect(), UpdateMode::Full));
+            self.children.push(Box::new(image_viewer) as Box<dyn View>);
+        }
+    }
+
     fn toggle_margin_cropper(&mut self, enable: bool, hub: &Hub, rq: &mut RenderQueue, context: &mut Context) {
         if let Some(index) = locate::<MarginCropper>(self) {
             if enable {
@@ -1822,6 +2064,9 @@ impl Reader {
                      EntryKind::RadioButton("Fit to Page".to_string(),
                                             EntryId::SetZoomMode(ZoomMode::FitToPage),
                                             zoom_mode == ZoomMode::FitToPage),
+                     EntryKind::RadioButton("Fit to Width".to_string(),
+                                            EntryId::SetZoomMode(ZoomMode::FitToWidth),
+                                            zoom_mode == ZoomMode::FitToWidth),
                      EntryKind::RadioButton(format!("Custom ({:.1}%)", 100.0 * sf),
                                             EntryId::SetZoomMode(ZoomMode::Custom(sf)),
                                             zoom_mode == ZoomMode::Custom(sf))])]
@@ -1833,6 +2078,9 @@ impl Reader {
                      EntryKind::RadioButton("Fit to Width".to_string(),
                                             EntryId::SetZoomMode(ZoomMode::FitToWidth),
                                             zoom_mode == ZoomMode::FitToWidth),
+                     EntryKind::RadioButton("Panel by Panel".to_string(),
+                                            EntryId::SetZoomMode(ZoomMode::Panel),
+                                            zoom_mode == ZoomMode::Panel),
                      EntryKind::RadioButton(format!("Custom ({:.1}%)", 100.0 * sf),
                                             EntryId::SetZoomMode(ZoomMode::Custom(sf)),
                                             zoom_mode == ZoomMode::Custom(sf))])]
@@ -1858,6 +2106,10 @@ impl Reader {
                 entries.push(EntryKind::Command("Bookmarks".to_string(), EntryId::Bookmarks));
             }
 
+            if !self.reflowable {
+                entries.push(EntryKind::Command("Page Grid".to_string(), EntryId::PageGrid));
+            }
+
             if !entries.is_empty() {
                 entries.push(EntryKind::Separator);
             }
@@ -1866,6 +2118,15 @@ impl Reader {
                                              EntryId::ToggleDithered,
                                              context.fb.dithered()));
 
+            if !self.reflowable {
+                entries.push(EntryKind::CheckBox("Right to Left".to_string(),
+                                                 EntryId::ToggleRightToLeft,
+                                                 self.info.reader.as_ref().is_some_and(|r| r.right_to_left)));
+                entries.push(EntryKind::CheckBox("Two Pages".to_string(),
+                                                 EntryId::ToggleTwoPages,
+                                                 self.two_pages));
+            }
+
             let mut title_menu = Menu::new(rect, ViewId::TitleMenu, MenuKind::DropDown, entries, context);
             title_menu.child_mut(1)
                       .downcast_mut::<MenuEntry>().unwrap()
@@ -2200,6 +2461,7 @@ impl Reader {
             }
         }
 
+        self.view_port.page_offset = pt!(0);
         self.cache.clear();
         self.text.clear();
         self.update(None, hub, rq, context);
@@ -2231,6 +2493,7 @@ impl Reader {
             }
         }
 
+        self.view_port.page_offset = pt!(0);
         self.cache.clear();
         self.text.clear();
         self.update(None, hub, rq, context);
@@ -2268,6 +2531,7 @@ impl Reader {
             }
         }
 
+        self.view_port.page_offset = pt!(0);
         self.cache.clear();
         self.text.clear();
         self.update(None, hub, rq, context);
@@ -2299,6 +2563,7 @@ impl Reader {
             }
         }
 
+        self.view_port.page_offset = pt!(0);
         self.cache.clear();
         self.text.clear();
         self.update(None, hub, rq, context);
@@ -2324,6 +2589,8 @@ impl Reader {
         }
 
         if self.reflowable {
+            self.view_port.text_margin = mm_to_px(width as f32, CURRENT_DEVICE.dpi).round() as i32;
+            self.view_port.page_offset = pt!(0);
             let mut doc = self.doc.lock().unwrap();
             doc.set_margin_width(width);
 
@@ -2405,6 +2672,7 @@ impl Reader {
         self.view_port.zoom_mode = zoom_mode;
         if reset_page_offset {
             self.view_port.page_offset = pt!(0, 0);
+            self.view_port.panel = 0;
         }
         self.cache.clear();
         self.update(None, hub, rq, context);
@@ -2618,7 +2886,25 @@ impl Reader {
         rq.add(RenderData::new(self.id, self.rect, UpdateMode::Gui));
     }
 
-    fn quit(&mut self, context: &mut Context) {
+    // Lets the hooks know about the reading activity.
+    fn report(&self, activity: Activity, hub: &Hub) {
+        if !self.ephemeral {
+            hub.send(Event::Activity(activity, Box::new(self.current_info()))).ok();
+        }
+    }
+
+    // Returns the document's metadata, with the reading state as it is now.
+    pub fn current_info(&self) -> Info {
+        let mut info = self.info.clone();
+        if let Some(ref mut r) = info.reader {
+            r.current_page = self.current_page;
+            r.pages_count = self.pages_count;
+            r.finished = self.finished;
+        }
+        info
+    }
+
+    pub fn quit(&mut self, context: &mut Context) {
         if let Some(ref mut s) = self.search {
             s.running.store(false, AtomicOrdering::Relaxed);
         }
@@ -2703,7 +2989,7 @@ impl View for Reader {
             },
             Event::Gesture(GestureEvent::Swipe { dir, start, end }) if self.rect.includes(start) => {
                 match self.view_port.zoom_mode {
-                    ZoomMode::FitToPage | ZoomMode::FitToWidth => {
+                    ZoomMode::FitToPage | ZoomMode::FitToWidth | ZoomMode::Panel => {
                         match dir {
                             Dir::West => self.go_to_neighbor(CycleDir::Next, hub, rq, context),
                             Dir::East => self.go_to_neighbor(CycleDir::Previous, hub, rq, context),
@@ -2726,27 +3012,19 @@ impl View for Reader {
                 true
             },
             Event::Gesture(GestureEvent::Spread { axis: Axis::Horizontal, center, .. }) if self.rect.includes(center) => {
-                if !self.reflowable {
-                    self.set_zoom_mode(ZoomMode::FitToWidth, true, hub, rq, context);
-                }
+                self.set_zoom_mode(ZoomMode::FitToWidth, true, hub, rq, context);
                 true
-
             },
             Event::Gesture(GestureEvent::Pinch { axis: Axis::Horizontal, center, .. }) if self.rect.includes(center) => {
                 self.set_zoom_mode(ZoomMode::FitToPage, true, hub, rq, context);
                 true
             },
             Event::Gesture(GestureEvent::Spread { axis: Axis::Vertical, center, .. }) if self.rect.includes(center) => {
-                if !self.reflowable {
-                    self.set_scroll_mode(ScrollMode::Screen, hub, rq, context);
-                }
+                self.set_scroll_mode(ScrollMode::Screen, hub, rq, context);
                 true
-
             },
             Event::Gesture(GestureEvent::Pinch { axis: Axis::Vertical, center, .. }) if self.rect.includes(center) => {
-                if !self.reflowable {
-                    self.set_scroll_mode(ScrollMode::Page, hub, rq, context);
-                }
+                self.set_scroll_mode(ScrollMode::Page, hub, rq, context);
                 true
             },
             Event::Gesture(GestureEvent::Spread { axis: Axis::Diagonal, center, factor }) |
@@ -3312,6 +3590,19 @@ impl View for Reader {
                         self.state = State::Selection(id);
                         rq.add(RenderData::new(self.id, rect, UpdateMode::Fast));
                     }
+                } else {
+                    // Open the image under the finger, if any.
+                    let image = self.chunks.iter().find_map(|chunk| {
+                        let (images, _) = self.doc.lock().ok()
+                                              .and_then(|mut doc| doc.images(Location::Exact(chunk.location)))?;
+                        images.into_iter().find(|image| {
+                            let rect = (*image * chunk.scale).to_rect() - chunk.frame.min + chunk.position;
+                            rect.includes(center)
+                        }).map(|image| (chunk.location, image))
+                    });
+                    if image.is_some() {
+                        self.toggle_image_viewer(image, hub, rq, context);
+                    }
                 }
 
                 true
@@ -3392,14 +3683,16 @@ impl View for Reader {
 
                 if let Some(sel) = selection {
                     let text = self.text_excerpt(sel).unwrap();
+                    let annot = Annotation {
+                        selection: sel,
+                        note: note.to_string(),
+                        text,
+                        modified: Local::now().naive_local(),
+                    };
                     if let Some(r) = self.info.reader.as_mut() {
-                        r.annotations.push(Annotation {
-                            selection: sel,
-                            note: note.to_string(),
-                            text,
-                            modified: Local::now().naive_local(),
-                        });
+                        r.annotations.push(annot.clone());
                     }
+                    self.report(Activity::Annotated(Box::new(annot)), hub);
                     if let Some(rect) = self.text_rect(sel) {
                         rq.add(RenderData::new(self.id, rect, UpdateMode::Gui));
                     }
@@ -3408,6 +3701,8 @@ impl View for Reader {
                         if let Some(annot) = self.find_annotation_mut(sel) {
                             annot.note = note.to_string();
                             annot.modified = Local::now().naive_local();
+                            let annot = annot.clone();
+                            self.report(Activity::Annotated(Box::new(annot)), hub);
                         }
                         if let Some(rect) = self.text_rect(sel) {
                             rq.add(RenderData::new(self.id, rect, UpdateMode::Gui));
@@ -3651,6 +3946,18 @@ impl View for Reader {
                 self.toggle_margin_cropper(false, hub, rq, context);
                 true
             },
+            Event::Select(EntryId::PageGrid) => {
+                self.toggle_page_grid(true, hub, rq, context);
+                true
+            },
+            Event::Close(ViewId::PageGrid) => {
+                self.toggle_page_grid(false, hub, rq, context);
+                true
+            },
+            Event::Close(ViewId::ImageViewer) => {
+                self.toggle_image_viewer(None, hub, rq, context);
+                true
+            },
             Event::SearchResult(location, ref rects) => {
                 if self.search.is_none() {
                     return true;
@@ -3699,14 +4006,16 @@ impl View for Reader {
             Event::Select(EntryId::HighlightSelection) => {
                 if let Some(sel) = self.selection.take() {
                     let text = self.text_excerpt([sel.start, sel.end]).unwrap();
+                    let annot = Annotation {
+                        selection: [sel.start, sel.end],
+                        note: String::new(),
+                        text,
+                        modified: Local::now().naive_local(),
+                    };
                     if let Some(r) = self.info.reader.as_mut() {
-                        r.annotations.push(Annotation {
-                            selection: [sel.start, sel.end],
-                            note: String::new(),
-                            text,
-                            modified: Local::now().naive_local(),
-                        });
+                        r.annotations.push(annot.clone());
                     }
+                    self.report(Activity::Annotated(Box::new(annot)), hub);
                     if let Some(rect) = self.text_rect([sel.start, sel.end]) {
                         rq.add(RenderData::new(self.id, rect, UpdateMode::Gui));
                     }
@@ -3799,6 +4108,30 @@ impl View for Reader {
                 self.set_scroll_mode(scroll_mode, hub, rq, context);
                 true
             },
+            Event::Select(EntryId::ToggleRightToLeft) => {
+                if let Some(r) = self.info.reader.as_mut() {
+                    r.right_to_left = !r.right_to_left;
+                }
+                self.panels.clear();
+                if self.view_port.zoom_mode == ZoomMode::Panel {
+                    self.view_port.panel = 0;
+                    self.update(None, hub, rq, context);
+                } else if self.spread_active() {
+                    self.update(None, hub, rq, context);
+                }
+                true
+            },
+            Event::Select(EntryId::ToggleTwoPages) => {
+                self.two_pages = !self.two_pages;
+                if let Some(r) = self.info.reader.as_mut() {
+                    r.two_pages = Some(self.two_pages);
+                }
+                if self.view_port.zoom_mode == ZoomMode::FitToPage {
+                    self.cache.clear();
+                    self.update(None, hub, rq, context);
+                }
+                true
+            },
             Event::Select(EntryId::Save) => {
                 let name = format!("{}-{}.{}", self.info.title.to_lowercase().replace(' ', "_"),
                                    Local::now().format("%Y%m%d_%H%M%S"),
@@ -4154,6 +4487,10 @@ impl View for Reader {
         }
 
         match self.view_port.zoom_mode {
+            // The pages of reflowable documents are about to be laid out again.
+            ZoomMode::FitToWidth if self.reflowable => {
+                self.view_port.page_offset = pt!(0);
+            },
             ZoomMode::FitToWidth => {
                 // Apply the scale change.
                 let ratio = (rect.width() as i32 - 2 * self.view_port.margin_width) as f32 /
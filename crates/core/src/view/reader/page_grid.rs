@@ -0,0 +1,314 @@
+use std::fs;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::collections::BTreeSet;
+use crate::device::CURRENT_DEVICE;
+use crate::document::{Document, Location};
+use crate::framebuffer::{Framebuffer, UpdateMode, Pixmap};
+use crate::font::{Fonts, font_from_style, NORMAL_STYLE};
+use crate::gesture::GestureEvent;
+use crate::geom::{Point, Rectangle, Dir, CycleDir, BorderSpec};
+use crate::view::{View, Event, Hub, Bus, Id, ID_FEEDER, RenderQueue, RenderData, ViewId, Align};
+use crate::view::{SMALL_BAR_HEIGHT, THICKNESS_SMALL, THICKNESS_LARGE};
+use crate::view::rounded_button::RoundedButton;
+use crate::view::label::Label;
+use crate::unit::{scale_by_dpi, mm_to_px};
+use crate::color::{BLACK, WHITE, GRAY08, TEXT_NORMAL};
+use crate::context::Context;
+
+const CELL_WIDTH: f32 = 280.0;
+const CELL_PADDING: f32 = 12.0;
+
+pub struct PageGrid {
+    id: Id,
+    rect: Rectangle,
+    children: Vec<Box<dyn View>>,
+    doc: Arc<Mutex<Box<dyn Document>>>,
+    thumbnails_dir: Option<PathBuf>,
+    thumbnails: Vec<(usize, Pixmap)>,   // Thumbnails of the current grid page.
+    bookmarks: BTreeSet<usize>,
+    annotated: BTreeSet<usize>,
+    current_page: usize,
+    pages_count: usize,
+    grid_page: usize,
+    columns: usize,
+    rows: usize,
+}
+
+impl PageGrid {
+    pub fn new(rect: Rectangle, doc: Arc<Mutex<Box<dyn Document>>>, thumbnails_dir: Option<PathBuf>,
+               current_page: usize, bookmarks: BTreeSet<usize>, annotated: BTreeSet<usize>) -> PageGrid {
+        let id = ID_FEEDER.next();
+        let pages_count = doc.lock().unwrap().pages_count();
+        let (columns, rows) = grid_dims(&rect);
+
+        let mut page_grid = PageGrid {
+            id,
+            rect,
+            children: Vec::new(),
+            doc,
+            thumbnails_dir,
+            thumbnails: Vec::new(),
+            bookmarks,
+            annotated,
+            current_page,
+            pages_count,
+            grid_page: current_page / (columns * rows),
+            columns,
+            rows,
+        };
+
+        page_grid.layout_children();
+        page_grid.load_thumbnails();
+        page_grid
+    }
+
+    fn layout_children(&mut self) {
+        let dpi = CURRENT_DEVICE.dpi;
+        let small_height = scale_by_dpi(SMALL_BAR_HEIGHT, dpi) as i32;
+        let button_diameter = small_height / 2;
+        let padding = (small_height - button_diameter) / 2;
+        let bar_rect = self.bar_rect();
+
+        self.children.clear();
+
+        let close_button = RoundedButton::new("close",
+                                              rect![bar_rect.min.x + padding,
+                                                    bar_rect.min.y + padding,
+                                                    bar_rect.min.x + padding + button_diameter,
+                                                    bar_rect.min.y + padding + button_diameter],
+                                              Event::Close(ViewId::PageGrid));
+        self.children.push(Box::new(close_button) as Box<dyn View>);
+
+        let label = Label::new(rect![bar_rect.min.x + small_height, bar_rect.min.y,
+                                     bar_rect.max.x - small_height, bar_rect.max.y],
+                               self.grid_page_label(),
+                               Align::Center);
+        self.children.push(Box::new(label) as Box<dyn View>);
+    }
+
+    fn per_page(&self) -> usize {
+        self.columns * self.rows
+    }
+
+    fn grid_pages_count(&self) -> usize {
+        self.pages_count.div_ceil(self.per_page()).max(1)
+    }
+
+    fn grid_page_label(&self) -> String {
+        format!("{} of {}", self.grid_page + 1, self.grid_pages_count())
+    }
+
+    fn bar_rect(&self) -> Rectangle {
+        let small_height = scale_by_dpi(SMALL_BAR_HEIGHT, CURRENT_DEVICE.dpi) as i32;
+        rect![self.rect.min.x, self.rect.max.y - small_height,
+              self.rect.max.x, self.rect.max.y]
+    }
+
+    fn cell_rect(&self, index: usize) -> Rectangle {
+        let grid_rect = rect![self.rect.min, pt!(self.rect.max.x, self.bar_rect().min.y)];
+        let cell_width = grid_rect.width() as i32 / self.columns as i32;
+        let cell_height = grid_rect.height() as i32 / self.rows as i32;
+        let x = grid_rect.min.x + (index % self.columns) as i32 * cell_width;
+        let y = grid_rect.min.y + (index / self.columns) as i32 * cell_height;
+        rect![x, y, x + cell_width, y + cell_height]
+    }
+
+    // The area of a cell available to the thumbnail and its label.
+    fn thumbnail_rects(&self, index: usize) -> (Rectangle, Rectangle) {
+        let dpi = CURRENT_DEVICE.dpi;
+        let padding = scale_by_dpi(CELL_PADDING, dpi) as i32;
+        let label_height = scale_by_dpi(SMALL_BAR_HEIGHT, dpi) as i32 / 3;
+        let cell_rect = self.cell_rect(index);
+        let thumbnail_rect = rect![cell_rect.min.x + padding, cell_rect.min.y + padding,
+                                   cell_rect.max.x - padding, cell_rect.max.y - label_height];
+        let label_rect = rect![cell_rect.min.x, cell_rect.max.y - label_height,
+                               cell_rect.max.x, cell_rect.max.y];
+        (thumbnail_rect, label_rect)
+    }
+
+    fn load_thumbnails(&mut self) {
+        self.thumbnails.clear();
+
+        if let Some(dir) = self.thumbnails_dir.as_ref() {
+            fs::create_dir_all(dir)
+              .map_err(|e| eprintln!("Can't create {}: {:#}.", dir.display(), e))
+              .ok();
+        }
+
+        let first_location = self.grid_page * self.per_page();
+        let last_location = (first_location + self.per_page()).min(self.pages_count);
+        let (thumbnail_rect, _) = self.thumbnail_rects(0);
+        let (max_width, max_height) = (thumbnail_rect.width(), thumbnail_rect.height());
+
+        for location in first_location..last_location {
+            let path = self.thumbnails_dir.as_ref()
+                           .map(|dir| dir.join(format!("{}.png", location)));
+
+            if let Some(pixmap) = path.as_ref().filter(|path| path.exists())
+                                      .and_then(|path| Pixmap::from_png(path).ok())
+                                      .filter(|pixmap| pixmap.width <= max_width &&
+                                                       pixmap.height <= max_height) {
+                self.thumbnails.push((location, pixmap));
+                continue;
+            }
+
+            let mut doc = self.doc.lock().unwrap();
+            let pixmap = doc.dims(location).and_then(|(width, height)| {
+                let scale = (max_width as f32 / width).min(max_height as f32 / height);
+                doc.pixmap(Location::Exact(location), scale, CURRENT_DEVICE.color_samples())
+            }).map(|(pixmap, _)| pixmap);
+
+            if let Some(pixmap) = pixmap {
+                if let Some(path) = path.as_ref().and_then(|path| path.to_str()) {
+                    pixmap.save(path)
+                          .map_err(|e| eprintln!("Can't save thumbnail {}: {:#}.", path, e))
+                          .ok();
+                }
+                self.thumbnails.push((location, pixmap));
+            }
+        }
+    }
+
+    fn set_grid_page(&mut self, dir: CycleDir, rq: &mut RenderQueue) {
+        let grid_page = match dir {
+            CycleDir::Next if self.grid_page + 1 < self.grid_pages_count() => self.grid_page + 1,
+            CycleDir::Previous if self.grid_page > 0 => self.grid_page - 1,
+            _ => return,
+        };
+
+        self.grid_page = grid_page;
+        self.load_thumbnails();
+        let text = self.grid_page_label();
+        if let Some(label) = self.children[1].downcast_mut::<Label>() {
+            label.update(&text, rq);
+        }
+        rq.add(RenderData::new(self.id, self.rect, UpdateMode::Partial));
+    }
+
+    fn location_at(&self, center: Point) -> Option<usize> {
+        let first_location = self.grid_page * self.per_page();
+        (0..self.per_page()).find(|&index| self.cell_rect(index).includes(center))
+                            .map(|index| first_location + index)
+                            .filter(|&location| location < self.pages_count)
+    }
+}
+
+fn grid_dims(rect: &Rectangle) -> (usize, usize) {
+    let dpi = CURRENT_DEVICE.dpi;
+    let cell_width = scale_by_dpi(CELL_WIDTH, dpi) as u32;
+    let cell_height = 4 * cell_width / 3 + scale_by_dpi(SMALL_BAR_HEIGHT, dpi) as u32 / 3;
+    let grid_height = rect.height().saturating_sub(scale_by_dpi(SMALL_BAR_HEIGHT, dpi) as u32);
+    let columns = (rect.width() / cell_width).max(2) as usize;
+    let rows = (grid_height / cell_height).max(2) as usize;
+    (columns, rows)
+}
+
+impl View for PageGrid {
+    fn handle_event(&mut self, evt: &Event, _hub: &Hub, bus: &mut Bus, rq: &mut RenderQueue, _context: &mut Context) -> bool {
+        match *evt {
+            Event::Gesture(GestureEvent::Tap(center)) if self.rect.includes(center) => {
+                if let Some(location) = self.location_at(center) {
+                    bus.push_back(Event::GoTo(location));
+                    bus.push_back(Event::Close(ViewId::PageGrid));
+                }
+                true
+            },
+            Event::Gesture(GestureEvent::Swipe { dir, start, .. }) if self.rect.includes(start) => {
+                match dir {
+                    Dir::West | Dir::North => self.set_grid_page(CycleDir::Next, rq),
+                    Dir::East | Dir::South => self.set_grid_page(CycleDir::Previous, rq),
+                }
+                true
+            },
+            Event::Gesture(GestureEvent::HoldFingerShort(center, ..)) |
+            Event::Gesture(GestureEvent::HoldFingerLong(center, ..)) if self.rect.includes(center) => true,
+            _ => false,
+        }
+    }
+
+    fn render(&self, fb: &mut dyn Framebuffer, rect: Rectangle, fonts: &mut Fonts) {
+        let dpi = CURRENT_DEVICE.dpi;
+        let thickness = scale_by_dpi(THICKNESS_SMALL, dpi) as u16;
+        let large_thickness = scale_by_dpi(THICKNESS_LARGE, dpi) as u16;
+        let radius = mm_to_px(0.6, dpi) as i32 + large_thickness as i32;
+
+        if let Some(r) = self.rect.intersection(&rect) {
+            fb.draw_rectangle(&r, WHITE);
+        }
+
+        let first_location = self.grid_page * self.per_page();
+        let font = font_from_style(fonts, &NORMAL_STYLE, dpi);
+        let x_height = font.x_heights.0 as i32;
+
+        for (location, pixmap) in &self.thumbnails {
+            let index = location - first_location;
+            let (thumbnail_rect, label_rect) = self.thumbnail_rects(index);
+
+            if !self.cell_rect(index).overlaps(&rect) {
+                continue;
+            }
+
+            let x0 = thumbnail_rect.min.x + (thumbnail_rect.width() as i32 - pixmap.width as i32) / 2;
+            let y0 = thumbnail_rect.max.y - pixmap.height as i32;
+            let pixmap_rect = rect![x0, y0, x0 + pixmap.width as i32, y0 + pixmap.height as i32];
+            fb.draw_pixmap(pixmap, pixmap_rect.min);
+
+            let border_thickness = if *location == self.current_page { large_thickness } else { thickness };
+            let border_rect = rect![pixmap_rect.min - pt!(border_thickness as i32),
+                                    pixmap_rect.max + pt!(border_thickness as i32)];
+            fb.draw_rectangle_outline(&border_rect,
+                                      &BorderSpec { thickness: border_thickness, color: BLACK });
+
+            if self.bookmarks.contains(location) {
+                fb.draw_disk(pt!(pixmap_rect.max.x - 2 * radius, pixmap_rect.min.y + 2 * radius), radius, BLACK);
+            }
+
+            if self.annotated.contains(location) {
+                fb.draw_disk(pt!(pixmap_rect.min.x + 2 * radius, pixmap_rect.min.y + 2 * radius), radius, GRAY08);
+            }
+
+            let text = (location + 1).to_string();
+            let plan = font.plan(&text, Some(label_rect.width() as i32), None);
+            let dx = (label_rect.width() as i32 - plan.width) / 2;
+            let dy = (label_rect.height() as i32 - x_height) / 2;
+            font.render(fb, TEXT_NORMAL[1], &plan, pt!(label_rect.min.x + dx, label_rect.max.y - dy));
+        }
+    }
+
+    fn render_rect(&self, rect: &Rectangle) -> Rectangle {
+        rect.intersection(&self.rect)
+            .unwrap_or(self.rect)
+    }
+
+    fn resize(&mut self, rect: Rectangle, _hub: &Hub, _rq: &mut RenderQueue, _context: &mut Context) {
+        let first_location = self.grid_page * self.per_page();
+        self.rect = rect;
+        let (columns, rows) = grid_dims(&rect);
+        self.columns = columns;
+        self.rows = rows;
+        self.grid_page = first_location / self.per_page();
+        self.layout_children();
+        self.load_thumbnails();
+    }
+
+    fn rect(&self) -> &Rectangle {
+        &self.rect
+    }
+
+    fn rect_mut(&mut self) -> &mut Rectangle {
+        &mut self.rect
+    }
+
+    fn children(&self) -> &Vec<Box<dyn View>> {
+        &self.children
+    }
+
+    fn children_mut(&mut self) -> &mut Vec<Box<dyn View>> {
+        &mut self.children
+    }
+
+    fn id(&self) -> Id {
+        self.id
+    }
+}
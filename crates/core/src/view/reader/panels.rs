@@ -0,0 +1,214 @@
+use crate::framebuffer::Pixmap;
+use crate::geom::Rectangle;
+
+// Maximum gray level difference between a gutter pixel and the background.
+const GUTTER_TOLERANCE: i16 = 48;
+// Minimum thickness of a gutter, relative to the corresponding page dimension.
+const MIN_GUTTER_RATIO: f32 = 0.006;
+// Panels smaller than this fraction of the page's area are considered noise.
+const MIN_PANEL_AREA_RATIO: f32 = 0.01;
+const MAX_DEPTH: usize = 8;
+
+struct Mask {
+    width: i32,
+    blank: Vec<bool>,
+}
+
+impl Mask {
+    fn new(pixmap: &Pixmap) -> Mask {
+        let background = background_level(pixmap) as i16;
+        let mut blank = Vec::with_capacity((pixmap.width * pixmap.height) as usize);
+        for y in 0..pixmap.height {
+            for x in 0..pixmap.width {
+                let level = pixmap.get_pixel(x, y).gray() as i16;
+                blank.push((level - background).abs() <= GUTTER_TOLERANCE);
+            }
+        }
+        Mask { width: pixmap.width as i32, blank }
+    }
+
+    #[inline]
+    fn is_blank(&self, x: i32, y: i32) -> bool {
+        self.blank[(y * self.width + x) as usize]
+    }
+
+    // A line is blank if it only contains a few specks.
+    fn is_blank_line(&self, rect: &Rectangle, index: i32, horizontal: bool) -> bool {
+        let (start, end) = if horizontal {
+            (rect.min.x, rect.max.x)
+        } else {
+            (rect.min.y, rect.max.y)
+        };
+        let max_specks = ((end - start) / 200).max(1);
+        let mut specks = 0;
+        for i in start..end {
+            let blank = if horizontal {
+                self.is_blank(i, index)
+            } else {
+                self.is_blank(index, i)
+            };
+            if !blank {
+                specks += 1;
+                if specks > max_specks {
+                    return false;
+                }
+            }
+        }
+        true
+    }
+
+    fn trim(&self, rect: &Rectangle) -> Option<Rectangle> {
+        let mut rect = *rect;
+        while rect.min.y < rect.max.y && self.is_blank_line(&rect, rect.min.y, true) {
+            rect.min.y += 1;
+        }
+        while rect.max.y > rect.min.y && self.is_blank_line(&rect, rect.max.y - 1, true) {
+            rect.max.y -= 1;
+        }
+        while rect.min.x < rect.max.x && self.is_blank_line(&rect, rect.min.x, false) {
+            rect.min.x += 1;
+        }
+        while rect.max.x > rect.min.x && self.is_blank_line(&rect, rect.max.x - 1, false) {
+            rect.max.x -= 1;
+        }
+        if rect.is_empty() {
+            None
+        } else {
+            Some(rect)
+        }
+    }
+
+    // Splits the given rectangle along the gutters that span it entirely.
+    fn split(&self, rect: &Rectangle, horizontal: bool, min_gap: i32) -> Vec<Rectangle> {
+        let (start, end) = if horizontal {
+            (rect.min.y, rect.max.y)
+        } else {
+            (rect.min.x, rect.max.x)
+        };
+        let mut pieces = Vec::new();
+        let mut piece_start = start;
+        let mut gap_start = None;
+
+        for i in start..end {
+            if self.is_blank_line(rect, i, horizontal) {
+                gap_start.get_or_insert(i);
+            } else if let Some(gs) = gap_start.take() {
+                if i - gs >= min_gap && gs > piece_start {
+                    pieces.push((piece_start, gs));
+                    piece_start = i;
+                }
+            }
+        }
+
+        pieces.push((piece_start, end));
+
+        pieces.into_iter().map(|(a, b)| {
+            if horizontal {
+                rect![rect.min.x, a, rect.max.x, b]
+            } else {
+                rect![a, rect.min.y, b, rect.max.y]
+            }
+        }).collect()
+    }
+
+    fn xy_cut(&self, rect: &Rectangle, min_gaps: (i32, i32), right_to_left: bool, depth: usize, panels: &mut Vec<Rectangle>) {
+        let rect = if let Some(rect) = self.trim(rect) {
+            rect
+        } else {
+            return;
+        };
+
+        if depth < MAX_DEPTH {
+            let rows = self.split(&rect, true, min_gaps.1);
+            if rows.len() > 1 {
+                for row in &rows {
+                    self.xy_cut(row, min_gaps, right_to_left, depth + 1, panels);
+                }
+                return;
+            }
+
+            let mut columns = self.split(&rect, false, min_gaps.0);
+            if columns.len() > 1 {
+                if right_to_left {
+                    columns.reverse();
+                }
+                for column in &columns {
+                    self.xy_cut(column, min_gaps, right_to_left, depth + 1, panels);
+                }
+                return;
+            }
+        }
+
+        panels.push(rect);
+    }
+}
+
+fn background_level(pixmap: &Pixmap) -> u8 {
+    let (width, height) = (pixmap.width, pixmap.height);
+    let mut sum = 0u64;
+    let mut count = 0u64;
+    for x in 0..width {
+        sum += pixmap.get_pixel(x, 0).gray() as u64;
+        sum += pixmap.get_pixel(x, height - 1).gray() as u64;
+        count += 2;
+    }
+    for y in 0..height {
+        sum += pixmap.get_pixel(0, y).gray() as u64;
+        sum += pixmap.get_pixel(width - 1, y).gray() as u64;
+        count += 2;
+    }
+    (sum / count) as u8
+}
+
+// Returns the panels of the given page, in reading order.
+// The panels are found by recursively splitting the page along its gutters.
+pub fn detect_panels(pixmap: &Pixmap, right_to_left: bool) -> Vec<Rectangle> {
+    if pixmap.data.is_empty() || pixmap.width == 0 || pixmap.height == 0 {
+        return Vec::new();
+    }
+
+    let mask = Mask::new(pixmap);
+    let rect = rect![0, 0, pixmap.width as i32, pixmap.height as i32];
+    let min_gaps = (((pixmap.width as f32 * MIN_GUTTER_RATIO).round() as i32).max(2),
+                    ((pixmap.height as f32 * MIN_GUTTER_RATIO).round() as i32).max(2));
+    let mut panels = Vec::new();
+
+    mask.xy_cut(&rect, min_gaps, right_to_left, 0, &mut panels);
+
+    let min_area = MIN_PANEL_AREA_RATIO * rect.area() as f32;
+    panels.retain(|panel| panel.area() as f32 >= min_area);
+
+    panels
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color::BLACK;
+    use crate::framebuffer::Framebuffer;
+
+    fn page() -> Pixmap {
+        let mut pixmap = Pixmap::new(200, 300, 1);
+        // A full width panel on top and two panels below it.
+        pixmap.draw_rectangle(&rect![10, 10, 190, 140], BLACK);
+        pixmap.draw_rectangle(&rect![10, 160, 90, 290], BLACK);
+        pixmap.draw_rectangle(&rect![110, 160, 190, 290], BLACK);
+        pixmap
+    }
+
+    #[test]
+    fn test_panels_left_to_right() {
+        let panels = detect_panels(&page(), false);
+        assert_eq!(panels, vec![rect![10, 10, 190, 140],
+                                rect![10, 160, 90, 290],
+                                rect![110, 160, 190, 290]]);
+    }
+
+    #[test]
+    fn test_panels_right_to_left() {
+        let panels = detect_panels(&page(), true);
+        assert_eq!(panels, vec![rect![10, 10, 190, 140],
+                                rect![110, 160, 190, 290],
+                                rect![10, 160, 90, 290]]);
+    }
+}
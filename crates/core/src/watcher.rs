@@ -0,0 +1,199 @@
+// Watches the directory tree of a library with inotify, and tells when
+// its files have changed and settled.
+
+use std::os::unix::io::AsFd;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+use fxhash::FxHashMap;
+use walkdir::WalkDir;
+use nix::errno::Errno;
+use nix::poll::{poll, PollFd, PollFlags, PollTimeout};
+use nix::sys::inotify::{AddWatchFlags, InitFlags, Inotify, WatchDescriptor};
+use anyhow::{Error, Context};
+use crate::helpers::IsHidden;
+
+// The files are considered settled after this delay without events.
+pub const SETTLE_DELAY: Duration = Duration::from_secs(3);
+// The files that are still open for writing after this delay without
+// events are considered settled anyway.
+pub const MAX_WRITE_DELAY: Duration = Duration::from_secs(120);
+
+pub struct Watcher {
+    inotify: Inotify,
+    watches: FxHashMap<WatchDescriptor, PathBuf>,
+    // The files being written, with the time of their last event.
+    writing: FxHashMap<PathBuf, Instant>,
+    // The time of the last event, if changes are pending.
+    last_event: Option<Instant>,
+}
+
+impl Watcher {
+    pub fn new<P: AsRef<Path>>(home: P) -> Result<Watcher, Error> {
+        let inotify = Inotify::init(InitFlags::IN_NONBLOCK | InitFlags::IN_CLOEXEC)
+                             .context("can't initialize inotify")?;
+        let mut watcher = Watcher {
+            inotify,
+            watches: FxHashMap::default(),
+            writing: FxHashMap::default(),
+            last_event: None,
+        };
+        watcher.add_tree(home.as_ref());
+        Ok(watcher)
+    }
+
+    // Returns whether some directories are still watched.
+    pub fn is_active(&self) -> bool {
+        !self.watches.is_empty()
+    }
+
+    // Waits until files have changed and settled, or until *timeout* expires.
+    // Returns whether files have changed.
+    pub fn wait(&mut self, timeout: Option<Duration>) -> Result<bool, Error> {
+        let start = Instant::now();
+
+        loop {
+            let now = Instant::now();
+            self.writing.retain(|_, time| now.duration_since(*time) < MAX_WRITE_DELAY);
+
+            let mut delay = None;
+
+            if let Some(last_event) = self.last_event {
+                let elapsed = now.duration_since(last_event);
+                if self.writing.is_empty() && elapsed >= SETTLE_DELAY {
+                    self.last_event = None;
+                    return Ok(true);
+                }
+                delay = Some(SETTLE_DELAY.saturating_sub(elapsed).max(Duration::from_millis(100)));
+            }
+
+            if let Some(timeout) = timeout {
+                let remaining = timeout.saturating_sub(now.duration_since(start));
+                if remaining.is_zero() {
+                    return Ok(false);
+                }
+                delay = Some(delay.map_or(remaining, |d| d.min(remaining)));
+            }
+
+            let timeout = delay.map_or(PollTimeout::NONE, |d| PollTimeout::try_from(d).unwrap_or(PollTimeout::MAX));
+            let mut fds = [PollFd::new(self.inotify.as_fd(), PollFlags::POLLIN)];
+
+            match poll(&mut fds, timeout) {
+                Ok(0) | Err(Errno::EINTR) => continue,
+                Ok(_) => {
+                    if self.read_events() {
+                        self.last_event = Some(Instant::now());
+                    }
+                },
+                Err(e) => return Err(Error::new(e).context("can't poll inotify")),
+            }
+        }
+    }
+
+    // Returns whether the library might have changed.
+    fn read_events(&mut self) -> bool {
+        let mut changed = false;
+
+        while let Ok(events) = self.inotify.read_events() {
+            if events.is_empty() {
+                break;
+            }
+
+            for event in events {
+                if event.mask.contains(AddWatchFlags::IN_Q_OVERFLOW) {
+                    changed = true;
+                    continue;
+                }
+
+                if event.mask.contains(AddWatchFlags::IN_IGNORED) {
+                    self.watches.remove(&event.wd);
+                    continue;
+                }
+
+                let Some(dir) = self.watches.get(&event.wd).cloned() else {
+                    continue;
+                };
+
+                // The temporary files of the synchronization services are hidden.
+                let Some(name) = event.name.filter(|name| !name.to_string_lossy().starts_with('.')) else {
+                    continue;
+                };
+
+                let path = dir.join(name);
+
+                if event.mask.contains(AddWatchFlags::IN_ISDIR) {
+                    if event.mask.intersects(AddWatchFlags::IN_CREATE | AddWatchFlags::IN_MOVED_TO) {
+                        self.add_tree(&path);
+                    } else if event.mask.contains(AddWatchFlags::IN_MOVED_FROM) {
+                        self.remove_tree(&path);
+                    }
+                } else if event.mask.intersects(AddWatchFlags::IN_CREATE | AddWatchFlags::IN_MODIFY) {
+                    self.writing.insert(path, Instant::now());
+                } else {
+                    self.writing.remove(&path);
+                }
+
+                changed = true;
+            }
+        }
+
+        changed
+    }
+
+    fn add_tree(&mut self, path: &Path) {
+        for entry in WalkDir::new(path).into_iter()
+                             .filter_entry(|e| e.depth() == 0 || !e.is_hidden())
+                             .filter_map(|e| e.ok())
+                             .filter(|e| e.file_type().is_dir()) {
+            let flags = AddWatchFlags::IN_CREATE | AddWatchFlags::IN_MODIFY |
+                        AddWatchFlags::IN_CLOSE_WRITE | AddWatchFlags::IN_DELETE |
+                        AddWatchFlags::IN_MOVED_FROM | AddWatchFlags::IN_MOVED_TO;
+            match self.inotify.add_watch(entry.path(), flags) {
+                Ok(wd) => {
+                    self.watches.insert(wd, entry.path().to_path_buf());
+                },
+                Err(e) => eprintln!("Can't watch {}: {}.", entry.path().display(), e),
+            }
+        }
+    }
+
+    fn remove_tree(&mut self, path: &Path) {
+        let inotify = &self.inotify;
+        self.watches.retain(|wd, dir| {
+            if dir.starts_with(path) {
+                inotify.rm_watch(*wd).ok();
+                false
+            } else {
+                true
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::fs;
+
+    #[test]
+    fn test_watcher() {
+        let home = env::temp_dir().join(format!("plato-watcher-{}", std::process::id()));
+        fs::create_dir_all(home.join("a")).unwrap();
+        let mut watcher = Watcher::new(&home).unwrap();
+        assert!(!watcher.wait(Some(Duration::from_millis(50))).unwrap());
+
+        fs::create_dir(home.join("a/b")).unwrap();
+        fs::write(home.join("a/.tmp"), "partial").unwrap();
+        assert!(watcher.wait(Some(Duration::from_millis(50))).is_ok_and(|v| !v));
+        assert!(watcher.wait(Some(SETTLE_DELAY * 2)).unwrap());
+
+        // The new directory is watched, and the hidden files are ignored.
+        fs::write(home.join("a/.tmp"), "complete").unwrap();
+        assert!(!watcher.wait(Some(Duration::from_millis(200))).unwrap());
+        fs::rename(home.join("a/.tmp"), home.join("a/b/book.epub")).unwrap();
+        assert!(watcher.wait(Some(SETTLE_DELAY * 2)).unwrap());
+        assert!(watcher.writing.is_empty());
+
+        fs::remove_dir_all(&home).ok();
+    }
+}
@@ -1,12 +1,16 @@
+mod script;
+
+use std::env;
 use std::mem;
 use std::thread;
-use std::fs::File;
+use std::fs::{self, File};
 use std::sync::mpsc;
 use std::collections::VecDeque;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::time::Duration;
-use plato_core::anyhow::{Error, Context as ResultExt};
+use plato_core::anyhow::{Error, Context as ResultExt, format_err};
 use plato_core::chrono::Local;
+use getopts::Options;
 use sdl2::event::Event as SdlEvent;
 use sdl2::keyboard::{Scancode, Keycode, Mod};
 use sdl2::render::{WindowCanvas, BlendMode};
@@ -15,11 +19,14 @@ use sdl2::mouse::MouseState;
 use sdl2::rect::Point as SdlPoint;
 use sdl2::rect::Rect as SdlRect;
 use plato_core::framebuffer::{Framebuffer, UpdateMode};
-use plato_core::input::{DeviceEvent, FingerStatus, ButtonCode, ButtonStatus};
+use plato_core::input::{DeviceEvent, InputEvent, TouchEncoder, device_events, input_event};
+use plato_core::input::{EV_KEY, VAL_PRESS, VAL_RELEASE, VAL_REPEAT};
+use plato_core::input::{KEY_POWER, KEY_HOME, KEY_LIGHT, KEY_BACKWARD, KEY_FORWARD, PEN_ERASE, PEN_HIGHLIGHT};
 use plato_core::document::sys_info_as_html;
 use plato_core::view::{View, Event, ViewId, EntryId, AppCmd, EntryKind};
 use plato_core::view::{process_render_queue, wait_for_all, handle_event, RenderQueue, RenderData};
 use plato_core::view::home::Home;
+use plato_core::control::{self, handle_command};
 use plato_core::view::reader::Reader;
 use plato_core::view::notification::Notification;
 use plato_core::view::dialog::Dialog;
@@ -31,14 +38,15 @@ use plato_core::view::calculator::Calculator;
 use plato_core::view::sketch::Sketch;
 use plato_core::view::touch_events::TouchEvents;
 use plato_core::view::rotation_values::RotationValues;
+use plato_core::view::book_details::BookDetails;
 use plato_core::view::common::{locate, locate_by_id, transfer_notifications, overlapping_rectangle};
 use plato_core::view::common::{toggle_input_history_menu, toggle_keyboard_layout_menu};
 use plato_core::helpers::{load_toml, save_toml};
 use plato_core::settings::{Settings, SETTINGS_PATH, IntermKind};
-use plato_core::geom::{Rectangle, Axis};
+use plato_core::geom::{Point, Rectangle, Axis};
 use plato_core::color::Color;
 use plato_core::gesture::{GestureEvent, gesture_events};
-use plato_core::device::CURRENT_DEVICE;
+use plato_core::device::{CURRENT_DEVICE, MODELS, Model, Orientation};
 use plato_core::battery::{Battery, FakeBattery};
 use plato_core::frontlight::{Frontlight, LightLevels};
 use plato_core::lightsensor::LightSensor;
@@ -47,9 +55,9 @@ use plato_core::font::Fonts;
 use plato_core::context::Context;
 use plato_core::pt;
 use plato_core::png;
+use crate::script::{Recorder, Player};
 
 pub const APP_NAME: &str = "Plato";
-const DEFAULT_ROTATION: i8 = 1;
 
 const CLOCK_REFRESH_INTERVAL: Duration = Duration::from_secs(60);
 
@@ -67,47 +75,56 @@ pub fn build_context(fb: Box<dyn Framebuffer>) -> Result<Context, Error> {
                     fonts, battery, frontlight, lightsensor))
 }
 
-#[inline]
-fn seconds(timestamp: u32) -> f64 {
-    timestamp as f64 / 1000.0
+fn code_from_key(key: Scancode) -> Option<u16> {
+    match key {
+        Scancode::B => Some(KEY_BACKWARD),
+        Scancode::F => Some(KEY_FORWARD),
+        Scancode::P => Some(KEY_POWER),
+        Scancode::L => Some(KEY_LIGHT),
+        Scancode::H => Some(KEY_HOME),
+        Scancode::E => Some(PEN_ERASE),
+        Scancode::G => Some(PEN_HIGHLIGHT),
+        _ => None,
+    }
 }
 
-#[inline]
-pub fn device_event(event: SdlEvent) -> Option<DeviceEvent> {
-    match event {
-        SdlEvent::MouseButtonDown { timestamp, x, y, .. } =>
-            Some(DeviceEvent::Finger { id: 0,
-                                       status: FingerStatus::Down,
-                                       position: pt!(x, y),
-                                       time: seconds(timestamp) }),
-        SdlEvent::MouseButtonUp { timestamp, x, y, .. } =>
-            Some(DeviceEvent::Finger { id: 0,
-                                       status: FingerStatus::Up,
-                                       position: pt!(x, y),
-                                       time: seconds(timestamp) }),
-        SdlEvent::MouseMotion { timestamp, x, y, .. } =>
-            Some(DeviceEvent::Finger { id: 0,
-                                       status: FingerStatus::Motion,
-                                       position: pt!(x, y),
-                                       time: seconds(timestamp) }),
-        _ => None,
+// Finds a model given its name or its product name.
+fn find_model(name: &str) -> Option<&'static (Model, &'static str, &'static str)> {
+    let normalize = |s: &str| s.chars().filter(|c| c.is_ascii_alphanumeric())
+                               .collect::<String>().to_lowercase();
+    let name = normalize(name);
+    MODELS.iter().find(|(model, ..)| normalize(&format!("{:?}", model)) == name)
+          .or_else(|| MODELS.iter().find(|(_, product, _)| product.to_lowercase() == name))
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+enum MultiTouch {
+    // The second finger is symmetric to the first one with respect to the center of the screen.
+    Mirrored,
+    // The second finger follows the first one at a fixed distance.
+    Parallel,
+}
+
+fn contacts(position: Point, multi_touch: Option<MultiTouch>, dims: (u32, u32)) -> Vec<Point> {
+    let (width, height) = (dims.0 as i32, dims.1 as i32);
+    match multi_touch {
+        None => vec![position],
+        Some(MultiTouch::Mirrored) => vec![position, pt!(width - 1 - position.x, height - 1 - position.y)],
+        Some(MultiTouch::Parallel) => {
+            let offset = if position.x < width / 2 { width / 6 } else { -width / 6 };
+            vec![position, pt!(position.x + offset, position.y)]
+        },
     }
 }
 
-fn code_from_key(key: Scancode) -> Option<ButtonCode> {
-    match key {
-        Scancode::B => Some(ButtonCode::Backward),
-        Scancode::F => Some(ButtonCode::Forward),
-        Scancode::P => Some(ButtonCode::Power),
-        Scancode::L => Some(ButtonCode::Light),
-        Scancode::H => Some(ButtonCode::Home),
-        Scancode::E => Some(ButtonCode::Erase),
-        Scancode::G => Some(ButtonCode::Highlight),
-        _ => None,
+// Encodes the given contacts as raw touch events for the device events parser.
+fn send_touch(encoder: &mut TouchEncoder, raw_sender: &mpsc::Sender<InputEvent>, contacts: &[Point]) {
+    for evt in encoder.touch(contacts) {
+        raw_sender.send(evt).ok();
     }
 }
 
-struct FBCanvas(WindowCanvas);
+struct FBCanvas(WindowCanvas, i8);
 
 impl Framebuffer for FBCanvas {
     fn set_pixel(&mut self, x: u32, y: u32, color: Color) {
@@ -186,15 +203,16 @@ impl Framebuffer for FBCanvas {
     }
 
     fn rotation(&self) -> i8 {
-        DEFAULT_ROTATION
+        CURRENT_DEVICE.transformed_rotation(self.1)
     }
 
     fn set_rotation(&mut self, n: i8) -> Result<(u32, u32), Error> {
         let (mut width, mut height) = self.dims();
-        if (width < height && n % 2 == 0) || (width > height && n % 2 == 1) {
+        if (width < height) != (CURRENT_DEVICE.orientation(n) == Orientation::Portrait) {
             mem::swap(&mut width, &mut height);
         }
         self.0.window_mut().set_size(width, height).ok();
+        self.1 = n;
         Ok((width, height))
     }
 
@@ -229,6 +247,47 @@ impl Framebuffer for FBCanvas {
 }
 
 fn main() -> Result<(), Error> {
+    let args: Vec<String> = env::args().skip(1).collect();
+
+    let mut opts = Options::new();
+
+    opts.optflag("h", "help", "Print this help message.");
+    opts.optopt("r", "record", "Record the input events to the given file.", "SCRIPT_PATH");
+    opts.optopt("p", "replay", "Replay the input events from the given file.", "SCRIPT_PATH");
+    opts.optopt("s", "screenshots", "Save the screenshots taken at the checkpoints in the given directory.", "SCREENSHOTS_DIR");
+    opts.optflag("x", "exit", "Exit when the replay is over.");
+    opts.optopt("m", "model", "The model of the emulated device.", "MODEL");
+    opts.optflag("l", "list-models", "List the available models.");
+    opts.optopt("c", "control-socket", "Listen for commands on the given socket.", "SOCKET_PATH");
+
+    let matches = opts.parse(&args).context("failed to parse the command line arguments")?;
+
+    if matches.opt_present("h") {
+        println!("{}", opts.usage("Usage: plato-emulator -h|-l|[-m MODEL] [-c SOCKET_PATH] [-r SCRIPT_PATH] [-p SCRIPT_PATH [-s SCREENSHOTS_DIR] [-x]]"));
+        return Ok(());
+    }
+
+    if matches.opt_present("l") {
+        for (model, product, _) in MODELS.iter() {
+            println!("{:?}\t{}\t{}", model, product, model);
+        }
+        return Ok(());
+    }
+
+    // The current device is defined by these variables: they need to be set before it's first accessed.
+    if let Some(name) = matches.opt_str("m") {
+        let (_, product, model_number) = find_model(&name)
+                                             .ok_or_else(|| format_err!("unknown model: {}", name))?;
+        env::set_var("PRODUCT", product);
+        env::set_var("MODEL_NUMBER", model_number);
+    }
+
+    let mut recorder = matches.opt_str("r").map(Recorder::new);
+    let mut player = matches.opt_str("p").map(|path| {
+        Player::new(path, matches.opt_str("s").map(PathBuf::from))
+    }).transpose()?;
+    let exit_after_replay = matches.opt_present("x");
+
     let sdl_context = sdl2::init().unwrap();
     let video_subsystem = sdl_context.video().unwrap();
     let (width, height) = CURRENT_DEVICE.dims;
@@ -241,7 +300,7 @@ fn main() -> Result<(), Error> {
     let mut fb = window.into_canvas().software().build().unwrap();
     fb.set_blend_mode(BlendMode::Blend);
 
-    let mut context = build_context(Box::new(FBCanvas(fb)))?;
+    let mut context = build_context(Box::new(FBCanvas(fb, CURRENT_DEVICE.startup_rotation())))?;
 
     if context.settings.import.startup_trigger {
         context.batch_import();
@@ -254,6 +313,19 @@ fn main() -> Result<(), Error> {
     let (ty, ry) = mpsc::channel();
     let touch_screen = gesture_events(ry);
 
+    // The mouse and keyboard inputs are converted into the raw events
+    // a device would emit, and parsed by the same code.
+    let (raw_sender, raw_receiver) = mpsc::channel();
+    let mut touch_encoder = TouchEncoder::new(context.display);
+    let device_inputs = device_events(raw_receiver, context.display, context.settings.button_scheme);
+
+    let ty2 = ty.clone();
+    thread::spawn(move || {
+        while let Ok(evt) = device_inputs.recv() {
+            ty2.send(evt).ok();
+        }
+    });
+
     let tx2 = tx.clone();
     thread::spawn(move || {
         while let Ok(evt) = touch_screen.recv() {
@@ -269,6 +341,17 @@ fn main() -> Result<(), Error> {
         }
     });
 
+    let control_socket = matches.opt_str("c").map(PathBuf::from)
+                                .or_else(|| context.settings.control_socket.clone());
+
+    if let Some(path) = control_socket.as_ref() {
+        control::listen(path, &tx)
+                .map_err(|e| eprintln!("Can't listen on the control socket: {:#}.", e))
+                .ok();
+    }
+
+    context.start_services(&tx);
+
     let mut history: Vec<Box<dyn View>> = Vec::new();
     let mut rq = RenderQueue::new();
     let mut view: Box<dyn View> = Box::new(Home::new(context.fb.rect(), &tx,
@@ -291,6 +374,8 @@ fn main() -> Result<(), Error> {
                                                         context.fb.rect().height());
 
     let mut bus = VecDeque::with_capacity(4);
+    let mut mouse_down = false;
+    let mut multi_touch = None;
 
     'outer: loop {
         let mut event_pump = sdl_context.event_pump().unwrap();
@@ -304,16 +389,12 @@ fn main() -> Result<(), Error> {
                     }
                     break 'outer;
                 },
-                SdlEvent::KeyUp { scancode: Some(scancode), keymod: Mod::NOMOD, timestamp, .. } => {
+                SdlEvent::KeyUp { scancode: Some(scancode), keymod: Mod::NOMOD, .. } => {
                     if let Some(code) = code_from_key(scancode) {
-                        ty.send(DeviceEvent::Button {
-                            time: seconds(timestamp),
-                            code,
-                            status: ButtonStatus::Released,
-                        }).ok();
+                        raw_sender.send(input_event(EV_KEY, code, VAL_RELEASE)).ok();
                     }
                 },
-                SdlEvent::KeyDown { scancode: Some(scancode), keymod, timestamp, repeat, .. } => {
+                SdlEvent::KeyDown { scancode: Some(scancode), keymod, repeat, .. } => {
                     match keymod {
                         Mod::NOMOD => {
                             match scancode {
@@ -328,19 +409,20 @@ fn main() -> Result<(), Error> {
                                 Scancode::S => {
                                     tx.send(Event::Select(EntryId::TakeScreenshot)).ok();
                                 },
+                                Scancode::C => {
+                                    if let Some(recorder) = recorder.as_mut() {
+                                        recorder.checkpoint();
+                                    }
+                                },
                                 Scancode::B | Scancode::F | Scancode::P | Scancode::L | Scancode::H |
                                     Scancode::E | Scancode::G => {
                                     if let Some(code) = code_from_key(scancode) {
-                                        let status = if repeat {
-                                            ButtonStatus::Repeated
+                                        let value = if repeat {
+                                            VAL_REPEAT
                                         } else {
-                                            ButtonStatus::Pressed
+                                            VAL_PRESS
                                         };
-                                        ty.send(DeviceEvent::Button {
-                                            time: seconds(timestamp),
-                                            code,
-                                            status,
-                                        }).ok();
+                                        raw_sender.send(input_event(EV_KEY, code, value)).ok();
                                     }
                                 },
                                 Scancode::I | Scancode::O => {
@@ -387,15 +469,41 @@ fn main() -> Result<(), Error> {
                         _ => (),
                     }
                 },
-                _ => {
-                    if let Some(dev_evt) = device_event(sdl_evt) {
-                        ty.send(dev_evt).ok();
-                    }
+                SdlEvent::MouseButtonDown { x, y, .. } => {
+                    let keymod = sdl_context.keyboard().mod_state();
+                    multi_touch = if keymod.intersects(Mod::LCTRLMOD | Mod::RCTRLMOD) {
+                        Some(MultiTouch::Mirrored)
+                    } else if keymod.intersects(Mod::LALTMOD | Mod::RALTMOD) {
+                        Some(MultiTouch::Parallel)
+                    } else {
+                        None
+                    };
+                    mouse_down = true;
+                    send_touch(&mut touch_encoder, &raw_sender,
+                               &contacts(pt!(x, y), multi_touch, context.display.dims));
+                },
+                SdlEvent::MouseMotion { x, y, .. } if mouse_down => {
+                    send_touch(&mut touch_encoder, &raw_sender,
+                               &contacts(pt!(x, y), multi_touch, context.display.dims));
+                },
+                SdlEvent::MouseButtonUp { .. } if mouse_down => {
+                    mouse_down = false;
+                    send_touch(&mut touch_encoder, &raw_sender, &[]);
                 },
+                _ => (),
+            }
+        }
+
+        if let Some(player) = player.as_mut() {
+            for evt in player.due_events() {
+                tx.send(evt).ok();
             }
         }
 
         while let Ok(evt) = rx.recv_timeout(Duration::from_millis(20)) {
+            if let Some(recorder) = recorder.as_mut() {
+                recorder.record(&evt);
+            }
             match evt {
                 Event::Open(info) => {
                     let rotation = context.display.rotation;
@@ -403,6 +511,7 @@ fn main() -> Result<(), Error> {
                                          .and_then(|r| r.rotation.map(|n| CURRENT_DEVICE.from_canonical(n))) {
                         if n != rotation {
                             if let Ok(dims) = context.fb.set_rotation(n) {
+                                raw_sender.send(touch_encoder.rotate(n)).ok();
                                 context.display.rotation = n;
                                 context.display.dims = dims;
                             }
@@ -417,6 +526,7 @@ fn main() -> Result<(), Error> {
                     } else {
                         if context.display.rotation != rotation {
                             if let Ok(dims) = context.fb.set_rotation(rotation) {
+                                raw_sender.send(touch_encoder.rotate(rotation)).ok();
                                 context.display.rotation = rotation;
                                 context.display.dims = dims;
                             }
@@ -450,6 +560,9 @@ fn main() -> Result<(), Error> {
                         AppCmd::RotationValues => {
                             Box::new(RotationValues::new(context.fb.rect(), &mut rq, &mut context))
                         },
+                        AppCmd::BookDetails { ref path } => {
+                            Box::new(BookDetails::new(context.fb.rect(), path, &tx, &mut rq, &mut context))
+                        },
                     };
                     transfer_notifications(view.as_mut(), next_view.as_mut(), &mut rq, &mut context);
                     history.push(view as Box<dyn View>);
@@ -459,9 +572,11 @@ fn main() -> Result<(), Error> {
                     if let Some(v) = history.pop() {
                         view = v;
                         if view.is::<Home>() {
-                            if context.display.rotation % 2 != 1 {
-                                if let Ok(dims) = context.fb.set_rotation(DEFAULT_ROTATION) {
-                                    context.display.rotation = DEFAULT_ROTATION;
+                            if CURRENT_DEVICE.orientation(context.display.rotation) != Orientation::Portrait {
+                                let rotation = CURRENT_DEVICE.startup_rotation();
+                                if let Ok(dims) = context.fb.set_rotation(rotation) {
+                                    raw_sender.send(touch_encoder.rotate(rotation)).ok();
+                                    context.display.rotation = rotation;
                                     context.display.dims = dims;
                                 }
                             }
@@ -536,6 +651,7 @@ fn main() -> Result<(), Error> {
                 Event::Select(EntryId::Rotate(n)) if n != context.display.rotation && view.might_rotate() => {
                     wait_for_all(&mut updating, &mut context);
                     if let Ok(dims) = context.fb.set_rotation(n) {
+                        raw_sender.send(touch_encoder.rotate(n)).ok();
                         context.display.rotation = n;
                         let fb_rect = Rectangle::from(dims);
                         if context.display.dims != dims {
@@ -564,11 +680,52 @@ fn main() -> Result<(), Error> {
                     let notif = Notification::new(msg, &tx, &mut rq, &mut context);
                     view.children_mut().push(Box::new(notif) as Box<dyn View>);
                 },
-                Event::Device(DeviceEvent::NetUp) |
+                Event::FetcherProgress(id, ref msg, value) => {
+                    let view_id = ViewId::ProgressNotif(id);
+                    if let Some(index) = locate_by_id(view.as_ref(), view_id) {
+                        if let Some(notif) = view.child_mut(index).downcast_mut::<Notification>() {
+                            notif.update_progress(msg.clone(), value, &tx, &mut rq);
+                        }
+                    } else {
+                        let notif = Notification::with_progress(view_id, msg.clone(), value, &tx, &mut rq, &mut context);
+                        view.children_mut().push(Box::new(notif) as Box<dyn View>);
+                    }
+                },
+                Event::FetcherAsk(id, ref msg) => {
+                    let mut dialog = Dialog::new(ViewId::FetcherDialog(id),
+                                                 Some(Event::FetcherAnswer(id, true)),
+                                                 msg.clone(),
+                                                 &mut context);
+                    dialog.set_cancel_event(Event::FetcherAnswer(id, false));
+                    rq.add(RenderData::new(dialog.id(), *dialog.rect(), UpdateMode::Gui));
+                    view.children_mut().push(Box::new(dialog) as Box<dyn View>);
+                },
+                Event::CheckService(index) => {
+                    context.services.check(index, &tx);
+                },
+                Event::StartService(index) => {
+                    context.services.spawn(index, context.settings.wifi, context.online, &tx);
+                },
+                Event::Control(ref cmd, ref responder) => {
+                    handle_command(cmd, responder, view.as_mut(), &tx, &mut updating, &mut context);
+                },
+                Event::Device(DeviceEvent::NetUp) => {
+                    context.services.notify_network_up();
+                    if let Some(home) = history.get_mut(0).filter(|view| view.is::<Home>()) {
+                        let (tx, _rx) = mpsc::channel();
+                        home.handle_event(&evt, &tx, &mut VecDeque::new(), &mut RenderQueue::new(), &mut context);
+                    } else {
+                        handle_event(view.as_mut(), &evt, &tx, &mut bus, &mut rq, &mut context);
+                    }
+                },
                 Event::CheckFetcher(..) |
                 Event::FetcherAddDocument(..) |
                 Event::FetcherRemoveDocument(..) |
-                Event::FetcherSearch { .. } if !view.is::<Home>() => {
+                Event::FetcherSearch { .. } |
+                Event::FetcherUpdateDocument(..) |
+                Event::FetcherReaderInfo(..) |
+                Event::FetcherAnswer(..) |
+                Event::Activity(..) if !view.is::<Home>() => {
                     if let Some(home) = history.get_mut(0).filter(|view| view.is::<Home>()) {
                         let (tx, _rx) = mpsc::channel();
                         home.handle_event(&evt, &tx, &mut VecDeque::new(), &mut RenderQueue::new(), &mut context);
@@ -605,6 +762,18 @@ fn main() -> Result<(), Error> {
         while let Some(ce) = bus.pop_front() {
             tx.send(ce).ok();
         }
+
+        if let Some(player) = player.as_mut() {
+            if let Some(path) = player.due_checkpoint() {
+                wait_for_all(&mut updating, &mut context);
+                context.fb.save(&path.to_string_lossy())
+                   .map_err(|e| eprintln!("Can't save {}: {:#}.", path.display(), e))
+                   .ok();
+            }
+            if exit_after_replay && player.is_over() {
+                break;
+            }
+        }
     }
 
     if !history.is_empty() {
@@ -619,8 +788,17 @@ fn main() -> Result<(), Error> {
         context.settings.frontlight_levels = context.frontlight.levels();
     }
 
+    context.services.stop();
     context.library.flush();
 
+    if let Some(recorder) = recorder.as_ref() {
+        recorder.save()?;
+    }
+
+    if let Some(path) = control_socket.as_ref() {
+        fs::remove_file(path).ok();
+    }
+
     let path = Path::new(SETTINGS_PATH);
     save_toml(&context.settings, path).context("can't save settings")?;
 
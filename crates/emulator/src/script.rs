@@ -0,0 +1,175 @@
+use std::fs::{self, File};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+use std::collections::VecDeque;
+use plato_core::anyhow::{Error, Context as ResultExt};
+use plato_core::serde::{Serialize, Deserialize};
+use plato_core::serde_json;
+use plato_core::input::DeviceEvent;
+use plato_core::gesture::GestureEvent;
+use plato_core::view::Event;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(crate = "plato_core::serde")]
+pub enum Input {
+    Device(DeviceEvent),
+    Gesture(GestureEvent),
+    // Take a screenshot once the previous inputs have been processed.
+    Checkpoint,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(crate = "plato_core::serde")]
+pub struct Entry {
+    // Number of seconds elapsed since the beginning of the recording.
+    pub time: f64,
+    pub input: Input,
+}
+
+pub struct Recorder {
+    start: Instant,
+    path: PathBuf,
+    entries: Vec<Entry>,
+}
+
+impl Recorder {
+    pub fn new<P: AsRef<Path>>(path: P) -> Recorder {
+        Recorder {
+            start: Instant::now(),
+            path: path.as_ref().to_path_buf(),
+            entries: Vec::new(),
+        }
+    }
+
+    pub fn record(&mut self, evt: &Event) {
+        let input = match *evt {
+            Event::Device(dev_evt) => Input::Device(dev_evt),
+            Event::Gesture(ges_evt) => Input::Gesture(ges_evt),
+            _ => return,
+        };
+        self.push(input);
+    }
+
+    pub fn checkpoint(&mut self) {
+        self.push(Input::Checkpoint);
+    }
+
+    fn push(&mut self, input: Input) {
+        let time = self.start.elapsed().as_secs_f64();
+        self.entries.push(Entry { time, input });
+    }
+
+    pub fn save(&self) -> Result<(), Error> {
+        let file = File::create(&self.path)
+                        .with_context(|| format!("can't create {}", self.path.display()))?;
+        serde_json::to_writer_pretty(file, &self.entries)
+                   .with_context(|| format!("can't write to {}", self.path.display()))?;
+        Ok(())
+    }
+}
+
+pub struct Player {
+    start: Instant,
+    entries: VecDeque<Entry>,
+    screenshots: Option<PathBuf>,
+    checkpoints_count: usize,
+}
+
+impl Player {
+    pub fn new<P: AsRef<Path>>(path: P, screenshots: Option<PathBuf>) -> Result<Player, Error> {
+        let path = path.as_ref();
+        let file = File::open(path)
+                        .with_context(|| format!("can't open {}", path.display()))?;
+        let entries: VecDeque<Entry> = serde_json::from_reader(file)
+                                                  .with_context(|| format!("can't parse {}", path.display()))?;
+        if let Some(dir) = screenshots.as_ref() {
+            fs::create_dir_all(dir)
+               .with_context(|| format!("can't create {}", dir.display()))?;
+        }
+        Ok(Player {
+            start: Instant::now(),
+            entries,
+            screenshots,
+            checkpoints_count: 0,
+        })
+    }
+
+    pub fn is_over(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    fn is_due(&self, entry: &Entry) -> bool {
+        self.start.elapsed() >= Duration::from_secs_f64(entry.time.max(0.0))
+    }
+
+    // Returns the events that are due, in order.
+    // Stops at the next checkpoint: the events sent before it need to be
+    // processed before the screenshot is taken.
+    pub fn due_events(&mut self) -> Vec<Event> {
+        let mut events = Vec::new();
+        while let Some(entry) = self.entries.front() {
+            if !self.is_due(entry) {
+                break;
+            }
+            match entry.input {
+                Input::Device(dev_evt) => events.push(Event::Device(dev_evt)),
+                Input::Gesture(ges_evt) => events.push(Event::Gesture(ges_evt)),
+                Input::Checkpoint => break,
+            }
+            self.entries.pop_front();
+        }
+        events
+    }
+
+    // Returns the path of the screenshot to take, if a checkpoint was reached.
+    pub fn due_checkpoint(&mut self) -> Option<PathBuf> {
+        let entry = self.entries.front()?;
+        if !matches!(entry.input, Input::Checkpoint) || !self.is_due(entry) {
+            return None;
+        }
+        self.entries.pop_front();
+        self.checkpoints_count += 1;
+        self.screenshots.as_ref().map(|dir| {
+            dir.join(format!("checkpoint-{:03}.png", self.checkpoints_count))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use plato_core::input::FingerStatus;
+    use plato_core::geom::Point;
+
+    #[test]
+    fn test_record_and_replay() {
+        let dir = env::temp_dir().join(format!("plato-script-{}", std::process::id()));
+        let path = dir.join("script.json");
+        fs::create_dir_all(&dir).unwrap();
+
+        let mut recorder = Recorder::new(&path);
+        recorder.record(&Event::Device(DeviceEvent::Finger { id: 3, time: 1.5, status: FingerStatus::Down,
+                                                             position: Point::new(10, 20) }));
+        recorder.record(&Event::Gesture(GestureEvent::Tap(Point::new(30, 40))));
+        recorder.record(&Event::Back);
+        recorder.checkpoint();
+        recorder.record(&Event::Device(DeviceEvent::CoverOff));
+        recorder.save().unwrap();
+
+        let mut player = Player::new(&path, Some(dir.join("screenshots"))).unwrap();
+        player.start -= Duration::from_secs(60);
+        let events = player.due_events();
+        assert_eq!(events.len(), 2);
+        assert!(matches!(events[0], Event::Device(DeviceEvent::Finger { id: 3, time, status: FingerStatus::Down, position })
+                                    if time == 1.5 && position == Point::new(10, 20)));
+        assert!(matches!(events[1], Event::Gesture(GestureEvent::Tap(pt)) if pt == Point::new(30, 40)));
+        assert!(player.due_events().is_empty());
+        assert_eq!(player.due_checkpoint(), Some(dir.join("screenshots/checkpoint-001.png")));
+        assert!(player.due_checkpoint().is_none());
+        assert!(matches!(player.due_events()[..], [Event::Device(DeviceEvent::CoverOff)]));
+        assert!(player.is_over());
+
+        fs::remove_dir_all(&dir).ok();
+    }
+}
@@ -0,0 +1,201 @@
+// A folder of text files listing the URLs of the articles, one per line.
+//
+// The folder can be shared with other devices through a file synchronization service.
+// The archived URLs are moved to `archive.txt`.
+
+use std::fs;
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+use plato_core::anyhow::{Error, format_err};
+use plato_core::helpers::{load_json, save_json, fnv1a, FNV_OFFSET_BASIS};
+use plato_core::serde::{Serialize, Deserialize};
+use reqwest::blocking::Client;
+use crate::html;
+use crate::net::{download, MAX_PAGE_SIZE};
+use super::{Backend, Entry};
+
+const ARCHIVE_NAME: &str = "archive.txt";
+const INBOX_NAME: &str = "urls.txt";
+const STATE_PATH: &str = ".folder.json";
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(crate = "plato_core::serde")]
+#[serde(default, rename_all = "kebab-case")]
+pub struct FolderSettings {
+    pub path: PathBuf,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(crate = "plato_core::serde")]
+#[serde(default, rename_all = "camelCase")]
+struct State {
+    // The identifiers of the downloaded entries.
+    downloaded: BTreeSet<String>,
+}
+
+pub struct Folder {
+    client: Client,
+    path: PathBuf,
+    state_path: PathBuf,
+    state: State,
+}
+
+impl Folder {
+    pub fn new(client: &Client, settings: &FolderSettings) -> Folder {
+        Folder::with_state(client, &settings.path, STATE_PATH)
+    }
+
+    fn with_state<P: AsRef<Path>, Q: AsRef<Path>>(client: &Client, path: P, state_path: Q) -> Folder {
+        Folder {
+            client: client.clone(),
+            path: path.as_ref().to_path_buf(),
+            state_path: state_path.as_ref().to_path_buf(),
+            state: load_json(state_path.as_ref()).unwrap_or_default(),
+        }
+    }
+
+    // The lists of URLs, with their modification times.
+    fn lists(&self) -> Result<Vec<(PathBuf, i64)>, Error> {
+        let mut lists = Vec::new();
+        for entry in fs::read_dir(&self.path)? {
+            let path = entry?.path();
+            if path.extension().is_some_and(|ext| ext == "txt") &&
+               path.file_name().is_some_and(|name| name != ARCHIVE_NAME) {
+                let modified = path.metadata()?.modified()?
+                                   .duration_since(std::time::UNIX_EPOCH)
+                                   .map_or(0, |d| d.as_secs() as i64);
+                lists.push((path, modified));
+            }
+        }
+        lists.sort();
+        Ok(lists)
+    }
+
+    // Removes the URL with the given identifier from the lists, and returns it.
+    fn remove(&mut self, id: &str) -> Result<String, Error> {
+        for (path, _) in self.lists()? {
+            let text = fs::read_to_string(&path)?;
+            let url = urls(&text).find(|url| identifier(url) == id).map(String::from);
+            if let Some(url) = url {
+                let rest: Vec<&str> = text.lines().filter(|line| line.trim() != url).collect();
+                fs::write(&path, rest.join("\n") + "\n")?;
+                self.state.downloaded.remove(id);
+                save_json(&self.state, &self.state_path)?;
+                return Ok(url);
+            }
+        }
+        Err(format_err!("unknown entry"))
+    }
+}
+
+impl Backend for Folder {
+    fn add(&mut self, url: &str) -> Result<(), Error> {
+        let path = self.path.join(INBOX_NAME);
+        let mut text = fs::read_to_string(&path).unwrap_or_default();
+        if !text.is_empty() && !text.ends_with('\n') {
+            text.push('\n');
+        }
+        text.push_str(url.trim());
+        text.push('\n');
+        fs::write(&path, text)?;
+        Ok(())
+    }
+
+    // The URLs already downloaded are skipped: *since* is ignored.
+    fn list(&mut self, _since: i64) -> Result<Vec<Entry>, Error> {
+        let mut entries = Vec::new();
+        for (path, modified) in self.lists()? {
+            let text = fs::read_to_string(&path)?;
+            for url in urls(&text) {
+                let id = identifier(url);
+                if self.state.downloaded.contains(&id) || entries.iter().any(|e: &Entry| e.id == id) {
+                    continue;
+                }
+                entries.push(Entry {
+                    id,
+                    url: url.to_string(),
+                    updated: modified,
+                    .. Default::default()
+                });
+            }
+        }
+        entries.sort_by_key(|entry| entry.updated);
+        Ok(entries)
+    }
+
+    fn download(&mut self, entry: &mut Entry) -> Result<String, Error> {
+        let data = download(&self.client, &entry.url, MAX_PAGE_SIZE)?;
+        let page = String::from_utf8_lossy(&data);
+        entry.title = html::title(&page).unwrap_or_else(|| entry.url.clone());
+        entry.author = html::meta(&page, "author")
+                            .or_else(|| html::meta(&page, "og:site_name"))
+                            .unwrap_or_default();
+        entry.year = html::meta(&page, "article:published_time")
+                          .and_then(|date| date.get(..4).map(String::from))
+                          .unwrap_or_default();
+        entry.language = html::language(&page).unwrap_or_default();
+        Ok(html::main_content(&page).to_string())
+    }
+
+    fn downloaded(&mut self, id: &str) -> Result<(), Error> {
+        self.state.downloaded.insert(id.to_string());
+        save_json(&self.state, &self.state_path)
+    }
+
+    fn archive(&mut self, id: &str) -> Result<(), Error> {
+        let url = self.remove(id)?;
+        let path = self.path.join(ARCHIVE_NAME);
+        let mut text = fs::read_to_string(&path).unwrap_or_default();
+        text.push_str(&url);
+        text.push('\n');
+        fs::write(&path, text)?;
+        Ok(())
+    }
+
+    fn delete(&mut self, id: &str) -> Result<(), Error> {
+        self.remove(id).map(|_| ())
+    }
+}
+
+fn urls(text: &str) -> impl Iterator<Item=&str> {
+    text.lines().map(str::trim)
+        .filter(|line| line.starts_with("http://") || line.starts_with("https://"))
+}
+
+// A stable identifier for *url*: its 64 bits FNV-1a hash.
+fn identifier(url: &str) -> String {
+    format!("{:016x}", fnv1a(FNV_OFFSET_BASIS, url.as_bytes()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+
+    #[test]
+    fn test_folder() {
+        let dir = env::temp_dir().join(format!("plato-folder-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("read.txt"), "https://example.org/1\n# comment\n\nhttps://example.org/2\n").unwrap();
+        fs::write(dir.join(ARCHIVE_NAME), "https://example.org/0\n").unwrap();
+        let state_path = dir.join("state.json");
+
+        let client = Client::new();
+        let mut folder = Folder::with_state(&client, &dir, &state_path);
+        folder.add("https://example.org/3").unwrap();
+        let entries = folder.list(0).unwrap();
+        assert_eq!(entries.iter().map(|e| e.url.as_str()).collect::<Vec<&str>>(),
+                   ["https://example.org/1", "https://example.org/2", "https://example.org/3"]);
+
+        folder.downloaded(&entries[1].id).unwrap();
+        assert_eq!(folder.list(0).unwrap().len(), 2);
+        folder.archive(&entries[0].id).unwrap();
+        folder.delete(&entries[2].id).unwrap();
+        assert_eq!(fs::read_to_string(dir.join("read.txt")).unwrap(), "# comment\n\nhttps://example.org/2\n");
+        assert_eq!(fs::read_to_string(dir.join(ARCHIVE_NAME)).unwrap(), "https://example.org/0\nhttps://example.org/1\n");
+        assert!(folder.list(0).unwrap().is_empty());
+        assert_eq!(identifier("https://example.org/1"), "4130b41a1cecd774");
+
+        fs::remove_dir_all(&dir).ok();
+    }
+}
@@ -0,0 +1,143 @@
+// Read-later services.
+
+mod wallabag;
+mod readeck;
+mod folder;
+
+use plato_core::anyhow::{Error, format_err};
+use plato_core::metadata::Annotation;
+use plato_core::serde::{Serialize, Deserialize};
+use reqwest::Url;
+
+pub use self::wallabag::{Wallabag, WallabagSettings, Token};
+pub use self::readeck::{Readeck, ReadeckSettings};
+pub use self::folder::{Folder, FolderSettings};
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(crate = "plato_core::serde")]
+#[serde(rename_all = "kebab-case")]
+pub enum BackendKind {
+    Wallabag,
+    Readeck,
+    Folder,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Entry {
+    pub id: String,
+    pub title: String,
+    pub author: String,
+    pub year: String,
+    pub url: String,
+    pub language: String,
+    // In minutes.
+    pub reading_time: u64,
+    pub tags: Vec<String>,
+    // The UNIX timestamp of the last modification.
+    pub updated: i64,
+    // The HTML content, when it's part of the listing.
+    pub content: Option<String>,
+}
+
+// A highlight made on the server.
+#[derive(Debug, Clone, Default)]
+pub struct Highlight {
+    pub quote: String,
+    pub note: String,
+}
+
+pub trait Backend {
+    // Saves the article at *url*.
+    fn add(&mut self, url: &str) -> Result<(), Error>;
+
+    // Returns the unread entries modified after *since*, from the oldest to the newest.
+    fn list(&mut self, since: i64) -> Result<Vec<Entry>, Error>;
+
+    // Returns the HTML content of an entry, and completes its metadata.
+    fn download(&mut self, entry: &mut Entry) -> Result<String, Error>;
+
+    // Called once the EPUB of the entry is saved.
+    fn downloaded(&mut self, _id: &str) -> Result<(), Error> {
+        Ok(())
+    }
+
+    // The URL against which the relative links of the content are resolved.
+    fn base(&self, entry: &Entry) -> Option<Url> {
+        Url::parse(&entry.url).ok()
+    }
+
+    fn archive(&mut self, id: &str) -> Result<(), Error>;
+
+    fn delete(&mut self, id: &str) -> Result<(), Error>;
+
+    // Returns an EPUB built by the service.
+    fn export(&mut self, _id: &str) -> Result<Vec<u8>, Error> {
+        Err(format_err!("unsupported"))
+    }
+
+    fn highlights(&mut self, _id: &str) -> Result<Vec<Highlight>, Error> {
+        Ok(Vec::new())
+    }
+
+    // Sends the annotations made on the device, and returns the number of annotations sent.
+    fn push_annotations(&mut self, _id: &str, _annotations: &[Annotation]) -> Result<usize, Error> {
+        Err(format_err!("unsupported"))
+    }
+
+    fn add_tags(&mut self, _id: &str, _tags: &[String]) -> Result<(), Error> {
+        Err(format_err!("unsupported"))
+    }
+
+    fn remove_tags(&mut self, _id: &str, _tags: &[String]) -> Result<(), Error> {
+        Err(format_err!("unsupported"))
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod mock {
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::TcpListener;
+    use std::sync::mpsc;
+    use std::thread;
+
+    // Answers *count* requests with *respond*, and returns the address of the server
+    // and the method, path and body of the requests received.
+    pub fn serve<F>(count: usize, respond: F) -> (String, mpsc::Receiver<(String, String, String)>)
+            where F: Fn(&str, &str) -> String + Send + 'static {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = format!("http://{}", listener.local_addr().unwrap());
+        let (tx, rx) = mpsc::channel();
+        thread::spawn(move || {
+            for stream in listener.incoming().take(count) {
+                let mut stream = stream.unwrap();
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+                let mut line = String::new();
+                reader.read_line(&mut line).unwrap();
+                let mut parts = line.split_whitespace();
+                let method = parts.next().unwrap_or_default().to_string();
+                let path = parts.next().unwrap_or_default().to_string();
+                let mut length = 0;
+                loop {
+                    line.clear();
+                    reader.read_line(&mut line).unwrap();
+                    if line.trim().is_empty() {
+                        break;
+                    }
+                    if let Some((key, value)) = line.split_once(':') {
+                        if key.eq_ignore_ascii_case("content-length") {
+                            length = value.trim().parse().unwrap();
+                        }
+                    }
+                }
+                let mut body = vec![0; length];
+                reader.read_exact(&mut body).unwrap();
+                let response = respond(&method, &path);
+                write!(stream, "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\n\
+                                Content-Length: {}\r\nConnection: close\r\n\r\n{}",
+                       response.len(), response).unwrap();
+                tx.send((method, path, String::from_utf8(body).unwrap())).unwrap();
+            }
+        });
+        (address, rx)
+    }
+}
@@ -0,0 +1,233 @@
+// The Readeck API.
+//
+// The API tokens are created in the *Settings → API Tokens* page of Readeck.
+
+use plato_core::anyhow::{Error, format_err};
+use plato_core::chrono::DateTime;
+use plato_core::serde::{Serialize, Deserialize};
+use plato_core::serde_json::{json, Value as JsonValue};
+use reqwest::Url;
+use reqwest::blocking::{Client, RequestBuilder, Response};
+use super::{Backend, Entry, Highlight};
+
+// The number of bookmarks per page.
+const PAGE_SIZE: usize = 50;
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(crate = "plato_core::serde")]
+#[serde(default, rename_all = "kebab-case")]
+pub struct ReadeckSettings {
+    pub base_url: String,
+    pub token: String,
+}
+
+pub struct Readeck {
+    client: Client,
+    base_url: String,
+    token: String,
+}
+
+impl Readeck {
+    pub fn new(client: &Client, settings: &ReadeckSettings) -> Readeck {
+        Readeck {
+            client: client.clone(),
+            base_url: settings.base_url.trim_end_matches('/').to_string(),
+            token: settings.token.clone(),
+        }
+    }
+
+    fn authorize(&self, request: RequestBuilder) -> RequestBuilder {
+        request.header(reqwest::header::AUTHORIZATION, format!("Bearer {}", self.token))
+    }
+
+    fn update(&self, id: &str, value: &JsonValue) -> Result<(), Error> {
+        let url = format!("{}/api/bookmarks/{}", self.base_url, id);
+        check(self.authorize(self.client.patch(&url)).json(value).send()?)?;
+        Ok(())
+    }
+}
+
+impl Backend for Readeck {
+    fn add(&mut self, url: &str) -> Result<(), Error> {
+        let api_url = format!("{}/api/bookmarks", self.base_url);
+        check(self.authorize(self.client.post(&api_url))
+                  .json(&json!({"url": url}))
+                  .send()?)?;
+        Ok(())
+    }
+
+    fn list(&mut self, since: i64) -> Result<Vec<Entry>, Error> {
+        let url = format!("{}/api/bookmarks", self.base_url);
+        let mut entries = Vec::new();
+        let mut offset = 0;
+
+        loop {
+            let query = [("is_archived", "false".to_string()), ("sort", "created".to_string()),
+                         ("limit", PAGE_SIZE.to_string()), ("offset", offset.to_string())];
+            let items: Vec<JsonValue> = check(self.authorize(self.client.get(&url))
+                                                  .query(&query)
+                                                  .send()?)?.json()?;
+            for element in &items {
+                let entry = entry(element)?;
+                if entry.updated > since {
+                    entries.push(entry);
+                }
+            }
+            if items.len() < PAGE_SIZE {
+                break;
+            }
+            offset += PAGE_SIZE;
+        }
+
+        entries.sort_by_key(|entry| entry.updated);
+        Ok(entries)
+    }
+
+    fn download(&mut self, entry: &mut Entry) -> Result<String, Error> {
+        let url = format!("{}/api/bookmarks/{}/article", self.base_url, entry.id);
+        let response = check(self.authorize(self.client.get(&url)).send()?)?;
+        Ok(response.text()?)
+    }
+
+    fn base(&self, _entry: &Entry) -> Option<Url> {
+        Url::parse(&format!("{}/", self.base_url)).ok()
+    }
+
+    fn archive(&mut self, id: &str) -> Result<(), Error> {
+        self.update(id, &json!({"is_archived": true}))
+    }
+
+    fn delete(&mut self, id: &str) -> Result<(), Error> {
+        let url = format!("{}/api/bookmarks/{}", self.base_url, id);
+        check(self.authorize(self.client.delete(&url)).send()?)?;
+        Ok(())
+    }
+
+    fn export(&mut self, id: &str) -> Result<Vec<u8>, Error> {
+        let url = format!("{}/api/bookmarks/{}/article.epub", self.base_url, id);
+        let response = check(self.authorize(self.client.get(&url)).send()?)?;
+        Ok(response.bytes()?.to_vec())
+    }
+
+    fn highlights(&mut self, id: &str) -> Result<Vec<Highlight>, Error> {
+        let url = format!("{}/api/bookmarks/{}/annotations", self.base_url, id);
+        let items: Vec<JsonValue> = check(self.authorize(self.client.get(&url)).send()?)?.json()?;
+        Ok(items.iter().map(|item| {
+            Highlight {
+                quote: item.get("text").and_then(JsonValue::as_str)
+                           .map(String::from).unwrap_or_default(),
+                note: item.get("note").and_then(JsonValue::as_str)
+                          .map(String::from).unwrap_or_default(),
+            }
+        }).collect())
+    }
+
+    fn add_tags(&mut self, id: &str, tags: &[String]) -> Result<(), Error> {
+        self.update(id, &json!({"add_labels": tags}))
+    }
+
+    fn remove_tags(&mut self, id: &str, tags: &[String]) -> Result<(), Error> {
+        self.update(id, &json!({"remove_labels": tags}))
+    }
+}
+
+fn check(response: Response) -> Result<Response, Error> {
+    let status = response.status();
+    if status.is_success() {
+        Ok(response)
+    } else {
+        let body: JsonValue = response.json().unwrap_or_default();
+        let message = body.get("message")
+                          .and_then(JsonValue::as_str)
+                          .or_else(|| status.canonical_reason())
+                          .unwrap_or_else(|| status.as_str());
+        Err(format_err!("{}", message))
+    }
+}
+
+fn entry(element: &JsonValue) -> Result<Entry, Error> {
+    let id = element.get("id")
+                    .and_then(JsonValue::as_str)
+                    .ok_or_else(|| format_err!("missing id"))?;
+
+    let authors = element.get("authors")
+                         .and_then(JsonValue::as_array)
+                         .map(|v| v.iter().filter_map(|x| x.as_str())
+                                          .filter(|x| !x.is_empty())
+                                          .collect::<Vec<&str>>()
+                                          .join(", "))
+                         .unwrap_or_default();
+    let site_name = element.get("site_name")
+                           .and_then(JsonValue::as_str)
+                           .unwrap_or_default();
+
+    let author = match (!authors.is_empty(), !site_name.is_empty()) {
+        (true, true) => format!("{} ({})", authors, site_name),
+        (true, false) => authors,
+        _ => site_name.to_string(),
+    };
+
+    let date = |key: &str| element.get(key)
+                                  .and_then(JsonValue::as_str)
+                                  .and_then(|v| DateTime::parse_from_rfc3339(v).ok());
+
+    let updated = date("updated").or_else(|| date("created"))
+                                 .ok_or_else(|| format_err!("missing updated"))?;
+
+    Ok(Entry {
+        id: id.to_string(),
+        title: element.get("title").and_then(JsonValue::as_str)
+                      .map(String::from).unwrap_or_default(),
+        author,
+        year: date("published").or_else(|| date("created"))
+                               .map(|v| v.format("%Y").to_string())
+                               .unwrap_or_default(),
+        url: element.get("url").and_then(JsonValue::as_str)
+                    .map(String::from).unwrap_or_default(),
+        language: element.get("lang").and_then(JsonValue::as_str)
+                         .map(String::from).unwrap_or_default(),
+        reading_time: element.get("reading_time").and_then(JsonValue::as_u64)
+                             .unwrap_or_default(),
+        tags: element.get("labels")
+                     .and_then(JsonValue::as_array)
+                     .map(|v| v.iter().filter_map(|x| x.as_str())
+                                      .map(String::from)
+                                      .collect())
+                     .unwrap_or_default(),
+        updated: updated.timestamp(),
+        content: None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::mock::serve;
+
+    #[test]
+    fn test_list() {
+        let (address, requests) = serve(1, |_, _| {
+            json!([
+                {"id": "b", "title": "Second", "url": "https://example.org/2", "site_name": "Example",
+                 "authors": ["Jane", "John"], "labels": ["rust"], "reading_time": 4,
+                 "created": "2024-03-02T06:00:00Z", "updated": "2024-03-02T06:00:00Z"},
+                {"id": "a", "title": "First", "url": "https://example.org/1",
+                 "published": "2021-01-01T00:00:00Z",
+                 "created": "2024-03-01T06:00:00Z", "updated": "2024-03-01T06:00:00Z"},
+                {"id": "c", "title": "Old", "created": "2020-01-01T00:00:00Z"},
+            ]).to_string()
+        });
+        let client = Client::new();
+        let settings = ReadeckSettings { base_url: address, token: "token".to_string() };
+        let mut readeck = Readeck::new(&client, &settings);
+        let entries = readeck.list(1_600_000_000).unwrap();
+        assert_eq!(entries.iter().map(|e| e.id.as_str()).collect::<Vec<&str>>(), ["a", "b"]);
+        assert_eq!(entries[0].year, "2021");
+        assert_eq!(entries[1].author, "Jane, John (Example)");
+        assert_eq!(entries[1].tags, ["rust"]);
+        assert_eq!(entries[1].reading_time, 4);
+        let (method, path, _) = requests.recv().unwrap();
+        assert_eq!(method, "GET");
+        assert!(path.starts_with("/api/bookmarks?is_archived=false"));
+    }
+}
@@ -0,0 +1,447 @@
+// The wallabag API.
+//
+// The annotations of wallabag are those of *annotator.js*: the quoted text is located
+// through XPath ranges relative to the content of the entry. The annotations of the device
+// are matched with the server's ones through their quoted text.
+
+use plato_core::anyhow::{Error, format_err};
+use plato_core::chrono::{DateTime, Duration, Utc};
+use plato_core::helpers::decode_entities;
+use plato_core::metadata::Annotation;
+use plato_core::serde::{Serialize, Deserialize};
+use plato_core::serde_json::{json, Value as JsonValue};
+use reqwest::blocking::{Client, RequestBuilder, Response};
+use crate::html::{TextNode, text_nodes, find_quote};
+use super::{Backend, Entry, Highlight};
+
+// Nearly RFC 3339
+const DATE_FORMAT: &str = "%FT%T%z";
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(crate = "plato_core::serde")]
+#[serde(default, rename_all = "kebab-case")]
+pub struct WallabagSettings {
+    pub base_url: String,
+    pub username: String,
+    pub password: String,
+    pub client_id: String,
+    pub client_secret: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(crate = "plato_core::serde")]
+#[serde(default, rename_all = "camelCase")]
+pub struct Token {
+    data: String,
+    valid_until: DateTime<Utc>,
+}
+
+impl Default for Token {
+    fn default() -> Self {
+        Token {
+            data: String::default(),
+            valid_until: Utc::now(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(crate = "plato_core::serde")]
+#[serde(default)]
+struct ServerAnnotation {
+    id: u64,
+    // The note.
+    text: String,
+    quote: String,
+    ranges: Vec<JsonValue>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(crate = "plato_core::serde")]
+#[serde(default)]
+struct Rows {
+    rows: Vec<ServerAnnotation>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(crate = "plato_core::serde")]
+#[serde(default)]
+struct Tag {
+    id: u64,
+    label: String,
+}
+
+pub struct Wallabag {
+    client: Client,
+    base_url: String,
+    token: String,
+}
+
+impl Wallabag {
+    // Renews *token* if it has expired.
+    pub fn connect(client: &Client, settings: &WallabagSettings, token: &mut Token) -> Result<Wallabag, Error> {
+        if token.valid_until <= Utc::now() {
+            *token = update_token(client, settings)?;
+        }
+        Ok(Wallabag::new(client, &settings.base_url, &token.data))
+    }
+
+    pub fn new(client: &Client, base_url: &str, token: &str) -> Wallabag {
+        Wallabag {
+            client: client.clone(),
+            base_url: base_url.to_string(),
+            token: token.to_string(),
+        }
+    }
+
+    fn authorize(&self, request: RequestBuilder) -> RequestBuilder {
+        request.header(reqwest::header::AUTHORIZATION, format!("Bearer {}", self.token))
+    }
+
+    // The *detail* parameter is only available in 2.4.0 and up.
+    fn is_detail_available(&self) -> bool {
+        // /api/info is only available in 2.4.0 and up.
+        let url = format!("{}/api/info", self.base_url);
+        self.client.get(&url).send()
+            .is_ok_and(|response| response.status().is_success())
+    }
+
+    // The HTML content of an entry.
+    fn content(&self, id: &str) -> Result<String, Error> {
+        let url = format!("{}/api/entries/{}.json", self.base_url, id);
+        let body: JsonValue = check(self.authorize(self.client.get(&url)).send()?)?.json()?;
+        Ok(body.get("content").and_then(JsonValue::as_str)
+               .map(String::from).unwrap_or_default())
+    }
+
+    fn annotations(&self, id: &str) -> Result<Vec<ServerAnnotation>, Error> {
+        let url = format!("{}/api/annotations/{}.json", self.base_url, id);
+        let body: Rows = check(self.authorize(self.client.get(&url)).send()?)?.json()?;
+        Ok(body.rows)
+    }
+
+    fn add_annotation(&self, id: &str, text: &str, quote: &str, ranges: &[JsonValue]) -> Result<(), Error> {
+        let url = format!("{}/api/annotations/{}.json", self.base_url, id);
+        check(self.authorize(self.client.post(&url))
+                  .json(&json!({"text": text, "quote": quote, "ranges": ranges}))
+                  .send()?)?;
+        Ok(())
+    }
+
+    fn update_annotation(&self, annotation_id: u64, text: &str) -> Result<(), Error> {
+        let url = format!("{}/api/annotations/{}.json", self.base_url, annotation_id);
+        check(self.authorize(self.client.put(&url))
+                  .json(&json!({"text": text}))
+                  .send()?)?;
+        Ok(())
+    }
+}
+
+impl Backend for Wallabag {
+    fn add(&mut self, url: &str) -> Result<(), Error> {
+        let api_url = format!("{}/api/entries", self.base_url);
+        check(self.authorize(self.client.post(&api_url))
+                  .json(&json!({"url": url}))
+                  .send()?)?;
+        Ok(())
+    }
+
+    fn list(&mut self, since: i64) -> Result<Vec<Entry>, Error> {
+        let url = format!("{}/api/entries", self.base_url);
+        let mut query = json!({
+            "since": since,
+            "sort": "updated",
+            "order": "asc",
+            "archive": 0,
+            "page": 1,
+            "perPage": 8,
+        });
+
+        if self.is_detail_available() {
+            query["perPage"] = JsonValue::from(100);
+            query["detail"] = JsonValue::from("metadata");
+        }
+
+        let mut entries = Vec::new();
+        let mut page = 1;
+
+        loop {
+            let body: JsonValue = check(self.authorize(self.client.get(&url))
+                                            .query(&query)
+                                            .send()?)?.json()?;
+
+            if let Some(items) = body.pointer("/_embedded/items").and_then(JsonValue::as_array) {
+                for element in items {
+                    entries.push(entry(element)?);
+                }
+            }
+
+            let pages_count = body.get("pages").and_then(JsonValue::as_u64).unwrap_or(0);
+            page += 1;
+
+            if page > pages_count {
+                break;
+            }
+
+            query["page"] = JsonValue::from(page);
+        }
+
+        Ok(entries)
+    }
+
+    fn download(&mut self, entry: &mut Entry) -> Result<String, Error> {
+        match entry.content.take() {
+            Some(content) => Ok(content),
+            // The content isn't part of the entries' metadata.
+            None => self.content(&entry.id),
+        }
+    }
+
+    fn archive(&mut self, id: &str) -> Result<(), Error> {
+        let url = format!("{}/api/entries/{}", self.base_url, id);
+        check(self.authorize(self.client.patch(&url))
+                  .json(&json!({"archive": 1}))
+                  .send()?)?;
+        Ok(())
+    }
+
+    fn delete(&mut self, id: &str) -> Result<(), Error> {
+        let url = format!("{}/api/entries/{}", self.base_url, id);
+        check(self.authorize(self.client.delete(&url)).send()?)?;
+        Ok(())
+    }
+
+    fn export(&mut self, id: &str) -> Result<Vec<u8>, Error> {
+        let url = format!("{}/api/entries/{}/export.epub", self.base_url, id);
+        let response = check(self.authorize(self.client.get(&url)).send()?)?;
+        Ok(response.bytes()?.to_vec())
+    }
+
+    fn highlights(&mut self, id: &str) -> Result<Vec<Highlight>, Error> {
+        Ok(self.annotations(id)?.into_iter()
+               .map(|annot| Highlight { quote: annot.quote, note: annot.text })
+               .collect())
+    }
+
+    // Sends the annotations of the device that the server doesn't know about, and the
+    // modified notes.
+    fn push_annotations(&mut self, id: &str, annotations: &[Annotation]) -> Result<usize, Error> {
+        let remote = self.annotations(id)?;
+        let mut content = None;
+        let mut count = 0;
+
+        for annot in annotations.iter().filter(|annot| !annot.text.trim().is_empty()) {
+            let quote = squeeze(&annot.text);
+            if let Some(server_annot) = remote.iter().find(|a| squeeze(&a.quote) == quote) {
+                if server_annot.text != annot.note {
+                    self.update_annotation(server_annot.id, &annot.note)?;
+                    count += 1;
+                }
+                continue;
+            }
+            if content.is_none() {
+                content = Some(text_nodes(&self.content(id)?));
+            }
+            let ranges = content.as_deref().map(|nodes| ranges(nodes, &annot.text))
+                                .unwrap_or_default();
+            self.add_annotation(id, &annot.note, annot.text.trim(), &ranges)?;
+            count += 1;
+        }
+
+        Ok(count)
+    }
+
+    fn add_tags(&mut self, id: &str, tags: &[String]) -> Result<(), Error> {
+        let url = format!("{}/api/entries/{}/tags.json", self.base_url, id);
+        check(self.authorize(self.client.post(&url))
+                  .json(&json!({"tags": tags.join(",")}))
+                  .send()?)?;
+        Ok(())
+    }
+
+    fn remove_tags(&mut self, id: &str, tags: &[String]) -> Result<(), Error> {
+        let url = format!("{}/api/entries/{}/tags.json", self.base_url, id);
+        let remote: Vec<Tag> = check(self.authorize(self.client.get(&url)).send()?)?.json()?;
+        for tag in remote.iter().filter(|tag| tags.contains(&tag.label)) {
+            let url = format!("{}/api/entries/{}/tags/{}.json", self.base_url, id, tag.id);
+            check(self.authorize(self.client.delete(&url)).send()?)?;
+        }
+        Ok(())
+    }
+}
+
+fn update_token(client: &Client, settings: &WallabagSettings) -> Result<Token, Error> {
+    let query = json!({
+        "grant_type": "password",
+        "client_id": &settings.client_id,
+        "client_secret": &settings.client_secret,
+        "username": &settings.username,
+        "password": &settings.password,
+    });
+
+    let url = format!("{}/oauth/v2/token", &settings.base_url);
+
+    let response = client.post(&url)
+                         .json(&query)
+                         .send()?;
+    let status = response.status();
+    let body: JsonValue = response.json()?;
+
+    if status.is_success() {
+        Ok(Token {
+            data: body.get("access_token")
+                      .and_then(|v| v.as_str())
+                      .map(String::from)
+                      .ok_or_else(|| format_err!("missing access token"))?,
+            valid_until: body.get("expires_in")
+                             .and_then(|v| v.as_i64())
+                             .map(|d| Utc::now() + Duration::seconds(d))
+                             .ok_or_else(|| format_err!("missing expires in"))?,
+        })
+    } else {
+        Err(format_err!("failed to authentificate: {}", error_description(status, &body)))
+    }
+}
+
+// Turns the unsuccessful responses into errors.
+fn check(response: Response) -> Result<Response, Error> {
+    let status = response.status();
+    if status.is_success() {
+        Ok(response)
+    } else {
+        let body: JsonValue = response.json().unwrap_or_default();
+        Err(format_err!("{}", error_description(status, &body)))
+    }
+}
+
+fn error_description(status: reqwest::StatusCode, body: &JsonValue) -> String {
+    body.get("error_description")
+        .and_then(JsonValue::as_str)
+        .or_else(|| status.canonical_reason())
+        .unwrap_or_else(|| status.as_str())
+        .to_string()
+}
+
+fn entry(element: &JsonValue) -> Result<Entry, Error> {
+    let id = element.get("id")
+                    .and_then(JsonValue::as_u64)
+                    .ok_or_else(|| format_err!("missing id"))?;
+
+    let title = element.get("title")
+                       .and_then(JsonValue::as_str)
+                       .map(decode_entities)
+                       .map(String::from)
+                       .unwrap_or_default();
+
+    let published_by = element.get("published_by")
+                              .and_then(JsonValue::as_array)
+                              .map(|v| v.iter().filter_map(|x| x.as_str())
+                                               .filter(|x| !x.is_empty())
+                                               .collect::<Vec<&str>>())
+                              .map(|v| v.join(", "))
+                              .filter(|v| !v.is_empty())
+                              .unwrap_or_default();
+    let domain_name = element.get("domain_name")
+                             .and_then(JsonValue::as_str)
+                             .map(String::from)
+                             .unwrap_or_default();
+
+    let author = match (!published_by.is_empty(), !domain_name.is_empty()) {
+        (true, true) => format!("{} ({})", published_by, domain_name),
+        (true, false) => published_by,
+        _ => domain_name,
+    };
+
+    let year = element.get("published_at")
+                      .filter(|v| v.is_string())
+                      .or_else(|| element.get("created_at"))
+                      .and_then(JsonValue::as_str)
+                      .and_then(|v| DateTime::parse_from_str(v, DATE_FORMAT).ok())
+                      .map(|v| v.format("%Y").to_string())
+                      .unwrap_or_default();
+
+    let updated = element.get("updated_at")
+                         .and_then(JsonValue::as_str)
+                         .and_then(|v| DateTime::parse_from_str(v, DATE_FORMAT).ok())
+                         .ok_or_else(|| format_err!("missing updated at"))?;
+
+    let tags = element.get("tags")
+                      .and_then(JsonValue::as_array)
+                      .map(|v| v.iter().filter_map(|x| x.get("label").and_then(JsonValue::as_str))
+                                       .map(String::from)
+                                       .collect::<Vec<String>>())
+                      .unwrap_or_default();
+
+    Ok(Entry {
+        id: id.to_string(),
+        title,
+        author,
+        year,
+        url: element.get("url").and_then(JsonValue::as_str)
+                    .map(String::from).unwrap_or_default(),
+        language: element.get("language").and_then(JsonValue::as_str)
+                         .map(String::from).unwrap_or_default(),
+        reading_time: element.get("reading_time").and_then(JsonValue::as_u64)
+                             .unwrap_or_default(),
+        tags,
+        updated: updated.timestamp(),
+        content: element.get("content").and_then(JsonValue::as_str)
+                        .map(String::from),
+    })
+}
+
+// The ranges of *quote* within the content of an entry.
+fn ranges(nodes: &[TextNode], quote: &str) -> Vec<JsonValue> {
+    find_quote(nodes, quote).map(|[start, end]| {
+        vec![json!({
+            "start": nodes[start.0].path,
+            "startOffset": nodes[start.0].position + start.1,
+            "end": nodes[end.0].path,
+            "endOffset": nodes[end.0].position + end.1 + 1,
+        })]
+    }).unwrap_or_default()
+}
+
+fn squeeze(text: &str) -> String {
+    text.chars().filter(|c| !c.is_whitespace()).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::mock::serve;
+
+    #[test]
+    fn test_push_annotations() {
+        let (address, requests) = serve(4, |method, path| {
+            match (method, path) {
+                ("GET", "/api/annotations/7.json") => {
+                    json!({"total": 1, "rows": [{"id": 3, "text": "", "quote": "First  paragraph.", "ranges": []}]}).to_string()
+                },
+                ("GET", "/api/entries/7.json") => {
+                    json!({"id": 7, "content": "<p>First paragraph.</p><p>Second <em>one</em>.</p>"}).to_string()
+                },
+                _ => "{}".to_string(),
+            }
+        });
+        let client = Client::new();
+        let mut wallabag = Wallabag::new(&client, &address, "token");
+        let annotations = vec![
+            Annotation { text: "First paragraph.".to_string(), note: "Nice".to_string(), .. Default::default() },
+            Annotation { text: "Second one.".to_string(), .. Default::default() },
+            Annotation { text: " ".to_string(), .. Default::default() },
+        ];
+        assert_eq!(wallabag.push_annotations("7", &annotations).unwrap(), 2);
+
+        let requests: Vec<(String, String, String)> = requests.iter().collect();
+        assert_eq!(requests[0].1, "/api/annotations/7.json");
+        assert_eq!((requests[1].0.as_str(), requests[1].1.as_str()), ("PUT", "/api/annotations/3.json"));
+        assert_eq!(requests[1].2, json!({"text": "Nice"}).to_string());
+        assert_eq!(requests[2].1, "/api/entries/7.json");
+        assert_eq!((requests[3].0.as_str(), requests[3].1.as_str()), ("POST", "/api/annotations/7.json"));
+        let body: JsonValue = plato_core::serde_json::from_str(&requests[3].2).unwrap();
+        assert_eq!(body, json!({"text": "", "quote": "Second one.",
+                                "ranges": [{"start": "/p[2]", "startOffset": 0,
+                                            "end": "/p[2]", "endOffset": 11}]}));
+    }
+}
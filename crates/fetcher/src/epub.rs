@@ -0,0 +1,253 @@
+// Writes EPUB 3 documents, with an EPUB 2 table of contents for older readers.
+
+use std::fs::File;
+use std::io::Write;
+use std::path::Path;
+use plato_core::anyhow::Error;
+use plato_core::chrono::Utc;
+use zip::{ZipWriter, CompressionMethod};
+use zip::write::SimpleFileOptions;
+use plato_core::helpers::escape;
+
+const STYLESHEET: &str = "body { margin: 0; }\n\
+                          img { max-width: 100%; }\n\
+                          .meta { font-size: smaller; font-style: italic; }\n";
+
+struct Chapter {
+    title: String,
+    body: String,
+    depth: usize,
+}
+
+struct Resource {
+    path: String,
+    media_type: &'static str,
+    data: Vec<u8>,
+}
+
+#[derive(Default)]
+pub struct EpubBuilder {
+    pub identifier: String,
+    pub title: String,
+    pub author: String,
+    pub language: String,
+    pub date: Option<String>,
+    pub description: Option<String>,
+    pub source: Option<String>,
+    pub subjects: Vec<String>,
+    chapters: Vec<Chapter>,
+    resources: Vec<Resource>,
+}
+
+impl EpubBuilder {
+    pub fn new(identifier: &str, title: &str) -> EpubBuilder {
+        EpubBuilder {
+            identifier: identifier.to_string(),
+            title: title.to_string(),
+            language: "en".to_string(),
+            .. Default::default()
+        }
+    }
+
+    // Adds a chapter at the given depth of the table of contents.
+    // *body* is the XHTML content of the chapter's *body* element.
+    pub fn add_chapter(&mut self, title: &str, body: String, depth: usize) {
+        self.chapters.push(Chapter { title: title.to_string(), body, depth });
+    }
+
+    // Adds an image and returns its path, relative to the chapters.
+    pub fn add_image(&mut self, data: Vec<u8>) -> Option<String> {
+        let (media_type, extension) = image_type(&data)?;
+        let path = format!("images/{}.{}", self.resources.len() + 1, extension);
+        self.resources.push(Resource { path: path.clone(), media_type, data });
+        Some(path)
+    }
+
+    // Returns the location, in the reader, of the byte at *offset* in the body of the
+    // chapter *index*. The chapters are the only items of the spine.
+    pub fn location(&self, index: usize, offset: usize) -> usize {
+        let start: usize = self.chapters[..index].iter()
+                               .map(|chapter| xhtml(&chapter.title, &chapter.body).len())
+                               .sum();
+        let prefix = xhtml(&self.chapters[index].title, "");
+        start + prefix.find("<body>\n").map_or(0, |index| index + 7) + offset
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.chapters.is_empty()
+    }
+
+    pub fn write<P: AsRef<Path>>(&self, path: P) -> Result<(), Error> {
+        let file = File::create(path.as_ref())?;
+        let mut zip = ZipWriter::new(file);
+        let stored = SimpleFileOptions::default().compression_method(CompressionMethod::Stored);
+        let deflated = SimpleFileOptions::default().compression_method(CompressionMethod::Deflated);
+
+        zip.start_file("mimetype", stored)?;
+        zip.write_all(b"application/epub+zip")?;
+
+        zip.start_file("META-INF/container.xml", deflated)?;
+        zip.write_all(CONTAINER.as_bytes())?;
+
+        zip.start_file("OEBPS/content.opf", deflated)?;
+        zip.write_all(self.package().as_bytes())?;
+
+        zip.start_file("OEBPS/toc.ncx", deflated)?;
+        zip.write_all(self.ncx().as_bytes())?;
+
+        zip.start_file("OEBPS/nav.xhtml", deflated)?;
+        zip.write_all(self.nav().as_bytes())?;
+
+        zip.start_file("OEBPS/style.css", deflated)?;
+        zip.write_all(STYLESHEET.as_bytes())?;
+
+        for (index, chapter) in self.chapters.iter().enumerate() {
+            zip.start_file(format!("OEBPS/{}", chapter_name(index)), deflated)?;
+            zip.write_all(xhtml(&chapter.title, &chapter.body).as_bytes())?;
+        }
+
+        for resource in &self.resources {
+            zip.start_file(format!("OEBPS/{}", resource.path), stored)?;
+            zip.write_all(&resource.data)?;
+        }
+
+        zip.finish()?;
+        Ok(())
+    }
+
+    fn package(&self) -> String {
+        let mut metadata = format!("<dc:identifier id=\"uid\">{}</dc:identifier>\n\
+                                    <dc:title>{}</dc:title>\n\
+                                    <dc:language>{}</dc:language>\n\
+                                    <meta property=\"dcterms:modified\">{}</meta>\n",
+                                   escape(&self.identifier), escape(&self.title),
+                                   escape(&self.language), Utc::now().format("%Y-%m-%dT%H:%M:%SZ"));
+        if !self.author.is_empty() {
+            metadata.push_str(&format!("<dc:creator>{}</dc:creator>\n", escape(&self.author)));
+        }
+        for (tag, value) in [("date", &self.date), ("description", &self.description), ("source", &self.source)] {
+            if let Some(value) = value {
+                metadata.push_str(&format!("<dc:{0}>{1}</dc:{0}>\n", tag, escape(value)));
+            }
+        }
+        for subject in &self.subjects {
+            metadata.push_str(&format!("<dc:subject>{}</dc:subject>\n", escape(subject)));
+        }
+
+        let mut manifest = String::from("<item id=\"nav\" href=\"nav.xhtml\" media-type=\"application/xhtml+xml\" properties=\"nav\"/>\n\
+                                         <item id=\"ncx\" href=\"toc.ncx\" media-type=\"application/x-dtbncx+xml\"/>\n\
+                                         <item id=\"css\" href=\"style.css\" media-type=\"text/css\"/>\n");
+        let mut spine = String::new();
+        for index in 0..self.chapters.len() {
+            manifest.push_str(&format!("<item id=\"c{0}\" href=\"{1}\" media-type=\"application/xhtml+xml\"/>\n",
+                                       index, chapter_name(index)));
+            spine.push_str(&format!("<itemref idref=\"c{}\"/>\n", index));
+        }
+        for (index, resource) in self.resources.iter().enumerate() {
+            manifest.push_str(&format!("<item id=\"r{}\" href=\"{}\" media-type=\"{}\"/>\n",
+                                       index, resource.path, resource.media_type));
+        }
+
+        format!("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+                 <package xmlns=\"http://www.idpf.org/2007/opf\" version=\"3.0\" unique-identifier=\"uid\">\n\
+                 <metadata xmlns:dc=\"http://purl.org/dc/elements/1.1/\">\n{}</metadata>\n\
+                 <manifest>\n{}</manifest>\n\
+                 <spine toc=\"ncx\">\n{}</spine>\n\
+                 </package>\n", metadata, manifest, spine)
+    }
+
+    fn ncx(&self) -> String {
+        let mut nav_map = String::new();
+        let mut depth = 0;
+        for (index, chapter) in self.chapters.iter().enumerate() {
+            let target = chapter.depth.min(depth + 1);
+            if index > 0 {
+                for _ in target..=depth {
+                    nav_map.push_str("</navPoint>\n");
+                }
+            }
+            nav_map.push_str(&format!("<navPoint id=\"n{0}\" playOrder=\"{1}\">\
+                                       <navLabel><text>{2}</text></navLabel>\
+                                       <content src=\"{3}\"/>\n",
+                                      index, index + 1, escape(&chapter.title), chapter_name(index)));
+            depth = target;
+        }
+        if !self.chapters.is_empty() {
+            for _ in 0..=depth {
+                nav_map.push_str("</navPoint>\n");
+            }
+        }
+
+        format!("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+                 <ncx xmlns=\"http://www.daisy.org/z3986/2005/ncx/\" version=\"2005-1\">\n\
+                 <head><meta name=\"dtb:uid\" content=\"{}\"/></head>\n\
+                 <docTitle><text>{}</text></docTitle>\n\
+                 <navMap>\n{}</navMap>\n\
+                 </ncx>\n", escape(&self.identifier), escape(&self.title), nav_map)
+    }
+
+    fn nav(&self) -> String {
+        let mut list = String::from("<ol>\n");
+        let mut depth = 0;
+        for (index, chapter) in self.chapters.iter().enumerate() {
+            let target = chapter.depth.min(depth + 1);
+            if index > 0 {
+                if target > depth {
+                    list.push_str("<ol>\n");
+                } else {
+                    list.push_str("</li>\n");
+                    for _ in target..depth {
+                        list.push_str("</ol></li>\n");
+                    }
+                }
+            }
+            list.push_str(&format!("<li><a href=\"{}\">{}</a>", chapter_name(index), escape(&chapter.title)));
+            depth = target;
+        }
+        if !self.chapters.is_empty() {
+            list.push_str("</li>\n");
+            for _ in 0..depth {
+                list.push_str("</ol></li>\n");
+            }
+        }
+        list.push_str("</ol>\n");
+
+        let body = format!("<nav epub:type=\"toc\" id=\"toc\">\n<h1>{}</h1>\n{}</nav>", escape(&self.title), list);
+        xhtml(&self.title, &body)
+    }
+}
+
+const CONTAINER: &str = "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+                         <container version=\"1.0\" xmlns=\"urn:oasis:names:tc:opendocument:xmlns:container\">\n\
+                         <rootfiles>\n\
+                         <rootfile full-path=\"OEBPS/content.opf\" media-type=\"application/oebps-package+xml\"/>\n\
+                         </rootfiles>\n\
+                         </container>\n";
+
+fn chapter_name(index: usize) -> String {
+    format!("chapter-{:03}.xhtml", index + 1)
+}
+
+fn xhtml(title: &str, body: &str) -> String {
+    format!("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+             <!DOCTYPE html>\n\
+             <html xmlns=\"http://www.w3.org/1999/xhtml\" xmlns:epub=\"http://www.idpf.org/2007/ops\">\n\
+             <head>\n<title>{}</title>\n<link rel=\"stylesheet\" type=\"text/css\" href=\"style.css\"/>\n</head>\n\
+             <body>\n{}\n</body>\n\
+             </html>\n", escape(title), body)
+}
+
+// Guesses the media type and extension of an image from its first bytes.
+fn image_type(data: &[u8]) -> Option<(&'static str, &'static str)> {
+    if data.starts_with(&[0xFF, 0xD8, 0xFF]) {
+        Some(("image/jpeg", "jpg"))
+    } else if data.starts_with(b"\x89PNG") {
+        Some(("image/png", "png"))
+    } else if data.starts_with(b"GIF8") {
+        Some(("image/gif", "gif"))
+    } else if data.len() > 12 && data.starts_with(b"RIFF") && &data[8..12] == b"WEBP" {
+        Some(("image/webp", "webp"))
+    } else {
+        None
+    }
+}
@@ -0,0 +1,222 @@
+// Parses RSS (0.9x, 1.0 and 2.0) and Atom feeds.
+
+use plato_core::anyhow::{Error, format_err};
+use plato_core::chrono::{DateTime, FixedOffset};
+use plato_core::document::html::dom::{NodeRef, NodeData};
+use plato_core::document::html::xml::XmlParser;
+use plato_core::helpers::{decode_entities, escape};
+
+#[derive(Debug, Clone, Default)]
+pub struct Feed {
+    pub title: String,
+    pub entries: Vec<Entry>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Entry {
+    pub id: String,
+    pub title: String,
+    pub link: String,
+    pub author: String,
+    pub published: Option<DateTime<FixedOffset>>,
+    // The HTML content of the entry.
+    pub content: String,
+}
+
+pub fn parse(input: &str) -> Result<Feed, Error> {
+    let input = escape_cdata(input);
+    let tree = XmlParser::new(&input).parse();
+    let root = tree.root();
+
+    if let Some(feed) = root.find("feed") {
+        Ok(parse_atom(feed))
+    } else if let Some(channel) = root.find("channel") {
+        // In RSS 1.0, the items are siblings of the channel.
+        let items = root.find("RDF").unwrap_or(channel);
+        Ok(parse_rss(channel, items))
+    } else {
+        Err(format_err!("unknown feed format"))
+    }
+}
+
+fn parse_atom(feed: NodeRef) -> Feed {
+    let title = child(feed, "title").map(text_content).unwrap_or_default();
+    let feed_author = child(feed, "author").and_then(|author| child(author, "name"))
+                                           .map(text).unwrap_or_default();
+    let entries = feed.children().filter(|node| node.tag_name() == Some("entry")).map(|entry| {
+        let link = entry.children()
+                        .filter(|node| node.tag_name() == Some("link"))
+                        .find(|node| node.attribute("rel").is_none_or(|rel| rel == "alternate"))
+                        .and_then(|node| node.attribute("href"))
+                        .map(|href| decode_entities(href).into_owned())
+                        .unwrap_or_default();
+        let author = entry.children()
+                          .filter(|node| node.tag_name() == Some("author"))
+                          .filter_map(|author| child(author, "name").map(text))
+                          .collect::<Vec<String>>().join(", ");
+        let published = child(entry, "published").or_else(|| child(entry, "updated"))
+                                                 .and_then(|node| DateTime::parse_from_rfc3339(text(node).trim()).ok());
+        let content = child(entry, "content").or_else(|| child(entry, "summary"))
+                                             .map(text_content).unwrap_or_default();
+        Entry {
+            id: child(entry, "id").map(text).unwrap_or_else(|| link.clone()),
+            title: child(entry, "title").map(|node| plain_text(&text_content(node))).unwrap_or_default(),
+            author: if author.is_empty() { feed_author.clone() } else { author },
+            link,
+            published,
+            content,
+        }
+    }).collect();
+    Feed { title: plain_text(&title), entries }
+}
+
+fn parse_rss(channel: NodeRef, items: NodeRef) -> Feed {
+    let title = child(channel, "title").map(text).unwrap_or_default();
+    let entries = items.children().filter(|node| node.tag_name() == Some("item")).map(|item| {
+        let link = child(item, "link").map(text).unwrap_or_default();
+        let published = child(item, "pubDate").and_then(|node| DateTime::parse_from_rfc2822(text(node).trim()).ok())
+                                              .or_else(|| child(item, "date").and_then(|node| DateTime::parse_from_rfc3339(text(node).trim()).ok()));
+        let content = item.children()
+                          .find(|node| node.tag_qualified_name() == Some("content:encoded"))
+                          .or_else(|| child(item, "description"))
+                          .map(text).unwrap_or_default();
+        Entry {
+            id: child(item, "guid").map(text).unwrap_or_else(|| link.clone()),
+            title: child(item, "title").map(|node| plain_text(&text(node))).unwrap_or_default(),
+            author: child(item, "creator").or_else(|| child(item, "author")).map(text).unwrap_or_default(),
+            link,
+            published,
+            content,
+        }
+    }).collect();
+    Feed { title, entries }
+}
+
+fn child<'a>(node: NodeRef<'a>, name: &str) -> Option<NodeRef<'a>> {
+    node.children().find(|child| child.tag_name() == Some(name))
+}
+
+// The decoded text of a node.
+fn text(node: NodeRef) -> String {
+    decode_entities(node.text().trim()).into_owned()
+}
+
+// The content of an Atom text construct, as HTML.
+fn text_content(node: NodeRef) -> String {
+    match node.attribute("type") {
+        Some("xhtml") => node.children().map(serialize).collect(),
+        Some("html") => text(node),
+        _ => escape(&text(node)).into_owned(),
+    }
+}
+
+fn serialize(node: NodeRef) -> String {
+    match node.data() {
+        NodeData::Text(data) | NodeData::Whitespace(data) => data.text.clone(),
+        NodeData::Element(data) => {
+            let mut buf = format!("<{}", data.name);
+            // The values are raw: they might contain double quotes if they're single-quoted.
+            for (key, value) in &data.attributes {
+                buf.push_str(&format!(" {}=\"{}\"", key, escape(&decode_entities(value))));
+            }
+            buf.push('>');
+            for child in node.children() {
+                buf.push_str(&serialize(child));
+            }
+            buf.push_str(&format!("</{}>", data.name));
+            buf
+        },
+        _ => String::new(),
+    }
+}
+
+// Strips the tags of an HTML fragment.
+fn plain_text(html: &str) -> String {
+    if !html.contains('<') {
+        return decode_entities(html).into_owned();
+    }
+    let mut buf = String::new();
+    let mut cursor = html;
+    while let Some(start) = cursor.find('<') {
+        buf.push_str(&cursor[..start]);
+        cursor = cursor[start..].find('>').map_or("", |end| &cursor[start+end+1..]);
+    }
+    buf.push_str(cursor);
+    decode_entities(&buf).into_owned()
+}
+
+// The XML parser drops the CDATA sections: replace them by their escaped content.
+fn escape_cdata(input: &str) -> String {
+    let mut buf = String::with_capacity(input.len());
+    let mut cursor = input;
+    while let Some(start) = cursor.find("<![CDATA[") {
+        buf.push_str(&cursor[..start]);
+        cursor = &cursor[start+9..];
+        let end = cursor.find("]]>").unwrap_or(cursor.len());
+        buf.push_str(&escape(&cursor[..end]));
+        cursor = cursor.get(end+3..).unwrap_or("");
+    }
+    buf.push_str(cursor);
+    buf
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_rss() {
+        let input = r#"<?xml version="1.0"?>
+<rss version="2.0" xmlns:content="http://purl.org/rss/1.0/modules/content/">
+<channel>
+<title>News &amp; Views</title>
+<item>
+<title>First</title>
+<link>https://example.org/1</link>
+<guid>1</guid>
+<pubDate>Fri, 01 Mar 2024 06:00:00 +0000</pubDate>
+<description>Short</description>
+<content:encoded><![CDATA[<p>Long &amp; <b>bold</b></p>]]></content:encoded>
+</item>
+<item>
+<title>Second</title>
+<link>https://example.org/2</link>
+<description>&lt;p&gt;Escaped&lt;/p&gt;</description>
+</item>
+</channel>
+</rss>"#;
+        let feed = parse(input).unwrap();
+        assert_eq!(feed.title, "News & Views");
+        assert_eq!(feed.entries.len(), 2);
+        assert_eq!(feed.entries[0].id, "1");
+        assert_eq!(feed.entries[0].content, "<p>Long &amp; <b>bold</b></p>");
+        assert_eq!(feed.entries[0].published.map(|d| d.timestamp()), Some(1709272800));
+        assert_eq!(feed.entries[1].id, "https://example.org/2");
+        assert_eq!(feed.entries[1].content, "<p>Escaped</p>");
+    }
+
+    #[test]
+    fn test_parse_atom() {
+        let input = r#"<?xml version="1.0" encoding="utf-8"?>
+<feed xmlns="http://www.w3.org/2005/Atom">
+<title type="html">A &lt;i&gt;Blog&lt;/i&gt;</title>
+<author><name>Jane</name></author>
+<entry>
+<title>Post</title>
+<id>urn:post:1</id>
+<link rel="self" href="https://example.org/post.atom"/>
+<link href="https://example.org/post"/>
+<updated>2024-03-01T06:00:00Z</updated>
+<content type="xhtml"><div xmlns="http://www.w3.org/1999/xhtml"><p title='A "quote" &amp; more'>Hi</p></div></content>
+</entry>
+</feed>"#;
+        let feed = parse(input).unwrap();
+        assert_eq!(feed.title, "A Blog");
+        let entry = &feed.entries[0];
+        assert_eq!(entry.id, "urn:post:1");
+        assert_eq!(entry.link, "https://example.org/post");
+        assert_eq!(entry.author, "Jane");
+        assert_eq!(entry.content, "<div xmlns=\"http://www.w3.org/1999/xhtml\">\
+                                   <p title=\"A &quot;quote&quot; &amp; more\">Hi</p></div>");
+    }
+}
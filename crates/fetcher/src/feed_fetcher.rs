@@ -0,0 +1,276 @@
+use std::io;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::collections::{BTreeMap, BTreeSet};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use plato_core::chrono::Local;
+use plato_core::serde::{Serialize, Deserialize};
+use plato_core::serde_json::json;
+use plato_core::anyhow::{Error, Context, format_err};
+use plato_core::helpers::{load_toml, load_json, save_json};
+use reqwest::Url;
+use reqwest::blocking::Client;
+use fetcher::epub::EpubBuilder;
+use fetcher::feed::{self, Entry};
+use fetcher::html;
+use plato_core::helpers::escape;
+use fetcher::net::embed_image;
+
+const SETTINGS_PATH: &str = "Settings.toml";
+const SESSION_PATH: &str = ".session.json";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(crate = "plato_core::serde")]
+#[serde(default, rename_all = "kebab-case")]
+struct Settings {
+    // Build one document per feed, or one document for all the feeds.
+    group_by: GroupBy,
+    // The maximum number of entries per feed and per run.
+    max_entries: usize,
+    download_images: bool,
+    feeds: Vec<FeedSettings>,
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(crate = "plato_core::serde")]
+#[serde(rename_all = "kebab-case")]
+enum GroupBy {
+    Feed,
+    Day,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(crate = "plato_core::serde")]
+#[serde(default, rename_all = "kebab-case")]
+struct FeedSettings {
+    url: String,
+    // Overrides the title of the feed.
+    name: Option<String>,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            group_by: GroupBy::Feed,
+            max_entries: 20,
+            download_images: true,
+            feeds: Vec::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(crate = "plato_core::serde")]
+#[serde(default, rename_all = "camelCase")]
+struct Session {
+    // The identifiers of the entries seen in each feed.
+    seen: BTreeMap<String, BTreeSet<String>>,
+}
+
+fn notify(message: &str) {
+    println!("{}", json!({"type": "notify", "message": message}));
+}
+
+fn progress(message: &str, value: f32) {
+    println!("{}", json!({"type": "progress", "message": message, "value": value}));
+}
+
+fn add_entry(builder: &mut EpubBuilder, entry: &Entry, depth: usize, client: &Client, settings: &Settings) {
+    let base = Url::parse(&entry.link).ok();
+    let content = html::clean(&entry.content, |src| {
+        if !settings.download_images {
+            return None;
+        }
+        embed_image(builder, client, base.as_ref(), src)
+    });
+
+    let mut meta = Vec::new();
+    if !entry.author.is_empty() {
+        meta.push(escape(&entry.author).into_owned());
+    }
+    if let Some(published) = entry.published {
+        meta.push(published.with_timezone(&Local).format("%Y-%m-%d %H:%M").to_string());
+    }
+    if entry.link.starts_with("http") {
+        let host = base.as_ref().and_then(Url::host_str).unwrap_or(&entry.link);
+        meta.push(format!("<a href=\"{}\">{}</a>", escape(&entry.link), escape(host)));
+    }
+
+    let title = if entry.title.is_empty() { "Untitled" } else { &entry.title };
+    let mut body = format!("<h1>{}</h1>\n", escape(title));
+    if !meta.is_empty() {
+        body.push_str(&format!("<p class=\"meta\">{}</p>\n", meta.join(" · ")));
+    }
+    body.push_str(&content);
+    builder.add_chapter(title, body, depth);
+}
+
+fn slug(text: &str) -> String {
+    let mut buf = String::new();
+    for c in text.chars() {
+        if c.is_alphanumeric() {
+            buf.extend(c.to_lowercase());
+        } else if !buf.is_empty() && !buf.ends_with('-') {
+            buf.push('-');
+        }
+    }
+    let slug = buf.trim_end_matches('-');
+    if slug.is_empty() { "feed".to_string() } else { slug.to_string() }
+}
+
+// Writes the document and sends the corresponding *addDocument* event.
+fn save(builder: &EpubBuilder, name: &str, library_path: &Path, save_path: &Path) -> Result<(), Error> {
+    let now = Local::now();
+    let epub_path = save_path.join(format!("{}-{}.epub", name, now.format("%Y%m%d-%H%M%S")));
+    builder.write(&epub_path)?;
+    let size = fs::metadata(&epub_path).map_or(0, |m| m.len());
+
+    if let Ok(path) = epub_path.strip_prefix(library_path) {
+        let info = json!({
+            "title": builder.title,
+            "author": builder.author,
+            "year": now.format("%Y").to_string(),
+            "identifier": builder.identifier,
+            "categories": ["Feeds"],
+            "added": now.format("%Y-%m-%d %H:%M:%S").to_string(),
+            "file": {
+                "path": path,
+                "kind": "epub",
+                "size": size,
+            },
+        });
+        println!("{}", json!({"type": "addDocument", "info": info}));
+    }
+
+    Ok(())
+}
+
+fn main() -> Result<(), Error> {
+    let mut args = env::args().skip(1);
+    let library_path = PathBuf::from(args.next()
+                                         .ok_or_else(|| format_err!("missing argument: library path"))?);
+    let save_path = PathBuf::from(args.next()
+                                      .ok_or_else(|| format_err!("missing argument: save path"))?);
+    let wifi = args.next()
+                   .ok_or_else(|| format_err!("missing argument: wifi status"))
+                   .and_then(|v| v.parse::<bool>().map_err(Into::into))?;
+    let online = args.next()
+                     .ok_or_else(|| format_err!("missing argument: online status"))
+                     .and_then(|v| v.parse::<bool>().map_err(Into::into))?;
+    let settings = load_toml::<Settings, _>(SETTINGS_PATH)
+                             .with_context(|| format!("can't load settings from {}", SETTINGS_PATH))?;
+    let mut session = load_json::<Session, _>(SESSION_PATH)
+                                .unwrap_or_default();
+
+    if !online {
+        if !wifi {
+            notify("Establishing a network connection.");
+            println!("{}", json!({"type": "setWifi", "enable": true}));
+        } else {
+            notify("Waiting for the network to come up.");
+        }
+        let mut line = String::new();
+        io::stdin().read_line(&mut line)?;
+    }
+
+    if !save_path.exists() {
+        fs::create_dir(&save_path)?;
+    }
+
+    let sigterm = Arc::new(AtomicBool::new(false));
+    signal_hook::flag::register(signal_hook::consts::SIGTERM, Arc::clone(&sigterm))?;
+
+    let client = Client::new();
+    let now = Local::now();
+    let mut digest = EpubBuilder::new(&format!("digest:{}", now.format("%Y%m%d%H%M%S")),
+                                      &format!("Digest of {}", now.format("%B %-d, %Y")));
+    digest.author = "Various".to_string();
+    digest.date = Some(now.format("%Y-%m-%d").to_string());
+    let mut entries_count = 0;
+
+    for (index, feed_settings) in settings.feeds.iter().enumerate() {
+        if sigterm.load(Ordering::Relaxed) {
+            break;
+        }
+
+        let name = feed_settings.name.as_deref().unwrap_or(&feed_settings.url);
+        progress(&format!("Fetching {}.", name), index as f32 / settings.feeds.len() as f32);
+
+        let feed = match client.get(&feed_settings.url).send()
+                               .and_then(|response| response.error_for_status())
+                               .and_then(|response| response.text())
+                               .map_err(Error::from)
+                               .and_then(|text| feed::parse(&text)) {
+            Ok(feed) => feed,
+            Err(e) => {
+                eprintln!("Can't fetch {}: {:#}.", feed_settings.url, e);
+                continue;
+            },
+        };
+
+        let title = feed_settings.name.clone()
+                                 .filter(|name| !name.is_empty())
+                                 .or_else(|| Some(feed.title.clone()).filter(|title| !title.is_empty()))
+                                 .unwrap_or_else(|| feed_settings.url.clone());
+        let seen = session.seen.get(&feed_settings.url);
+        let mut entries: Vec<&Entry> = feed.entries.iter()
+                                           .filter(|entry| seen.is_none_or(|seen| !seen.contains(&entry.id)))
+                                           .collect();
+        entries.sort_by_key(|entry| entry.published);
+        let skipped = entries.len().saturating_sub(settings.max_entries);
+        entries.drain(..skipped);
+
+        if !entries.is_empty() {
+            match settings.group_by {
+                GroupBy::Feed => {
+                    let mut builder = EpubBuilder::new(&format!("feed:{}:{}", feed_settings.url, now.format("%Y%m%d%H%M%S")),
+                                                       &format!("{} — {}", title, now.format("%B %-d, %Y")));
+                    builder.author = title.clone();
+                    builder.source = Some(feed_settings.url.clone());
+                    builder.date = Some(now.format("%Y-%m-%d").to_string());
+                    for entry in &entries {
+                        add_entry(&mut builder, entry, 0, &client, &settings);
+                    }
+                    if let Err(e) = save(&builder, &slug(&title), &library_path, &save_path) {
+                        eprintln!("Can't save {}: {:#}.", title, e);
+                        continue;
+                    }
+                },
+                GroupBy::Day => {
+                    digest.add_chapter(&title, format!("<h1>{}</h1>", escape(&title)), 0);
+                    for entry in &entries {
+                        add_entry(&mut digest, entry, 1, &client, &settings);
+                    }
+                },
+            }
+            entries_count += entries.len();
+        }
+
+        // The entries that aren't in the feed anymore won't come back.
+        session.seen.insert(feed_settings.url.clone(),
+                            feed.entries.iter().map(|entry| entry.id.clone()).collect());
+    }
+
+    progress("Fetching feeds.", 1.0);
+
+    if !digest.is_empty() {
+        save(&digest, "digest", &library_path, &save_path)
+            .with_context(|| format!("can't save {}", digest.title))?;
+    }
+
+    let message = if entries_count > 0 {
+        format!("Downloaded {} entr{}.", entries_count, if entries_count != 1 { "ies" } else { "y" })
+    } else {
+        "No new entries.".to_string()
+    };
+    notify(&message);
+
+    if !wifi {
+        println!("{}", json!({"type": "setWifi", "enable": false}));
+    }
+
+    save_json(&session, SESSION_PATH).context("can't save session")?;
+    Ok(())
+}
@@ -0,0 +1,428 @@
+// Turns arbitrary HTML into a well-formed XHTML fragment.
+//
+// Only a subset of the elements and attributes is kept: the other elements are dropped,
+// but their content is kept, unless they're part of `SKIPPED_ELEMENTS`.
+
+use plato_core::helpers::{decode_entities, escape};
+
+const SKIPPED_ELEMENTS: [&str; 16] = ["script", "style", "noscript", "iframe", "object", "embed",
+                                      "svg", "math", "form", "button", "select", "textarea",
+                                      "template", "head", "title", "video"];
+
+const VOID_ELEMENTS: [&str; 3] = ["br", "hr", "img"];
+
+const BLOCK_ELEMENTS: [&str; 20] = ["p", "div", "h1", "h2", "h3", "h4", "h5", "h6", "ul", "ol",
+                                    "dl", "pre", "blockquote", "table", "figure", "hr",
+                                    "section", "article", "header", "footer"];
+
+const KEPT_ELEMENTS: [&str; 44] = ["p", "div", "br", "hr", "h1", "h2", "h3", "h4", "h5", "h6",
+                                   "blockquote", "pre", "code", "em", "i", "strong", "b", "u",
+                                   "s", "sub", "sup", "small", "ul", "ol", "li", "dl", "dt", "dd",
+                                   "a", "img", "figure", "figcaption", "table", "thead", "tbody",
+                                   "tfoot", "tr", "td", "th", "caption", "q", "cite", "abbr", "del"];
+
+// Elements replaced by a *div*.
+const SECTIONING_ELEMENTS: [&str; 6] = ["section", "article", "header", "footer", "main", "aside"];
+
+// Cleans *html*. The sources of the images are given to *image*, which returns the new
+// source of each image, or `None` if the image should be dropped.
+pub fn clean<F>(html: &str, mut image: F) -> String where F: FnMut(&str) -> Option<String> {
+    let mut buf = String::with_capacity(html.len());
+    let mut stack: Vec<&'static str> = Vec::new();
+    let mut cursor = html;
+
+    while !cursor.is_empty() {
+        if let Some(rest) = cursor.strip_prefix("<!--") {
+            cursor = rest.find("-->").map_or("", |index| &rest[index+3..]);
+        } else if cursor.starts_with("<!") || cursor.starts_with("<?") {
+            cursor = cursor.find('>').map_or("", |index| &cursor[index+1..]);
+        } else if let Some(rest) = cursor.strip_prefix("</") {
+            let end = rest.find('>').unwrap_or(rest.len());
+            let name = rest[..end].trim().to_ascii_lowercase();
+            cursor = rest.get(end+1..).unwrap_or("");
+            if let Some(name) = kept_name(&name) {
+                if let Some(index) = stack.iter().rposition(|&n| n == name) {
+                    for name in stack.drain(index..).rev() {
+                        close(&mut buf, name);
+                    }
+                }
+            }
+        } else if cursor.starts_with('<') && cursor[1..].starts_with(|c: char| c.is_ascii_alphabetic()) {
+            let (tag, rest) = parse_tag(&cursor[1..]);
+            cursor = rest;
+            if SKIPPED_ELEMENTS.contains(&tag.name.as_str()) {
+                if !tag.self_closing {
+                    cursor = skip_element(cursor, &tag.name);
+                }
+                continue;
+            }
+            let name = match kept_name(&tag.name) {
+                Some(name) => name,
+                None => continue,
+            };
+            implicit_close(&mut buf, &mut stack, name);
+            let mut attrs = String::new();
+            for (key, value) in &tag.attributes {
+                match (name, key.as_str()) {
+                    ("a", "href") if value.starts_with("http://") || value.starts_with("https://") => (),
+                    ("img", "alt") | ("td", "colspan") | ("td", "rowspan") |
+                    ("th", "colspan") | ("th", "rowspan") | ("ol", "start") => (),
+                    _ => continue,
+                }
+                attrs.push_str(&format!(" {}=\"{}\"", key, escape(value)));
+            }
+            if name == "img" {
+                let src = tag.attributes.iter()
+                             .find(|(key, _)| key == "src")
+                             .and_then(|(_, value)| image(value));
+                if let Some(src) = src {
+                    if !attrs.contains(" alt=") {
+                        attrs.push_str(" alt=\"\"");
+                    }
+                    buf.push_str(&format!("<img src=\"{}\"{}/>", escape(&src), attrs));
+                }
+            } else if VOID_ELEMENTS.contains(&name) {
+                buf.push_str(&format!("<{}{}/>", name, attrs));
+            } else {
+                buf.push_str(&format!("<{}{}>", name, attrs));
+                if !tag.self_closing {
+                    stack.push(name);
+                } else {
+                    close(&mut buf, name);
+                }
+            }
+        } else {
+            let end = cursor.char_indices().skip(1)
+                            .find(|&(_, c)| c == '<')
+                            .map_or(cursor.len(), |(index, _)| index);
+            buf.push_str(&escape(&decode_entities(&cursor[..end])));
+            cursor = &cursor[end..];
+        }
+    }
+
+    for name in stack.drain(..).rev() {
+        close(&mut buf, name);
+    }
+
+    buf
+}
+
+struct Tag {
+    name: String,
+    attributes: Vec<(String, String)>,
+    self_closing: bool,
+}
+
+// Parses the content of a start tag, after the `<`.
+fn parse_tag(input: &str) -> (Tag, &str) {
+    let end = input.find(|c: char| c.is_whitespace() || c == '>' || c == '/')
+                   .unwrap_or(input.len());
+    let name = input[..end].to_ascii_lowercase();
+    let mut cursor = &input[end..];
+    let mut attributes = Vec::new();
+    let mut self_closing = false;
+
+    loop {
+        cursor = cursor.trim_start();
+        if cursor.is_empty() {
+            break;
+        } else if let Some(rest) = cursor.strip_prefix('>') {
+            cursor = rest;
+            break;
+        } else if let Some(rest) = cursor.strip_prefix('/') {
+            self_closing = rest.starts_with('>');
+            cursor = rest;
+            continue;
+        }
+        let end = cursor.find(|c: char| c.is_whitespace() || c == '=' || c == '>' || c == '/')
+                        .unwrap_or(cursor.len()).max(1);
+        let key = cursor[..end].to_ascii_lowercase();
+        cursor = cursor[end..].trim_start();
+        let mut value = String::new();
+        if let Some(rest) = cursor.strip_prefix('=') {
+            let rest = rest.trim_start();
+            if let Some(quote) = rest.chars().next().filter(|&c| c == '"' || c == '\'') {
+                let rest = &rest[1..];
+                let end = rest.find(quote).unwrap_or(rest.len());
+                value = decode_entities(&rest[..end]).into_owned();
+                cursor = rest.get(end+1..).unwrap_or("");
+            } else {
+                let end = rest.find(|c: char| c.is_whitespace() || c == '>')
+                              .unwrap_or(rest.len());
+                value = decode_entities(&rest[..end]).into_owned();
+                cursor = &rest[end..];
+            }
+        }
+        attributes.push((key, value));
+    }
+
+    (Tag { name, attributes, self_closing }, cursor)
+}
+
+// Skips the content of the element *name*, and its closing tag.
+fn skip_element<'a>(input: &'a str, name: &str) -> &'a str {
+    let pattern = format!("</{}", name);
+    let mut offset = 0;
+    while let Some(index) = input[offset..].find('<') {
+        let start = offset + index;
+        if input.get(start..start+pattern.len())
+                .is_some_and(|prefix| prefix.eq_ignore_ascii_case(&pattern)) {
+            return input[start..].find('>').map_or("", |end| &input[start+end+1..]);
+        }
+        offset = start + 1;
+    }
+    ""
+}
+
+fn kept_name(name: &str) -> Option<&'static str> {
+    if SECTIONING_ELEMENTS.contains(&name) {
+        return Some("div");
+    }
+    KEPT_ELEMENTS.iter().find(|&&n| n == name).copied()
+}
+
+// Closes the elements that can't contain *name*.
+fn implicit_close(buf: &mut String, stack: &mut Vec<&'static str>, name: &'static str) {
+    let siblings: &[&str] = match name {
+        "li" => &["li"],
+        "dt" | "dd" => &["dt", "dd"],
+        "tr" => &["tr", "td", "th"],
+        "td" | "th" => &["td", "th"],
+        _ if BLOCK_ELEMENTS.contains(&name) => &["p"],
+        _ => &[],
+    };
+    // Don't look past the enclosing container.
+    let limit = stack.iter().rposition(|&n| matches!(n, "ul" | "ol" | "dl" | "table" | "tbody" |
+                                                         "thead" | "tfoot" | "blockquote" | "div"))
+                     .map_or(0, |index| index + 1);
+    if let Some(index) = stack[limit..].iter().rposition(|n| siblings.contains(n)) {
+        for name in stack.drain(limit+index..).rev() {
+            close(buf, name);
+        }
+    }
+}
+
+fn close(buf: &mut String, name: &str) {
+    buf.push_str(&format!("</{}>", name));
+}
+
+// Returns the attributes of the first start tag named *name* whose attributes satisfy *f*.
+fn find_tag<F>(html: &str, name: &str, mut f: F) -> Option<Vec<(String, String)>>
+        where F: FnMut(&[(String, String)]) -> bool {
+    let lowercase = html.to_ascii_lowercase();
+    let pattern = format!("<{}", name);
+    let mut offset = 0;
+    while let Some(index) = lowercase[offset..].find(&pattern) {
+        let start = offset + index + 1;
+        offset = start;
+        if !html[start+name.len()..].starts_with(|c: char| c.is_whitespace() || c == '>' || c == '/') {
+            continue;
+        }
+        let (tag, _) = parse_tag(&html[start..]);
+        if f(&tag.attributes) {
+            return Some(tag.attributes);
+        }
+    }
+    None
+}
+
+fn attribute<'a>(attributes: &'a [(String, String)], key: &str) -> Option<&'a str> {
+    attributes.iter().find(|(k, _)| k == key).map(|(_, v)| v.as_str())
+}
+
+// The title of an HTML document.
+pub fn title(html: &str) -> Option<String> {
+    if let Some(title) = meta(html, "og:title") {
+        return Some(title);
+    }
+    let lowercase = html.to_ascii_lowercase();
+    let start = lowercase.find("<title")?;
+    let start = start + lowercase[start..].find('>')? + 1;
+    let end = start + lowercase[start..].find("</title")?;
+    Some(decode_entities(html[start..end].trim()).into_owned()).filter(|title| !title.is_empty())
+}
+
+// The content of the *meta* element whose *name* or *property* is *key*.
+pub fn meta(html: &str, key: &str) -> Option<String> {
+    let attributes = find_tag(html, "meta", |attributes| {
+        attribute(attributes, "name").or_else(|| attribute(attributes, "property"))
+                                     .is_some_and(|name| name.eq_ignore_ascii_case(key))
+    })?;
+    attribute(&attributes, "content").map(|content| content.trim().to_string())
+                                     .filter(|content| !content.is_empty())
+}
+
+// The language of an HTML document.
+pub fn language(html: &str) -> Option<String> {
+    let attributes = find_tag(html, "html", |_| true)?;
+    attribute(&attributes, "lang").map(String::from)
+}
+
+// The main part of an HTML document: its first *article* or *main* element, if any.
+pub fn main_content(html: &str) -> &str {
+    let lowercase = html.to_ascii_lowercase();
+    for name in ["article", "main"] {
+        let start = lowercase.match_indices(&format!("<{}", name))
+                             .map(|(index, _)| index)
+                             .find(|&index| lowercase[index+name.len()+1..].starts_with(|c: char| c.is_whitespace() || c == '>'));
+        if let (Some(start), Some(end)) = (start, lowercase.rfind(&format!("</{}", name))) {
+            if start < end {
+                return &html[start..end];
+            }
+        }
+    }
+    html
+}
+
+// A text node of an HTML document.
+#[derive(Debug, Clone)]
+pub struct TextNode {
+    // The XPath of the parent element, relative to the root of the document.
+    pub path: String,
+    // The character offset of the node within the text of its parent.
+    pub position: usize,
+    // The decoded characters of the node, with their byte offsets in the document.
+    pub chars: Vec<(usize, char)>,
+}
+
+// Returns the text nodes of *html*.
+pub fn text_nodes(html: &str) -> Vec<TextNode> {
+    struct Element {
+        name: String,
+        path: String,
+        // The number of characters in the element.
+        length: usize,
+        // The number of children with a given name.
+        counts: Vec<(String, usize)>,
+    }
+
+    let mut nodes = Vec::new();
+    let mut stack = vec![Element { name: String::new(), path: String::new(), length: 0, counts: Vec::new() }];
+    let mut offset = 0;
+
+    while offset < html.len() {
+        let cursor = &html[offset..];
+        if let Some(rest) = cursor.strip_prefix("<!--") {
+            offset = rest.find("-->").map_or(html.len(), |index| offset + 4 + index + 3);
+        } else if cursor.starts_with("<!") || cursor.starts_with("<?") {
+            offset = cursor.find('>').map_or(html.len(), |index| offset + index + 1);
+        } else if let Some(rest) = cursor.strip_prefix("</") {
+            let end = rest.find('>').unwrap_or(rest.len());
+            let name = rest[..end].trim().to_ascii_lowercase();
+            if let Some(index) = stack.iter().rposition(|e| e.name == name).filter(|&index| index > 0) {
+                stack.truncate(index);
+            }
+            offset = (offset + 2 + end + 1).min(html.len());
+        } else if cursor.starts_with('<') && cursor[1..].starts_with(|c: char| c.is_ascii_alphabetic()) {
+            let (tag, rest) = parse_tag(&cursor[1..]);
+            offset = html.len() - rest.len();
+            if tag.self_closing || matches!(tag.name.as_str(), "br" | "hr" | "img" | "input" | "meta" |
+                                                                "link" | "source" | "wbr" | "area" | "col") {
+                continue;
+            }
+            let parent = stack.last_mut().unwrap();
+            let count = match parent.counts.iter_mut().find(|(name, _)| *name == tag.name) {
+                Some((_, count)) => { *count += 1; *count },
+                None => { parent.counts.push((tag.name.clone(), 1)); 1 },
+            };
+            let path = format!("{}/{}[{}]", parent.path, tag.name, count);
+            stack.push(Element { name: tag.name, path, length: 0, counts: Vec::new() });
+        } else {
+            let end = cursor.char_indices().skip(1)
+                            .find(|&(_, c)| c == '<')
+                            .map_or(cursor.len(), |(index, _)| index);
+            let mut chars = Vec::new();
+            let mut text = cursor[..end].char_indices().peekable();
+            while let Some((index, c)) = text.next() {
+                if c == '&' {
+                    if let Some(length) = cursor[index..end].find(';').filter(|&length| length <= 32) {
+                        let decoded = decode_entities(&cursor[index..=index+length]);
+                        if decoded.len() != length + 1 {
+                            chars.extend(decoded.chars().map(|c| (offset + index, c)));
+                            while text.next_if(|&(i, _)| i <= index + length).is_some() {}
+                            continue;
+                        }
+                    }
+                }
+                chars.push((offset + index, c));
+            }
+            let parent = stack.last().unwrap();
+            let node = TextNode { path: parent.path.clone(), position: parent.length, chars };
+            for element in &mut stack {
+                element.length += node.chars.len();
+            }
+            nodes.push(node);
+            offset += end;
+        }
+    }
+
+    nodes
+}
+
+// Finds *quote* in the given text nodes, ignoring white space, and returns the node and
+// character indices of its first and last characters.
+pub fn find_quote(nodes: &[TextNode], quote: &str) -> Option<[(usize, usize); 2]> {
+    let mut text = String::new();
+    let mut indices = Vec::new();
+    for (i, node) in nodes.iter().enumerate() {
+        for (j, &(_, c)) in node.chars.iter().enumerate() {
+            if !c.is_whitespace() {
+                text.push(c);
+                indices.push((i, j));
+            }
+        }
+    }
+    let quote: String = quote.chars().filter(|c| !c.is_whitespace()).collect();
+    if quote.is_empty() {
+        return None;
+    }
+    let start = text.find(&quote)?;
+    let start = text[..start].chars().count();
+    let end = start + quote.chars().count() - 1;
+    Some([indices[start], indices[end]])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_clean() {
+        let html = r#"<p class="x">A<br>B &nbsp;&amp; <span>C</span><script>alert("<p>")</script>
+                      <p>D<img src="a.png" width=3><img src="b.png"></p><ul><li>E<li>F</ul>
+                      <a href="https://example.org/" onclick="x()">G</a><a href="/rel">H</a>"#;
+        let result = clean(html, |src| Some(src).filter(|&src| src == "a.png")
+                                                .map(|_| "images/1.png".to_string()));
+        assert_eq!(result, "<p>A<br/>B \u{a0}&amp; C\n                      </p>\
+                            <p>D<img src=\"images/1.png\" alt=\"\"/></p><ul><li>E</li><li>F</li></ul>\n                      \
+                            <a href=\"https://example.org/\">G</a><a>H</a>");
+    }
+
+    #[test]
+    fn test_metadata() {
+        let html = r#"<!DOCTYPE html><html lang="fr"><head><title> A &amp; B </title>
+                      <meta name="Author" content="Jane"><meta property="og:site_name" content="Site">
+                      </head><body><nav>Menu</nav><article class="post"><p>Text</p></article></body></html>"#;
+        assert_eq!(title(html).as_deref(), Some("A & B"));
+        assert_eq!(meta(html, "author").as_deref(), Some("Jane"));
+        assert_eq!(meta(html, "og:site_name").as_deref(), Some("Site"));
+        assert_eq!(language(html).as_deref(), Some("fr"));
+        assert_eq!(main_content(html), r#"<article class="post"><p>Text</p>"#);
+        assert_eq!(main_content("<p>Text</p>"), "<p>Text</p>");
+    }
+
+    #[test]
+    fn test_find_quote() {
+        let html = "<div><p>One</p><p>Two &amp;\n<b>three</b> four</p><br><p>Five</p></div>";
+        let nodes = text_nodes(html);
+        let [start, end] = find_quote(&nodes, "& three four Fi").unwrap();
+        assert_eq!(nodes[start.0].path, "/div[1]/p[2]");
+        assert_eq!(nodes[start.0].position + start.1, 4);
+        assert_eq!(nodes[start.0].chars[start.1], (22, '&'));
+        assert_eq!(nodes[end.0].path, "/div[1]/p[3]");
+        assert_eq!(nodes[end.0].position + end.1, 1);
+        assert_eq!(nodes[2].path, "/div[1]/p[2]/b[1]");
+        assert_eq!(nodes[3].position, 11);
+        assert!(find_quote(&nodes, "Six").is_none());
+    }
+}
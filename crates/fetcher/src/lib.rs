@@ -0,0 +1,5 @@
+pub mod html;
+pub mod epub;
+pub mod feed;
+pub mod net;
+pub mod backend;
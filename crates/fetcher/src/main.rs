@@ -1,120 +1,224 @@
 use std::io;
 use std::env;
-use std::fs::{self, File};
-use std::path::PathBuf;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::collections::{BTreeMap, BTreeSet};
 use std::sync::Arc;
 use std::sync::atomic::{AtomicBool, Ordering};
-use plato_core::chrono::{Duration, Utc, Local, DateTime};
+use plato_core::chrono::{Local, TimeZone};
 use plato_core::serde::{Serialize, Deserialize};
 use plato_core::serde_json::{self, json, Value as JsonValue};
 use reqwest::blocking::Client;
 use plato_core::anyhow::{Error, Context, format_err};
-use plato_core::helpers::{load_toml, load_json, save_json, decode_entities};
+use plato_core::helpers::{load_toml, load_json, save_json};
+use plato_core::helpers::datetime_format::FORMAT as DATETIME_FORMAT;
+use plato_core::metadata::Annotation;
+use plato_core::document::TextLocation;
+use fetcher::epub::EpubBuilder;
+use fetcher::html;
+use plato_core::helpers::escape;
+use fetcher::net::{download, embed_image, MAX_PAGE_SIZE};
+use fetcher::backend::{Backend, BackendKind, Entry, Token};
+use fetcher::backend::{Wallabag, WallabagSettings, Readeck, ReadeckSettings, Folder, FolderSettings};
 
 const SETTINGS_PATH: &str = "Settings.toml";
 const SESSION_PATH: &str = ".session.json";
 const URLS_PATH: &str = "urls.txt";
-// Nearly RFC 3339
-const DATE_FORMAT: &str = "%FT%T%z";
 
-#[derive(Default, Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(crate = "plato_core::serde")]
 #[serde(default, rename_all = "kebab-case")]
 struct Settings {
-    base_url: String,
-    username: String,
-    password: String,
-    client_id: String,
-    client_secret: String,
+    backend: BackendKind,
+    // The settings of wallabag are at the top level.
+    #[serde(flatten)]
+    wallabag: WallabagSettings,
+    readeck: ReadeckSettings,
+    folder: FolderSettings,
     sync_finished: bool,
+    // Delete the finished articles from the service instead of archiving them.
+    delete_finished: bool,
     remove_finished: bool,
     balance_limit: usize,
+    // Download the EPUB exported by the service instead of building it.
+    server_export: bool,
+    download_images: bool,
+    sync_annotations: bool,
+    sync_tags: bool,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            backend: BackendKind::Wallabag,
+            wallabag: WallabagSettings::default(),
+            readeck: ReadeckSettings::default(),
+            folder: FolderSettings::default(),
+            sync_finished: false,
+            delete_finished: false,
+            remove_finished: false,
+            balance_limit: 0,
+            server_export: false,
+            download_images: true,
+            sync_annotations: false,
+            sync_tags: false,
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(crate = "plato_core::serde")]
 #[serde(default, rename_all = "camelCase")]
 struct Session {
+    // The backend the entries come from.
+    backend: Option<BackendKind>,
     since: i64,
     access_token: Token,
     downloads_count: usize,
     removals_count: usize,
     last_opened: String,
-}
-
-#[derive(Debug, Clone, Serialize, Deserialize)]
-#[serde(crate = "plato_core::serde")]
-#[serde(default, rename_all = "camelCase")]
-struct Token {
-    data: String,
-    valid_until: DateTime<Utc>,
-}
-
-impl Default for Token {
-    fn default() -> Self {
-        Token {
-            data: String::default(),
-            valid_until: Utc::now(),
-        }
-    }
+    // The most recent modification of the annotations sent to the server.
+    last_annotated: String,
+    // The tags of each entry, as known by the server.
+    tags: BTreeMap<String, BTreeSet<String>>,
 }
 
 impl Default for Session {
     fn default() -> Self {
         Session {
+            backend: None,
             since: 0,
             access_token: Token::default(),
             downloads_count: 0,
             removals_count: 0,
             last_opened: "0000-00-00 00:00:00".to_string(),
+            last_annotated: "0000-00-00 00:00:00".to_string(),
+            tags: BTreeMap::new(),
         }
     }
 }
 
-fn update_token(client: &Client, session: &mut Session, settings: &Settings) -> Result<(), Error> {
-    let query = json!({
-        "grant_type": "password",
-        "client_id": &settings.client_id,
-        "client_secret": &settings.client_secret,
-        "username": &settings.username,
-        "password": &settings.password,
+// Builds a self-contained EPUB from the content of the entry, and returns the highlights
+// of the server that were found in the content.
+fn build_epub(client: &Client, backend: &mut dyn Backend, settings: &Settings,
+              entry: &mut Entry, epub_path: &Path) -> Result<Vec<Annotation>, Error> {
+    let content = backend.download(entry)?;
+
+    // The service couldn't extract the article: fetch the page ourselves.
+    let content = if content.trim().is_empty() {
+        let data = download(client, &entry.url, MAX_PAGE_SIZE)?;
+        html::main_content(&String::from_utf8_lossy(&data)).to_string()
+    } else {
+        content
+    };
+
+    let mut builder = EpubBuilder::new(&format!("{}:{}", backend_name(settings.backend), entry.id), &entry.title);
+    builder.author = entry.author.clone();
+    builder.date = Some(entry.year.clone()).filter(|year| !year.is_empty());
+    builder.source = Some(entry.url.clone()).filter(|url| !url.is_empty());
+    builder.subjects = entry.tags.clone();
+    if !entry.language.is_empty() {
+        builder.language = entry.language.split(['_', '-']).next().unwrap_or(&entry.language).to_string();
+    }
+
+    let base = backend.base(entry);
+    let content = html::clean(&content, |src| {
+        if !settings.download_images {
+            return None;
+        }
+        embed_image(&mut builder, client, base.as_ref(), src)
     });
 
-    let url = format!("{}/oauth/v2/token", &settings.base_url);
-
-    let response = client.post(&url)
-                         .json(&query)
-                         .send()?;
-    let status = response.status();
-    let body: JsonValue = response.json()?;
-
-    if status.is_success() {
-        session.access_token = Token {
-            data: body.get("access_token")
-                      .and_then(|v| v.as_str())
-                      .map(String::from)
-                      .ok_or_else(|| format_err!("missing access token"))?,
-            valid_until: body.get("expires_in")
-                             .and_then(|v| v.as_i64())
-                             .map(|d| Utc::now() + Duration::seconds(d))
-                             .ok_or_else(|| format_err!("missing expires in"))?,
-        };
-        Ok(())
+    let mut meta = Vec::new();
+    if !entry.author.is_empty() {
+        meta.push(escape(&entry.author).into_owned());
+    }
+    if entry.reading_time > 0 {
+        meta.push(format!("{} min", entry.reading_time));
+    }
+    if entry.url.starts_with("http://") || entry.url.starts_with("https://") {
+        let host = reqwest::Url::parse(&entry.url).ok()
+                                .and_then(|url| url.host_str().map(String::from))
+                                .unwrap_or_else(|| entry.url.clone());
+        meta.push(format!("<a href=\"{}\">{}</a>", escape(&entry.url), escape(&host)));
+    }
+    if !entry.tags.is_empty() {
+        meta.push(escape(&entry.tags.join(", ")).into_owned());
+    }
+
+    let title = if entry.title.is_empty() { "Untitled" } else { &entry.title };
+    let mut body = format!("<h1>{}</h1>\n", escape(title));
+    if !meta.is_empty() {
+        body.push_str(&format!("<p class=\"meta\">{}</p>\n", meta.join(" · ")));
+    }
+    body.push_str(&content);
+
+    let highlights = if settings.sync_annotations {
+        backend.highlights(&entry.id)
+               .map_err(|e| eprintln!("Can't get annotations of {}: {:#}.", entry.id, e))
+               .unwrap_or_default()
     } else {
-        let err_desc = body.get("error_description")
-                           .and_then(JsonValue::as_str)
-                           .or_else(|| status.canonical_reason())
-                           .unwrap_or_else(|| status.as_str());
-        Err(format_err!("failed to authentificate: {}", err_desc))
+        Vec::new()
+    };
+    let nodes = if highlights.is_empty() { Vec::new() } else { html::text_nodes(&body) };
+
+    builder.add_chapter(title, body, 0);
+    builder.write(epub_path)?;
+
+    let annotations = highlights.iter().filter_map(|highlight| {
+        let [start, end] = html::find_quote(&nodes, &highlight.quote)?;
+        let start = builder.location(0, nodes[start.0].chars[start.1].0);
+        let end = builder.location(0, nodes[end.0].chars[end.1].0);
+        Some(Annotation {
+            note: highlight.note.clone(),
+            text: highlight.quote.clone(),
+            selection: [TextLocation::Dynamic(start), TextLocation::Dynamic(end)],
+            .. Default::default()
+        })
+    }).collect();
+
+    Ok(annotations)
+}
+
+fn backend_name(kind: BackendKind) -> &'static str {
+    match kind {
+        BackendKind::Wallabag => "wallabag",
+        BackendKind::Readeck => "readeck",
+        BackendKind::Folder => "folder",
+    }
+}
+
+// The identifiers of the library entries are prefixed with the name of their backend,
+// except for wallabag, whose entries predate the other backends.
+fn entry_identifier(kind: BackendKind, id: &str) -> String {
+    match kind {
+        BackendKind::Wallabag => id.to_string(),
+        _ => format!("{}:{}", backend_name(kind), id),
     }
 }
 
-// The *detail* parameter is only available in 2.4.0 and up.
-fn is_detail_available(client: &Client, settings: &Settings) -> bool {
-    // /api/info is only available in 2.4.0 and up.
-    let url = format!("{}/api/info", settings.base_url);
-    client.get(&url).send()
-          .map_or(false, |response| response.status().is_success())
+// Returns the id of the entry with the given identifier within the backend, if it belongs to it.
+fn backend_id(kind: BackendKind, identifier: &str) -> Option<&str> {
+    let owner = [BackendKind::Readeck, BackendKind::Folder].into_iter().find_map(|other| {
+        identifier.strip_prefix(backend_name(other))
+                  .and_then(|rest| rest.strip_prefix(':'))
+                  .map(|id| (other, id))
+    }).unwrap_or((BackendKind::Wallabag, identifier));
+    Some(owner.1).filter(|id| owner.0 == kind && !id.is_empty())
+}
+
+fn epub_name(kind: BackendKind, id: &str) -> String {
+    match kind {
+        BackendKind::Wallabag => format!("{}.epub", id),
+        _ => format!("{}-{}.epub", backend_name(kind), id),
+    }
+}
+
+// The reading state of the search results is sent as *readerInfo*.
+fn entry_annotations(entry: &JsonValue) -> Vec<Annotation> {
+    entry.pointer("/readerInfo/annotations")
+         .and_then(|v| serde_json::from_value::<Vec<Annotation>>(v.clone()).ok())
+         .unwrap_or_default()
 }
 
 fn main() -> Result<(), Error> {
@@ -134,6 +238,15 @@ fn main() -> Result<(), Error> {
     let mut session = load_json::<Session, _>(SESSION_PATH)
                                 .unwrap_or_default();
 
+    // The entries of the previous backend don't tell anything about the current one.
+    if session.backend != Some(settings.backend) {
+        if session.backend.is_some() {
+            session.since = 0;
+            session.tags.clear();
+        }
+        session.backend = Some(settings.backend);
+    }
+
     if !online {
         if !wifi {
             let event = json!({
@@ -163,31 +276,19 @@ fn main() -> Result<(), Error> {
 
     let client = Client::new();
 
-    if session.access_token.valid_until <= Utc::now() {
-        update_token(&client, &mut session, &settings)?;
-    }
+    let mut backend: Box<dyn Backend> = match settings.backend {
+        BackendKind::Wallabag => Box::new(Wallabag::connect(&client, &settings.wallabag, &mut session.access_token)?),
+        BackendKind::Readeck => Box::new(Readeck::new(&client, &settings.readeck)),
+        BackendKind::Folder => Box::new(Folder::new(&client, &settings.folder)),
+    };
 
     let sigterm = Arc::new(AtomicBool::new(false));
     signal_hook::flag::register(signal_hook::consts::SIGTERM, Arc::clone(&sigterm))?;
 
     if let Ok(contents) = fs::read_to_string(URLS_PATH) {
-        for line in contents.lines() {
-            let query = json!({"url": line});
-            let url = format!("{}/api/entries", &settings.base_url);
-            let response = client.post(&url)
-                                 .header(reqwest::header::AUTHORIZATION,
-                                         format!("Bearer {}", &session.access_token.data))
-                                 .json(&query)
-                                 .send();
-            let response = response.unwrap();
-            if !response.status().is_success() {
-                let status = response.status();
-                let body: JsonValue = response.json()?;
-                let err_desc = body.get("error_description")
-                                   .and_then(JsonValue::as_str)
-                                   .or_else(|| status.canonical_reason())
-                                   .unwrap_or_else(|| status.as_str());
-                eprintln!("Can't add {}: {}.", line, err_desc);
+        for line in contents.lines().map(str::trim).filter(|line| !line.is_empty()) {
+            if let Err(e) = backend.add(line) {
+                eprintln!("Can't add {}: {:#}.", line, e);
             }
         }
     }
@@ -228,27 +329,18 @@ fn main() -> Result<(), Error> {
                     }
 
                     if settings.sync_finished {
+                        // The entries of the other backends are skipped.
                         if let Some(id) = entry.get("identifier")
                                                .and_then(JsonValue::as_str)
-                                               .and_then(|v| v.parse::<u64>().ok()) {
-                            let url = format!("{}/api/entries/{}", &settings.base_url, id);
-                            let query = json!({"archive": 1});
-                            let response = client.patch(&url)
-                                                 .header(reqwest::header::AUTHORIZATION,
-                                                         format!("Bearer {}", &session.access_token.data))
-                                                 .json(&query)
-                                                 .send();
-                            let response = response.unwrap();
-                            if response.status().is_success() {
-                                archivals_count += 1;
+                                               .and_then(|v| backend_id(settings.backend, v)) {
+                            let result = if settings.delete_finished {
+                                backend.delete(id)
                             } else {
-                                let status = response.status();
-                                let body: JsonValue = response.json()?;
-                                let err_desc = body.get("error_description")
-                                                   .and_then(JsonValue::as_str)
-                                                   .or_else(|| status.canonical_reason())
-                                                   .unwrap_or_else(|| status.as_str());
-                                eprintln!("Can't mark {} as read: {}.", id, err_desc);
+                                backend.archive(id)
+                            };
+                            match result {
+                                Ok(()) => archivals_count += 1,
+                                Err(e) => eprintln!("Can't mark {} as read: {:#}.", id, e),
                             }
                         }
                     }
@@ -303,170 +395,185 @@ fn main() -> Result<(), Error> {
         }
     }
 
-    let mut page = 1;
-    let mut pages_count = 0;
-    let last_downloads_count = session.downloads_count;
-    let url = format!("{}/api/entries", &settings.base_url);
-    let mut query = json!({
-        "since": session.since,
-        "sort": "updated",
-        "order": "asc",
-        "archive": 0,
-        "page": page,
-        "perPage": 8,
-    });
+    if settings.sync_annotations || settings.sync_tags {
+        let event = json!({
+            "type": "search",
+            "path": save_path,
+        });
+        println!("{}", event);
 
-    if is_detail_available(&client, &settings) {
-        query["perPage"] = JsonValue::from(100);
-        query["detail"] = JsonValue::from("metadata");
-    }
+        let mut line = String::new();
+        io::stdin().read_line(&mut line)?;
 
-    'outer: loop {
-        let entries: JsonValue = client.get(&url)
-                                       .header(reqwest::header::AUTHORIZATION,
-                                               format!("Bearer {}", &session.access_token.data))
-                                       .query(&query)
-                                       .send()?
-                                       .json()?;
+        let mut last_annotated = session.last_annotated.clone();
+        let mut annotations_count = 0;
+        let mut tags_count = 0;
 
-        if entries.get("total").is_none() {
-            break;
-        } else {
-            if page == 1 {
-                let total = entries.get("total")
-                                   .and_then(|v| v.as_u64())
-                                   .unwrap();
-                let message = if total == 0 {
-                    "No new articles.".to_string()
-                } else {
-                    format!("Found {} new article{}.", total, if total != 1 { "s" } else { "" })
-                };
-                let event = json!({
-                    "type": "notify",
-                    "message": &message,
-                });
-                println!("{}", event);
-                if total > 0 {
-                    pages_count = entries.get("pages")
-                                         .and_then(|v| v.as_u64())
-                                         .unwrap();
+        if let Ok(event) = serde_json::from_str::<JsonValue>(&line) {
+            if let Some(results) = event.get("results").and_then(JsonValue::as_array) {
+                for entry in results {
+                    if sigterm.load(Ordering::Relaxed) {
+                        break;
+                    }
+
+                    let id = match entry.get("identifier")
+                                        .and_then(JsonValue::as_str)
+                                        .and_then(|v| backend_id(settings.backend, v)) {
+                        Some(id) => id.to_string(),
+                        None => continue,
+                    };
+
+                    if settings.sync_annotations {
+                        let annotations = entry_annotations(entry);
+                        let modified = annotations.iter()
+                                                  .map(|annot| annot.modified.format(DATETIME_FORMAT).to_string())
+                                                  .max();
+                        if let Some(modified) = modified.filter(|m| *m > session.last_annotated) {
+                            match backend.push_annotations(&id, &annotations) {
+                                Ok(count) => {
+                                    annotations_count += count;
+                                    last_annotated = last_annotated.max(modified);
+                                },
+                                Err(e) => eprintln!("Can't send annotations of {}: {:#}.", id, e),
+                            }
+                        }
+                    }
+
+                    if settings.sync_tags {
+                        let categories = entry.get("categories")
+                                              .and_then(JsonValue::as_array)
+                                              .map(|v| v.iter().filter_map(|x| x.as_str())
+                                                               .map(String::from)
+                                                               .collect::<BTreeSet<String>>())
+                                              .unwrap_or_default();
+                        let known = session.tags.get(&id).cloned().unwrap_or_default();
+                        let added = categories.difference(&known).cloned().collect::<Vec<String>>();
+                        let removed = known.difference(&categories).cloned().collect::<Vec<String>>();
+                        if added.is_empty() && removed.is_empty() {
+                            continue;
+                        }
+                        let mut result = Ok(());
+                        if !added.is_empty() {
+                            result = backend.add_tags(&id, &added);
+                        }
+                        if result.is_ok() && !removed.is_empty() {
+                            result = backend.remove_tags(&id, &removed);
+                        }
+                        match result {
+                            Ok(()) => {
+                                tags_count += 1;
+                                session.tags.insert(id, categories);
+                            },
+                            Err(e) => eprintln!("Can't send tags of {}: {:#}.", id, e),
+                        }
+                    }
                 }
             }
         }
 
-        if let Some(items) = entries.pointer("/_embedded/items").and_then(|v| v.as_array()) {
-            for element in items {
-                if sigterm.load(Ordering::Relaxed) ||
-                    (settings.balance_limit > 0 &&
-                     session.downloads_count.saturating_sub(session.removals_count) >= settings.balance_limit) {
-                    break 'outer;
-                }
+        session.last_annotated = last_annotated;
 
-                let id = element.get("id")
-                                .and_then(JsonValue::as_u64)
-                                .ok_or_else(|| format_err!("missing id"))?;
-
-                let title = element.get("title")
-                                   .and_then(JsonValue::as_str)
-                                   .map(decode_entities)
-                                   .map(String::from)
-                                   .unwrap_or_default();
-
-                let published_by = element.get("published_by")
-                                          .and_then(JsonValue::as_array)
-                                          .map(|v| v.iter().filter_map(|x| x.as_str())
-                                                           .filter(|x| !x.is_empty())
-                                                           .collect::<Vec<&str>>())
-                                          .map(|v| v.join(", "))
-                                          .filter(|v| !v.is_empty())
-                                          .unwrap_or_default();
-                let domain_name = element.get("domain_name")
-                                         .and_then(JsonValue::as_str)
-                                         .map(String::from)
-                                         .unwrap_or_default();
-
-                let author = match (!published_by.is_empty(), !domain_name.is_empty()) {
-                    (true, true) => format!("{} ({})", published_by, domain_name),
-                    (true, false) => published_by,
-                    _ => domain_name,
-                };
+        if annotations_count > 0 {
+            let message = format!("Sent {} annotation{}.", annotations_count, if annotations_count != 1 { "s" } else { "" });
+            println!("{}", json!({"type": "notify", "message": &message}));
+        }
 
-                let year = element.get("published_at")
-                                  .filter(|v| v.is_string())
-                                  .or_else(|| element.get("created_at"))
-                                  .and_then(JsonValue::as_str)
-                                  .and_then(|v| DateTime::parse_from_str(v, DATE_FORMAT).ok())
-                                  .map(|v| v.format("%Y").to_string())
-                                  .unwrap_or_default();
+        if tags_count > 0 {
+            let message = format!("Sent the tags of {} article{}.", tags_count, if tags_count != 1 { "s" } else { "" });
+            println!("{}", json!({"type": "notify", "message": &message}));
+        }
+    }
 
-                let updated_at = element.get("updated_at")
-                                        .and_then(JsonValue::as_str)
-                                        .and_then(|v| DateTime::parse_from_str(v, DATE_FORMAT).ok())
-                                        .ok_or_else(|| format_err!("missing updated at"))?;
+    let last_downloads_count = session.downloads_count;
+    let mut entries = backend.list(session.since)?;
 
-                session.since = updated_at.timestamp();
+    let message = if entries.is_empty() {
+        "No new articles.".to_string()
+    } else {
+        format!("Found {} new article{}.", entries.len(), if entries.len() != 1 { "s" } else { "" })
+    };
+    let event = json!({
+        "type": "notify",
+        "message": &message,
+    });
+    println!("{}", event);
 
-                let epub_path = save_path.join(&format!("{}.epub", id));
-                if epub_path.exists() {
-                    continue;
-                }
+    for entry in &mut entries {
+        if sigterm.load(Ordering::Relaxed) ||
+            (settings.balance_limit > 0 &&
+             session.downloads_count.saturating_sub(session.removals_count) >= settings.balance_limit) {
+            break;
+        }
 
-                let mut file = File::create(&epub_path)?;
-                let url = format!("{}/api/entries/{}/export.epub", settings.base_url, id);
+        session.since = entry.updated;
 
-                let response = client.get(&url)
-                                     .header(reqwest::header::AUTHORIZATION,
-                                             format!("Bearer {}", &session.access_token.data))
-                                     .send()
-                                     .and_then(|mut body| body.copy_to(&mut file));
+        let epub_path = save_path.join(epub_name(settings.backend, &entry.id));
+        if epub_path.exists() {
+            continue;
+        }
 
-                if let Err(err) = response {
-                    eprintln!("Can't download {}: {:#}.", id, err);
-                    fs::remove_file(epub_path).ok();
-                    continue;
-                }
+        let result = if settings.server_export {
+            backend.export(&entry.id)
+                   .and_then(|data| fs::write(&epub_path, data).map_err(Into::into))
+                   .map(|_| Vec::new())
+        } else {
+            build_epub(&client, backend.as_mut(), &settings, entry, &epub_path)
+        };
 
-                session.downloads_count = session.downloads_count.wrapping_add(1);
-
-                if let Ok(path) = epub_path.strip_prefix(&library_path) {
-                    let file_info = json!({
-                        "path": path,
-                        "kind": "epub",
-                        "size": file.metadata().ok()
-                                    .map_or(0, |m| m.len()),
-                    });
-
-                    let info = json!({
-                        "title": title,
-                        "author": author,
-                        "year": year,
-                        "identifier": id.to_string(),
-                        "added": updated_at.with_timezone(&Local)
-                                           .format("%Y-%m-%d %H:%M:%S")
-                                           .to_string(),
-                        "file": file_info,
-                    });
-
-                    let event = json!({
-                        "type": "addDocument",
-                        "info": &info,
-                    });
-
-                    println!("{}", event);
-                }
-            }
-        }
+        let annotations = match result {
+            Ok(annotations) => annotations,
+            Err(err) => {
+                eprintln!("Can't download {}: {:#}.", entry.id, err);
+                fs::remove_file(epub_path).ok();
+                continue;
+            },
+        };
 
-        page += 1;
+        backend.downloaded(&entry.id)
+               .map_err(|e| eprintln!("Can't save the state of {}: {:#}.", entry.id, e))
+               .ok();
 
-        if page > pages_count {
-            break;
+        if settings.sync_tags {
+            session.tags.insert(entry.id.clone(), entry.tags.iter().cloned().collect());
         }
 
-        query["page"] = JsonValue::from(page);
+        session.downloads_count = session.downloads_count.wrapping_add(1);
+
+        if let Ok(path) = epub_path.strip_prefix(&library_path) {
+            let file_info = json!({
+                "path": path,
+                "kind": "epub",
+                "size": fs::metadata(&epub_path).ok()
+                                .map_or(0, |m| m.len()),
+            });
+
+            let mut info = json!({
+                "title": entry.title,
+                "author": entry.author,
+                "year": entry.year,
+                "identifier": entry_identifier(settings.backend, &entry.id),
+                "categories": entry.tags,
+                "added": Local.timestamp_opt(entry.updated, 0).single()
+                              .unwrap_or_else(Local::now)
+                              .format("%Y-%m-%d %H:%M:%S")
+                              .to_string(),
+                "file": file_info,
+            });
+
+            if !annotations.is_empty() {
+                info["reader"] = json!({"annotations": annotations});
+            }
+
+            let event = json!({
+                "type": "addDocument",
+                "info": &info,
+            });
+
+            println!("{}", event);
+        }
     }
 
-    if pages_count > 0 {
+    if !entries.is_empty() {
         let downloads_count = session.downloads_count
                                      .saturating_sub(last_downloads_count);
         let message = if downloads_count > 0 {
@@ -492,3 +599,40 @@ fn main() -> Result<(), Error> {
     save_json(&session, SESSION_PATH).context("can't save session")?;
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::mem;
+    use plato_core::metadata::{Info, ReaderInfo};
+
+    #[test]
+    fn test_backend_id() {
+        let identifier = entry_identifier(BackendKind::Readeck, "x4Tq");
+        assert_eq!(identifier, "readeck:x4Tq");
+        assert_eq!(backend_id(BackendKind::Readeck, &identifier), Some("x4Tq"));
+        assert_eq!(backend_id(BackendKind::Wallabag, &identifier), None);
+        assert_eq!(backend_id(BackendKind::Wallabag, "1234"), Some("1234"));
+        assert_eq!(backend_id(BackendKind::Folder, "1234"), None);
+        assert_eq!(backend_id(BackendKind::Folder, "folder:"), None);
+    }
+
+    #[test]
+    fn test_entry_annotations() {
+        let annotation = Annotation { text: "Highlight".to_string(), .. Default::default() };
+        let mut info = Info {
+            identifier: "1234".to_string(),
+            reader: Some(ReaderInfo { annotations: vec![annotation], .. Default::default() }),
+            .. Default::default()
+        };
+        // The search results are serialized like in *Home*.
+        mem::swap(&mut info.reader, &mut info.reader_info);
+        let line = json!({"type": "search", "results": [info]}).to_string();
+        let event = serde_json::from_str::<JsonValue>(&line).unwrap();
+        let entry = &event["results"][0];
+        let annotations = entry_annotations(entry);
+        assert_eq!(annotations.len(), 1);
+        assert_eq!(annotations[0].text, "Highlight");
+        assert!(entry_annotations(&json!({"identifier": "1234"})).is_empty());
+    }
+}
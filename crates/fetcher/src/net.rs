@@ -0,0 +1,69 @@
+// Downloads the resources referenced by the fetched documents.
+
+use std::io::Read;
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use plato_core::anyhow::{Error, format_err};
+use reqwest::Url;
+use reqwest::blocking::Client;
+use crate::epub::EpubBuilder;
+
+// Larger images are skipped.
+pub const MAX_IMAGE_SIZE: u64 = 4 * 1024 * 1024;
+// Larger pages are skipped.
+pub const MAX_PAGE_SIZE: u64 = 8 * 1024 * 1024;
+
+// Downloads at most *limit* bytes from *url*.
+pub fn download(client: &Client, url: &str, limit: u64) -> Result<Vec<u8>, Error> {
+    let response = client.get(url).send()?.error_for_status()?;
+    if response.content_length().is_some_and(|length| length > limit) {
+        return Err(format_err!("too large"));
+    }
+    let mut data = Vec::new();
+    response.take(limit + 1).read_to_end(&mut data)?;
+    if data.len() as u64 > limit {
+        return Err(format_err!("too large"));
+    }
+    Ok(data)
+}
+
+// Adds the image referenced by *src* to *builder*, and returns its path within the document.
+// The relative sources are resolved against *base*, and the *data* URIs are decoded.
+pub fn embed_image(builder: &mut EpubBuilder, client: &Client, base: Option<&Url>, src: &str) -> Option<String> {
+    if let Some(data) = decode_data_uri(src) {
+        return builder.add_image(data);
+    }
+    let url = match base {
+        Some(base) => base.join(src).ok()?,
+        None => Url::parse(src).ok()?,
+    };
+    if !matches!(url.scheme(), "http" | "https") {
+        return None;
+    }
+    download(client, url.as_str(), MAX_IMAGE_SIZE)
+        .map_err(|e| eprintln!("Can't download {}: {:#}.", url, e))
+        .ok()
+        .and_then(|data| builder.add_image(data))
+}
+
+fn decode_data_uri(src: &str) -> Option<Vec<u8>> {
+    let rest = src.strip_prefix("data:")?;
+    let (header, data) = rest.split_once(',')?;
+    if !header.ends_with(";base64") {
+        return None;
+    }
+    let data: String = data.chars().filter(|c| !c.is_whitespace()).collect();
+    STANDARD.decode(data).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode_data_uri() {
+        assert_eq!(decode_data_uri("data:image/gif;base64,R0lG\nODlh").as_deref(), Some(&b"GIF89a"[..]));
+        assert_eq!(decode_data_uri("data:image/svg+xml,<svg/>"), None);
+        assert_eq!(decode_data_uri("https://example.org/a.png"), None);
+    }
+}
@@ -0,0 +1,570 @@
+// Exports the entries of a library as CSV, JSON, an OPDS acquisition feed or a static HTML catalog.
+
+use std::fs;
+use std::io::{self, Write};
+use std::borrow::Cow;
+use std::str::FromStr;
+use std::path::Path;
+use percent_encoding::{utf8_percent_encode, AsciiSet, CONTROLS};
+use plato_core::anyhow::{Error, Context, format_err};
+use plato_core::chrono::{Local, NaiveDateTime, TimeZone};
+use plato_core::serde::Serialize;
+use plato_core::serde_json;
+use plato_core::helpers::{Fp, datetime_format, escape};
+use plato_core::library::Library;
+use plato_core::metadata::{Info, SimpleStatus};
+use plato_core::settings::LibraryMode;
+
+const COVERS_DIRNAME: &str = "covers";
+
+// The characters that can't appear in a path segment.
+const SEGMENT: &AsciiSet = &CONTROLS.add(b' ').add(b'"').add(b'#').add(b'%').add(b'<')
+                                    .add(b'>').add(b'?').add(b'`').add(b'{').add(b'}')
+                                    .add(b'\'').add(b'&');
+
+const CSV_HEADER: [&str; 20] = ["path", "kind", "size", "title", "subtitle", "author",
+                                "year", "language", "publisher", "series", "edition",
+                                "volume", "number", "identifier", "description", "categories",
+                                "status", "progress", "added", "opened"];
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Format {
+    Csv,
+    Json,
+    Opds,
+    Html,
+}
+
+impl FromStr for Format {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "csv" => Ok(Format::Csv),
+            "json" => Ok(Format::Json),
+            "opds" => Ok(Format::Opds),
+            "html" => Ok(Format::Html),
+            _ => Err(format_err!("unknown export format: {}", s)),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+#[serde(crate = "plato_core::serde", rename_all = "camelCase")]
+struct Entry<'a> {
+    #[serde(skip)]
+    fp: Fp,
+    path: &'a Path,
+    kind: &'a str,
+    size: u64,
+    title: &'a str,
+    subtitle: &'a str,
+    author: &'a str,
+    year: &'a str,
+    language: &'a str,
+    publisher: &'a str,
+    series: &'a str,
+    edition: &'a str,
+    volume: &'a str,
+    number: &'a str,
+    identifier: &'a str,
+    description: &'a str,
+    categories: Vec<&'a str>,
+    status: &'static str,
+    progress: f32,
+    #[serde(with = "datetime_format")]
+    added: NaiveDateTime,
+    opened: Option<String>,
+}
+
+impl<'a> Entry<'a> {
+    fn new(fp: Fp, info: &'a Info) -> Entry<'a> {
+        let (status, progress) = match info.simple_status() {
+            SimpleStatus::New => ("new", 0.0),
+            SimpleStatus::Finished => ("finished", 1.0),
+            SimpleStatus::Reading => {
+                let progress = info.reader.as_ref()
+                                   .filter(|r| r.pages_count > 0)
+                                   .map_or(0.0, |r| r.current_page as f32 / r.pages_count as f32);
+                ("reading", progress)
+            },
+        };
+
+        Entry {
+            fp,
+            path: &info.file.path,
+            kind: &info.file.kind,
+            size: info.file.size,
+            title: &info.title,
+            subtitle: &info.subtitle,
+            author: &info.author,
+            year: &info.year,
+            language: &info.language,
+            publisher: &info.publisher,
+            series: &info.series,
+            edition: &info.edition,
+            volume: &info.volume,
+            number: &info.number,
+            identifier: &info.identifier,
+            description: &info.description,
+            categories: info.categories.iter().map(String::as_str).collect(),
+            status,
+            progress,
+            added: info.added,
+            opened: info.reader.as_ref()
+                        .map(|r| r.opened.format(datetime_format::FORMAT).to_string()),
+        }
+    }
+
+    fn label(&self) -> Cow<'a, str> {
+        if self.title.is_empty() {
+            self.path.file_stem().map_or(Cow::Borrowed(""), |s| s.to_string_lossy())
+        } else {
+            Cow::Borrowed(self.title)
+        }
+    }
+
+    fn series_label(&self) -> String {
+        if self.number.is_empty() {
+            self.series.to_string()
+        } else {
+            format!("{} #{}", self.series, self.number)
+        }
+    }
+}
+
+// Writes the catalog to *output*, or to the standard output.
+// The links to the documents are relative to *base_url*.
+// The cover thumbnails are copied next to *output*, or linked from the library.
+pub fn export(library: &Library, format: Format, output: Option<&Path>, base_url: &str) -> Result<(), Error> {
+    if library.mode != LibraryMode::Database {
+        return Err(format_err!("the export requires a database library"));
+    }
+
+    let entries: Vec<Entry> = library.db.iter()
+                                     .map(|(fp, info)| Entry::new(*fp, info))
+                                     .collect();
+
+    let base_url = if base_url.ends_with('/') {
+        base_url.to_string()
+    } else {
+        format!("{}/", base_url)
+    };
+
+    let covers = if matches!(format, Format::Opds | Format::Html) {
+        copy_covers(library, &entries, output, &base_url)?
+    } else {
+        Vec::new()
+    };
+
+    let text = match format {
+        Format::Csv => to_csv(&entries),
+        Format::Json => serde_json::to_string_pretty(&entries)? + "\n",
+        Format::Opds => to_opds(library, &entries, &covers, &base_url),
+        Format::Html => to_html(library, &entries, &covers, &base_url),
+    };
+
+    if let Some(path) = output {
+        fs::write(path, text).with_context(|| format!("can't write {}", path.display()))?;
+    } else {
+        io::stdout().write_all(text.as_bytes())?;
+    }
+
+    Ok(())
+}
+
+// Returns the URL of the cover of each entry.
+fn copy_covers(library: &Library, entries: &[Entry], output: Option<&Path>, base_url: &str) -> Result<Vec<Option<String>>, Error> {
+    let covers_dir = output.map(|path| path.parent().unwrap_or_else(|| Path::new("")).join(COVERS_DIRNAME));
+
+    if let Some(dir) = covers_dir.as_ref() {
+        fs::create_dir_all(dir).with_context(|| format!("can't create {}", dir.display()))?;
+    }
+
+    let mut covers = Vec::with_capacity(entries.len());
+
+    for entry in entries {
+        let source = library.thumbnail_preview(entry.path);
+        if !source.exists() {
+            covers.push(None);
+            continue;
+        }
+        let name = format!("{}.png", entry.fp);
+        if let Some(dir) = covers_dir.as_ref() {
+            fs::copy(&source, dir.join(&name))
+               .map_err(|e| eprintln!("Can't copy {}: {:#}.", source.display(), e))
+               .ok();
+            covers.push(Some(format!("{}/{}", COVERS_DIRNAME, name)));
+        } else {
+            covers.push(source.strip_prefix(&library.home).ok()
+                              .map(|relat| format!("{}{}", base_url, encode_path(relat))));
+        }
+    }
+
+    Ok(covers)
+}
+
+fn to_csv(entries: &[Entry]) -> String {
+    let mut text = CSV_HEADER.join(",");
+    text.push_str("\r\n");
+
+    for entry in entries {
+        let categories = entry.categories.join("; ");
+        let fields = [entry.path.to_string_lossy(), Cow::Borrowed(entry.kind),
+                      Cow::Owned(entry.size.to_string()), Cow::Borrowed(entry.title),
+                      Cow::Borrowed(entry.subtitle), Cow::Borrowed(entry.author),
+                      Cow::Borrowed(entry.year), Cow::Borrowed(entry.language),
+                      Cow::Borrowed(entry.publisher), Cow::Borrowed(entry.series),
+                      Cow::Borrowed(entry.edition), Cow::Borrowed(entry.volume),
+                      Cow::Borrowed(entry.number), Cow::Borrowed(entry.identifier), Cow::Borrowed(entry.description),
+                      Cow::Borrowed(categories.as_str()), Cow::Borrowed(entry.status),
+                      Cow::Owned(format!("{:.3}", entry.progress)),
+                      Cow::Owned(entry.added.format(datetime_format::FORMAT).to_string()),
+                      Cow::Borrowed(entry.opened.as_deref().unwrap_or(""))];
+        let line = fields.iter().map(|f| csv_field(f)).collect::<Vec<Cow<str>>>().join(",");
+        text.push_str(&line);
+        text.push_str("\r\n");
+    }
+
+    text
+}
+
+fn to_opds(library: &Library, entries: &[Entry], covers: &[Option<String>], base_url: &str) -> String {
+    let mut text = String::new();
+
+    text.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    text.push_str("<feed xmlns=\"http://www.w3.org/2005/Atom\" xmlns:dc=\"http://purl.org/dc/terms/\" \
+                   xmlns:opds=\"http://opds-spec.org/2010/catalog\">\n");
+    text.push_str(&format!("  <id>{}</id>\n", escape(base_url)));
+    text.push_str(&format!("  <title>{}</title>\n", escape(&library_name(library))));
+    text.push_str(&format!("  <updated>{}</updated>\n", rfc3339(&Local::now().naive_local())));
+    text.push_str("  <link rel=\"start\" href=\"\" type=\"application/atom+xml;profile=opds-catalog;kind=acquisition\"/>\n");
+
+    for (entry, cover) in entries.iter().zip(covers) {
+        text.push_str("  <entry>\n");
+        text.push_str(&format!("    <title>{}</title>\n", escape(&entry.label())));
+        text.push_str(&format!("    <id>urn:plato:{}</id>\n", entry.fp));
+        text.push_str(&format!("    <updated>{}</updated>\n", rfc3339(&entry.added)));
+        if !entry.author.is_empty() {
+            text.push_str(&format!("    <author><name>{}</name></author>\n", escape(entry.author)));
+        }
+        for (name, value) in [("language", entry.language), ("publisher", entry.publisher),
+                              ("issued", entry.year), ("identifier", entry.identifier)] {
+            if !value.is_empty() {
+                text.push_str(&format!("    <dc:{0}>{1}</dc:{0}>\n", name, escape(value)));
+            }
+        }
+        for category in &entry.categories {
+            text.push_str(&format!("    <category term=\"{0}\" label=\"{0}\"/>\n", escape(category)));
+        }
+        let mut summary = vec![entry.subtitle.to_string(), entry.series_label()];
+        summary.retain(|s| !s.is_empty());
+        if !summary.is_empty() {
+            text.push_str(&format!("    <summary>{}</summary>\n", escape(&summary.join(" — "))));
+        }
+        if !entry.description.is_empty() {
+            text.push_str(&format!("    <content type=\"text\">{}</content>\n", escape(entry.description)));
+        }
+        text.push_str(&format!("    <link rel=\"http://opds-spec.org/acquisition\" href=\"{}\" type=\"{}\" length=\"{}\"/>\n",
+                               escape(&document_url(base_url, entry.path)), mime_type(entry.kind), entry.size));
+        if let Some(cover) = cover {
+            for rel in ["http://opds-spec.org/image", "http://opds-spec.org/image/thumbnail"] {
+                text.push_str(&format!("    <link rel=\"{}\" href=\"{}\" type=\"image/png\"/>\n", rel, escape(cover)));
+            }
+        }
+        text.push_str("  </entry>\n");
+    }
+
+    text.push_str("</feed>\n");
+    text
+}
+
+fn to_html(library: &Library, entries: &[Entry], covers: &[Option<String>], base_url: &str) -> String {
+    let mut items: Vec<(&Entry, &Option<String>)> = entries.iter().zip(covers).collect();
+    items.sort_by_cached_key(|(entry, _)| (entry.author.to_lowercase(), entry.label().to_lowercase()));
+
+    let name = escape(&library_name(library)).into_owned();
+    let mut text = String::new();
+
+    text.push_str("<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n");
+    text.push_str("<meta name=\"viewport\" content=\"width=device-width, initial-scale=1\">\n");
+    text.push_str(&format!("<title>{}</title>\n", name));
+    text.push_str("<style>\n\
+                   body { font-family: sans-serif; margin: 2em auto; max-width: 60em; padding: 0 1em; }\n\
+                   input { font-size: 1em; margin-bottom: 1em; padding: 0.3em; width: 100%; }\n\
+                   article { border-bottom: 1px solid #ddd; display: flex; gap: 1em; padding: 1em 0; }\n\
+                   article img { height: 8em; object-fit: contain; width: 6em; }\n\
+                   article .cover { flex: none; width: 6em; }\n\
+                   h2 { font-size: 1.1em; margin: 0 0 0.3em; }\n\
+                   p { margin: 0.2em 0; }\n\
+                   .meta { color: #666; font-size: 0.9em; }\n\
+                   </style>\n</head>\n<body>\n");
+    text.push_str(&format!("<h1>{}</h1>\n", name));
+    text.push_str(&format!("<p class=\"meta\">{} books, generated on {}.</p>\n",
+                           entries.len(), Local::now().format(datetime_format::FORMAT)));
+    text.push_str("<input type=\"search\" placeholder=\"Search\" oninput=\"filter(this.value)\">\n");
+
+    for (entry, cover) in items {
+        let url = escape(&document_url(base_url, entry.path)).into_owned();
+        text.push_str("<article>\n<div class=\"cover\">");
+        if let Some(cover) = cover {
+            text.push_str(&format!("<a href=\"{}\"><img src=\"{}\" alt=\"\" loading=\"lazy\"></a>", url, escape(cover)));
+        }
+        text.push_str("</div>\n<div>\n");
+        text.push_str(&format!("<h2><a href=\"{}\">{}</a></h2>\n", url, escape(&entry.label())));
+        if !entry.subtitle.is_empty() {
+            text.push_str(&format!("<p>{}</p>\n", escape(entry.subtitle)));
+        }
+        if !entry.author.is_empty() {
+            text.push_str(&format!("<p>{}</p>\n", escape(entry.author)));
+        }
+        let details = [entry.series_label(), entry.year.to_string(), entry.publisher.to_string(),
+                       entry.language.to_string(), entry.kind.to_uppercase(), file_size(entry.size)];
+        let details: Vec<&str> = details.iter().map(String::as_str).filter(|s| !s.is_empty()).collect();
+        text.push_str(&format!("<p class=\"meta\">{}</p>\n", escape(&details.join(" · "))));
+        if !entry.categories.is_empty() {
+            text.push_str(&format!("<p class=\"meta\">{}</p>\n", escape(&entry.categories.join(", "))));
+        }
+        let status = match entry.status {
+            "reading" => format!("Reading ({:.0}%)", 100.0 * entry.progress),
+            "finished" => "Finished".to_string(),
+            _ => "New".to_string(),
+        };
+        text.push_str(&format!("<p class=\"meta\">{}, added on {}.</p>\n",
+                               status, entry.added.format("%Y-%m-%d")));
+        text.push_str("</div>\n</article>\n");
+    }
+
+    text.push_str("<script>\n\
+                   function filter(query) {\n\
+                   \x20 query = query.toLowerCase();\n\
+                   \x20 for (const article of document.querySelectorAll('article')) {\n\
+                   \x20   article.hidden = !article.textContent.toLowerCase().includes(query);\n\
+                   \x20 }\n\
+                   }\n\
+                   </script>\n</body>\n</html>\n");
+    text
+}
+
+fn library_name(library: &Library) -> String {
+    library.home.canonicalize().ok()
+           .and_then(|path| path.file_name().map(|name| name.to_string_lossy().into_owned()))
+           .unwrap_or_else(|| "Library".to_string())
+}
+
+fn document_url(base_url: &str, path: &Path) -> String {
+    format!("{}{}", base_url, encode_path(path))
+}
+
+fn encode_path(path: &Path) -> String {
+    path.iter()
+        .map(|segment| utf8_percent_encode(&segment.to_string_lossy(), SEGMENT).to_string())
+        .collect::<Vec<String>>()
+        .join("/")
+}
+
+fn rfc3339(datetime: &NaiveDateTime) -> String {
+    Local.from_local_datetime(datetime).earliest()
+         .map_or_else(|| datetime.format("%Y-%m-%dT%H:%M:%SZ").to_string(),
+                      |datetime| datetime.to_rfc3339())
+}
+
+fn file_size(size: u64) -> String {
+    let mut size = size as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < 3 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    format!("{:.1} {}", size, ["B", "KiB", "MiB", "GiB"][unit])
+}
+
+fn mime_type(kind: &str) -> &str {
+    match kind {
+        "epub" => "application/epub+zip",
+        "pdf" => "application/pdf",
+        "djvu" | "djv" => "image/vnd.djvu",
+        "cbz" => "application/vnd.comicbook+zip",
+        "fb2" => "application/x-fictionbook+xml",
+        "mobi" => "application/x-mobipocket-ebook",
+        "azw3" => "application/vnd.amazon.ebook",
+        "xps" | "oxps" => "application/oxps",
+        "txt" => "text/plain",
+        "html" | "htm" => "text/html",
+        _ => "application/octet-stream",
+    }
+}
+
+fn csv_field(field: &str) -> Cow<'_, str> {
+    if field.contains([',', '"', '\r', '\n']) {
+        Cow::Owned(format!("\"{}\"", field.replace('"', "\"\"")))
+    } else {
+        Cow::Borrowed(field)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::path::PathBuf;
+    use plato_core::chrono::NaiveDate;
+    use plato_core::metadata::ReaderInfo;
+
+    fn date(day: u32, hour: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(2024, 3, day).unwrap().and_hms_opt(hour, 0, 0).unwrap()
+    }
+
+    fn fixtures() -> Vec<(Fp, Info)> {
+        let mut dune = Info {
+            title: "Dune".to_string(),
+            author: "Frank Herbert".to_string(),
+            year: "1965".to_string(),
+            language: "en".to_string(),
+            series: "Dune".to_string(),
+            number: "1".to_string(),
+            categories: ["Fiction".to_string(), "Sci-Fi".to_string()].into_iter().collect(),
+            added: date(1, 10),
+            reader: Some(ReaderInfo { opened: date(2, 20), current_page: 50, pages_count: 200,
+                                      ..Default::default() }),
+            ..Default::default()
+        };
+        dune.file.path = PathBuf::from("Sci-Fi/Dune.epub");
+        dune.file.kind = "epub".to_string();
+        dune.file.size = 1024;
+
+        let mut essays = Info {
+            author: "Doe, John".to_string(),
+            description: "First line\nSecond, \"quoted\" line".to_string(),
+            added: date(1, 11),
+            ..Default::default()
+        };
+        essays.file.path = PathBuf::from("Essays.pdf");
+        essays.file.kind = "pdf".to_string();
+        essays.file.size = 2048;
+
+        let mut poems = Info {
+            title: "Poems & Songs".to_string(),
+            added: date(1, 12),
+            reader: Some(ReaderInfo { opened: date(3, 8), finished: true, ..Default::default() }),
+            ..Default::default()
+        };
+        poems.file.path = PathBuf::from("Poems & Songs.txt");
+        poems.file.kind = "txt".to_string();
+        poems.file.size = 512;
+
+        [dune, essays, poems].into_iter().enumerate()
+                             .map(|(i, info)| (Fp::from_str(&format!("{:016X}", i + 1)).unwrap(), info))
+                             .collect()
+    }
+
+    fn library(name: &str) -> Library {
+        let home = env::temp_dir().join(format!("plato-export-{}-{}", name, std::process::id()));
+        Library::new(&home, LibraryMode::Database).unwrap()
+    }
+
+    #[test]
+    fn test_escaping() {
+        assert_eq!(csv_field("Doe, John"), "\"Doe, John\"");
+        assert_eq!(csv_field("The \"Best\""), "\"The \"\"Best\"\"\"");
+        assert_eq!(csv_field("Plain"), "Plain");
+        assert_eq!(escape("Tom & Jerry <3"), "Tom &amp; Jerry &lt;3");
+        assert_eq!(encode_path(Path::new("Sci-Fi/Dune #1 (50%).epub")),
+                   "Sci-Fi/Dune%20%231%20(50%25).epub");
+    }
+
+    #[test]
+    fn test_csv() {
+        let fixtures = fixtures();
+        let entries: Vec<Entry> = fixtures.iter().map(|(fp, info)| Entry::new(*fp, info)).collect();
+        let text = to_csv(&entries);
+        let lines: Vec<&str> = text.split("\r\n").collect();
+        assert_eq!(lines[0], "path,kind,size,title,subtitle,author,year,language,publisher,series,\
+                              edition,volume,number,identifier,description,categories,status,progress,added,opened");
+        assert_eq!(lines[1], "Sci-Fi/Dune.epub,epub,1024,Dune,,Frank Herbert,1965,en,,Dune,,,1,,,\
+                              Fiction; Sci-Fi,reading,0.250,2024-03-01 10:00:00,2024-03-02 20:00:00");
+        assert_eq!(lines[2], "Essays.pdf,pdf,2048,,,\"Doe, John\",,,,,,,,,\
+                              \"First line\nSecond, \"\"quoted\"\" line\",,new,0.000,2024-03-01 11:00:00,");
+        assert_eq!(lines[3], "Poems & Songs.txt,txt,512,Poems & Songs,,,,,,,,,,,,,\
+                              finished,1.000,2024-03-01 12:00:00,2024-03-03 08:00:00");
+        assert_eq!(lines[4], "");
+        assert_eq!(lines.len(), 5);
+    }
+
+    #[test]
+    fn test_json() {
+        let fixtures = fixtures();
+        let entries: Vec<Entry> = fixtures.iter().map(|(fp, info)| Entry::new(*fp, info)).collect();
+        let text = serde_json::to_string_pretty(&entries).unwrap();
+        let value: serde_json::Value = serde_json::from_str(&text).unwrap();
+        assert_eq!(value.as_array().map(Vec::len), Some(3));
+        assert_eq!(value[0]["path"], "Sci-Fi/Dune.epub");
+        assert_eq!(value[0]["categories"], serde_json::json!(["Fiction", "Sci-Fi"]));
+        assert_eq!(value[0]["progress"], 0.25);
+        assert_eq!(value[0]["opened"], "2024-03-02 20:00:00");
+        assert_eq!(value[1]["author"], "Doe, John");
+        assert_eq!(value[1]["status"], "new");
+        assert!(value[1]["opened"].is_null());
+        assert_eq!(value[2]["added"], "2024-03-01 12:00:00");
+        assert!(value[0].get("fp").is_none());
+        let keys = ["\"path\"", "\"kind\"", "\"size\"", "\"title\"", "\"author\"", "\"categories\"",
+                    "\"status\"", "\"progress\"", "\"added\"", "\"opened\""];
+        let positions: Vec<usize> = keys.iter().map(|key| text.find(key).unwrap()).collect();
+        assert!(positions.windows(2).all(|w| w[0] < w[1]));
+    }
+
+    #[test]
+    fn test_opds() {
+        let library = library("opds");
+        let fixtures = fixtures();
+        let entries: Vec<Entry> = fixtures.iter().map(|(fp, info)| Entry::new(*fp, info)).collect();
+        let covers = vec![Some("covers/0000000000000001.png".to_string()), None, None];
+        let text = to_opds(&library, &entries, &covers, "https://example.org/books/");
+        assert!(text.starts_with("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<feed "));
+        assert!(text.ends_with("</feed>\n"));
+        assert_eq!(text.matches("<entry>").count(), 3);
+        assert!(text.contains("<link rel=\"http://opds-spec.org/acquisition\" \
+                               href=\"https://example.org/books/Sci-Fi/Dune.epub\" \
+                               type=\"application/epub+zip\" length=\"1024\"/>"));
+        assert!(text.contains("<link rel=\"http://opds-spec.org/acquisition\" \
+                               href=\"https://example.org/books/Poems%20%26%20Songs.txt\" \
+                               type=\"text/plain\" length=\"512\"/>"));
+        assert!(text.contains("<link rel=\"http://opds-spec.org/image\" \
+                               href=\"covers/0000000000000001.png\" type=\"image/png\"/>"));
+        assert!(text.contains("<link rel=\"http://opds-spec.org/image/thumbnail\" \
+                               href=\"covers/0000000000000001.png\" type=\"image/png\"/>"));
+        assert_eq!(text.matches("type=\"image/png\"").count(), 2);
+        assert!(text.contains("<title>Essays</title>"));
+        assert!(text.contains("<title>Poems &amp; Songs</title>"));
+        assert!(text.contains("<summary>Dune #1</summary>"));
+        assert!(text.contains("<category term=\"Sci-Fi\" label=\"Sci-Fi\"/>"));
+        fs::remove_dir_all(&library.home).ok();
+    }
+
+    #[test]
+    fn test_html() {
+        let library = library("html");
+        let fixtures = fixtures();
+        let entries: Vec<Entry> = fixtures.iter().map(|(fp, info)| Entry::new(*fp, info)).collect();
+        let covers = vec![Some("covers/0000000000000001.png".to_string()), None, None];
+        let text = to_html(&library, &entries, &covers, "https://example.org/books/");
+        assert!(text.starts_with("<!DOCTYPE html>\n"));
+        assert!(text.contains("<p class=\"meta\">3 books, generated on "));
+        assert_eq!(text.matches("<article>").count(), 3);
+        // Sorted by author, then by title.
+        let poems = text.find("<h2><a href=\"https://example.org/books/Poems%20%26%20Songs.txt\">\
+                               Poems &amp; Songs</a></h2>").unwrap();
+        let essays = text.find("<h2><a href=\"https://example.org/books/Essays.pdf\">Essays</a></h2>").unwrap();
+        let dune = text.find("<h2><a href=\"https://example.org/books/Sci-Fi/Dune.epub\">Dune</a></h2>").unwrap();
+        assert!(poems < essays && essays < dune);
+        assert!(text.contains("<a href=\"https://example.org/books/Sci-Fi/Dune.epub\">\
+                               <img src=\"covers/0000000000000001.png\" alt=\"\" loading=\"lazy\"></a>"));
+        assert_eq!(text.matches("<img ").count(), 1);
+        assert!(text.contains("<p class=\"meta\">Dune #1 · 1965 · en · EPUB · 1.0 KiB</p>"));
+        assert!(text.contains("<p class=\"meta\">Fiction, Sci-Fi</p>"));
+        assert!(text.contains("<p class=\"meta\">Reading (25%), added on 2024-03-01.</p>"));
+        assert!(text.contains("<p class=\"meta\">Finished, added on 2024-03-01.</p>"));
+        assert!(text.contains("<p class=\"meta\">New, added on 2024-03-01.</p>"));
+        fs::remove_dir_all(&library.home).ok();
+    }
+}
@@ -1,13 +1,22 @@
+mod export;
+
 use std::env;
-use std::path::Path;
+use std::fs;
+use std::path::{Path, PathBuf};
 use getopts::Options;
 use plato_core::chrono::NaiveDateTime;
 use plato_core::anyhow::{Error, Context, format_err};
 use plato_core::helpers::datetime_format;
+use plato_core::serde_json;
 use plato_core::library::Library;
+use plato_core::duplicates::{find_duplicates, merge};
+use plato_core::view::home::TRASH_DIRNAME;
+use plato_core::watcher::Watcher;
+use plato_core::calibre::{import_database, sidecar_opf};
 use plato_core::settings::{LibraryMode, ImportSettings};
 use plato_core::metadata::{extract_metadata_from_document, extract_metadata_from_filename};
 use plato_core::metadata::{consolidate, rename_from_info};
+use crate::export::{export, Format};
 
 fn main() -> Result<(), Error> {
     let args: Vec<String> = env::args().skip(1).collect();
@@ -16,11 +25,19 @@ fn main() -> Result<(), Error> {
 
     opts.optflag("h", "help", "Print this help message.");
     opts.optflag("I", "import", "Import new files or update existing files.");
+    opts.optflag("W", "watch", "Keep importing as the files of the library change.");
     opts.optflag("C", "clean-up", "Remove reading states with unknown fingerprints.");
     opts.optflag("E", "extract-metadata-document", "Extract metadata from documents.");
     opts.optflag("F", "extract-metadata-filename", "Extract metadata from filenames.");
     opts.optflag("S", "consolidate", "Autocorrect simple typographic mistakes.");
     opts.optflag("N", "rename-from-info", "Rename files based on their information.");
+    opts.optflag("D", "duplicates", "Report the entries that are copies of the same book.");
+    opts.optflag("J", "json", "Print the duplicates report in the JSON format.");
+    opts.optopt("M", "merge-duplicates", "Merge the duplicates of the given entry into it, and move them to the trash.", "PATH");
+    opts.optopt("L", "calibre-library", "Import the metadata of the given Calibre library.", "CALIBRE_LIBRARY_PATH");
+    opts.optopt("X", "export", "Export the catalog in the given format (`csv`, `json`, `opds` or `html`).", "FORMAT");
+    opts.optopt("o", "output", "Write the exported catalog to the given file.", "OUTPUT_PATH");
+    opts.optopt("u", "base-url", "The URL of the library in the exported catalog.", "BASE_URL");
     opts.optopt("k", "allowed-kinds", "Comma separated list of allowed kinds.", "ALLOWED_KINDS");
     opts.optopt("e", "metadata-kinds", "Comma separated list of metadata kinds.", "METADATA_KINDS");
     opts.optopt("a", "added-after", "Only process entries added after the given date-time.", "ADDED_DATETIME");
@@ -29,7 +46,7 @@ fn main() -> Result<(), Error> {
     let matches = opts.parse(&args).context("failed to parse the command line arguments")?;
 
     if matches.opt_present("h") {
-        println!("{}", opts.usage("Usage: plato-import -h|-I|-C|-EFSN [-k ALLOWED_KINDS] [-e METADATA_KINDS] [-a ADDED_DATETIME] [-m LIBRARY_MODE] LIBRARY_PATH"));
+        println!("{}", opts.usage("Usage: plato-import -h|-I [-W]|-L CALIBRE_LIBRARY_PATH|-C|-D [-J]|-M PATH|-X FORMAT [-o OUTPUT_PATH] [-u BASE_URL]|-EFSN [-k ALLOWED_KINDS] [-e METADATA_KINDS] [-a ADDED_DATETIME] [-m LIBRARY_MODE] LIBRARY_PATH"));
         return Ok(());
     }
 
@@ -68,8 +85,65 @@ fn main() -> Result<(), Error> {
 
     if matches.opt_present("I") {
         library.import(&import_settings);
+        if matches.opt_present("W") {
+            if mode != LibraryMode::Database {
+                return Err(format_err!("the watch mode requires a database library"));
+            }
+            library.flush();
+            let mut watcher = Watcher::new(library_path)?;
+            while watcher.is_active() {
+                if watcher.wait(None)? {
+                    library.import(&import_settings);
+                    library.flush();
+                }
+            }
+        }
+    } else if let Some(calibre_path) = matches.opt_str("L") {
+        library.import(&import_settings);
+        let count = import_database(&mut library, calibre_path)?;
+        println!("Updated {} entries.", count);
     } else if matches.opt_present("C") {
         library.clean_up();
+    } else if matches.opt_present("D") {
+        let duplicates = find_duplicates(&library);
+        if matches.opt_present("J") {
+            println!("{}", serde_json::to_string_pretty(&duplicates)?);
+        } else {
+            for group in &duplicates {
+                let kinds = group.kinds.iter().map(|k| k.label()).collect::<Vec<&str>>().join(", ");
+                println!("{} — {} ({}):", group.title, group.author, kinds);
+                for path in &group.paths {
+                    println!("    {}", path.display());
+                }
+            }
+        }
+    } else if let Some(keep) = matches.opt_str("M") {
+        let keep = PathBuf::from(keep);
+        let keep = keep.strip_prefix(library_path).unwrap_or(&keep).to_path_buf();
+        let group = find_duplicates(&library).into_iter()
+                                              .find(|group| group.paths.contains(&keep))
+                                              .ok_or_else(|| format_err!("no duplicates for {}", keep.display()))?;
+        let trash_path = library_path.join(TRASH_DIRNAME);
+        fs::create_dir_all(&trash_path)?;
+        let mut trash = Library::new(trash_path, LibraryMode::Database)?;
+        for path in group.paths.iter().filter(|path| **path != keep) {
+            println!("Merge {} into {}.", path.display(), keep.display());
+            merge(&mut library, &keep, path)?;
+            library.move_to(path, &mut trash)?;
+        }
+        trash.flush();
+    } else if let Some(format) = matches.opt_str("X") {
+        let format: Format = format.parse()?;
+        let output = matches.opt_str("o").map(PathBuf::from);
+        let base_url = match matches.opt_str("u") {
+            Some(url) => url,
+            None => {
+                let home = library_path.canonicalize()?;
+                format!("file://{}", home.display())
+            },
+        };
+        export(&library, format, output.as_deref(), &base_url)?;
+        return Ok(());
     } else {
         let opt_extract_metadata_document = matches.opt_present("E");
         let opt_extract_metadata_filename = matches.opt_present("F");
@@ -79,7 +153,8 @@ fn main() -> Result<(), Error> {
         library.apply(|path, info| {
             if added_after.map_or(true, |added| info.added >= added) {
                 if opt_extract_metadata_document &&
-                   import_settings.metadata_kinds.contains(&info.file.kind) {
+                   (import_settings.metadata_kinds.contains(&info.file.kind) ||
+                    sidecar_opf(path.join(&info.file.path)).is_some()) {
                     extract_metadata_from_document(path, info);
                 }
 
@@ -1,4 +1,4 @@
-use std::fs::File;
+use std::fs::{self, File};
 use std::env;
 use std::thread;
 use std::process::Command;
@@ -7,7 +7,7 @@ use std::sync::mpsc::{self, Receiver, Sender};
 use std::collections::VecDeque;
 use std::time::{Duration, Instant};
 use plato_core::anyhow::{Error, Context as ResultExt, format_err};
-use plato_core::chrono::Local;
+use plato_core::chrono::{Local, Utc};
 use plato_core::framebuffer::{Framebuffer, KoboFramebuffer1, KoboFramebuffer2, UpdateMode};
 use plato_core::view::{View, Event, EntryId, EntryKind, ViewId, AppCmd, RenderData, RenderQueue, UpdateData};
 use plato_core::view::{handle_event, process_render_queue, wait_for_all};
@@ -20,6 +20,7 @@ use plato_core::view::calculator::Calculator;
 use plato_core::view::sketch::Sketch;
 use plato_core::view::touch_events::TouchEvents;
 use plato_core::view::rotation_values::RotationValues;
+use plato_core::view::book_details::BookDetails;
 use plato_core::document::sys_info_as_html;
 use plato_core::input::{DeviceEvent, PowerSource, ButtonCode, ButtonStatus, VAL_RELEASE, VAL_PRESS};
 use plato_core::input::{raw_events, device_events, usb_events, display_rotate_event, button_scheme_event};
@@ -39,6 +40,8 @@ use plato_core::device::{CURRENT_DEVICE, Orientation, FrontlightKind};
 use plato_core::library::Library;
 use plato_core::font::Fonts;
 use plato_core::rtc::Rtc;
+use plato_core::scheduler::{Scheduler, AlarmKind};
+use plato_core::control::{self, handle_command};
 use plato_core::context::Context;
 
 pub const APP_NAME: &str = "Plato";
@@ -75,6 +78,7 @@ enum TaskId {
     CheckBattery,
     PrepareSuspend,
     Suspend,
+    WakeUp,
 }
 
 struct HistoryItem {
@@ -146,6 +150,7 @@ fn schedule_task(id: TaskId, event: Event, delay: Duration, hub: &Sender<Event>,
 fn resume(id: TaskId, tasks: &mut Vec<Task>, view: &mut dyn View, hub: &Sender<Event>, rq: &mut RenderQueue, context: &mut Context) {
     if id == TaskId::Suspend {
         tasks.retain(|task| task.id != TaskId::Suspend);
+        context.services.notify_resume();
         if context.settings.frontlight {
             let levels = context.settings.frontlight_levels;
             context.frontlight.set_warmth(levels.warmth);
@@ -181,6 +186,12 @@ fn power_off(view: &mut dyn View, history: &mut Vec<HistoryItem>, updating: &mut
     context.fb.update(interm.rect(), UpdateMode::Full).ok();
 }
 
+fn finish_wake_up(scheduler: &mut Scheduler, tasks: &mut Vec<Task>, context: &mut Context) {
+    tasks.retain(|task| task.id != TaskId::WakeUp);
+    let wifi = scheduler.stop();
+    set_wifi(wifi, context);
+}
+
 fn set_wifi(enable: bool, context: &mut Context) {
     if context.settings.wifi == enable {
         return;
@@ -297,6 +308,14 @@ pub fn run() -> Result<(), Error> {
         });
     }
 
+    if let Some(path) = context.settings.control_socket.as_ref() {
+        control::listen(path, &tx)
+                .map_err(|e| eprintln!("Can't listen on the control socket: {:#}.", e))
+                .ok();
+    }
+
+    context.start_services(&tx);
+
     context.fb.set_inverted(context.settings.inverted);
 
     if context.settings.wifi {
@@ -315,6 +334,7 @@ pub fn run() -> Result<(), Error> {
     }
 
     let mut tasks: Vec<Task> = Vec::new();
+    let mut scheduler = Scheduler::default();
     let mut history: Vec<HistoryItem> = Vec::new();
     let mut rq = RenderQueue::new();
     let mut view: Box<dyn View> = Box::new(Home::new(context.fb.rect(), &tx,
@@ -347,6 +367,10 @@ pub fn run() -> Result<(), Error> {
                             resume(TaskId::PrepareSuspend, &mut tasks, view.as_mut(), &tx, &mut rq, &mut context);
                         } else if tasks.iter().any(|task| task.id == TaskId::Suspend) {
                             resume(TaskId::Suspend, &mut tasks, view.as_mut(), &tx, &mut rq, &mut context);
+                        } else if tasks.iter().any(|task| task.id == TaskId::WakeUp) {
+                            finish_wake_up(&mut scheduler, &mut tasks, &mut context);
+                            scheduler.reset();
+                            resume(TaskId::Suspend, &mut tasks, view.as_mut(), &tx, &mut rq, &mut context);
                         } else {
                             view.handle_event(&Event::Suspend, &tx, &mut bus, &mut rq, &mut context);
                             let interm = Intermission::new(context.fb.rect(), IntermKind::Suspend, &context);
@@ -368,7 +392,8 @@ pub fn run() -> Result<(), Error> {
 
                         if !context.settings.sleep_cover || context.shared ||
                            tasks.iter().any(|task| task.id == TaskId::PrepareSuspend ||
-                                                   task.id == TaskId::Suspend) {
+                                                   task.id == TaskId::Suspend ||
+                                                   task.id == TaskId::WakeUp) {
                             continue;
                         }
 
@@ -394,6 +419,10 @@ pub fn run() -> Result<(), Error> {
                             resume(TaskId::PrepareSuspend, &mut tasks, view.as_mut(), &tx, &mut rq, &mut context);
                         } else if tasks.iter().any(|task| task.id == TaskId::Suspend) {
                             resume(TaskId::Suspend, &mut tasks, view.as_mut(), &tx, &mut rq, &mut context);
+                        } else if tasks.iter().any(|task| task.id == TaskId::WakeUp) {
+                            finish_wake_up(&mut scheduler, &mut tasks, &mut context);
+                            scheduler.reset();
+                            resume(TaskId::Suspend, &mut tasks, view.as_mut(), &tx, &mut rq, &mut context);
                         }
                     },
                     DeviceEvent::NetUp => {
@@ -401,6 +430,12 @@ pub fn run() -> Result<(), Error> {
                                                    task.id == TaskId::Suspend) {
                             continue;
                         }
+                        if tasks.iter().any(|task| task.id == TaskId::WakeUp) {
+                            context.online = true;
+                            context.services.notify_network_up();
+                            scheduler.notify_network_up();
+                            continue;
+                        }
                         let ip = Command::new("scripts/ip.sh").output()
                                          .map(|o| String::from_utf8_lossy(&o.stdout).trim_end().to_string())
                                          .unwrap_or_default();
@@ -410,6 +445,7 @@ pub fn run() -> Result<(), Error> {
                         let notif = Notification::new(format!("Network is up ({}, {}).", ip, essid),
                                                       &tx, &mut rq, &mut context);
                         context.online = true;
+                        context.services.notify_network_up();
                         view.children_mut().push(Box::new(notif) as Box<dyn View>);
                         if view.is::<Home>() {
                             view.handle_event(&evt, &tx, &mut bus, &mut rq, &mut context);
@@ -442,6 +478,10 @@ pub fn run() -> Result<(), Error> {
                                     resume(TaskId::PrepareSuspend, &mut tasks, view.as_mut(), &tx, &mut rq, &mut context);
                                 } else if tasks.iter().any(|task| task.id == TaskId::Suspend) {
                                     resume(TaskId::Suspend, &mut tasks, view.as_mut(), &tx, &mut rq, &mut context);
+                                } else if tasks.iter().any(|task| task.id == TaskId::WakeUp) {
+                                    finish_wake_up(&mut scheduler, &mut tasks, &mut context);
+                                    scheduler.reset();
+                                    resume(TaskId::Suspend, &mut tasks, view.as_mut(), &tx, &mut rq, &mut context);
                                 }
 
                                 if context.settings.auto_share {
@@ -515,7 +555,8 @@ pub fn run() -> Result<(), Error> {
                     },
                     DeviceEvent::RotateScreen(n) => {
                         if context.shared || tasks.iter().any(|task| task.id == TaskId::PrepareSuspend ||
-                                                                     task.id == TaskId::Suspend) {
+                                                                     task.id == TaskId::Suspend ||
+                                                                     task.id == TaskId::WakeUp) {
                             continue;
                         }
 
@@ -563,6 +604,7 @@ pub fn run() -> Result<(), Error> {
             },
             Event::PrepareSuspend => {
                 tasks.retain(|task| task.id != TaskId::PrepareSuspend);
+                context.services.notify_suspend();
                 wait_for_all(&mut updating, &mut context);
                 let path = Path::new(SETTINGS_PATH);
                 save_toml(&context.settings, path).map_err(|e| eprintln!("Can't save settings: {:#}.", e)).ok();
@@ -584,12 +626,8 @@ pub fn run() -> Result<(), Error> {
                               SUSPEND_WAIT_DELAY, &tx, &mut tasks);
             },
             Event::Suspend => {
-                if context.settings.auto_power_off > 0.0 {
-                    context.rtc.iter().for_each(|rtc| {
-                        rtc.set_alarm(context.settings.auto_power_off)
-                           .map_err(|e| eprintln!("Can't set alarm: {:#}.", e))
-                           .ok();
-                    });
+                if let Some(rtc) = context.rtc.as_ref() {
+                    scheduler.arm(rtc, &Local::now(), &context.settings);
                 }
                 let before = Local::now();
                 println!("{}", before.format("Went to sleep on %B %-d, %Y at %H:%M:%S."));
@@ -605,29 +643,38 @@ pub fn run() -> Result<(), Error> {
                 // If the wake is legitimate, the task will be cancelled by `resume`.
                 schedule_task(TaskId::Suspend, Event::Suspend,
                               SUSPEND_WAIT_DELAY, &tx, &mut tasks);
-                if context.settings.auto_power_off > 0.0 {
-                    let dur = plato_core::chrono::Duration::seconds((86_400.0 * context.settings.auto_power_off) as i64);
-                    if let Some(fired) = context.rtc.as_ref()
-                                                .and_then(|rtc| rtc.alarm()
-                                                                   .map_err(|e| eprintln!("Can't get alarm: {:#}", e))
-                                                                   .map(|rwa| !rwa.enabled() ||
-                                                                              (rwa.year() <= 1970 &&
-                                                                               ((after - before) - dur).num_seconds().abs() < 3))
-                                                                   .ok()) {
-                        if fired {
-                            power_off(view.as_mut(), &mut history, &mut updating, &mut context);
-                            exit_status = ExitStatus::PowerOff;
-                            break;
-                        } else {
-                            context.rtc.iter().for_each(|rtc| {
-                                rtc.disable_alarm()
-                                   .map_err(|e| eprintln!("Can't disable alarm: {:#}.", e))
-                                   .ok();
-                            });
+                match context.rtc.as_ref().and_then(|rtc| scheduler.check(rtc, after.with_timezone(&Utc))) {
+                    Some(AlarmKind::PowerOff) => {
+                        power_off(view.as_mut(), &mut history, &mut updating, &mut context);
+                        exit_status = ExitStatus::PowerOff;
+                        break;
+                    },
+                    Some(AlarmKind::WakeUp) => {
+                        // Run the wake-up hooks, and go back to sleep once they're done.
+                        tasks.retain(|task| task.id != TaskId::Suspend);
+                        let wifi = context.settings.wifi;
+                        context.settings.wifi = true;
+                        Command::new("scripts/wifi-enable.sh")
+                                .status()
+                                .ok();
+                        let duration = Duration::from_secs(context.settings.wake_up.max_duration);
+                        schedule_task(TaskId::WakeUp, Event::FinishWakeUp,
+                                      duration, &tx, &mut tasks);
+                        if scheduler.start(wifi, &tx, &context) == 0 {
+                            tx.send(Event::FinishWakeUp).ok();
                         }
-                    }
+                    },
+                    None => (),
                 }
             },
+            Event::FinishWakeUp => {
+                if tasks.iter().all(|task| task.id != TaskId::WakeUp) {
+                    continue;
+                }
+                finish_wake_up(&mut scheduler, &mut tasks, &mut context);
+                schedule_task(TaskId::PrepareSuspend, Event::PrepareSuspend,
+                              PREPARE_SUSPEND_WAIT_DELAY, &tx, &mut tasks);
+            },
             Event::PrepareShare => {
                 if context.shared {
                     continue;
@@ -807,6 +854,9 @@ pub fn run() -> Result<(), Error> {
                     AppCmd::RotationValues => {
                         Box::new(RotationValues::new(context.fb.rect(), &mut rq, &mut context))
                     },
+                    AppCmd::BookDetails { ref path } => {
+                        Box::new(BookDetails::new(context.fb.rect(), path, &tx, &mut rq, &mut context))
+                    },
                 };
                 transfer_notifications(view.as_mut(), next_view.as_mut(), &mut rq, &mut context);
                 history.push(HistoryItem {
@@ -937,10 +987,29 @@ pub fn run() -> Result<(), Error> {
                 let notif = Notification::new(msg, &tx, &mut rq, &mut context);
                 view.children_mut().push(Box::new(notif) as Box<dyn View>);
             },
+            Event::CheckFetcher(id) if scheduler.owns(id) => {
+                scheduler.check_hook(id);
+                if scheduler.is_done() {
+                    tx.send(Event::FinishWakeUp).ok();
+                }
+            },
+            Event::FetcherAnswer(id, answer) if scheduler.owns(id) => {
+                scheduler.answer(id, answer);
+            },
+            // Keep the sleep screen during a scheduled wake-up.
+            Event::Notify(..) |
+            Event::FetcherProgress(..) if tasks.iter().any(|task| task.id == TaskId::WakeUp) => (),
+            Event::FetcherAsk(id, _) if tasks.iter().any(|task| task.id == TaskId::WakeUp) => {
+                tx.send(Event::FetcherAnswer(id, false)).ok();
+            },
             Event::CheckFetcher(..) |
             Event::FetcherAddDocument(..) |
             Event::FetcherRemoveDocument(..) |
-            Event::FetcherSearch { .. } if !view.is::<Home>() => {
+            Event::FetcherSearch { .. } |
+            Event::FetcherUpdateDocument(..) |
+            Event::FetcherReaderInfo(..) |
+            Event::FetcherAnswer(..) |
+            Event::Activity(..) if !view.is::<Home>() => {
                 if let Some(entry) = history.get_mut(0).filter(|entry| entry.view.is::<Home>()) {
                     let (tx, _rx) = mpsc::channel();
                     entry.view.handle_event(&evt, &tx, &mut VecDeque::new(), &mut RenderQueue::new(), &mut context);
@@ -950,6 +1019,35 @@ pub fn run() -> Result<(), Error> {
                 let notif = Notification::new(msg, &tx, &mut rq, &mut context);
                 view.children_mut().push(Box::new(notif) as Box<dyn View>);
             },
+            Event::FetcherProgress(id, ref msg, value) => {
+                let view_id = ViewId::ProgressNotif(id);
+                if let Some(index) = locate_by_id(view.as_ref(), view_id) {
+                    if let Some(notif) = view.child_mut(index).downcast_mut::<Notification>() {
+                        notif.update_progress(msg.clone(), value, &tx, &mut rq);
+                    }
+                } else {
+                    let notif = Notification::with_progress(view_id, msg.clone(), value, &tx, &mut rq, &mut context);
+                    view.children_mut().push(Box::new(notif) as Box<dyn View>);
+                }
+            },
+            Event::FetcherAsk(id, ref msg) => {
+                let mut dialog = Dialog::new(ViewId::FetcherDialog(id),
+                                             Some(Event::FetcherAnswer(id, true)),
+                                             msg.clone(),
+                                             &mut context);
+                dialog.set_cancel_event(Event::FetcherAnswer(id, false));
+                rq.add(RenderData::new(dialog.id(), *dialog.rect(), UpdateMode::Gui));
+                view.children_mut().push(Box::new(dialog) as Box<dyn View>);
+            },
+            Event::CheckService(index) => {
+                context.services.check(index, &tx);
+            },
+            Event::StartService(index) => {
+                context.services.spawn(index, context.settings.wifi, context.online, &tx);
+            },
+            Event::Control(ref cmd, ref responder) => {
+                handle_command(cmd, responder, view.as_mut(), &tx, &mut updating, &mut context);
+            },
             Event::Select(EntryId::Reboot) => {
                 exit_status = ExitStatus::Reboot;
                 break;
@@ -959,7 +1057,8 @@ pub fn run() -> Result<(), Error> {
             },
             Event::MightSuspend if context.settings.auto_suspend > 0.0 => {
                 if context.shared || tasks.iter().any(|task| task.id == TaskId::PrepareSuspend ||
-                                                             task.id == TaskId::Suspend) {
+                                                             task.id == TaskId::Suspend ||
+                                                             task.id == TaskId::WakeUp) {
                     inactive_since = Instant::now();
                     continue;
                 }
@@ -995,8 +1094,14 @@ pub fn run() -> Result<(), Error> {
         }
     }
 
+    context.services.stop();
+    scheduler.stop();
     context.library.flush();
 
+    if let Some(path) = context.settings.control_socket.as_ref() {
+        fs::remove_file(path).ok();
+    }
+
     let path = Path::new(SETTINGS_PATH);
     save_toml(&context.settings, path).context("can't save settings")?;
 
@@ -0,0 +1,292 @@
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use getopts::Options;
+use plato_core::anyhow::{Error, Context, format_err};
+use plato_core::serde_json::{json, Value as JsonValue};
+use plato_core::document::{Document, Location, TocEntry, open, file_kind};
+use plato_core::framebuffer::Framebuffer;
+use plato_core::metadata::{Info, FileInfo, TextAlign, extract_metadata_from_document};
+use plato_core::settings::{Settings, SETTINGS_PATH, HYPHEN_PENALTY, STRETCH_TOLERANCE};
+use plato_core::settings::{DEFAULT_FONT_FAMILY, DEFAULT_TEXT_ALIGN, DEFAULT_LINE_HEIGHT, DEFAULT_MARGIN_WIDTH};
+use plato_core::helpers::{load_toml, save_json};
+use plato_core::device::CURRENT_DEVICE;
+
+const THUMBNAIL_NAME: &str = "thumbnail.png";
+const INFO_NAME: &str = "info.json";
+
+struct Layout {
+    width: u32,
+    height: u32,
+    dpi: u16,
+    font_family: String,
+    font_size: f32,
+    margin_width: i32,
+    line_height: f32,
+    text_align: TextAlign,
+}
+
+// Parses a list of page ranges, e.g. `1,4-6,10-`.
+// The page numbers start at one, the end of a range is inclusive and can be omitted.
+fn parse_pages(text: &str) -> Result<Vec<(usize, Option<usize>)>, Error> {
+    text.split(',').map(|range| {
+        let mut bounds = range.splitn(2, '-').map(str::trim);
+        let start = bounds.next().unwrap_or_default().parse::<usize>()
+                          .with_context(|| format!("invalid page range: {}", range))?;
+        let end = match bounds.next() {
+            None => Some(start),
+            Some("") => None,
+            Some(v) => Some(v.parse::<usize>().with_context(|| format!("invalid page range: {}", range))?),
+        };
+        if start == 0 || end.is_some_and(|end| end < start) {
+            return Err(format_err!("invalid page range: {}", range));
+        }
+        Ok((start, end))
+    }).collect()
+}
+
+fn parse_dims(text: &str) -> Result<(u32, u32), Error> {
+    let (width, height) = text.split_once('x')
+                              .ok_or_else(|| format_err!("invalid dimensions: {}", text))?;
+    Ok((width.parse().with_context(|| format!("invalid width: {}", width))?,
+        height.parse().with_context(|| format!("invalid height: {}", height))?))
+}
+
+fn parse_text_align(text: &str) -> Result<TextAlign, Error> {
+    match text {
+        "justify" => Ok(TextAlign::Justify),
+        "left" => Ok(TextAlign::Left),
+        "right" => Ok(TextAlign::Right),
+        "center" => Ok(TextAlign::Center),
+        _ => Err(format_err!("invalid text alignment: {}", text)),
+    }
+}
+
+// Applies the layout settings the same way the reader does.
+fn apply_layout(doc: &mut dyn Document, layout: &Layout, settings: &Settings) {
+    doc.layout(layout.width, layout.height, layout.font_size, layout.dpi);
+
+    if layout.margin_width != DEFAULT_MARGIN_WIDTH {
+        doc.set_margin_width(layout.margin_width);
+    }
+
+    if layout.font_family != DEFAULT_FONT_FAMILY {
+        doc.set_font_family(&layout.font_family, &settings.reader.font_path);
+    }
+
+    if (layout.line_height - DEFAULT_LINE_HEIGHT).abs() > f32::EPSILON {
+        doc.set_line_height(layout.line_height);
+    }
+
+    if layout.text_align != DEFAULT_TEXT_ALIGN {
+        doc.set_text_align(layout.text_align);
+    }
+
+    let hyphen_penalty = settings.reader.paragraph_breaker.hyphen_penalty;
+
+    if hyphen_penalty != HYPHEN_PENALTY {
+        doc.set_hyphen_penalty(hyphen_penalty);
+    }
+
+    let stretch_tolerance = settings.reader.paragraph_breaker.stretch_tolerance;
+
+    if stretch_tolerance != STRETCH_TOLERANCE {
+        doc.set_stretch_tolerance(stretch_tolerance);
+    }
+
+    if settings.reader.ignore_document_css {
+        doc.set_ignore_document_css(true);
+    }
+}
+
+// Returns the locations of the first pages of the document, at most *limit* of them.
+fn page_locations(doc: &mut dyn Document, limit: Option<usize>) -> Vec<usize> {
+    let limit = limit.unwrap_or(usize::MAX);
+    let mut locations = Vec::new();
+    let mut location = doc.resolve_location(Location::Exact(0));
+    while let Some(loc) = location {
+        if locations.len() >= limit {
+            break;
+        }
+        locations.push(loc);
+        location = doc.resolve_location(Location::Next(loc));
+    }
+    locations
+}
+
+fn render_pages(doc: &mut dyn Document, pages: &[(usize, Option<usize>)], layout: &Layout,
+                samples: usize, output: &Path) -> Result<(), Error> {
+    let limit = if pages.iter().any(|(_, end)| end.is_none()) {
+        None
+    } else {
+        pages.iter().filter_map(|(_, end)| *end).max()
+    };
+    let locations = page_locations(doc, limit);
+
+    for &(start, end) in pages {
+        let end = end.unwrap_or(locations.len()).min(locations.len());
+        for number in start..=end {
+            let location = locations[number - 1];
+            let scale = if doc.is_reflowable() {
+                1.0
+            } else {
+                doc.dims(location).map_or(1.0, |(width, height)| {
+                    (layout.width as f32 / width).min(layout.height as f32 / height)
+                })
+            };
+            let (pixmap, _) = doc.pixmap(Location::Exact(location), scale, samples)
+                                 .ok_or_else(|| format_err!("can't render page {}", number))?;
+            let path = output.join(format!("page-{:04}.png", number));
+            pixmap.save(&path.to_string_lossy())?;
+        }
+    }
+
+    Ok(())
+}
+
+fn toc_as_json(doc: &mut dyn Document, toc: &[TocEntry], locations: &[usize]) -> Vec<JsonValue> {
+    toc.iter().map(|entry| {
+        let location = doc.resolve_location(entry.location.clone());
+        let page = location.map(|loc| match locations.binary_search(&loc) {
+            Ok(index) => index + 1,
+            Err(index) => index.max(1),
+        });
+        json!({
+            "title": entry.title,
+            "location": location,
+            "page": page,
+            "children": toc_as_json(doc, &entry.children, locations),
+        })
+    }).collect()
+}
+
+fn main() -> Result<(), Error> {
+    let args: Vec<String> = env::args().skip(1).collect();
+
+    let mut opts = Options::new();
+
+    opts.optflag("h", "help", "Print this help message.");
+    opts.optopt("o", "output", "The directory where the files are written.", "OUTPUT_DIR");
+    opts.optopt("p", "pages", "Comma separated list of page ranges to render (e.g. `1,4-6,10-`).", "PAGES");
+    opts.optopt("t", "thumbnail", "Write a thumbnail of the given dimensions.", "WIDTHxHEIGHT");
+    opts.optflag("i", "info", "Write the metadata and the table of contents.");
+    opts.optflag("c", "color", "Render in color.");
+    opts.optopt("W", "width", "The width of the pages.", "WIDTH");
+    opts.optopt("H", "height", "The height of the pages.", "HEIGHT");
+    opts.optopt("d", "dpi", "The resolution of the screen.", "DPI");
+    opts.optopt("f", "font-family", "The font family.", "FONT_FAMILY");
+    opts.optopt("s", "font-size", "The font size.", "FONT_SIZE");
+    opts.optopt("m", "margin-width", "The margin width, in millimeters.", "MARGIN_WIDTH");
+    opts.optopt("l", "line-height", "The line height.", "LINE_HEIGHT");
+    opts.optopt("a", "text-align", "The text alignment (`justify`, `left`, `right` or `center`).", "TEXT_ALIGN");
+
+    let matches = opts.parse(&args).context("failed to parse the command line arguments")?;
+
+    if matches.opt_present("h") {
+        println!("{}", opts.usage("Usage: plato-render -h|[-p PAGES] [-t WIDTHxHEIGHT] [-i] [-c] [-o OUTPUT_DIR] [-W WIDTH] [-H HEIGHT] [-d DPI] [-f FONT_FAMILY] [-s FONT_SIZE] [-m MARGIN_WIDTH] [-l LINE_HEIGHT] [-a TEXT_ALIGN] DOCUMENT_PATH"));
+        return Ok(());
+    }
+
+    if matches.free.is_empty() {
+        return Err(format_err!("missing required argument: document path"));
+    }
+
+    let path = Path::new(&matches.free[0]);
+    let output = matches.opt_str("o").map_or_else(|| PathBuf::from("."), PathBuf::from);
+    fs::create_dir_all(&output).with_context(|| format!("can't create {}", output.display()))?;
+
+    let settings = if Path::new(SETTINGS_PATH).exists() {
+        load_toml::<Settings, _>(SETTINGS_PATH).context("can't load settings")?
+    } else {
+        Settings::default()
+    };
+
+    let (width, height) = CURRENT_DEVICE.dims;
+    let layout = Layout {
+        width: matches.opt_get_default("W", width)?,
+        height: matches.opt_get_default("H", height)?,
+        dpi: matches.opt_get_default("d", CURRENT_DEVICE.dpi)?,
+        font_family: matches.opt_str("f").unwrap_or_else(|| settings.reader.font_family.clone()),
+        font_size: matches.opt_get_default("s", settings.reader.font_size)?,
+        margin_width: matches.opt_get_default("m", settings.reader.margin_width)?,
+        line_height: matches.opt_get_default("l", settings.reader.line_height)?,
+        text_align: matches.opt_str("a").map(|v| parse_text_align(&v))
+                           .transpose()?.unwrap_or(settings.reader.text_align),
+    };
+    let samples = if matches.opt_present("c") { 3 } else { 1 };
+
+    let mut doc = open(path).ok_or_else(|| format_err!("can't open {}", path.display()))?;
+    apply_layout(doc.as_mut(), &layout, &settings);
+
+    if let Some(pages) = matches.opt_str("p") {
+        let pages = parse_pages(&pages)?;
+        render_pages(doc.as_mut(), &pages, &layout, samples, &output)?;
+    }
+
+    if let Some(dims) = matches.opt_str("t") {
+        let (width, height) = parse_dims(&dims)?;
+        let pixmap = doc.preview_pixmap(width as f32, height as f32, samples)
+                        .ok_or_else(|| format_err!("can't render the thumbnail"))?;
+        pixmap.save(&output.join(THUMBNAIL_NAME).to_string_lossy())?;
+    }
+
+    if matches.opt_present("i") {
+        let mut info = Info {
+            file: FileInfo {
+                path: path.file_name().map(PathBuf::from).unwrap_or_default(),
+                kind: file_kind(path).unwrap_or_default(),
+                size: fs::metadata(path).map(|m| m.len()).unwrap_or_default(),
+            },
+            .. Default::default()
+        };
+        extract_metadata_from_document(path.parent().unwrap_or_else(|| Path::new("")), &mut info);
+        if info.title.is_empty() {
+            info.title = doc.title().unwrap_or_default();
+        }
+        if info.author.is_empty() {
+            info.author = doc.author().unwrap_or_default();
+        }
+        let locations = page_locations(doc.as_mut(), None);
+        let toc = doc.toc().map(|toc| toc_as_json(doc.as_mut(), &toc, &locations))
+                     .unwrap_or_default();
+        let value = json!({
+            "info": info,
+            "pagesCount": locations.len(),
+            "toc": toc,
+        });
+        save_json(&value, output.join(INFO_NAME))?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_pages() {
+        assert_eq!(parse_pages("3").unwrap(), [(3, Some(3))]);
+        assert_eq!(parse_pages("1,4-6,10-").unwrap(), [(1, Some(1)), (4, Some(6)), (10, None)]);
+        assert_eq!(parse_pages("2 - 5, 7").unwrap(), [(2, Some(5)), (7, Some(7))]);
+        assert_eq!(parse_pages("5-5").unwrap(), [(5, Some(5))]);
+        assert!(parse_pages("").is_err());
+        assert!(parse_pages("1,").is_err());
+        assert!(parse_pages("0").is_err());
+        assert!(parse_pages("6-4").is_err());
+        assert!(parse_pages("-4").is_err());
+        assert!(parse_pages("a-b").is_err());
+        assert!(parse_pages("1-2-3").is_err());
+    }
+
+    #[test]
+    fn test_parse_dims() {
+        assert_eq!(parse_dims("600x800").unwrap(), (600, 800));
+        assert!(parse_dims("").is_err());
+        assert!(parse_dims("x").is_err());
+        assert!(parse_dims("600").is_err());
+        assert!(parse_dims("600x").is_err());
+        assert!(parse_dims("-600x800").is_err());
+        assert!(parse_dims("600×800").is_err());
+    }
+}